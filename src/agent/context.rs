@@ -0,0 +1,382 @@
+//! Conversation context — the agent transcript as context segments.
+//!
+//! With a librarian attached, every message in `AgentThread.messages` is
+//! mirrored into the thread's context store: text turns as `message`
+//! segments, tool results as `code` (file-read), `search-result` (grep,
+//! glob) or `tool-result`. The librarian pages and folds these like any
+//! other segment, and the handler sends the projected transcript — shelved
+//! and folded entries replaced by a short stub — instead of the raw history.
+//!
+//! Nothing is deleted: the raw transcript stays in the thread, and unfolding
+//! or paging a segment back in restores it on the next call. The last
+//! [`PROTECTED_TAIL`] messages are always sent verbatim so the exchange in
+//! flight (tool_use ↔ tool_result pairing, thinking blocks) is never altered.
+
+use crate::kernel::context_store::{ContextSegment, ContextStore, SegmentStatus};
+use crate::kernel::error::KernelResult;
+use crate::llm::types::{ContentBlock, Message, MessageContent};
+
+/// Prefix of segment IDs mirrored from the agent transcript.
+pub const TRANSCRIPT_PREFIX: &str = "turn:";
+
+/// Trailing messages that are never replaced by stubs.
+pub const PROTECTED_TAIL: usize = 2;
+
+/// Token budget the librarian curates the working set (transcript included)
+/// into before each agent call.
+pub const TOKEN_BUDGET: usize = 50_000;
+
+/// Whether a segment ID belongs to a mirrored transcript.
+///
+/// Transcript segments reach the model through the message list, so the
+/// librarian leaves them out of the system-prompt context.
+pub fn is_transcript_segment(id: &str) -> bool {
+    id.starts_with(TRANSCRIPT_PREFIX)
+}
+
+/// ID of transcript message `index` (e.g. "msg-0003").
+///
+/// Also keys file checkpoints, so `/rewind <message>` takes the same IDs.
+pub fn message_id(index: usize) -> String {
+    format!("msg-{index:04}")
+}
+
+/// Segment ID for the text of message `index`.
+pub fn message_segment_id(index: usize) -> String {
+    format!("{TRANSCRIPT_PREFIX}{}", message_id(index))
+}
+
+/// Segment ID for a tool result.
+pub fn tool_segment_id(tool_use_id: &str) -> String {
+    format!("{TRANSCRIPT_PREFIX}tool-{tool_use_id}")
+}
+
+/// Segment tag for a tool's result.
+pub fn tool_result_tag(tool_name: &str) -> &'static str {
+    match tool_name {
+        "file-read" => "code",
+        "grep" | "glob" => "search-result",
+        _ => "tool-result",
+    }
+}
+
+/// Mirror transcript messages into the thread's context store.
+///
+/// Idempotent: segments that already exist are left alone (the librarian
+/// may have shelved or folded them), so this can run before every call.
+/// Creates the thread's context on first use.
+pub fn mirror_transcript(
+    contexts: &mut ContextStore,
+    thread_id: &str,
+    messages: &[Message],
+) -> KernelResult<()> {
+    if !contexts.exists(thread_id) {
+        contexts.create(thread_id)?;
+    }
+
+    let created_at = now_millis();
+    for (index, msg) in messages.iter().enumerate() {
+        for (id, tag, content) in segments_for(messages, index, msg) {
+            if contexts.get_segment(thread_id, &id).is_ok() {
+                continue;
+            }
+            contexts.add_segment(
+                thread_id,
+                ContextSegment {
+                    id,
+                    tag: tag.to_string(),
+                    content: content.into_bytes(),
+                    status: SegmentStatus::Active,
+                    relevance: 1.0,
+                    created_at,
+                    fold_ref: None,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Build the message list to send: the transcript with every shelved or
+/// folded segment replaced by its stub.
+///
+/// Messages without a mirrored segment (or a missing context) pass through
+/// unchanged, as do the last [`PROTECTED_TAIL`] messages.
+pub fn project_transcript(
+    contexts: &ContextStore,
+    thread_id: &str,
+    messages: &[Message],
+) -> Vec<Message> {
+    let protected_from = messages.len().saturating_sub(PROTECTED_TAIL);
+    let stub_for = |id: &str| -> Option<String> {
+        let seg = contexts.get_segment(thread_id, id).ok()?;
+        match seg.status {
+            SegmentStatus::Active => None,
+            SegmentStatus::Folded => Some(String::from_utf8_lossy(&seg.content).into_owned()),
+            SegmentStatus::Shelved => Some(format!("[shelved: {}]", seg.id)),
+        }
+    };
+
+    messages
+        .iter()
+        .enumerate()
+        .map(|(index, msg)| {
+            if index >= protected_from {
+                return msg.clone();
+            }
+            let content = match &msg.content {
+                MessageContent::Text(text) => MessageContent::Text(
+                    stub_for(&message_segment_id(index)).unwrap_or_else(|| text.clone()),
+                ),
+                MessageContent::Blocks(blocks) => {
+                    let text_stub = stub_for(&message_segment_id(index));
+                    let mut stubbed_text = false;
+                    let mut out = Vec::with_capacity(blocks.len());
+                    for block in blocks {
+                        match block {
+                            ContentBlock::Text { .. } if text_stub.is_some() => {
+                                // All text blocks collapse into one stub
+                                if !stubbed_text {
+                                    out.push(ContentBlock::Text {
+                                        text: text_stub.clone().unwrap_or_default(),
+                                    });
+                                    stubbed_text = true;
+                                }
+                            }
+                            ContentBlock::ToolResult {
+                                tool_use_id,
+                                content,
+                                is_error,
+                            } => out.push(ContentBlock::ToolResult {
+                                tool_use_id: tool_use_id.clone(),
+                                content: stub_for(&tool_segment_id(tool_use_id))
+                                    .map(MessageContent::Text)
+                                    .or_else(|| content.clone()),
+                                is_error: *is_error,
+                            }),
+                            other => out.push(other.clone()),
+                        }
+                    }
+                    MessageContent::Blocks(out)
+                }
+            };
+            Message {
+                role: msg.role.clone(),
+                content,
+            }
+        })
+        .collect()
+}
+
+/// The segments one message mirrors to: `(id, tag, content)`.
+fn segments_for(
+    messages: &[Message],
+    index: usize,
+    msg: &Message,
+) -> Vec<(String, &'static str, String)> {
+    let mut segments = Vec::new();
+    match &msg.content {
+        MessageContent::Text(text) => {
+            segments.push((message_segment_id(index), "message", text.clone()));
+        }
+        MessageContent::Blocks(blocks) => {
+            let text: Vec<&str> = blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            if !text.is_empty() {
+                segments.push((message_segment_id(index), "message", text.join("\n")));
+            }
+            for block in blocks {
+                if let ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } = block
+                {
+                    let tool_name = tool_name_for(&messages[..index], tool_use_id);
+                    segments.push((
+                        tool_segment_id(tool_use_id),
+                        tool_result_tag(tool_name.unwrap_or_default()),
+                        content.as_ref().and_then(|c| c.text()).unwrap_or_default(),
+                    ));
+                }
+            }
+        }
+    }
+    segments
+}
+
+/// Find the tool name of the `tool_use` a result answers (latest first).
+fn tool_name_for<'a>(earlier: &'a [Message], tool_use_id: &str) -> Option<&'a str> {
+    earlier.iter().rev().find_map(|msg| match &msg.content {
+        MessageContent::Blocks(blocks) => blocks.iter().find_map(|b| match b {
+            ContentBlock::ToolUse { id, name, .. } if id == tool_use_id => Some(name.as_str()),
+            _ => None,
+        }),
+        MessageContent::Text(_) => None,
+    })
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::ToolResultBlock;
+
+    fn transcript() -> Vec<Message> {
+        vec![
+            Message::text("user", "Find the parser"),
+            Message::assistant_blocks(vec![
+                ContentBlock::Text {
+                    text: "Searching.".into(),
+                },
+                ContentBlock::ToolUse {
+                    id: "t1".into(),
+                    name: "grep".into(),
+                    input: serde_json::json!({"pattern": "fn parse"}),
+                },
+                ContentBlock::ToolUse {
+                    id: "t2".into(),
+                    name: "file-read".into(),
+                    input: serde_json::json!({"path": "src/parser.rs"}),
+                },
+            ]),
+            Message::tool_results(vec![
+                ToolResultBlock {
+                    tool_use_id: "t1".into(),
+                    content: "src/parser.rs:10: fn parse".into(),
+                    is_error: false,
+                    media: vec![],
+                },
+                ToolResultBlock {
+                    tool_use_id: "t2".into(),
+                    content: "fn parse() {}".into(),
+                    is_error: false,
+                    media: vec![],
+                },
+            ]),
+            Message::assistant_blocks(vec![ContentBlock::Text {
+                text: "Found it.".into(),
+            }]),
+            Message::text("user", "Now fix it"),
+        ]
+    }
+
+    fn mirrored() -> ContextStore {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut store = ContextStore::open(dir.path()).unwrap();
+        mirror_transcript(&mut store, "t", &transcript()).unwrap();
+        store
+    }
+
+    fn tool_result_content(msg: &Message, wanted: &str) -> Option<String> {
+        match &msg.content {
+            MessageContent::Blocks(blocks) => blocks.iter().find_map(|b| match b {
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } if tool_use_id == wanted => content.as_ref().and_then(|c| c.text()),
+                _ => None,
+            }),
+            MessageContent::Text(_) => None,
+        }
+    }
+
+    #[test]
+    fn mirror_tags_tool_results() {
+        let store = mirrored();
+        assert_eq!(store.get_segment("t", &tool_segment_id("t1")).unwrap().tag, "search-result");
+        assert_eq!(store.get_segment("t", &tool_segment_id("t2")).unwrap().tag, "code");
+        let msg = store.get_segment("t", &message_segment_id(0)).unwrap();
+        assert_eq!(msg.tag, "message");
+        assert_eq!(msg.content, b"Find the parser");
+        // 4 text turns + 2 tool results
+        assert_eq!(store.get_inventory("t").unwrap().segments.len(), 6);
+    }
+
+    #[test]
+    fn mirror_is_idempotent_and_keeps_status() {
+        let mut store = mirrored();
+        store.page_out("t", &tool_segment_id("t1")).unwrap();
+        mirror_transcript(&mut store, "t", &transcript()).unwrap();
+        assert_eq!(store.get_inventory("t").unwrap().segments.len(), 6);
+        assert_eq!(
+            store.get_segment("t", &tool_segment_id("t1")).unwrap().status,
+            SegmentStatus::Shelved
+        );
+    }
+
+    #[test]
+    fn project_all_active_is_verbatim() {
+        let store = mirrored();
+        let projected = project_transcript(&store, "t", &transcript());
+        assert_eq!(
+            serde_json::to_value(&projected).unwrap(),
+            serde_json::to_value(transcript()).unwrap()
+        );
+    }
+
+    #[test]
+    fn project_stubs_shelved_and_folded_results() {
+        let mut store = mirrored();
+        store.page_out("t", &tool_segment_id("t1")).unwrap();
+        store
+            .fold("t", &tool_segment_id("t2"), b"parser.rs: one empty fn".to_vec())
+            .unwrap();
+
+        let projected = project_transcript(&store, "t", &transcript());
+        assert_eq!(projected.len(), 5);
+        assert_eq!(
+            tool_result_content(&projected[2], "t1").unwrap(),
+            "[shelved: turn:tool-t1]"
+        );
+        assert_eq!(
+            tool_result_content(&projected[2], "t2").unwrap(),
+            "parser.rs: one empty fn"
+        );
+        // Tool-use pairing is untouched
+        assert_eq!(projected[1].content.tool_use_blocks().len(), 2);
+    }
+
+    #[test]
+    fn project_stubs_message_text() {
+        let mut store = mirrored();
+        store.page_out("t", &message_segment_id(1)).unwrap();
+        let projected = project_transcript(&store, "t", &transcript());
+        assert_eq!(projected[1].content.text().unwrap(), "[shelved: turn:msg-0001]");
+        assert_eq!(projected[1].content.tool_use_blocks().len(), 2);
+    }
+
+    #[test]
+    fn project_protects_tail() {
+        let mut store = mirrored();
+        store.page_out("t", &message_segment_id(4)).unwrap();
+        let projected = project_transcript(&store, "t", &transcript());
+        assert_eq!(projected[4].content.text().unwrap(), "Now fix it");
+    }
+
+    #[test]
+    fn project_without_context_passes_through() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = ContextStore::open(dir.path()).unwrap();
+        let projected = project_transcript(&store, "missing", &transcript());
+        assert_eq!(projected[0].content.text().unwrap(), "Find the parser");
+    }
+
+    #[test]
+    fn transcript_prefix() {
+        assert!(is_transcript_segment(&tool_segment_id("x")));
+        assert!(!is_transcript_segment("code:parser.rs"));
+    }
+}
@@ -27,22 +27,64 @@
 //!                   ▼                 ▼          │
 //!             Send next         Call Opus again──┘
 //! ```
+//!
+//! ## Structured Answers
+//!
+//! Agents configured with a `response_schema` get a terminal `submit` tool.
+//! A valid `submit` call ends the turn and its JSON input becomes the
+//! `<result>`; an invalid one (or a plain end_turn) is fed back to Opus and
+//! retried up to the schema's `max_retries`.
+//!
+//! ## Self-Verification
+//!
+//! Agents with a `verify:` block hold their final reply after any
+//! file-write/file-edit and run each verify command through command-exec
+//! (`Verifying` state). Failures go back to Opus as a user turn, up to
+//! `max_retries` rounds; the outcome is summarized in `<verification>`.
+//!
+//! ## Context Management
+//!
+//! With a librarian attached, the transcript is mirrored into the thread's
+//! context store before every call (see [`super::context`]). The librarian
+//! shelves or folds stale turns and tool results, and Opus receives the
+//! projected transcript — never a compacted one; the raw history is kept.
+//!
+//! ## Checkpoints
+//!
+//! With a kernel attached, the target of every file-write/file-edit is
+//! checkpointed just before the call is sent, keyed by thread and the
+//! transcript message ID issuing it (`msg-0004`). The TUI's `/undo` and
+//! `/rewind` restore from those checkpoints.
+//!
+//! The kernel also tracks which version of each file the thread last saw
+//! (from file-read, file-write and file-edit results, and from the
+//! thread's own `replace`, `diagnostics` and `command-exec` redirection
+//! writes). A write to a file the thread has seen carries that version as
+//! `expected_hash`, so the tool rejects it if someone changed the file in
+//! between; a write to an existing file it never read gets a warning
+//! appended to the result.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
 use tokio::sync::{broadcast, Mutex};
 
+use crate::kernel::Kernel;
 use crate::librarian::Librarian;
 use crate::llm::types::{ContentBlock, ToolDefinition, ToolResultBlock};
 use crate::llm::LlmPool;
-use crate::organism::AgentConfig;
+use crate::organism::{AgentConfig, ResponseSchema, VerifyConfig};
 use crate::pipeline::events::{ConversationEntry, PipelineEvent};
 use crate::routing::{RouteDecision, SemanticRouter};
+use crate::tools::{command_policy, version};
+use crate::tools::workspace::{self, Access, Workspace};
 
-use super::state::{AgentState, AgentThread, PendingToolCall};
+use super::context;
+use super::schema::{self, SUBMIT_TOOL_NAME};
+use super::state::{AgentState, AgentThread, PendingToolCall, VerifyOutcome};
 use super::translate;
 
 /// A snapshot of an agent thread's state (for TUI display).
@@ -73,6 +115,19 @@ pub struct CodingAgentHandler {
     max_tokens: u32,
     /// Model override. None = pool default.
     model: Option<String>,
+    /// Structured final-answer schema (enables the `submit` tool).
+    response_schema: Option<ResponseSchema>,
+    /// Extended thinking budget in tokens. None = thinking disabled.
+    thinking_budget: Option<u32>,
+    /// Self-verification commands run before accepting end_turn.
+    verify: Option<VerifyConfig>,
+    /// Kernel whose checkpoint store records file pre-images before
+    /// mutating tool calls (enables `/undo` and `/rewind`) and whose
+    /// read-version table tracks the file versions each thread has seen.
+    checkpoints: Option<Arc<Mutex<Kernel>>>,
+    /// Workspaces the tools are jailed to, by profile name (resolves
+    /// checkpointed paths in the calling thread's workspace).
+    workspaces: HashMap<String, Arc<Workspace>>,
 }
 
 /// Type alias — generic agent handler (same implementation, data-driven identity).
@@ -84,6 +139,27 @@ const DEFAULT_MAX_ROUTING_ITERATIONS: usize = 5;
 /// Default max agentic loop iterations (Opus→tool→Opus cycles).
 const DEFAULT_MAX_AGENTIC_ITERATIONS: usize = 25;
 
+/// Tools whose use makes a thread eligible for self-verification.
+const MUTATING_TOOLS: &[&str] = &["file-write", "file-edit", "notebook-edit"];
+
+/// Tools that checkpoint the files they change themselves (they aren't named
+/// in the call); their applying calls get the issuing message's ID as
+/// `message_id`.
+const SELF_CHECKPOINTING_TOOLS: &[&str] = &["replace", "diagnostics"];
+
+/// Tools whose results carry a `[version: …]` token for the file they touched.
+const VERSIONED_TOOLS: &[&str] = &["file-read", "file-write", "file-edit", "notebook-edit"];
+
+/// Tool the `verify:` commands are sent through.
+const VERIFY_TOOL: &str = "command-exec";
+
+/// Tool that can change files without naming them; only the seen files its
+/// command line redirects output into get their new versions recorded.
+const SHELL_TOOL: &str = "command-exec";
+
+/// Max characters of failing command output fed back to Opus (tail kept).
+const VERIFY_FEEDBACK_CHARS: usize = 4000;
+
 impl CodingAgentHandler {
     /// Create a new coding agent handler.
     pub fn new(
@@ -103,6 +179,11 @@ impl CodingAgentHandler {
             event_tx: None,
             max_tokens: 4096,
             model: None,
+            response_schema: None,
+            thinking_budget: None,
+            verify: None,
+            checkpoints: None,
+            workspaces: HashMap::new(),
         }
     }
 
@@ -113,6 +194,10 @@ impl CodingAgentHandler {
         system_prompt: String,
         config: &AgentConfig,
     ) -> Self {
+        let mut tool_definitions = tool_definitions;
+        if let Some(ref rs) = config.response_schema {
+            tool_definitions.push(schema::submit_tool_definition(rs));
+        }
         Self {
             pool,
             librarian: None,
@@ -125,6 +210,11 @@ impl CodingAgentHandler {
             event_tx: None,
             max_tokens: config.max_tokens,
             model: config.model.clone(),
+            response_schema: config.response_schema.clone(),
+            thinking_budget: config.thinking_budget,
+            verify: config.verify.clone(),
+            checkpoints: None,
+            workspaces: HashMap::new(),
         }
     }
 
@@ -147,6 +237,11 @@ impl CodingAgentHandler {
             event_tx: None,
             max_tokens: 4096,
             model: None,
+            response_schema: None,
+            thinking_budget: None,
+            verify: None,
+            checkpoints: None,
+            workspaces: HashMap::new(),
         }
     }
 
@@ -169,6 +264,11 @@ impl CodingAgentHandler {
             event_tx: None,
             max_tokens: 4096,
             model: None,
+            response_schema: None,
+            thinking_budget: None,
+            verify: None,
+            checkpoints: None,
+            workspaces: HashMap::new(),
         }
     }
 
@@ -184,6 +284,44 @@ impl CodingAgentHandler {
         self
     }
 
+    /// Checkpoint file mutations in a kernel (builder-style).
+    ///
+    /// Before each file-write/file-edit is sent, the target's current
+    /// content is recorded under the thread and the transcript message
+    /// issuing the call, and the file versions the thread sees are tracked
+    /// for stale-write detection. Pass the workspaces the tools are jailed
+    /// to, by profile, so paths resolve the same way the tools resolve them
+    /// for the calling thread's profile.
+    pub fn with_checkpoints_attached(
+        mut self,
+        kernel: Arc<Mutex<Kernel>>,
+        workspaces: HashMap<String, Arc<Workspace>>,
+    ) -> Self {
+        self.checkpoints = Some(kernel);
+        self.workspaces = workspaces;
+        self
+    }
+
+    /// The workspace `thread_id`'s profile jails its tools to.
+    ///
+    /// `Ok(None)` when no kernel is attached to look the profile up in (an
+    /// unjailed handler). An error when the thread or its profile has no
+    /// workspace, so nothing is resolved outside one.
+    async fn thread_workspace(&self, thread_id: &str) -> Result<Option<Arc<Workspace>>, String> {
+        let Some(ref kernel) = self.checkpoints else {
+            return Ok(None);
+        };
+        let kernel = kernel.lock().await;
+        let profile = kernel
+            .threads()
+            .get_profile(thread_id)
+            .ok_or_else(|| format!("thread {thread_id} has no profile"))?;
+        match self.workspaces.get(profile) {
+            Some(ws) => Ok(Some(ws.clone())),
+            None => Err(format!("profile '{profile}' declares no workspace")),
+        }
+    }
+
     /// Set the maximum routing iterations per turn.
     pub fn set_max_routing_iterations(&mut self, max: usize) {
         self.max_routing_iterations = max;
@@ -199,8 +337,12 @@ impl CodingAgentHandler {
         if let Ok(HandlerResponse::Reply { ref payload_xml }) = result {
             if let Some(ref tx) = self.event_tx {
                 let text = String::from_utf8_lossy(payload_xml);
-                let response_text = extract_tag(&text, "result")
+                let mut response_text = extract_tag(&text, "result")
+                    .or_else(|| extract_tag(&text, "error").map(|e| format!("Error: {e}")))
                     .unwrap_or_else(|| text.to_string());
+                if let Some(summary) = extract_tag(&text, "verification") {
+                    response_text = format!("{response_text}\n\nVerification:\n{summary}");
+                }
                 let _ = tx.send(PipelineEvent::AgentResponse {
                     thread_id: thread_id.to_string(),
                     text: response_text,
@@ -276,6 +418,10 @@ impl CodingAgentHandler {
                         collected,
                         ..
                     } => format!("AwaitingTools({}/{})", collected.len(), pending.len()),
+                    AgentState::Verifying { current_index, .. } => {
+                        let total = self.verify.as_ref().map_or(0, |v| v.commands.len());
+                        format!("Verifying({}/{})", current_index + 1, total)
+                    }
                 };
                 AgentThreadSnapshot {
                     thread_id: id.clone(),
@@ -287,32 +433,63 @@ impl CodingAgentHandler {
     }
 
     /// Call the LLM API with the current conversation state.
+    ///
+    /// Thinking blocks come back as part of `content` and are replayed
+    /// verbatim with the rest of the assistant turn; their text is also
+    /// emitted as an `AgentReasoning` event for display.
     async fn call_opus(
         &self,
+        thread_id: &str,
         thread: &AgentThread,
     ) -> Result<crate::llm::types::MessagesResponse, String> {
         // Optional: curate context before the API call
         let mut system = self.system_prompt.clone();
+        let mut messages = thread.messages.clone();
         if let Some(ref librarian) = self.librarian {
             let lib = librarian.lock().await;
-            let budget = 6000usize;
-            if let Ok(result) = lib.curate("agent", &thread.messages, budget).await {
+            {
+                let mut kernel = lib.kernel.lock().await;
+                if let Err(e) =
+                    context::mirror_transcript(kernel.contexts_mut(), thread_id, &thread.messages)
+                {
+                    tracing::warn!("failed to mirror transcript for {thread_id}: {e}");
+                }
+            }
+            let recent_from = thread.messages.len().saturating_sub(context::PROTECTED_TAIL);
+            if let Ok(result) = lib
+                .curate(thread_id, &thread.messages[recent_from..], context::TOKEN_BUDGET)
+                .await
+            {
                 if let Some(ctx) = result.system_context {
                     system = format!("{system}\n\n{ctx}");
                 }
             }
+            let kernel = lib.kernel.lock().await;
+            messages = context::project_transcript(kernel.contexts(), thread_id, &thread.messages);
+        }
+
+        let response = {
+            let pool = self.pool.lock().await;
+            pool.complete_with_thinking(
+                self.model.as_deref(),
+                messages,
+                self.max_tokens,
+                Some(&system),
+                self.tool_definitions.clone(),
+                self.thinking_budget,
+            )
+            .await
+            .map_err(|e| format!("LLM API error: {e}"))?
+        };
+
+        if let Some(text) = response.thinking() {
+            self.maybe_emit(PipelineEvent::AgentReasoning {
+                thread_id: thread_id.to_string(),
+                text,
+            });
         }
 
-        let pool = self.pool.lock().await;
-        pool.complete_with_tools(
-            self.model.as_deref(),
-            thread.messages.clone(),
-            self.max_tokens,
-            Some(&system),
-            self.tool_definitions.clone(),
-        )
-        .await
-        .map_err(|e| format!("LLM API error: {e}"))
+        Ok(response)
     }
 
     /// Process an Opus response: extract tool calls or final text.
@@ -347,6 +524,19 @@ impl CodingAgentHandler {
                 })
                 .collect();
 
+            if self.response_schema.is_some() {
+                if let Some(pos) = pending.iter().position(|p| p.tool_name == SUBMIT_TOOL_NAME) {
+                    let mut pending = pending;
+                    let submit = pending.remove(pos);
+                    return ResponseAction::Submit {
+                        blocks: response.content.clone(),
+                        tool_use_id: submit.tool_use_id,
+                        input: submit.input,
+                        skipped: pending.into_iter().map(|p| p.tool_use_id).collect(),
+                    };
+                }
+            }
+
             ResponseAction::ToolCalls {
                 blocks: response.content.clone(),
                 pending,
@@ -375,6 +565,9 @@ impl CodingAgentHandler {
                         payload_xml: reply_xml.as_bytes().to_vec(),
                     });
                 }
+                if pending.iter().any(is_mutation) {
+                    thread.files_modified = true;
+                }
                 let first_name = pending[0].tool_name.clone();
                 let first_xml =
                     translate::tool_call_to_xml(&pending[0].tool_name, &pending[0].input);
@@ -399,6 +592,497 @@ impl CodingAgentHandler {
                     payload_xml: reply_xml.into_bytes(),
                 })
             }
+            ResponseAction::Submit {
+                blocks,
+                tool_use_id,
+                input,
+                skipped,
+            } => {
+                // Close out the tool_use blocks so the thread stays well-formed
+                thread.push_assistant_blocks(blocks);
+                let mut results = vec![ToolResultBlock {
+                    tool_use_id,
+                    content: "accepted".into(),
+                    is_error: false,
+                    media: Vec::new(),
+                }];
+                results.extend(skipped_tool_results(skipped));
+                thread.push_tool_results(results);
+                let reply_xml = format!(
+                    "<AgentResponse><result>{}</result></AgentResponse>",
+                    translate::xml_escape_text(&input.to_string())
+                );
+                Ok(HandlerResponse::Reply {
+                    payload_xml: reply_xml.into_bytes(),
+                })
+            }
+        }
+    }
+
+    /// Prepare a file-write/file-edit about to be sent.
+    ///
+    /// Looks at the call `result` sends (the current pending call). A
+    /// `replace` applying a preview or a `diagnostics` applying suggestions
+    /// only gets the message ID to checkpoint under. Otherwise, if it
+    /// mutates a file:
+    /// - records the file's pre-image keyed by the thread and the ID the
+    ///   issuing assistant message will get (failures are logged, not fatal);
+    /// - adds the version the thread last saw as `expected_hash`, unless the
+    ///   call already gives one;
+    /// - notes a warning if the file exists but the thread never read it.
+    async fn prepare_mutation(
+        &self,
+        thread_id: &str,
+        thread: &mut AgentThread,
+        result: HandlerResult,
+    ) -> HandlerResult {
+        let Some(ref kernel) = self.checkpoints else {
+            return result;
+        };
+        let Ok(HandlerResponse::Send { ref to, .. }) = result else {
+            return result;
+        };
+        let AgentState::AwaitingTools {
+            pending,
+            current_index,
+            ..
+        } = &thread.state
+        else {
+            return result;
+        };
+        let Some(call) = pending.get(*current_index) else {
+            return result;
+        };
+        if &call.tool_name != to || !is_mutation(call) {
+            return result;
+        }
+        // The assistant message is pushed once all results are collected,
+        // so it lands at the current end of the transcript
+        let message_id = context::message_id(thread.messages.len());
+        if SELF_CHECKPOINTING_TOOLS.contains(&call.tool_name.as_str()) {
+            let mut input = call.input.clone();
+            input["message_id"] = message_id.into();
+            return Ok(HandlerResponse::Send {
+                to: to.clone(),
+                payload_xml: translate::tool_call_to_xml(&call.tool_name, &input).into_bytes(),
+            });
+        }
+        let Ok(workspace) = self.thread_workspace(thread_id).await else {
+            return result;
+        };
+        let Some(path) = self.call_path(workspace.as_deref(), call, Access::Write) else {
+            return result;
+        };
+
+        let mut kernel = kernel.lock().await;
+        if let Err(e) = kernel.record_checkpoint(thread_id, &message_id, &path) {
+            tracing::warn!("checkpoint of {} failed: {e}", path.display());
+        }
+
+        match kernel.file_version(thread_id, &path) {
+            Some(seen) if call.input.get("expected_hash").is_none() => {
+                let mut input = call.input.clone();
+                input["expected_hash"] = seen.into();
+                Ok(HandlerResponse::Send {
+                    to: to.clone(),
+                    payload_xml: translate::tool_call_to_xml(&call.tool_name, &input)
+                        .into_bytes(),
+                })
+            }
+            None if path.exists() => {
+                thread.write_warning = Some(format!(
+                    "warning: {} was changed without being read first in this thread; \
+                     read files before editing them to avoid overwriting newer content",
+                    call.input["path"].as_str().unwrap_or_default()
+                ));
+                result
+            }
+            _ => result,
+        }
+    }
+
+    /// Track the file version a completed file tool reports, and append any
+    /// pending never-read warning to a successful write's result.
+    ///
+    /// After a command-exec, the seen files its command line writes with a
+    /// redirection get their new versions, failed or not. Any other change
+    /// keeps the old version, so the next write to that file conflicts. A
+    /// session call also notes the `cwd:` its shell reports, which later
+    /// redirections in that session are resolved against.
+    async fn observe_file_result(
+        &self,
+        thread_id: &str,
+        thread: &mut AgentThread,
+        call: Option<&PendingToolCall>,
+        content: &mut String,
+        is_error: bool,
+    ) {
+        let warning = thread.write_warning.take();
+        let Some(ref kernel) = self.checkpoints else {
+            return;
+        };
+        let Ok(workspace) = self.thread_workspace(thread_id).await else {
+            return;
+        };
+        let workspace = workspace.as_deref();
+        if let Some(call) = call.filter(|c| c.tool_name == SHELL_TOOL) {
+            let session = call.input.get("session").and_then(|s| s.as_str());
+            let session_dir = session.and_then(|s| thread.session_dirs.get(s).cloned());
+            let targets = self.exec_write_targets(workspace, call, session_dir);
+            if let Some(session) = session {
+                let action = call.input.get("action").and_then(|a| a.as_str());
+                let closed =
+                    action == Some("close") || (is_error && content.contains("left the workspace"));
+                match content.lines().nth(1).and_then(|l| l.strip_prefix("cwd: ")) {
+                    _ if closed => {
+                        thread.session_dirs.remove(session);
+                    }
+                    Some(cwd) if !is_error => {
+                        thread.session_dirs.insert(session.to_string(), cwd.into());
+                    }
+                    _ => {}
+                }
+            }
+            let written: Vec<(PathBuf, String)> = targets
+                .into_iter()
+                .filter_map(|path| {
+                    let data = std::fs::read(&path).ok()?;
+                    Some((path, version::content_version(&data)))
+                })
+                .collect();
+            let mut kernel = kernel.lock().await;
+            for (path, now) in written {
+                // Only files the thread has seen are tracked
+                if kernel.file_version(thread_id, &path).is_none_or(|seen| seen == now) {
+                    continue;
+                }
+                if let Err(e) = kernel.record_file_version(thread_id, &path, &now) {
+                    tracing::warn!("recording version of {} failed: {e}", path.display());
+                }
+            }
+            return;
+        }
+        let Some(call) = call.filter(|c| VERSIONED_TOOLS.contains(&c.tool_name.as_str())) else {
+            return;
+        };
+        if is_error {
+            return;
+        }
+        if let Some(warning) = warning {
+            content.push('\n');
+            content.push_str(&warning);
+        }
+        let access = if call.tool_name == "file-read" {
+            Access::Read
+        } else {
+            Access::Write
+        };
+        let (Some(path), Some(seen)) = (
+            self.call_path(workspace, call, access),
+            version::parse(content),
+        ) else {
+            return;
+        };
+        if let Err(e) = kernel.lock().await.record_file_version(thread_id, &path, seen) {
+            tracing::warn!("recording version of {} failed: {e}", path.display());
+        }
+    }
+
+    /// The file a tool call targets, resolved the way the tool resolves it.
+    ///
+    /// `None` if the call has no `path` or it falls outside the workspace
+    /// (the tool refuses those).
+    fn call_path(
+        &self,
+        workspace: Option<&Workspace>,
+        call: &PendingToolCall,
+        access: Access,
+    ) -> Option<PathBuf> {
+        let path = call.input.get("path").and_then(|p| p.as_str())?;
+        self.resolve_path(workspace, path, access)
+    }
+
+    /// The files a command-exec call redirects output into.
+    ///
+    /// Each command's targets are resolved against the directory it runs
+    /// in: the session's last reported `dir` (else `working_dir`), moved by
+    /// the line's own `cd`/`pushd`s as they come.
+    fn exec_write_targets(
+        &self,
+        workspace: Option<&Workspace>,
+        call: &PendingToolCall,
+        dir: Option<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let Some(command) = call.input.get("command").and_then(|c| c.as_str()) else {
+            return Vec::new();
+        };
+        let Ok(parsed) = command_policy::parse(command) else {
+            return Vec::new();
+        };
+        let mut dir = dir.or_else(|| {
+            call.input
+                .get("working_dir")
+                .and_then(|d| d.as_str())
+                .map(PathBuf::from)
+        });
+        let mut targets = Vec::new();
+        for cmd in &parsed.commands {
+            // Redirections open before the command runs, so a `cd`'s own
+            // target lands in the old directory
+            for target in &cmd.write_targets {
+                // Joining keeps an absolute target as it is
+                let target = dir
+                    .as_ref()
+                    .map_or_else(|| target.into(), |d| d.join(target));
+                targets.extend(self.resolve_path(
+                    workspace,
+                    &target.to_string_lossy(),
+                    Access::Write,
+                ));
+            }
+            if let Ok(Some(to)) = command_policy::cd_target(cmd) {
+                dir = Some(dir.map_or_else(|| to.into(), |d| d.join(to)));
+            }
+        }
+        targets
+    }
+
+    /// Resolve `path` the way the file tools do: through the workspace if
+    /// jailed, else against the current directory.
+    fn resolve_path(
+        &self,
+        workspace: Option<&Workspace>,
+        path: &str,
+        access: Access,
+    ) -> Option<PathBuf> {
+        match workspace {
+            Some(ws) => ws.locate(path, access),
+            None => Some(
+                std::env::current_dir()
+                    .map(|cwd| cwd.join(path))
+                    .unwrap_or_else(|_| path.into()),
+            ),
+        }
+    }
+
+    /// Hold a final reply and start running `verify:` commands, if due.
+    ///
+    /// Verification runs only when a `verify:` block is configured, the
+    /// thread has modified files since the last pass, and the reply carries
+    /// a `<result>`. Anything else passes through unchanged.
+    fn maybe_start_verification(
+        &self,
+        thread_id: &str,
+        thread: &mut AgentThread,
+        result: HandlerResult,
+    ) -> HandlerResult {
+        let Some(ref verify) = self.verify else {
+            return result;
+        };
+        if !thread.files_modified {
+            return result;
+        }
+        match result {
+            Ok(HandlerResponse::Reply { payload_xml })
+                if String::from_utf8_lossy(&payload_xml).contains("<result>") =>
+            {
+                thread.state = AgentState::Verifying {
+                    reply_xml: payload_xml,
+                    outcomes: Vec::new(),
+                    current_index: 0,
+                };
+                Ok(self.send_verify_command(thread_id, verify, 0))
+            }
+            other => other,
+        }
+    }
+
+    /// Build the command-exec message for the verify command at `index`.
+    fn send_verify_command(
+        &self,
+        thread_id: &str,
+        verify: &VerifyConfig,
+        index: usize,
+    ) -> HandlerResponse {
+        let command = &verify.commands[index];
+        let mut input = serde_json::json!({ "command": command });
+        if let Some(timeout) = verify.timeout_secs {
+            input["timeout"] = serde_json::json!(timeout);
+        }
+        self.maybe_emit(PipelineEvent::ToolDispatched {
+            thread_id: thread_id.to_string(),
+            tool_name: VERIFY_TOOL.to_string(),
+            detail: format!("verify: {command}"),
+        });
+        HandlerResponse::Send {
+            to: VERIFY_TOOL.to_string(),
+            payload_xml: translate::tool_call_to_xml(VERIFY_TOOL, &input).into_bytes(),
+        }
+    }
+
+    /// Handle a command-exec result while in the `Verifying` state.
+    async fn continue_verification(
+        &self,
+        thread_id: &str,
+        thread: &mut AgentThread,
+        reply_xml: Vec<u8>,
+        mut outcomes: Vec<VerifyOutcome>,
+        current_index: usize,
+        tool_result: (String, bool),
+    ) -> HandlerResult {
+        let (result_content, is_error) = tool_result;
+        let Some(ref verify) = self.verify else {
+            return Ok(HandlerResponse::Reply {
+                payload_xml: reply_xml,
+            });
+        };
+
+        let passed = !is_error && exit_code(&result_content) == Some(0);
+        self.maybe_emit(PipelineEvent::ToolCompleted {
+            thread_id: thread_id.to_string(),
+            tool_name: VERIFY_TOOL.to_string(),
+            success: passed,
+            detail: String::new(),
+        });
+        outcomes.push(VerifyOutcome {
+            command: verify.commands[current_index].clone(),
+            passed,
+            output: result_content,
+        });
+
+        let next_index = current_index + 1;
+        if next_index < verify.commands.len() {
+            thread.state = AgentState::Verifying {
+                reply_xml,
+                outcomes,
+                current_index: next_index,
+            };
+            return Ok(self.send_verify_command(thread_id, verify, next_index));
+        }
+
+        let all_passed = outcomes.iter().all(|o| o.passed);
+        if all_passed || thread.verify_retries >= verify.max_retries {
+            thread.files_modified = false;
+            thread.verify_retries = 0;
+            return Ok(HandlerResponse::Reply {
+                payload_xml: with_verification_summary(&reply_xml, &outcomes),
+            });
+        }
+
+        // Feed failures back and let Opus fix them
+        thread.verify_retries += 1;
+        thread.push_user_message(&verification_feedback(&outcomes));
+
+        self.maybe_emit(PipelineEvent::AgentThinking {
+            thread_id: thread_id.to_string(),
+        });
+        if let Some(result) = self.check_agentic_limit(thread) {
+            return result;
+        }
+
+        let response = match self.call_opus(thread_id, thread).await {
+            Ok(r) => r,
+            Err(e) => {
+                self.emit_error(thread_id, &e);
+                return Err(PipelineError::Handler(e));
+            }
+        };
+        let action = self.process_response(&response);
+        if let ResponseAction::ToolCalls { ref pending, .. } = action {
+            if let Some(first) = pending.first() {
+                self.maybe_emit(PipelineEvent::ToolDispatched {
+                    thread_id: thread_id.to_string(),
+                    tool_name: first.tool_name.clone(),
+                    detail: summarize_tool_input(&first.tool_name, &first.input),
+                });
+            }
+        }
+        let result = self.dispatch_or_route(thread_id, thread, action, &[]).await;
+        self.maybe_start_verification(thread_id, thread, result)
+    }
+
+    /// Enforce the response schema on a terminal action.
+    ///
+    /// A valid `submit` call becomes the reply. An invalid submission, or
+    /// plain end_turn text, is fed back to Opus and retried up to the
+    /// schema's `max_retries`.
+    async fn dispatch_structured(
+        &self,
+        thread_id: &str,
+        thread: &mut AgentThread,
+        mut action: ResponseAction,
+    ) -> HandlerResult {
+        let Some(ref response_schema) = self.response_schema else {
+            return Self::dispatch_response(thread, action);
+        };
+
+        loop {
+            match action {
+                ResponseAction::Submit {
+                    blocks,
+                    tool_use_id,
+                    input,
+                    skipped,
+                } => {
+                    let errors = schema::validate(&response_schema.schema, &input);
+                    if errors.is_empty() {
+                        thread.schema_retries = 0;
+                        return Self::dispatch_response(
+                            thread,
+                            ResponseAction::Submit {
+                                blocks,
+                                tool_use_id,
+                                input,
+                                skipped,
+                            },
+                        );
+                    }
+                    thread.push_assistant_blocks(blocks);
+                    let mut results = vec![ToolResultBlock {
+                        tool_use_id,
+                        content: format!(
+                            "submission rejected, fix these errors and call submit again:\n{}",
+                            errors.join("\n")
+                        ),
+                        is_error: true,
+                        media: Vec::new(),
+                    }];
+                    results.extend(skipped_tool_results(skipped));
+                    thread.push_tool_results(results);
+                }
+                ResponseAction::FinalText { blocks, .. } => {
+                    thread.push_assistant_blocks(blocks);
+                    thread.push_user_message(schema::SUBMIT_REMINDER);
+                }
+                other => return Self::dispatch_response(thread, other),
+            }
+
+            thread.schema_retries += 1;
+            if thread.schema_retries > response_schema.max_retries {
+                thread.schema_retries = 0;
+                let msg = format!(
+                    "no valid submission after {} attempts",
+                    response_schema.max_retries + 1
+                );
+                let reply_xml = format!(
+                    "<AgentResponse><error>{}</error></AgentResponse>",
+                    translate::xml_escape_text(&msg)
+                );
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: reply_xml.into_bytes(),
+                });
+            }
+
+            // Check agentic iteration limit before calling Opus
+            if let Some(result) = self.check_agentic_limit(thread) {
+                return result;
+            }
+
+            let response = self
+                .call_opus(thread_id, thread)
+                .await
+                .map_err(PipelineError::Handler)?;
+            action = self.process_response(&response);
         }
     }
 
@@ -408,13 +1092,19 @@ impl CodingAgentHandler {
     /// the routing loop handles it. Otherwise, normal dispatch.
     async fn dispatch_or_route(
         &self,
+        thread_id: &str,
         thread: &mut AgentThread,
         action: ResponseAction,
         allowed_tools: &[String],
     ) -> HandlerResult {
         match action {
+            ResponseAction::Submit { .. } | ResponseAction::FinalText { .. }
+                if self.response_schema.is_some() =>
+            {
+                self.dispatch_structured(thread_id, thread, action).await
+            }
             ResponseAction::FinalText { blocks, text } if self.semantic_router.is_some() => {
-                self.dispatch_with_routing(thread, blocks, text, allowed_tools, 0)
+                self.dispatch_with_routing(thread_id, thread, blocks, text, allowed_tools, 0)
                     .await
             }
             _ => Self::dispatch_response(thread, action),
@@ -451,6 +1141,7 @@ impl CodingAgentHandler {
     /// - Recurses up to `max_routing_iterations` times
     async fn dispatch_with_routing(
         &self,
+        thread_id: &str,
         thread: &mut AgentThread,
         blocks: Vec<ContentBlock>,
         text: String,
@@ -487,7 +1178,7 @@ impl CodingAgentHandler {
 
                 // Call Opus again — it sees the result in context
                 let response = self
-                    .call_opus(thread)
+                    .call_opus(thread_id, thread)
                     .await
                     .map_err(PipelineError::Handler)?;
                 let action = self.process_response(&response);
@@ -499,6 +1190,7 @@ impl CodingAgentHandler {
                     } => {
                         // Recurse: Opus might express another tool intent
                         Box::pin(self.dispatch_with_routing(
+                            thread_id,
                             thread,
                             new_blocks,
                             new_text,
@@ -522,7 +1214,7 @@ impl CodingAgentHandler {
 
                 // Call Opus again with the failure note
                 let response = self
-                    .call_opus(thread)
+                    .call_opus(thread_id, thread)
                     .await
                     .map_err(PipelineError::Handler)?;
                 let action = self.process_response(&response);
@@ -553,6 +1245,78 @@ enum ResponseAction {
         blocks: Vec<ContentBlock>,
         text: String,
     },
+    /// A `submit` call on a schema-bound agent (terminal).
+    Submit {
+        blocks: Vec<ContentBlock>,
+        tool_use_id: String,
+        input: serde_json::Value,
+        /// Other tool_use ids in the same response (not executed).
+        skipped: Vec<String>,
+    },
+}
+
+/// Parse the `exit_code: N` line of a command-exec result.
+fn exit_code(output: &str) -> Option<i32> {
+    output
+        .lines()
+        .find_map(|l| l.strip_prefix("exit_code:"))
+        .and_then(|code| code.trim().parse().ok())
+}
+
+/// One line per verify command: `PASS cmd` / `FAIL cmd (exit_code: N)`.
+fn verification_summary(outcomes: &[VerifyOutcome]) -> String {
+    outcomes
+        .iter()
+        .map(|o| {
+            if o.passed {
+                format!("PASS {}", o.command)
+            } else {
+                match exit_code(&o.output) {
+                    Some(code) => format!("FAIL {} (exit_code: {code})", o.command),
+                    None => format!("FAIL {} ({})", o.command, truncate_text(&o.output, 80)),
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Insert a `<verification>` summary into a held `<AgentResponse>` reply.
+fn with_verification_summary(reply_xml: &[u8], outcomes: &[VerifyOutcome]) -> Vec<u8> {
+    let reply = String::from_utf8_lossy(reply_xml);
+    let summary = format!(
+        "<verification>{}</verification></AgentResponse>",
+        translate::xml_escape_text(&verification_summary(outcomes))
+    );
+    reply.replacen("</AgentResponse>", &summary, 1).into_bytes()
+}
+
+/// User turn describing failed verify commands (output tail only).
+fn verification_feedback(outcomes: &[VerifyOutcome]) -> String {
+    let mut msg = String::from(
+        "Verification failed. Fix the problems below, then finish again.\n",
+    );
+    for o in outcomes.iter().filter(|o| !o.passed) {
+        let chars: Vec<char> = o.output.chars().collect();
+        let tail: String = if chars.len() > VERIFY_FEEDBACK_CHARS {
+            let kept: String = chars[chars.len() - VERIFY_FEEDBACK_CHARS..].iter().collect();
+            format!("...\n{kept}")
+        } else {
+            o.output.clone()
+        };
+        msg.push_str(&format!("\n$ {}\n{tail}\n", o.command));
+    }
+    msg
+}
+
+/// Error results for tool calls issued alongside `submit`.
+fn skipped_tool_results(ids: Vec<String>) -> impl Iterator<Item = ToolResultBlock> {
+    ids.into_iter().map(|tool_use_id| ToolResultBlock {
+        tool_use_id,
+        content: "not executed: submit ends the turn".into(),
+        is_error: true,
+        media: Vec::new(),
+    })
 }
 
 #[async_trait]
@@ -570,7 +1334,8 @@ impl Handler for CodingAgentHandler {
 
         if is_tool_response {
             // ── Tool response path ──
-            let (result_content, is_error) = translate::xml_response_to_result(&xml_str);
+            let (mut result_content, is_error) = translate::xml_response_to_result(&xml_str);
+            let media: Vec<ContentBlock> = translate::xml_response_media(&xml_str).into_iter().collect();
 
             // Extract state, replacing with Ready temporarily
             let old_state = std::mem::replace(&mut thread.state, AgentState::Ready);
@@ -604,10 +1369,20 @@ impl Handler for CodingAgentHandler {
                         detail: completed_detail,
                     });
 
+                    self.observe_file_result(
+                        &thread_id,
+                        thread,
+                        pending.get(current_index),
+                        &mut result_content,
+                        is_error,
+                    )
+                    .await;
+
                     collected.push(ToolResultBlock {
                         tool_use_id,
                         content: result_content,
                         is_error,
+                        media,
                     });
 
                     let next_index = current_index + 1;
@@ -632,10 +1407,11 @@ impl Handler for CodingAgentHandler {
                             collected,
                             current_index: next_index,
                         };
-                        return Ok(HandlerResponse::Send {
+                        let result = Ok(HandlerResponse::Send {
                             to: next_name,
                             payload_xml: xml.into_bytes(),
                         });
+                        return self.prepare_mutation(&thread_id, thread, result).await;
                     }
 
                     // All collected — record in conversation history and call Opus again
@@ -655,7 +1431,7 @@ impl Handler for CodingAgentHandler {
                         return result;
                     }
 
-                    let response = match self.call_opus(thread).await {
+                    let response = match self.call_opus(&thread_id, thread).await {
                         Ok(r) => r,
                         Err(e) => {
                             self.emit_error(&thread_id, &e);
@@ -675,7 +1451,29 @@ impl Handler for CodingAgentHandler {
                         }
                     }
 
-                    let result = self.dispatch_or_route(thread, action, &[]).await;
+                    let result = self.dispatch_or_route(&thread_id, thread, action, &[]).await;
+                    let result = self.maybe_start_verification(&thread_id, thread, result);
+                    let result = self.prepare_mutation(&thread_id, thread, result).await;
+                    self.maybe_emit_response(&thread_id, &result);
+                    self.maybe_emit_conversation(&thread_id, thread);
+                    result
+                }
+                AgentState::Verifying {
+                    reply_xml,
+                    outcomes,
+                    current_index,
+                } => {
+                    let result = self
+                        .continue_verification(
+                            &thread_id,
+                            thread,
+                            reply_xml,
+                            outcomes,
+                            current_index,
+                            (result_content, is_error),
+                        )
+                        .await;
+                    let result = self.prepare_mutation(&thread_id, thread, result).await;
                     self.maybe_emit_response(&thread_id, &result);
                     self.maybe_emit_conversation(&thread_id, thread);
                     result
@@ -695,8 +1493,20 @@ impl Handler for CodingAgentHandler {
                 .or_else(|| extract_tag(&xml_str, "content"))
                 .unwrap_or_else(|| xml_str.to_string());
 
-            thread.push_user_message(&task);
+            let attachments = extract_all_tags(&xml_str, "attach");
+            if attachments.is_empty() {
+                thread.push_user_message(&task);
+            } else {
+                let workspace = self.thread_workspace(&thread_id).await;
+                thread.push_user_blocks(attachment_blocks(
+                    workspace.as_ref().map(|ws| ws.as_deref()).map_err(|e| e.as_str()),
+                    &attachments,
+                    &task,
+                ));
+            }
             thread.state = AgentState::Ready;
+            thread.schema_retries = 0;
+            thread.verify_retries = 0;
 
             // Lifecycle: thinking (new task)
             self.maybe_emit(PipelineEvent::AgentThinking {
@@ -710,7 +1520,7 @@ impl Handler for CodingAgentHandler {
                 return result;
             }
 
-            let response = match self.call_opus(thread).await {
+            let response = match self.call_opus(&thread_id, thread).await {
                 Ok(r) => r,
                 Err(e) => {
                     self.emit_error(&thread_id, &e);
@@ -730,7 +1540,9 @@ impl Handler for CodingAgentHandler {
                 }
             }
 
-            let result = self.dispatch_or_route(thread, action, &[]).await;
+            let result = self.dispatch_or_route(&thread_id, thread, action, &[]).await;
+            let result = self.maybe_start_verification(&thread_id, thread, result);
+            let result = self.prepare_mutation(&thread_id, thread, result).await;
             self.maybe_emit_response(&thread_id, &result);
             self.maybe_emit_conversation(&thread_id, thread);
             result
@@ -739,7 +1551,18 @@ impl Handler for CodingAgentHandler {
 }
 
 /// Convert a slice of Messages into ConversationEntry items for TUI display.
+///
+/// Thinking blocks are left out; see [`build_conversation_entries_with_thinking`].
 pub fn build_conversation_entries(messages: &[crate::llm::types::Message]) -> Vec<ConversationEntry> {
+    build_conversation_entries_with_thinking(messages, false)
+}
+
+/// Like [`build_conversation_entries`], optionally including thinking blocks
+/// as `"thinking"` entries.
+pub fn build_conversation_entries_with_thinking(
+    messages: &[crate::llm::types::Message],
+    include_thinking: bool,
+) -> Vec<ConversationEntry> {
     use crate::llm::types::{ContentBlock, MessageContent};
     let mut entries = Vec::new();
     for msg in messages {
@@ -779,15 +1602,43 @@ pub fn build_conversation_entries(messages: &[crate::llm::types::Message]) -> Ve
                             content, is_error, ..
                         } => {
                             let err = is_error.unwrap_or(false);
-                            let text = content.as_deref().unwrap_or("");
+                            let text = content.as_ref().and_then(|c| c.text()).unwrap_or_default();
                             entries.push(ConversationEntry {
                                 role: "tool_result".into(),
-                                summary: truncate_text(text, 120),
+                                summary: truncate_text(&text, 120),
                                 is_tool_use: false,
                                 tool_name: None,
                                 is_error: err,
                             });
                         }
+                        ContentBlock::Thinking { thinking, .. } if include_thinking => {
+                            entries.push(ConversationEntry {
+                                role: "thinking".into(),
+                                summary: truncate_text(thinking, 200),
+                                is_tool_use: false,
+                                tool_name: None,
+                                is_error: false,
+                            });
+                        }
+                        ContentBlock::RedactedThinking { .. } if include_thinking => {
+                            entries.push(ConversationEntry {
+                                role: "thinking".into(),
+                                summary: "(redacted)".into(),
+                                is_tool_use: false,
+                                tool_name: None,
+                                is_error: false,
+                            });
+                        }
+                        ContentBlock::Image { source } | ContentBlock::Document { source } => {
+                            entries.push(ConversationEntry {
+                                role: msg.role.clone(),
+                                summary: format!("[attachment: {}]", source.media_type),
+                                is_tool_use: false,
+                                tool_name: None,
+                                is_error: false,
+                            });
+                        }
+                        ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
                     }
                 }
             }
@@ -796,14 +1647,29 @@ pub fn build_conversation_entries(messages: &[crate::llm::types::Message]) -> Ve
     entries
 }
 
+/// Whether a tool call changes files: a file-write, file-edit or
+/// notebook-edit, a `replace` applying its preview (a `replace` without a
+/// token is a dry run), or a `diagnostics` applying suggestions.
+fn is_mutation(call: &PendingToolCall) -> bool {
+    match call.tool_name.as_str() {
+        "replace" => call.input.get("token").is_some(),
+        "diagnostics" => call.input["action"] == "apply_suggestion",
+        name => MUTATING_TOOLS.contains(&name),
+    }
+}
+
 /// Truncate text to a maximum character count, appending "..." if truncated.
 fn truncate_text(text: &str, max: usize) -> String {
     // Take first line only for summary
     let first_line = text.lines().next().unwrap_or(text);
-    if first_line.len() <= max {
+    if first_line.chars().count() <= max {
         first_line.to_string()
     } else {
-        format!("{}...", &first_line[..max.saturating_sub(3)])
+        let cut = first_line
+            .char_indices()
+            .nth(max.saturating_sub(3))
+            .map_or(first_line.len(), |(i, _)| i);
+        format!("{}...", &first_line[..cut])
     }
 }
 
@@ -860,6 +1726,51 @@ fn extract_tag(xml: &str, tag: &str) -> Option<String> {
     }
 }
 
+/// Extract the text of every `<tag>…</tag>` occurrence, in order.
+fn extract_all_tags(xml: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let body = &rest[start + open.len()..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        found.push(xml_unescape(&body[..end]));
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+/// Build a task message with attached images/PDFs ahead of the text.
+///
+/// Paths are resolved for reading through the workspace, when there is
+/// one, like any file tool's; with `Err` (the thread's profile has no
+/// workspace) none are loaded. Attachments that fail to resolve or load are
+/// noted in the text instead, so the task still runs and Opus knows what it
+/// is missing.
+fn attachment_blocks(
+    workspace: Result<Option<&Workspace>, &str>,
+    paths: &[String],
+    task: &str,
+) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut text = task.to_string();
+    for path in paths {
+        let loaded = workspace
+            .map_err(str::to_string)
+            .and_then(|ws| workspace::resolve_in(ws, "attach", path, Access::Read))
+            .and_then(|path| crate::llm::media::load_media(&path));
+        match loaded {
+            Ok(block) => blocks.push(block),
+            Err(e) => text.push_str(&format!("\n\n(attachment not loaded: {e})")),
+        }
+    }
+    blocks.push(ContentBlock::Text { text });
+    blocks
+}
+
 /// Unescape XML entities back to plain text.
 fn xml_unescape(s: &str) -> String {
     s.replace("&amp;", "&")
@@ -1130,6 +2041,7 @@ mod tests {
             tool_use_id: "t1".into(),
             content: "42".into(),
             is_error: false,
+            media: vec![],
         }]);
         assert_eq!(thread.messages.len(), 3);
     }
@@ -1141,6 +2053,66 @@ mod tests {
         assert_eq!(task, "Read src/main.rs");
     }
 
+    #[test]
+    fn extract_all_attach_tags() {
+        let xml = "<AgentTask><task>Build it</task><attach>a.png</attach><attach>b&amp;c.pdf</attach></AgentTask>";
+        assert_eq!(extract_all_tags(xml, "attach"), vec!["a.png", "b&c.pdf"]);
+        assert!(extract_all_tags(xml, "missing").is_empty());
+    }
+
+    #[test]
+    fn attachments_precede_task_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("mockup.png");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR").unwrap();
+        let paths = vec![
+            png.to_string_lossy().into_owned(),
+            dir.path().join("gone.pdf").to_string_lossy().into_owned(),
+        ];
+
+        let blocks = attachment_blocks(Ok(None), &paths, "Match this mockup");
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(blocks[0], ContentBlock::Image { .. }));
+        match &blocks[1] {
+            ContentBlock::Text { text } => {
+                assert!(text.starts_with("Match this mockup"));
+                assert!(text.contains("attachment not loaded"));
+            }
+            other => panic!("expected text, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn attachments_stay_in_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        std::fs::create_dir(&root).unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+        std::fs::write(root.join("mockup.png"), png).unwrap();
+        std::fs::write(dir.path().join("secret.png"), png).unwrap();
+        let ws = Workspace::new("coding", &root, &[]).unwrap();
+        let paths = vec!["mockup.png".to_string(), "../secret.png".to_string()];
+
+        // Relative paths are taken from the root; escapes are refused
+        let blocks = attachment_blocks(Ok(Some(&ws)), &paths, "Match this mockup");
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(blocks[0], ContentBlock::Image { .. }));
+        match &blocks[1] {
+            ContentBlock::Text { text } => {
+                assert!(text.contains("path outside workspace: ../secret.png"), "{text}");
+            }
+            other => panic!("expected text, got {other:?}"),
+        }
+
+        // A profile without a workspace loads nothing
+        let blocks = attachment_blocks(Err("profile 'admin' declares no workspace"), &paths, "Match this mockup");
+        assert_eq!(blocks.len(), 1);
+        match &blocks[0] {
+            ContentBlock::Text { text } => assert!(text.contains("declares no workspace"), "{text}"),
+            other => panic!("expected text, got {other:?}"),
+        }
+    }
+
     // ── Semantic Routing Integration Tests ──
 
     fn build_test_router() -> crate::routing::SemanticRouter {
@@ -1308,6 +2280,15 @@ mod tests {
         assert!(result.ends_with("..."));
     }
 
+    #[test]
+    fn truncate_text_cuts_on_char_boundaries() {
+        let text = "é".repeat(100);
+        let cut = truncate_text(&text, 80);
+        assert_eq!(cut.chars().count(), 80);
+        assert!(cut.ends_with("..."));
+        assert_eq!(truncate_text("日本語", 3), "日本語");
+    }
+
     #[test]
     fn summarize_grep_search() {
         let input = serde_json::json!({"pattern": "fn main"});
@@ -1472,6 +2453,1148 @@ mod tests {
         }
     }
 
+    // ── Structured answer (response_schema) tests ──
+
+    fn schema_config(max_retries: usize) -> AgentConfig {
+        AgentConfig {
+            response_schema: Some(ResponseSchema {
+                description: "Report the verdict.".into(),
+                schema: serde_json::json!({
+                    "type": "object",
+                    "properties": { "verdict": { "type": "string", "enum": ["pass", "fail"] } },
+                    "required": ["verdict"]
+                }),
+                max_retries,
+            }),
+            ..AgentConfig::default()
+        }
+    }
+
+    fn submit_response(input: serde_json::Value) -> crate::llm::types::MessagesResponse {
+        crate::llm::types::MessagesResponse {
+            id: "msg_s".into(),
+            model: "test".into(),
+            content: vec![
+                ContentBlock::ToolUse {
+                    id: "toolu_read".into(),
+                    name: "file-read".into(),
+                    input: serde_json::json!({"path": "a.rs"}),
+                },
+                ContentBlock::ToolUse {
+                    id: "toolu_submit".into(),
+                    name: SUBMIT_TOOL_NAME.into(),
+                    input,
+                },
+            ],
+            stop_reason: Some("tool_use".into()),
+            usage: crate::llm::types::Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+            },
+        }
+    }
+
+    /// (content, is_error) of each tool_result block in a message.
+    fn tool_result_blocks(msg: &crate::llm::types::Message) -> Vec<(String, bool)> {
+        match &msg.content {
+            crate::llm::types::MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolResult {
+                        content, is_error, ..
+                    } => Some((
+                        content.as_ref().and_then(|c| c.text()).unwrap_or_default(),
+                        is_error.unwrap_or(false),
+                    )),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn from_config_with_schema_adds_submit_tool() {
+        let handler = CodingAgentHandler::from_config(
+            mock_pool(),
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(3),
+        );
+        assert_eq!(handler.tool_definitions.len(), 3);
+        let submit = handler.tool_definitions.last().unwrap();
+        assert_eq!(submit.name, SUBMIT_TOOL_NAME);
+        assert_eq!(submit.input_schema["required"][0], "verdict");
+    }
+
+    #[test]
+    fn process_response_submit_is_terminal() {
+        let handler = CodingAgentHandler::from_config(
+            mock_pool(),
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(3),
+        );
+        let action = handler.process_response(&submit_response(serde_json::json!({"verdict": "pass"})));
+        match action {
+            ResponseAction::Submit {
+                tool_use_id,
+                input,
+                skipped,
+                ..
+            } => {
+                assert_eq!(tool_use_id, "toolu_submit");
+                assert_eq!(input["verdict"], "pass");
+                assert_eq!(skipped, vec!["toolu_read".to_string()]);
+            }
+            _ => panic!("expected Submit"),
+        }
+    }
+
+    #[test]
+    fn process_response_submit_ignored_without_schema() {
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into());
+        let action = handler.process_response(&submit_response(serde_json::json!({"verdict": "pass"})));
+        assert!(matches!(action, ResponseAction::ToolCalls { .. }));
+    }
+
+    #[tokio::test]
+    async fn valid_submission_replies_with_json() {
+        let handler = CodingAgentHandler::from_config(
+            mock_pool(),
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(3),
+        );
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Check it");
+        let action = handler.process_response(&submit_response(serde_json::json!({"verdict": "fail"})));
+
+        let result = handler.dispatch_or_route("t1", &mut thread, action, &[]).await.unwrap();
+        match result {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let json = extract_tag(&xml, "result").unwrap();
+                let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+                assert_eq!(value["verdict"], "fail");
+            }
+            _ => panic!("expected Reply"),
+        }
+        // user + assistant(tool_use) + tool_results(submit + skipped)
+        assert_eq!(thread.messages.len(), 3);
+        let results = tool_result_blocks(&thread.messages[2]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], ("accepted".to_string(), false));
+        assert!(results[1].1);
+    }
+
+    #[tokio::test]
+    async fn invalid_submission_gives_up_after_retries() {
+        let handler = CodingAgentHandler::from_config(
+            mock_pool(),
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(0),
+        );
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Check it");
+        let action =
+            handler.process_response(&submit_response(serde_json::json!({"verdict": "maybe"})));
+
+        let result = handler.dispatch_or_route("t1", &mut thread, action, &[]).await.unwrap();
+        match result {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("<error>no valid submission after 1 attempts</error>"));
+            }
+            _ => panic!("expected Reply"),
+        }
+        // The rejection was recorded for the model to see
+        let results = tool_result_blocks(&thread.messages[2]);
+        assert!(results[0].1);
+        assert!(results[0].0.contains("$.verdict: must be one of"));
+        assert_eq!(thread.schema_retries, 0);
+    }
+
+    /// A stand-in Messages API on 127.0.0.1 that answers each request with
+    /// the next canned response. Returns the pool pointed at it and the
+    /// request bodies it received.
+    async fn mock_api(
+        responses: Vec<serde_json::Value>,
+    ) -> (Arc<Mutex<LlmPool>>, Arc<std::sync::Mutex<Vec<serde_json::Value>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let Ok((mut conn, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let head_end = loop {
+                    if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                    match conn.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..head_end]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(0);
+                while request.len() < head_end + length {
+                    match conn.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                if let Ok(body) = serde_json::from_slice(&request[head_end..]) {
+                    recorded.lock().unwrap().push(body);
+                }
+                let body = response.to_string();
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = conn.write_all(reply.as_bytes()).await;
+            }
+        });
+        let base_url = format!("http://127.0.0.1:{port}");
+        let pool = LlmPool::with_base_url("test-key".into(), "opus", base_url);
+        (Arc::new(Mutex::new(pool)), requests)
+    }
+
+    /// The `messages` of a recorded API request.
+    fn request_messages(body: &serde_json::Value) -> Vec<crate::llm::types::Message> {
+        serde_json::from_value(body["messages"].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn plain_text_is_reprompted_when_schema_set() {
+        let (pool, requests) = mock_api(vec![serde_json::json!({
+            "id": "msg_2",
+            "model": "test",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_submit",
+                "name": SUBMIT_TOOL_NAME,
+                "input": {"verdict": "pass"}
+            }],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        })])
+        .await;
+        let handler = CodingAgentHandler::from_config(
+            pool,
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(1),
+        );
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Check it");
+        let action = ResponseAction::FinalText {
+            blocks: vec![ContentBlock::Text {
+                text: "It passes.".into(),
+            }],
+            text: "It passes.".into(),
+        };
+
+        let result = handler.dispatch_or_route("t1", &mut thread, action, &[]).await.unwrap();
+        // The retry submitted a valid answer, which becomes the reply
+        match result {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("pass"), "{xml}");
+                assert!(!xml.contains("<error>"), "{xml}");
+            }
+            _ => panic!("expected Reply"),
+        }
+        assert_eq!(thread.schema_retries, 0);
+
+        // The second request carried the corrective message after the plain text
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let messages = request_messages(&requests[0]);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "assistant");
+        let last = messages.last().unwrap();
+        assert_eq!(last.role, "user");
+        assert_eq!(last.content.text().as_deref(), Some(schema::SUBMIT_REMINDER));
+    }
+
+    #[tokio::test]
+    async fn plain_text_without_retries_left_is_an_error() {
+        let handler = CodingAgentHandler::from_config(
+            mock_pool(),
+            sample_tool_defs(),
+            "test".into(),
+            &schema_config(0),
+        );
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Check it");
+        let action = ResponseAction::FinalText {
+            blocks: vec![ContentBlock::Text {
+                text: "It passes.".into(),
+            }],
+            text: "It passes.".into(),
+        };
+
+        let result = handler.dispatch_or_route("t1", &mut thread, action, &[]).await.unwrap();
+        match result {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("no valid submission after 1 attempts"), "{xml}");
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    // ── Context projection tests ──
+
+    /// A canned end_turn text response from the mock API.
+    fn text_response(text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": "msg_t",
+            "model": "test",
+            "content": [{"type": "text", "text": text}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        })
+    }
+
+    /// The text of a tool_result block for `tool_use_id` in a message.
+    fn tool_result_text(msg: &crate::llm::types::Message, tool_use_id: &str) -> Option<String> {
+        let crate::llm::types::MessageContent::Blocks(blocks) = &msg.content else {
+            return None;
+        };
+        blocks.iter().find_map(|b| match b {
+            ContentBlock::ToolResult {
+                tool_use_id: id,
+                content,
+                ..
+            } if id == tool_use_id => content.as_ref().and_then(|c| c.text()),
+            _ => None,
+        })
+    }
+
+    #[tokio::test]
+    async fn curated_call_sends_projected_transcript() {
+        // The librarian shelves both tool results and folds the first reply
+        let curation = format!(
+            "<CurationDecision><page_out><segment id=\"{}\"/><segment id=\"{}\"/></page_out>\
+             <fold><segment id=\"{}\"/></fold></CurationDecision>",
+            context::tool_segment_id("toolu_1"),
+            context::tool_segment_id("toolu_2"),
+            context::message_segment_id(1),
+        );
+        let (pool, requests) =
+            mock_api(vec![text_response(&curation), text_response("Done.")]).await;
+        let tmp = tempfile::TempDir::new().unwrap();
+        let kernel = crate::kernel::Kernel::open(&tmp.path().join("data")).unwrap();
+        let kernel_arc = Arc::new(Mutex::new(kernel));
+        let lib = Arc::new(Mutex::new(Librarian::new(pool.clone(), kernel_arc)));
+        let handler =
+            CodingAgentHandler::with_librarian(pool, lib, sample_tool_defs(), "test".into());
+
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Fix the failing test");
+        for (id, name, output) in [
+            ("toolu_1", "file-read", "fn parse() {}"),
+            ("toolu_2", "command-exec", "exit_code: 0"),
+        ] {
+            thread.push_assistant_blocks(vec![
+                ContentBlock::Text {
+                    text: format!("Running {name}."),
+                },
+                ContentBlock::ToolUse {
+                    id: id.into(),
+                    name: name.into(),
+                    input: serde_json::json!({}),
+                },
+            ]);
+            thread.push_tool_results(vec![ToolResultBlock {
+                tool_use_id: id.into(),
+                content: output.into(),
+                is_error: false,
+                media: Vec::new(),
+            }]);
+        }
+
+        handler.call_opus("t1", &thread).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let curation_prompt = request_messages(&requests[0])[0].content.text().unwrap();
+        let budget = format!("<token_budget>{}</token_budget>", context::TOKEN_BUDGET);
+        assert!(curation_prompt.contains(&budget));
+
+        let sent = request_messages(&requests[1]);
+        assert_eq!(sent.len(), thread.messages.len());
+        // Outside the tail: shelved and folded segments go out as stubs
+        let shelved = format!("[shelved: {}]", context::tool_segment_id("toolu_1"));
+        assert_eq!(tool_result_text(&sent[2], "toolu_1"), Some(shelved));
+        let folded = format!("[folded: {}]", context::message_segment_id(1));
+        assert_eq!(sent[1].content.text(), Some(folded));
+        // The protected tail is sent verbatim, even though its result was shelved
+        let tail = sent.len() - context::PROTECTED_TAIL;
+        for (sent, raw) in sent[tail..].iter().zip(&thread.messages[tail..]) {
+            assert_eq!(
+                serde_json::to_value(sent).unwrap(),
+                serde_json::to_value(raw).unwrap()
+            );
+        }
+        assert_eq!(tool_result_text(&sent[4], "toolu_2").as_deref(), Some("exit_code: 0"));
+        // Every tool_use is still answered by a tool_result in the next message
+        for (index, msg) in sent.iter().enumerate() {
+            let crate::llm::types::MessageContent::Blocks(blocks) = &msg.content else {
+                continue;
+            };
+            for block in blocks {
+                if let ContentBlock::ToolUse { id, .. } = block {
+                    assert!(tool_result_text(&sent[index + 1], id).is_some(), "{id} unanswered");
+                }
+            }
+        }
+    }
+
+    // ── Self-verification (verify:) tests ──
+
+    fn verify_handler(max_retries: usize) -> CodingAgentHandler {
+        let config = AgentConfig {
+            verify: Some(VerifyConfig {
+                commands: vec!["cargo check".into(), "cargo test -q".into()],
+                max_retries,
+                timeout_secs: Some(300),
+            }),
+            ..AgentConfig::default()
+        };
+        CodingAgentHandler::from_config(mock_pool(), sample_tool_defs(), "test".into(), &config)
+    }
+
+    fn final_reply(text: &str) -> HandlerResult {
+        Ok(HandlerResponse::Reply {
+            payload_xml: format!("<AgentResponse><result>{text}</result></AgentResponse>")
+                .into_bytes(),
+        })
+    }
+
+    fn exec_response(code: i32, stdout: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: crate::tools::ToolResponse::ok(&format!(
+                "exit_code: {code}\nstdout:\n{stdout}\nstderr:\n"
+            )),
+            tag: "ToolResponse".into(),
+        }
+    }
+
+    fn exec_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "command-exec".into(),
+            own_name: "coding-agent".into(),
+        }
+    }
+
+    #[test]
+    fn file_edit_marks_thread_modified() {
+        let mut thread = AgentThread::new();
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![PendingToolCall {
+                tool_use_id: "toolu_1".into(),
+                tool_name: "file-edit".into(),
+                input: serde_json::json!({"path": "a.rs"}),
+            }],
+        };
+        CodingAgentHandler::dispatch_response(&mut thread, action).unwrap();
+        assert!(thread.files_modified);
+    }
+
+    /// `ws` as the `coding` profile's workspace, with thread `t1` running
+    /// under that profile in `kernel`.
+    async fn coding_workspace(
+        kernel: &Arc<Mutex<Kernel>>,
+        ws: Arc<Workspace>,
+    ) -> HashMap<String, Arc<Workspace>> {
+        kernel.lock().await.threads_mut().register_thread("t1", "console", "coding-agent", "coding");
+        HashMap::from([("coding".to_string(), ws)])
+    }
+
+    #[tokio::test]
+    async fn checkpoints_resolve_in_the_callers_workspace() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let coding = dir.path().join("coding");
+        std::fs::create_dir(&coding).unwrap();
+        std::fs::write(coding.join("a.rs"), "old").unwrap();
+        let ws = Arc::new(Workspace::new("coding", &coding, &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let workspaces = coding_workspace(&kernel, ws).await;
+        kernel.lock().await.threads_mut().register_thread("t2", "console", "coding-agent", "admin");
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), workspaces);
+
+        let edit = |thread: &mut AgentThread| {
+            thread.push_user_message("fix a.rs");
+            let action = ResponseAction::ToolCalls {
+                blocks: vec![],
+                pending: vec![PendingToolCall {
+                    tool_use_id: "toolu_1".into(),
+                    tool_name: "file-edit".into(),
+                    input: serde_json::json!({"path": "a.rs"}),
+                }],
+            };
+            CodingAgentHandler::dispatch_response(thread, action)
+        };
+
+        // t2's profile has no workspace: nothing is resolved for it
+        let mut thread = AgentThread::new();
+        let result = edit(&mut thread);
+        handler.prepare_mutation("t2", &mut thread, result).await.unwrap();
+        assert_eq!(kernel.lock().await.checkpoints().count(), 0);
+
+        // t1's path lands in the coding workspace
+        let mut thread = AgentThread::new();
+        let result = edit(&mut thread);
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+        let k = kernel.lock().await;
+        let cp = k.checkpoints().last_active().unwrap();
+        assert_eq!(cp.path, coding.canonicalize().unwrap().join("a.rs").to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn mutating_call_is_checkpointed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "old").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws).await);
+
+        let mut thread = AgentThread::new();
+        thread.push_user_message("fix a.rs");
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![
+                PendingToolCall {
+                    tool_use_id: "toolu_1".into(),
+                    tool_name: "file-read".into(),
+                    input: serde_json::json!({"path": "a.rs"}),
+                },
+                PendingToolCall {
+                    tool_use_id: "toolu_2".into(),
+                    tool_name: "file-edit".into(),
+                    input: serde_json::json!({"path": "a.rs"}),
+                },
+            ],
+        };
+
+        // Reads are not checkpointed
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+        assert_eq!(kernel.lock().await.checkpoints().count(), 0);
+
+        if let AgentState::AwaitingTools { current_index, .. } = &mut thread.state {
+            *current_index = 1;
+        }
+        let result = Ok(HandlerResponse::Send {
+            to: "file-edit".into(),
+            payload_xml: Vec::new(),
+        });
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+
+        let k = kernel.lock().await;
+        let cp = k.checkpoints().last_active().unwrap();
+        assert_eq!(cp.thread_id, "t1");
+        assert_eq!(cp.message_id, "msg-0001");
+        assert!(cp.path.ends_with("a.rs"));
+        assert!(cp.pre_image.is_some());
+    }
+
+    #[tokio::test]
+    async fn notebook_edit_can_be_undone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let nb = dir.path().join("nb.ipynb");
+        std::fs::write(&nb, "{\"cells\": []}").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws).await);
+
+        let mut thread = AgentThread::new();
+        thread.push_user_message("add a cell");
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![PendingToolCall {
+                tool_use_id: "toolu_1".into(),
+                tool_name: "notebook-edit".into(),
+                input: serde_json::json!({"path": "nb.ipynb", "action": "insert"}),
+            }],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        assert!(thread.files_modified);
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+
+        // The tool writes; /undo puts the notebook back
+        std::fs::write(&nb, "{\"cells\": [{}]}").unwrap();
+        let undone = kernel.lock().await.undo_last().unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(std::fs::read_to_string(&nb).unwrap(), "{\"cells\": []}");
+    }
+
+    #[tokio::test]
+    async fn replace_apply_gets_message_id() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), HashMap::new());
+
+        let call = |id: &str, input: serde_json::Value| PendingToolCall {
+            tool_use_id: id.into(),
+            tool_name: "replace".into(),
+            input,
+        };
+        let mut thread = AgentThread::new();
+        thread.push_user_message("rename foo");
+
+        // A preview changes nothing and goes out as-is
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_1", serde_json::json!({"pattern": "foo"}))],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        assert!(!thread.files_modified);
+        let Ok(HandlerResponse::Send { payload_xml, .. }) =
+            handler.prepare_mutation("t1", &mut thread, result).await
+        else {
+            panic!("expected Send");
+        };
+        assert!(!String::from_utf8(payload_xml).unwrap().contains("message_id"));
+
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_2", serde_json::json!({"token": "0123456789ab"}))],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        assert!(thread.files_modified);
+        let Ok(HandlerResponse::Send { payload_xml, .. }) =
+            handler.prepare_mutation("t1", &mut thread, result).await
+        else {
+            panic!("expected Send");
+        };
+        let xml = String::from_utf8(payload_xml).unwrap();
+        assert!(xml.contains("<message_id>msg-0001</message_id>"), "{xml}");
+        assert_eq!(kernel.lock().await.checkpoints().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn diagnostics_apply_is_a_mutation() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), HashMap::new());
+
+        let call = |id: &str, input: serde_json::Value| PendingToolCall {
+            tool_use_id: id.into(),
+            tool_name: "diagnostics".into(),
+            input,
+        };
+        let mut thread = AgentThread::new();
+        thread.push_user_message("fix the warnings");
+
+        // A check changes nothing
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_1", serde_json::json!({"action": "check"}))],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        assert!(!thread.files_modified);
+        let Ok(HandlerResponse::Send { payload_xml, .. }) =
+            handler.prepare_mutation("t1", &mut thread, result).await
+        else {
+            panic!("expected Send");
+        };
+        assert!(!String::from_utf8(payload_xml).unwrap().contains("message_id"));
+
+        // Applying suggestions marks the thread and carries the message ID
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call(
+                "toolu_2",
+                serde_json::json!({"action": "apply_suggestion", "ids": ["1.1"]}),
+            )],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        assert!(thread.files_modified);
+        let Ok(HandlerResponse::Send { payload_xml, .. }) =
+            handler.prepare_mutation("t1", &mut thread, result).await
+        else {
+            panic!("expected Send");
+        };
+        let xml = String::from_utf8(payload_xml).unwrap();
+        assert!(xml.contains("<message_id>msg-0001</message_id>"), "{xml}");
+    }
+
+    #[tokio::test]
+    async fn seen_version_becomes_expected_hash() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn a() {}").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn b() {}").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws).await);
+
+        let call = |id: &str, tool: &str, path: &str| PendingToolCall {
+            tool_use_id: id.into(),
+            tool_name: tool.into(),
+            input: serde_json::json!({"path": path}),
+        };
+        let mut thread = AgentThread::new();
+        let read = call("toolu_1", "file-read", "a.rs");
+        let mut content = "1| fn a() {}\n[version: 0123456789abcdef mtime: 1]".to_string();
+        handler
+            .observe_file_result("t1", &mut thread, Some(&read), &mut content, false)
+            .await;
+
+        // a.rs was read: its version rides along as expected_hash
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![
+                call("toolu_2", "file-edit", "a.rs"),
+                call("toolu_3", "file-write", "b.rs"),
+            ],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        let Ok(HandlerResponse::Send { payload_xml, .. }) =
+            handler.prepare_mutation("t1", &mut thread, result).await
+        else {
+            panic!("expected Send");
+        };
+        let xml = String::from_utf8(payload_xml).unwrap();
+        assert!(xml.contains("<expected_hash>0123456789abcdef</expected_hash>"));
+        assert!(thread.write_warning.is_none());
+
+        // b.rs was never read: the write goes out as-is, with a warning queued
+        if let AgentState::AwaitingTools { current_index, .. } = &mut thread.state {
+            *current_index = 1;
+        }
+        let result = Ok(HandlerResponse::Send {
+            to: "file-write".into(),
+            payload_xml: Vec::new(),
+        });
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+        let mut content = "wrote 9 bytes to b.rs [version: fedcba9876543210 mtime: 1]".to_string();
+        let write = call("toolu_3", "file-write", "b.rs");
+        handler
+            .observe_file_result("t1", &mut thread, Some(&write), &mut content, false)
+            .await;
+        assert!(content.contains("warning: b.rs was changed without being read"));
+
+        let k = kernel.lock().await;
+        let b = dir.path().canonicalize().unwrap().join("b.rs");
+        assert_eq!(k.file_version("t1", &b), Some("fedcba9876543210"));
+    }
+
+    /// Run a call the handler prepared through `tool`; returns (result, is_error).
+    async fn run_prepared<T: Handler>(
+        tool: &T,
+        name: &str,
+        prepared: HandlerResult,
+    ) -> (String, bool) {
+        let Ok(HandlerResponse::Send { to, payload_xml }) = prepared else {
+            panic!("expected Send");
+        };
+        assert_eq!(to, name);
+        let ctx = HandlerContext {
+            thread_id: "t1".into(),
+            from: "coding-agent".into(),
+            own_name: name.into(),
+        };
+        let payload = ValidatedPayload {
+            xml: payload_xml,
+            tag: translate::xml_tag_for_tool(name),
+        };
+        match tool.handle(payload, ctx).await.unwrap() {
+            HandlerResponse::Reply { payload_xml } => {
+                translate::xml_response_to_result(&String::from_utf8(payload_xml).unwrap())
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[tokio::test]
+    async fn file_edit_after_replace_is_not_a_conflict() {
+        use crate::tools::{file_edit::FileEditTool, replace::ReplaceTool, ToolPeer};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn old() {}\n").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws.clone()).await);
+        let mut replace = ReplaceTool::new();
+        replace.set_workspace(ws.clone());
+        replace.set_kernel(kernel.clone());
+        let mut file_edit = FileEditTool::default();
+        file_edit.set_workspace(ws);
+
+        let call = |id: &str, tool: &str, input: serde_json::Value| PendingToolCall {
+            tool_use_id: id.into(),
+            tool_name: tool.into(),
+            input,
+        };
+        let mut thread = AgentThread::new();
+        thread.push_user_message("rename old");
+        let read = call("toolu_1", "file-read", serde_json::json!({"path": "a.rs"}));
+        let mut content = format!(
+            "1| fn old() {{}}\n[version: {} mtime: 1]",
+            version::content_version(b"fn old() {}\n")
+        );
+        handler
+            .observe_file_result("t1", &mut thread, Some(&read), &mut content, false)
+            .await;
+
+        // Preview, then apply the preview
+        let preview =
+            serde_json::json!({"pattern": "old", "replacement": "new", "glob_filter": "*.rs"});
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_2", "replace", preview)],
+        };
+        let prepared = CodingAgentHandler::dispatch_response(&mut thread, action);
+        let prepared = handler.prepare_mutation("t1", &mut thread, prepared).await;
+        let (preview, is_error) = run_prepared(&replace, "replace", prepared).await;
+        assert!(!is_error, "{preview}");
+        let start = preview.rfind("token ").unwrap() + "token ".len();
+        let token = preview[start..].trim_end_matches('.');
+
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_3", "replace", serde_json::json!({"token": token}))],
+        };
+        let prepared = CodingAgentHandler::dispatch_response(&mut thread, action);
+        let prepared = handler.prepare_mutation("t1", &mut thread, prepared).await;
+        let (applied, is_error) = run_prepared(&replace, "replace", prepared).await;
+        assert!(!is_error, "{applied}");
+
+        // The edit's expected_hash is the version replace left, not the read one
+        let edit =
+            serde_json::json!({"path": "a.rs", "old_string": "fn new", "new_string": "fn newer"});
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![call("toolu_4", "file-edit", edit)],
+        };
+        let prepared = CodingAgentHandler::dispatch_response(&mut thread, action);
+        let prepared = handler.prepare_mutation("t1", &mut thread, prepared).await;
+        let (edited, is_error) = run_prepared(&file_edit, "file-edit", prepared).await;
+        assert!(!is_error, "{edited}");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.rs")).unwrap(),
+            "fn newer() {}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn command_exec_refreshes_only_its_redirect_targets() {
+        use crate::tools::{file_edit::FileEditTool, ToolPeer};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a.rs"), "a v1").unwrap();
+        std::fs::write(root.join("b.rs"), "b v1").unwrap();
+        let ws = Arc::new(Workspace::new("coding", &root, &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&root.join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws.clone()).await);
+        let mut file_edit = FileEditTool::default();
+        file_edit.set_workspace(ws);
+
+        let mut thread = AgentThread::new();
+        for (path, text) in [("a.rs", "a v1"), ("b.rs", "b v1")] {
+            let read = PendingToolCall {
+                tool_use_id: "toolu_1".into(),
+                tool_name: "file-read".into(),
+                input: serde_json::json!({"path": path}),
+            };
+            let mut content = format!(
+                "[version: {} mtime: 1]",
+                version::content_version(text.as_bytes())
+            );
+            handler
+                .observe_file_result("t1", &mut thread, Some(&read), &mut content, false)
+                .await;
+        }
+
+        // The command writes a.rs; someone edits b.rs while it runs
+        let exec = PendingToolCall {
+            tool_use_id: "toolu_2".into(),
+            tool_name: "command-exec".into(),
+            input: serde_json::json!({"command": "cargo run -q > a.rs"}),
+        };
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![exec.clone()],
+        };
+        let result = CodingAgentHandler::dispatch_response(&mut thread, action);
+        handler.prepare_mutation("t1", &mut thread, result).await.unwrap();
+        std::fs::write(root.join("a.rs"), "a v2").unwrap();
+        std::fs::write(root.join("b.rs"), "b v2").unwrap();
+        let mut content = "exit_code: 0".to_string();
+        handler
+            .observe_file_result("t1", &mut thread, Some(&exec), &mut content, false)
+            .await;
+
+        {
+            let k = kernel.lock().await;
+            let a_v2 = version::content_version(b"a v2");
+            assert_eq!(k.file_version("t1", &root.join("a.rs")), Some(a_v2.as_str()));
+            let b_v1 = version::content_version(b"b v1");
+            assert_eq!(k.file_version("t1", &root.join("b.rs")), Some(b_v1.as_str()));
+        }
+
+        // The outside edit to b.rs is still caught by the next write
+        let edit = PendingToolCall {
+            tool_use_id: "toolu_3".into(),
+            tool_name: "file-edit".into(),
+            input: serde_json::json!({"path": "b.rs", "old_string": "b v2", "new_string": "b v3"}),
+        };
+        let action = ResponseAction::ToolCalls {
+            blocks: vec![],
+            pending: vec![edit],
+        };
+        let prepared = CodingAgentHandler::dispatch_response(&mut thread, action);
+        let prepared = handler.prepare_mutation("t1", &mut thread, prepared).await;
+        let (result, is_error) = run_prepared(&file_edit, "file-edit", prepared).await;
+        assert!(is_error);
+        assert!(result.contains("conflict"), "{result}");
+        assert_eq!(std::fs::read_to_string(root.join("b.rs")).unwrap(), "b v2");
+    }
+
+    #[tokio::test]
+    async fn session_redirects_resolve_from_the_session_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        let ws = Arc::new(Workspace::new("coding", &root, &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&root.join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let handler = CodingAgentHandler::new(mock_pool(), sample_tool_defs(), "test".into())
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws.clone()).await);
+        let exec = |input: serde_json::Value| PendingToolCall {
+            tool_use_id: "toolu_1".into(),
+            tool_name: "command-exec".into(),
+            input,
+        };
+
+        let mut thread = AgentThread::new();
+        let cd = exec(serde_json::json!({"command": "cd src", "session": "main"}));
+        let mut content = format!("exit_code: 0\ncwd: {}\noutput:\n", root.join("src").display());
+        handler
+            .observe_file_result("t1", &mut thread, Some(&cd), &mut content, false)
+            .await;
+        assert_eq!(thread.session_dirs.get("main"), Some(&root.join("src")));
+
+        // From the session's directory, then from wherever the line cds to
+        let run = exec(serde_json::json!({
+            "command": "cargo run > a.rs; cd .. && echo hi > b.rs",
+            "session": "main",
+            "working_dir": "elsewhere",
+        }));
+        let targets = handler.exec_write_targets(Some(&ws), &run, thread.session_dirs.get("main").cloned());
+        assert_eq!(targets, vec![root.join("src/a.rs"), root.join("b.rs")]);
+
+        let close = exec(serde_json::json!({"action": "close", "session": "main"}));
+        let mut content = "closed session main".to_string();
+        handler
+            .observe_file_result("t1", &mut thread, Some(&close), &mut content, false)
+            .await;
+        assert!(thread.session_dirs.is_empty());
+    }
+
+    #[test]
+    fn verification_skipped_without_edits() {
+        let handler = verify_handler(2);
+        let mut thread = AgentThread::new();
+        let result = handler.maybe_start_verification("t1", &mut thread, final_reply("done"));
+        assert!(matches!(result, Ok(HandlerResponse::Reply { .. })));
+        assert!(matches!(thread.state, AgentState::Ready));
+    }
+
+    #[test]
+    fn verification_holds_reply_and_runs_first_command() {
+        let handler = verify_handler(2);
+        let mut thread = AgentThread::new();
+        thread.files_modified = true;
+        let result = handler.maybe_start_verification("t1", &mut thread, final_reply("done"));
+        match result.unwrap() {
+            HandlerResponse::Send { to, payload_xml } => {
+                assert_eq!(to, "command-exec");
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("<command>cargo check</command>"));
+                assert!(xml.contains("<timeout>300</timeout>"));
+            }
+            _ => panic!("expected Send"),
+        }
+        assert!(matches!(
+            thread.state,
+            AgentState::Verifying {
+                current_index: 0,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn verification_pass_releases_reply_with_summary() {
+        let handler = verify_handler(2);
+        let mut thread = AgentThread::new();
+        thread.files_modified = true;
+        let first = handler.maybe_start_verification("t1", &mut thread, final_reply("done"));
+        assert!(matches!(first, Ok(HandlerResponse::Send { .. })));
+        handler.threads.lock().await.insert("t1".into(), thread);
+
+        // cargo check passes → cargo test is sent next
+        let next = handler.handle(exec_response(0, ""), exec_ctx()).await.unwrap();
+        match next {
+            HandlerResponse::Send { payload_xml, .. } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("<command>cargo test -q</command>"));
+            }
+            _ => panic!("expected Send for second command"),
+        }
+
+        let reply = handler.handle(exec_response(0, "ok"), exec_ctx()).await.unwrap();
+        match reply {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("<result>done</result>"));
+                assert!(xml.contains("PASS cargo check\nPASS cargo test -q"));
+            }
+            _ => panic!("expected Reply"),
+        }
+        let threads = handler.threads.lock().await;
+        assert!(!threads["t1"].files_modified);
+    }
+
+    #[tokio::test]
+    async fn verification_failure_without_retries_reports_fail() {
+        let handler = verify_handler(0);
+        let mut thread = AgentThread::new();
+        thread.files_modified = true;
+        let _ = handler.maybe_start_verification("t1", &mut thread, final_reply("done"));
+        handler.threads.lock().await.insert("t1".into(), thread);
+
+        let _ = handler.handle(exec_response(101, "error[E0308]"), exec_ctx()).await.unwrap();
+        let reply = handler.handle(exec_response(0, ""), exec_ctx()).await.unwrap();
+        match reply {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                assert!(xml.contains("FAIL cargo check (exit_code: 101)"));
+                assert!(xml.contains("PASS cargo test -q"));
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[tokio::test]
+    async fn edit_during_verification_can_be_undone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("a.rs");
+        std::fs::write(&file, "old").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        let (pool, _requests) = mock_api(vec![serde_json::json!({
+            "id": "msg_fix",
+            "model": "test",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_fix",
+                "name": "file-edit",
+                "input": {"path": "a.rs", "old_string": "old", "new_string": "new"}
+            }],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        })])
+        .await;
+        let config = AgentConfig {
+            verify: Some(VerifyConfig {
+                commands: vec!["cargo check".into()],
+                max_retries: 1,
+                timeout_secs: None,
+            }),
+            ..AgentConfig::default()
+        };
+        let tool_defs = crate::agent::tools::build_tool_definitions(&["file-edit", "command-exec"]);
+        let handler = CodingAgentHandler::from_config(pool, tool_defs, "test".into(), &config)
+            .with_checkpoints_attached(kernel.clone(), coding_workspace(&kernel, ws).await);
+
+        let mut thread = AgentThread::new();
+        thread.files_modified = true;
+        let _ = handler.maybe_start_verification("t1", &mut thread, final_reply("done"));
+        handler.threads.lock().await.insert("t1".into(), thread);
+
+        // cargo check fails, and the model answers with a fix
+        let next = handler.handle(exec_response(101, "error[E0308]"), exec_ctx()).await.unwrap();
+        match next {
+            HandlerResponse::Send { to, .. } => assert_eq!(to, "file-edit"),
+            _ => panic!("expected Send to file-edit"),
+        }
+        assert_eq!(kernel.lock().await.checkpoints().count(), 1);
+
+        // The tool writes; /undo puts the file back
+        std::fs::write(&file, "new").unwrap();
+        let undone = kernel.lock().await.undo_last().unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "old");
+    }
+
+    #[test]
+    fn exit_code_parsing() {
+        assert_eq!(exit_code("exit_code: 0\nstdout:\n"), Some(0));
+        assert_eq!(exit_code("exit_code: 101\nstdout:\n"), Some(101));
+        assert_eq!(exit_code("command timed out"), None);
+    }
+
+    #[test]
+    fn verification_feedback_keeps_output_tail() {
+        let long_output = format!("{}LAST LINE", "x".repeat(10_000));
+        let outcomes = vec![
+            VerifyOutcome {
+                command: "cargo check".into(),
+                passed: true,
+                output: "exit_code: 0".into(),
+            },
+            VerifyOutcome {
+                command: "cargo test -q".into(),
+                passed: false,
+                output: long_output,
+            },
+        ];
+        let msg = verification_feedback(&outcomes);
+        assert!(msg.starts_with("Verification failed."));
+        assert!(!msg.contains("$ cargo check"));
+        assert!(msg.contains("$ cargo test -q"));
+        assert!(msg.contains("LAST LINE"));
+        assert!(msg.len() < 5000);
+    }
+
     // ── ConversationEntry conversion tests ──
 
     #[test]
@@ -1523,6 +3646,7 @@ mod tests {
             tool_use_id: "toolu_1".into(),
             content: "fn main() {}".into(),
             is_error: false,
+            media: vec![],
         }])];
         let entries = build_conversation_entries(&messages);
         assert_eq!(entries.len(), 1);
@@ -1531,6 +3655,90 @@ mod tests {
         assert!(entries[0].summary.contains("fn main"));
     }
 
+    fn thinking_message() -> crate::llm::types::Message {
+        crate::llm::types::Message::assistant_blocks(vec![
+            ContentBlock::Thinking {
+                thinking: "The bug is probably in the lexer.".into(),
+                signature: "sig==".into(),
+            },
+            ContentBlock::RedactedThinking {
+                data: "opaque".into(),
+            },
+            ContentBlock::Text {
+                text: "Let me check the lexer.".into(),
+            },
+        ])
+    }
+
+    #[test]
+    fn conversation_entries_skip_thinking_by_default() {
+        let entries = build_conversation_entries(&[thinking_message()]);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].role, "assistant");
+        assert!(entries[0].summary.contains("lexer"));
+    }
+
+    #[test]
+    fn conversation_entries_with_thinking() {
+        let entries = build_conversation_entries_with_thinking(&[thinking_message()], true);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].role, "thinking");
+        assert!(entries[0].summary.contains("probably in the lexer"));
+        assert_eq!(entries[1].summary, "(redacted)");
+        assert_eq!(entries[2].role, "assistant");
+    }
+
+    #[test]
+    fn from_config_reads_thinking_budget() {
+        let config = AgentConfig {
+            max_tokens: 16000,
+            thinking_budget: Some(8000),
+            ..AgentConfig::default()
+        };
+        let handler =
+            CodingAgentHandler::from_config(mock_pool(), sample_tool_defs(), "test".into(), &config);
+        assert_eq!(handler.thinking_budget, Some(8000));
+    }
+
+    #[test]
+    fn thinking_blocks_survive_tool_turn() {
+        // Blocks captured in AwaitingTools are replayed verbatim, thinking first
+        let blocks = vec![
+            ContentBlock::Thinking {
+                thinking: "Read the file first.".into(),
+                signature: "sig==".into(),
+            },
+            ContentBlock::ToolUse {
+                id: "toolu_1".into(),
+                name: "file-read".into(),
+                input: serde_json::json!({"path": "foo.rs"}),
+            },
+        ];
+        let mut thread = AgentThread::new();
+        thread.push_user_message("Read foo.rs");
+        let action = ResponseAction::ToolCalls {
+            blocks,
+            pending: vec![PendingToolCall {
+                tool_use_id: "toolu_1".into(),
+                tool_name: "file-read".into(),
+                input: serde_json::json!({"path": "foo.rs"}),
+            }],
+        };
+        CodingAgentHandler::dispatch_response(&mut thread, action).unwrap();
+        let AgentState::AwaitingTools {
+            assistant_blocks, ..
+        } = std::mem::replace(&mut thread.state, AgentState::Ready)
+        else {
+            panic!("expected AwaitingTools");
+        };
+        thread.push_assistant_blocks(assistant_blocks);
+
+        let json = serde_json::to_value(&thread.messages[1]).unwrap();
+        assert_eq!(json["content"][0]["type"], "thinking");
+        assert_eq!(json["content"][0]["signature"], "sig==");
+        assert_eq!(json["content"][1]["type"], "tool_use");
+    }
+
     #[test]
     fn conversation_entry_full_conversation() {
         use crate::llm::types::{Message, ToolResultBlock};
@@ -1550,6 +3758,7 @@ mod tests {
                 tool_use_id: "toolu_1".into(),
                 content: "pub fn foo() {}".into(),
                 is_error: false,
+                media: vec![],
             }]),
             Message::assistant_blocks(vec![ContentBlock::Text {
                 text: "The file contains a foo function.".into(),
@@ -10,11 +10,15 @@
 //! - `state`: Per-thread state machine (Ready → AwaitingTools → ...)
 //! - `handler`: CodingAgentHandler — the stateful Handler impl
 //! - `prompts`: System prompt templates
+//! - `schema`: Structured final answers (`submit` tool + validation)
+//! - `context`: Transcript ↔ context-store mirroring for librarian curation
 //! - `ralph`: Ralph Method story decomposition
 
+pub mod context;
 pub mod handler;
 pub mod prompts;
 pub mod ralph;
+pub mod schema;
 pub mod state;
 pub mod tools;
 pub mod translate;
@@ -0,0 +1,257 @@
+//! Structured final answers — the terminal `submit` tool.
+//!
+//! An agent with a `response_schema` finishes by calling `submit` with its
+//! answer as the tool input. The handler validates that input against the
+//! schema here and re-prompts the model with the errors on mismatch.
+//!
+//! Validation covers the JSON Schema subset tool schemas actually use:
+//! `type`, `enum`, `properties`, `required`, `additionalProperties: false`,
+//! `items`, `minItems`/`maxItems` and `minimum`/`maximum`.
+
+use serde_json::Value;
+
+use crate::llm::types::ToolDefinition;
+use crate::organism::ResponseSchema;
+
+/// Name of the terminal tool given to schema-bound agents.
+pub const SUBMIT_TOOL_NAME: &str = "submit";
+
+/// Reminder injected when the model ends its turn without calling `submit`.
+pub const SUBMIT_REMINDER: &str = "You must finish by calling the `submit` tool with your final \
+answer. Do not reply with plain text.";
+
+/// Build the `submit` tool definition for a response schema.
+pub fn submit_tool_definition(schema: &ResponseSchema) -> ToolDefinition {
+    ToolDefinition {
+        name: SUBMIT_TOOL_NAME.to_string(),
+        description: format!(
+            "{} Call this exactly once, alone, when the task is complete. \
+             The input is your final answer.",
+            schema.description
+        ),
+        input_schema: schema.schema.clone(),
+    }
+}
+
+/// Validate `value` against a JSON Schema.
+///
+/// Returns every violation found, as `"<path>: <problem>"` strings.
+/// An empty vector means the value is valid.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "$", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` / `{}`-like schemas accept anything
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!(
+                "{path}: expected {}, got {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            // Further checks would only cascade from the type mismatch
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let listed: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+            errors.push(format!("{path}: must be one of [{}]", listed.join(", ")));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{path}: missing required property '{key}'"));
+                    }
+                }
+            }
+
+            for (key, child) in map {
+                let child_path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child_schema, child, &child_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: unexpected property '{key}'"));
+                        }
+                        Some(extra @ Value::Object(_)) => {
+                            validate_at(extra, child, &child_path, errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{path}: expected at least {min} items"));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) > max {
+                    errors.push(format!("{path}: expected at most {max} items"));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+                if n < min {
+                    errors.push(format!("{path}: must be >= {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+                if n > max {
+                    errors.push(format!("{path}: must be <= {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        // JSON Schema counts 3.0 as an integer: only the fraction matters
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().is_some_and(|f| f.is_finite() && f.fract() == 0.0)
+        }
+        _ => true, // unknown type keywords are not enforced
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn verdict_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "verdict": { "type": "string", "enum": ["pass", "fail"] },
+                "score": { "type": "integer", "minimum": 0, "maximum": 10 },
+                "files": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+            },
+            "required": ["verdict"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn valid_value_has_no_errors() {
+        let value = json!({"verdict": "pass", "score": 7, "files": ["a.rs"]});
+        assert!(validate(&verdict_schema(), &value).is_empty());
+    }
+
+    #[test]
+    fn missing_required_property() {
+        let errors = validate(&verdict_schema(), &json!({"score": 3}));
+        assert_eq!(errors, vec!["$: missing required property 'verdict'"]);
+    }
+
+    #[test]
+    fn enum_and_range_violations() {
+        let errors = validate(&verdict_schema(), &json!({"verdict": "maybe", "score": 11}));
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("$.verdict: must be one of")));
+        assert!(errors.iter().any(|e| e == "$.score: must be <= 10"));
+    }
+
+    #[test]
+    fn nested_item_paths() {
+        let errors = validate(&verdict_schema(), &json!({"verdict": "fail", "files": ["ok", 3]}));
+        assert_eq!(errors, vec!["$.files[1]: expected string, got integer"]);
+    }
+
+    #[test]
+    fn min_items() {
+        let errors = validate(&verdict_schema(), &json!({"verdict": "fail", "files": []}));
+        assert_eq!(errors, vec!["$.files: expected at least 1 items"]);
+    }
+
+    #[test]
+    fn additional_properties_rejected() {
+        let errors = validate(&verdict_schema(), &json!({"verdict": "pass", "extra": true}));
+        assert_eq!(errors, vec!["$: unexpected property 'extra'"]);
+    }
+
+    #[test]
+    fn additional_properties_allowed_by_default() {
+        let schema = json!({"type": "object", "properties": {}});
+        assert!(validate(&schema, &json!({"anything": 1})).is_empty());
+    }
+
+    #[test]
+    fn wrong_top_level_type() {
+        let errors = validate(&verdict_schema(), &json!("pass"));
+        assert_eq!(errors, vec!["$: expected object, got string"]);
+    }
+
+    #[test]
+    fn integer_vs_number() {
+        let schema = json!({"type": "integer"});
+        assert!(validate(&schema, &json!(3)).is_empty());
+        assert!(validate(&schema, &json!(3.0)).is_empty());
+        assert_eq!(validate(&schema, &json!(3.5)), vec!["$: expected integer, got number"]);
+        assert!(validate(&json!({"type": "number"}), &json!(3)).is_empty());
+    }
+
+    #[test]
+    fn submit_tool_uses_schema() {
+        let rs = ResponseSchema {
+            description: "Report the verdict.".into(),
+            schema: verdict_schema(),
+            max_retries: 3,
+        };
+        let def = submit_tool_definition(&rs);
+        assert_eq!(def.name, SUBMIT_TOOL_NAME);
+        assert!(def.description.starts_with("Report the verdict."));
+        assert_eq!(def.input_schema, verdict_schema());
+    }
+}
@@ -1,7 +1,11 @@
 //! Agent state machine — per-thread conversation state.
 //!
 //! Each thread tracked by the CodingAgent has its own state machine:
-//! Ready → AwaitingTools → Ready (loop until end_turn).
+//! Ready → AwaitingTools → Ready (loop until end_turn), with an optional
+//! Verifying detour before the final reply when `verify:` is configured.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 use crate::llm::types::{ContentBlock, Message, ToolResultBlock};
 
@@ -13,6 +17,17 @@ pub struct AgentThread {
     pub state: AgentState,
     /// Counter for the global agentic loop (Opus→tool→Opus cycles).
     pub agentic_iterations: usize,
+    /// Re-prompts spent on missing/invalid `submit` calls this task.
+    pub schema_retries: usize,
+    /// Whether file-write/file-edit ran since the last verification pass.
+    pub files_modified: bool,
+    /// Verification rounds that failed and were fed back this task.
+    pub verify_retries: usize,
+    /// Note for the in-flight file-write/file-edit, whose target this
+    /// thread never read (appended to the tool result).
+    pub write_warning: Option<String>,
+    /// Working directory of each command-exec session, as last reported.
+    pub session_dirs: HashMap<String, PathBuf>,
 }
 
 /// State machine for the agentic loop.
@@ -30,6 +45,24 @@ pub enum AgentState {
         /// Index of the tool call currently being dispatched.
         current_index: usize,
     },
+    /// Running `verify:` commands before releasing the final reply.
+    Verifying {
+        /// The held `<AgentResponse>` reply, released once verification ends.
+        reply_xml: Vec<u8>,
+        /// Outcomes of the commands run so far (in order).
+        outcomes: Vec<VerifyOutcome>,
+        /// Index of the verify command currently running.
+        current_index: usize,
+    },
+}
+
+/// Result of one `verify:` command.
+#[derive(Debug, Clone)]
+pub struct VerifyOutcome {
+    pub command: String,
+    pub passed: bool,
+    /// Raw command-exec result (exit code, stdout, stderr) or error text.
+    pub output: String,
 }
 
 /// A pending tool call extracted from an Opus response.
@@ -46,6 +79,11 @@ impl Default for AgentThread {
             messages: Vec::new(),
             state: AgentState::Ready,
             agentic_iterations: 0,
+            schema_retries: 0,
+            files_modified: false,
+            verify_retries: 0,
+            write_warning: None,
+            session_dirs: HashMap::new(),
         }
     }
 }
@@ -61,6 +99,11 @@ impl AgentThread {
         self.messages.push(Message::text("user", content));
     }
 
+    /// Add a user message made of content blocks (task text + attachments).
+    pub fn push_user_blocks(&mut self, blocks: Vec<ContentBlock>) {
+        self.messages.push(Message::user_blocks(blocks));
+    }
+
     /// Add the assistant's response to the conversation history.
     pub fn push_assistant_blocks(&mut self, blocks: Vec<ContentBlock>) {
         self.messages.push(Message::assistant_blocks(blocks));
@@ -135,6 +178,7 @@ mod tests {
             tool_use_id: "t1".into(),
             content: "42".into(),
             is_error: false,
+            media: vec![],
         }]);
         assert_eq!(thread.messages.len(), 1);
         assert_eq!(thread.messages[0].role, "user");
@@ -177,6 +221,7 @@ mod tests {
                 tool_use_id: "t1".into(),
                 content: "ok".into(),
                 is_error: false,
+                media: vec![],
             }],
             current_index: 1,
         };
@@ -48,6 +48,10 @@ pub fn file_write_definition() -> ToolDefinition {
                 "content": {
                     "type": "string",
                     "description": "The content to write to the file"
+                },
+                "allow_lossy": {
+                    "type": "boolean",
+                    "description": "Write '?' for characters the existing file's encoding can't hold instead of failing"
                 }
             },
             "required": ["path", "content"]
@@ -59,7 +63,7 @@ pub fn file_write_definition() -> ToolDefinition {
 pub fn file_edit_definition() -> ToolDefinition {
     ToolDefinition {
         name: "file-edit".into(),
-        description: "Surgical text replacement in a file. Replaces old_string with new_string. The old_string must match exactly once. Returns unified diff.".into(),
+        description: "Surgical text replacement in a file. Give exactly one of: old_string/new_string (one replacement; old_string must match exactly once unless replace_all), edits (ordered batch applied all-or-nothing), or patch (unified diff, hunks matched fuzzily). Returns unified diff.".into(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {
@@ -69,14 +73,39 @@ pub fn file_edit_definition() -> ToolDefinition {
                 },
                 "old_string": {
                     "type": "string",
-                    "description": "The exact text to find and replace (must be unique in the file)"
+                    "description": "The exact text to find and replace (must be unique in the file unless replace_all)"
                 },
                 "new_string": {
                     "type": "string",
                     "description": "The replacement text"
+                },
+                "replace_all": {
+                    "type": "boolean",
+                    "description": "Replace every occurrence of old_string (default: false)"
+                },
+                "edits": {
+                    "type": "array",
+                    "description": "Ordered edits applied in sequence; if any fails, none are written",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old_string": { "type": "string" },
+                            "new_string": { "type": "string" },
+                            "replace_all": { "type": "boolean" }
+                        },
+                        "required": ["old_string", "new_string"]
+                    }
+                },
+                "patch": {
+                    "type": "string",
+                    "description": "Unified diff to apply (@@ hunks; line numbers may be approximate)"
+                },
+                "allow_lossy": {
+                    "type": "boolean",
+                    "description": "Write '?' for characters the file's encoding can't hold instead of failing"
                 }
             },
-            "required": ["path", "old_string", "new_string"]
+            "required": ["path"]
         }),
     }
 }
@@ -85,7 +114,7 @@ pub fn file_edit_definition() -> ToolDefinition {
 pub fn glob_definition() -> ToolDefinition {
     ToolDefinition {
         name: "glob".into(),
-        description: "Find files matching a glob pattern (e.g. **/*.rs, src/*.txt).".into(),
+        description: "Find files matching a glob pattern (e.g. **/*.rs, src/*.txt). Honours .gitignore unless include_ignored is set; results are most recently modified first unless sort is path.".into(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {
@@ -96,6 +125,25 @@ pub fn glob_definition() -> ToolDefinition {
                 "base_path": {
                     "type": "string",
                     "description": "Base directory for the pattern (default: current directory)"
+                },
+                "sort": {
+                    "type": "string",
+                    "enum": ["mtime", "path"],
+                    "description": "mtime (default, newest first) or path"
+                },
+                "kind": {
+                    "type": "string",
+                    "enum": ["file", "dir", "any"],
+                    "description": "Only files, only directories, or any (default)"
+                },
+                "exclude": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Glob patterns to leave out; without a / they match a name at any depth"
+                },
+                "include_ignored": {
+                    "type": "boolean",
+                    "description": "Also match files ignored by .gitignore and .ignore"
                 }
             },
             "required": ["pattern"]
@@ -107,7 +155,7 @@ pub fn glob_definition() -> ToolDefinition {
 pub fn grep_definition() -> ToolDefinition {
     ToolDefinition {
         name: "grep".into(),
-        description: "Regex search across files. Recursively searches directories, skips binary files.".into(),
+        description: "Regex search across files. Recursively searches directories, honouring .gitignore and .ignore; skips hidden and binary files. Results stop at 500 matches and say how many there were in total.".into(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {
@@ -126,6 +174,26 @@ pub fn grep_definition() -> ToolDefinition {
                 "case_insensitive": {
                     "type": "boolean",
                     "description": "Case insensitive search (default: false)"
+                },
+                "context": {
+                    "type": "integer",
+                    "description": "Lines of context before and after each match (like grep -C)"
+                },
+                "before_context": {
+                    "type": "integer",
+                    "description": "Lines of context before each match (like grep -B; overrides context)"
+                },
+                "after_context": {
+                    "type": "integer",
+                    "description": "Lines of context after each match (like grep -A; overrides context)"
+                },
+                "output_mode": {
+                    "type": "string",
+                    "description": "content (matching lines, default), files_with_matches (paths only) or count (matches per file)"
+                },
+                "multiline": {
+                    "type": "boolean",
+                    "description": "Let the pattern span lines; . matches newlines and ^/$ match at every line (default: false)"
                 }
             },
             "required": ["pattern"]
@@ -137,24 +205,395 @@ pub fn grep_definition() -> ToolDefinition {
 pub fn command_exec_definition() -> ToolDefinition {
     ToolDefinition {
         name: "command-exec".into(),
-        description: "Execute a shell command. Only allowed commands can be run (cargo, git, npm, etc). Captures stdout, stderr, and exit code.".into(),
+        description: "Execute a shell command. Only allowed commands can be run (cargo, git, npm, etc). Captures stdout, stderr, and exit code. Name a session to run in a persistent shell that keeps cd and exported variables between calls; commands still running at the timeout keep running there, to poll or kill.".into(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {
                 "command": {
                     "type": "string",
-                    "description": "The command to execute"
+                    "description": "The command to execute (for run)"
                 },
                 "timeout": {
                     "type": "integer",
-                    "description": "Timeout in seconds (default: 30)"
+                    "description": "Timeout in seconds (default: 30); for poll, how long to wait for the job (default: 0)"
                 },
                 "working_dir": {
                     "type": "string",
-                    "description": "Working directory for the command"
+                    "description": "Working directory for the command (for a session: where it starts)"
+                },
+                "session": {
+                    "type": "string",
+                    "description": "Persistent shell session to use, e.g. main or dev-server"
+                },
+                "action": {
+                    "type": "string",
+                    "enum": ["run", "poll", "kill", "close", "list"],
+                    "description": "run (default), poll (new output from a session's job), kill (interrupt it), close (end the session) or list (this thread's sessions)"
+                },
+                "background": {
+                    "type": "boolean",
+                    "description": "Start the command in the session and return without waiting (default: false)"
+                }
+            },
+            "required": []
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the git tool.
+pub fn git_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "git".into(),
+        description: "Git operations with JSON results. Actions: status; diff (unstaged, staged, or against rev; hunks are numbered); log; blame; show (a commit, or a file at rev); branch (list, or create name); switch (to name, create to make it); stage (a path, everything, or some hunks of a path by their unstaged diff numbers); commit; fetch, pull (fast-forward only), push.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["status", "diff", "log", "blame", "show", "branch", "switch", "stage", "commit", "fetch", "pull", "push"],
+                    "description": "The git operation"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory, relative to the workspace"
+                },
+                "rev": {
+                    "type": "string",
+                    "description": "Commit, branch or range: diff against it, log from it, blame or show at it, start a branch from it"
+                },
+                "staged": {
+                    "type": "boolean",
+                    "description": "diff: staged changes instead of unstaged"
+                },
+                "hunks": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "description": "stage: hunk numbers from an unstaged diff of path"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "branch, switch: branch name; fetch, pull, push: remote branch"
+                },
+                "create": {
+                    "type": "boolean",
+                    "description": "switch: create the branch first"
+                },
+                "message": {
+                    "type": "string",
+                    "description": "commit: commit message"
+                },
+                "amend": {
+                    "type": "boolean",
+                    "description": "commit: amend the last commit (rewrites history)"
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "push: force with lease (rewrites remote history)"
+                },
+                "remote": {
+                    "type": "string",
+                    "description": "fetch, pull, push: remote name"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "log: most commits to list (default: 20)"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "description": "blame: first line (1-based)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "description": "blame: last line"
+                }
+            },
+            "required": ["action"]
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the output-page tool.
+pub fn output_page_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "output-page".into(),
+        description: "Read a page of a long tool output that was cut short. The note in the cut output names the output (e.g. output-3) and the offset to continue from.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "The stored output's id, e.g. output-3"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Byte offset to start from (default 0)"
+                },
+                "length": {
+                    "type": "integer",
+                    "description": "Bytes to return (default 16384)"
+                }
+            },
+            "required": ["id"]
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the test-run tool.
+pub fn test_run_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "test-run".into(),
+        description: "Run tests and get a compact summary: pass/fail/ignored counts, then each failing test with its assertion message and file:line. Runs cargo test (doc tests excluded), cargo nextest or pytest, detected from the project unless runner is given. Set failed to rerun only the tests that failed in this thread's last run.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "runner": {
+                    "type": "string",
+                    "enum": ["cargo", "nextest", "pytest"],
+                    "description": "Test runner (default: detected from the project)"
+                },
+                "filter": {
+                    "type": "string",
+                    "description": "Only tests whose names contain this (pytest: a -k expression)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Package directory (cargo, nextest) or test file or directory (pytest)"
+                },
+                "package": {
+                    "type": "string",
+                    "description": "cargo, nextest: the package to test"
+                },
+                "failed": {
+                    "type": "boolean",
+                    "description": "Rerun only the tests that failed in this thread's last run"
+                },
+                "timeout": {
+                    "type": "integer",
+                    "description": "Timeout in seconds (default 600)"
+                }
+            },
+            "required": []
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the diagnostics tool.
+pub fn diagnostics_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "diagnostics".into(),
+        description: "Compiler and linter diagnostics. check runs cargo check, ruff or mypy (detected from the project unless checker is given) and returns numbered, deduplicated diagnostics grouped by file, with spans and suggested fixes. apply_suggestion applies fixes from the last check by id (e.g. 3.1); only machine-applicable or safe fixes are accepted.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["check", "apply_suggestion"],
+                    "description": "check (default) or apply_suggestion"
+                },
+                "checker": {
+                    "type": "string",
+                    "enum": ["cargo", "ruff", "mypy"],
+                    "description": "Checker (default: detected from the project)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Package directory (cargo) or file or directory to check (ruff, mypy)"
+                },
+                "package": {
+                    "type": "string",
+                    "description": "cargo: the package to check"
+                },
+                "ids": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "apply_suggestion: fix ids from the last check"
+                }
+            },
+            "required": []
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the lsp tool.
+pub fn lsp_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "lsp".into(),
+        description: "Semantic code navigation through the workspace's language server (rust-analyzer, pyright), started on first use and kept running. definition, references and hover take path, line and column (or symbol, a name on that line); workspace-symbol searches by query; rename-preview shows the edits renaming to new_name would make as a diff, without writing; diagnostics lists the server's errors and warnings for path. Lines and columns are 1-based.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["definition", "references", "hover", "workspace-symbol", "rename-preview", "diagnostics"],
+                    "description": "The navigation to perform"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "The file, relative to the workspace"
+                },
+                "line": {
+                    "type": "integer",
+                    "description": "1-based line of the symbol"
+                },
+                "column": {
+                    "type": "integer",
+                    "description": "1-based column of the symbol"
+                },
+                "symbol": {
+                    "type": "string",
+                    "description": "The symbol's name on that line, instead of column"
+                },
+                "query": {
+                    "type": "string",
+                    "description": "workspace-symbol: the name to search for"
+                },
+                "new_name": {
+                    "type": "string",
+                    "description": "rename-preview: the new name"
+                }
+            },
+            "required": ["action"]
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the list-dir tool.
+pub fn list_dir_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "list-dir".into(),
+        description: "Directory overview: a tree to the given depth with each file's line count, size, language and modification time, and file and size totals per directory. Honours .gitignore and skips dotfiles unless hidden is set. Deeper directories and very large ones are summarized as counts.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Directory to list (default: current directory)"
+                },
+                "depth": {
+                    "type": "integer",
+                    "description": "Levels to show (default 2, at most 8)"
+                },
+                "hidden": {
+                    "type": "boolean",
+                    "description": "Include dotfiles and ignored entries"
+                }
+            },
+            "required": []
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the notebook-edit tool.
+pub fn notebook_edit_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "notebook-edit".into(),
+        description: "Edit a Jupyter notebook (.ipynb) cell by cell: replace a cell's source or type, insert a new cell, or delete one, addressed by 0-based index (as file-read shows them) or cell id. Keeps the notebook JSON valid; replacing a code cell's source clears its outputs.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The notebook path"
+                },
+                "action": {
+                    "type": "string",
+                    "enum": ["replace", "insert", "delete"],
+                    "description": "The cell operation to perform"
+                },
+                "index": {
+                    "type": "integer",
+                    "description": "Cell index (0-based); for insert, the position of the new cell (default: append)"
+                },
+                "cell_id": {
+                    "type": "string",
+                    "description": "Cell id, instead of index; for insert, the new cell goes after it"
+                },
+                "cell_type": {
+                    "type": "string",
+                    "enum": ["code", "markdown", "raw"],
+                    "description": "Cell type (insert default: code)"
+                },
+                "source": {
+                    "type": "string",
+                    "description": "The cell's new source"
+                },
+                "expected_hash": {
+                    "type": "string",
+                    "description": "Version hash from file-read; the edit is rejected if the file has changed since"
+                }
+            },
+            "required": ["path", "action"]
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the http-fetch tool.
+pub fn http_fetch_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "http-fetch".into(),
+        description: "Fetch a web page over http(s), e.g. documentation or an API reference. Only hosts the profile's network ports allow are reachable, redirects included. HTML is returned as markdown, other text as-is; binary content is refused and bodies are cut at 2 MB. Pages are cached for 15 minutes.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The URL to fetch"
+                },
+                "raw": {
+                    "type": "boolean",
+                    "description": "Return HTML as-is instead of converting it to markdown"
+                },
+                "refresh": {
+                    "type": "boolean",
+                    "description": "Fetch again even if the page is cached"
                 }
             },
-            "required": ["command"]
+            "required": ["url"]
+        }),
+    }
+}
+
+/// Build a ToolDefinition for the replace tool.
+pub fn replace_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "replace".into(),
+        description: "Search and replace across many files, e.g. to rename an identifier. Without token it is a dry run: returns per-file diffs with replacement counts and a token, writing nothing. Call again with just that token to apply exactly the previewed change, all files or none; it is refused if any file changed since the preview.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regex to search for; it runs over whole files, so use (?m) for ^ and $ at line starts and ends"
+                },
+                "replacement": {
+                    "type": "string",
+                    "description": "Replacement text; $1 or ${name} insert capture groups unless literal"
+                },
+                "literal": {
+                    "type": "boolean",
+                    "description": "Treat pattern and replacement as plain text (default: false)"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Case insensitive search (default: false)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to search (default: current directory)"
+                },
+                "glob_filter": {
+                    "type": "string",
+                    "description": "Only files matching this glob: a file name pattern (*.rs) or, with a /, a path under path (src/**/*.rs)"
+                },
+                "token": {
+                    "type": "string",
+                    "description": "Apply the preview that returned this token"
+                },
+                "allow_lossy": {
+                    "type": "boolean",
+                    "description": "Write characters a file's encoding can't hold as ? instead of refusing (default: false)"
+                }
+            }
         }),
     }
 }
@@ -213,6 +652,15 @@ pub fn definition_for_peer(name: &str) -> Option<ToolDefinition> {
         "glob" => Some(glob_definition()),
         "grep" => Some(grep_definition()),
         "command-exec" => Some(command_exec_definition()),
+        "git" => Some(git_definition()),
+        "test-run" => Some(test_run_definition()),
+        "diagnostics" => Some(diagnostics_definition()),
+        "lsp" => Some(lsp_definition()),
+        "list-dir" => Some(list_dir_definition()),
+        "notebook-edit" => Some(notebook_edit_definition()),
+        "http-fetch" => Some(http_fetch_definition()),
+        "replace" => Some(replace_definition()),
+        "output-page" => Some(output_page_definition()),
         "codebase-index" => Some(codebase_index_definition()),
         _ => None,
     }
@@ -273,6 +721,8 @@ mod tests {
         assert!(props.get("path").is_some());
         assert!(props.get("old_string").is_some());
         assert!(props.get("new_string").is_some());
+        assert_eq!(props["edits"]["type"], "array");
+        assert!(props.get("patch").is_some());
     }
 
     #[test]
@@ -281,6 +731,7 @@ mod tests {
         assert_eq!(def.name, "glob");
         let props = &def.input_schema["properties"];
         assert!(props.get("pattern").is_some());
+        assert_eq!(props["exclude"]["type"], "array");
     }
 
     #[test]
@@ -292,6 +743,8 @@ mod tests {
         assert!(props.get("path").is_some());
         assert!(props.get("glob_filter").is_some());
         assert!(props.get("case_insensitive").is_some());
+        assert!(props.get("output_mode").is_some());
+        assert!(props.get("after_context").is_some());
     }
 
     #[test]
@@ -302,6 +755,96 @@ mod tests {
         assert!(props.get("command").is_some());
         assert!(props.get("timeout").is_some());
         assert!(props.get("working_dir").is_some());
+        assert!(props.get("session").is_some());
+        assert!(props.get("action").is_some());
+    }
+
+    #[test]
+    fn git_def_is_valid() {
+        let def = git_definition();
+        assert_eq!(def.name, "git");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("action").is_some());
+        assert!(props.get("hunks").is_some());
+        assert!(props.get("start_line").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!(["action"]));
+    }
+
+    #[test]
+    fn output_page_def_is_valid() {
+        let def = output_page_definition();
+        assert_eq!(def.name, "output-page");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("id").is_some());
+        assert!(props.get("offset").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!(["id"]));
+    }
+
+    #[test]
+    fn test_run_def_is_valid() {
+        let def = test_run_definition();
+        assert_eq!(def.name, "test-run");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("runner").is_some());
+        assert!(props.get("failed").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn diagnostics_def_is_valid() {
+        let def = diagnostics_definition();
+        assert_eq!(def.name, "diagnostics");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("action").is_some());
+        assert_eq!(props["ids"]["type"], "array");
+        assert_eq!(def.input_schema["required"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn lsp_def_is_valid() {
+        let def = lsp_definition();
+        assert_eq!(def.name, "lsp");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("symbol").is_some());
+        assert!(props.get("new_name").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!(["action"]));
+    }
+
+    #[test]
+    fn list_dir_def_is_valid() {
+        let def = list_dir_definition();
+        assert_eq!(def.name, "list-dir");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("depth").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn notebook_edit_def_is_valid() {
+        let def = notebook_edit_definition();
+        assert_eq!(def.name, "notebook-edit");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("cell_id").is_some());
+        assert!(props.get("source").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!(["path", "action"]));
+    }
+
+    #[test]
+    fn http_fetch_def_is_valid() {
+        let def = http_fetch_definition();
+        assert_eq!(def.name, "http-fetch");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("refresh").is_some());
+        assert_eq!(def.input_schema["required"], serde_json::json!(["url"]));
+    }
+
+    #[test]
+    fn replace_def_is_valid() {
+        let def = replace_definition();
+        assert_eq!(def.name, "replace");
+        let props = &def.input_schema["properties"];
+        assert!(props.get("token").is_some());
+        assert!(def.input_schema.get("required").is_none());
     }
 
     #[test]
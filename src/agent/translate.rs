@@ -35,6 +35,14 @@ pub fn xml_response_to_result(xml: &str) -> (String, bool) {
     }
 }
 
+/// Extract the image/document a `<ToolResponse>` carries in `<media>`, if any.
+pub fn xml_response_media(xml: &str) -> Option<crate::llm::types::ContentBlock> {
+    let media = extract_tag(xml, "media")?;
+    let media_type = extract_tag(&media, "media_type")?;
+    let data = extract_tag(&media, "data")?;
+    Some(crate::llm::media::block_from_base64(&media_type, data))
+}
+
 /// Get the XML request tag name for a tool.
 ///
 /// Known tools return static strings. Unknown tools get a dynamic PascalCase
@@ -308,4 +316,30 @@ mod tests {
         let xml = tool_call_to_xml_with_tag("MyToolRequest", &input);
         assert!(xml.contains("a &lt; b &amp; c &gt; d"));
     }
+
+    #[test]
+    fn media_response_extracts_block() {
+        let xml = String::from_utf8(crate::tools::ToolResponse::ok_media(
+            "mockup.png (image/png, 4 bytes)",
+            "image/png",
+            b"\x89PNG",
+        ))
+        .unwrap();
+        let (content, is_error) = xml_response_to_result(&xml);
+        assert!(!is_error);
+        assert_eq!(content, "mockup.png (image/png, 4 bytes)");
+        match xml_response_media(&xml) {
+            Some(crate::llm::types::ContentBlock::Image { source }) => {
+                assert_eq!(source.media_type, "image/png");
+                assert_eq!(source.data, "iVBORw==");
+            }
+            other => panic!("expected image block, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn plain_response_has_no_media() {
+        let xml = "<ToolResponse><success>true</success><result>ok</result></ToolResponse>";
+        assert!(xml_response_media(xml).is_none());
+    }
 }
@@ -25,7 +25,7 @@ use crate::llm::LlmPool;
 use crate::organism::parser::load_organism;
 use crate::organism::{BufferConfig, CallableConfig, Organism};
 use crate::pipeline::events::PipelineEvent;
-use crate::pipeline::AgentPipelineBuilder;
+use crate::pipeline::{AgentPipelineBuilder, NATIVE_TOOLS};
 use crate::tools::{self, ToolResponse};
 
 /// Buffer handler — manages ephemeral child pipeline lifecycles.
@@ -80,7 +80,9 @@ impl BufferHandler {
         // Inject shared LLM pool
         builder = builder.with_shared_llm_pool(self.pool.clone())?;
 
-        // Register required tools as fresh instances
+        let profile = child_profile(&self.child_organism, &self.buffer_config)?;
+
+        // Register required tools as fresh instances, jailed per profile
         builder = register_required_tools(builder, &self.callable.requires)?;
 
         // Wire agents from child organism config
@@ -90,22 +92,10 @@ impl BufferHandler {
         let mut child_pipeline = builder.build()?;
 
         // Initialize root thread
-        let child_profiles: Vec<String> = child_pipeline
-            .organism()
-            .profile_names()
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect();
-        let profile = child_profiles
-            .first()
-            .ok_or_else(|| "child organism has no profiles".to_string())?;
         let root_uuid = child_pipeline
-            .initialize_root("buffer-child", profile)
+            .initialize_root("buffer-child", &profile)
             .await?;
 
-        // Subscribe to child events
-        let mut rx = child_pipeline.subscribe();
-
         // Start child pipeline
         child_pipeline.run();
 
@@ -118,37 +108,11 @@ impl BufferHandler {
             .ok_or_else(|| "child organism has no agent listeners".to_string())?
             .clone();
 
-        // Build and inject the task envelope
-        let escaped_task = tools::xml_escape(task_text);
-        let xml = format!(
-            "<{tag}><task>{escaped_task}</task></{tag}>",
-            tag = agent_def.payload_tag
-        );
-        let envelope = build_envelope("user", &agent_def.name, &root_uuid, xml.as_bytes())
-            .map_err(|e| format!("envelope build failed: {e}"))?;
-        child_pipeline.inject_raw(envelope).await?;
-
-        // Await AgentResponse with timeout
+        // Send the task and await the AgentResponse with timeout
         let timeout = std::time::Duration::from_secs(self.buffer_config.timeout_secs);
-        let result = tokio::time::timeout(timeout, async {
-            loop {
-                match rx.recv().await {
-                    Ok(PipelineEvent::AgentResponse { text, .. }) => return Ok(text),
-                    Ok(_) => continue, // skip other events
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                    Err(broadcast::error::RecvError::Closed) => {
-                        return Err("child event bus closed".to_string());
-                    }
-                }
-            }
-        })
-        .await
-        .map_err(|_| {
-            format!(
-                "buffer timeout after {}s",
-                self.buffer_config.timeout_secs
-            )
-        })?;
+        let result = child_pipeline
+            .ask_agent(&agent_def.name, &root_uuid, task_text, timeout)
+            .await;
 
         // Shutdown child
         child_pipeline.shutdown().await;
@@ -189,25 +153,40 @@ impl Handler for BufferHandler {
     }
 }
 
+/// The child profile a buffer runs under: the one `buffer.profile` names,
+/// or the child organism's only profile. Anything else is an error rather
+/// than a guess, since the profile decides the child's jail.
+fn child_profile(organism: &Organism, config: &BufferConfig) -> Result<String, String> {
+    if let Some(ref name) = config.profile {
+        return match organism.get_profile(name) {
+            Some(_) => Ok(name.clone()),
+            None => Err(format!("buffer profile '{name}' not in child organism")),
+        };
+    }
+    match organism.profile_names().as_slice() {
+        [only] => Ok(only.to_string()),
+        [] => Err("child organism has no profiles".into()),
+        several => Err(format!(
+            "child organism has {} profiles; name one with buffer.profile",
+            several.len()
+        )),
+    }
+}
+
 /// Register fresh tool instances for the required tools.
 ///
-/// Matches tool names to their constructors. Unknown tools are hard errors.
+/// Each is configured from the child's profiles, as the main binary
+/// configures its tools. Unknown tools are hard errors, as are file tools
+/// for a profile without a workspace.
 fn register_required_tools(
     mut builder: AgentPipelineBuilder,
     requires: &[String],
 ) -> Result<AgentPipelineBuilder, String> {
     for name in requires {
-        builder = match name.as_str() {
-            "file-read" => builder.register_tool(name, crate::tools::file_read::FileReadTool)?,
-            "file-write" => builder.register_tool(name, crate::tools::file_write::FileWriteTool)?,
-            "file-edit" => builder.register_tool(name, crate::tools::file_edit::FileEditTool)?,
-            "glob" => builder.register_tool(name, crate::tools::glob_tool::GlobTool)?,
-            "grep" => builder.register_tool(name, crate::tools::grep::GrepTool)?,
-            "command-exec" => {
-                builder.register_tool(name, crate::tools::command_exec::CommandExecTool::new())?
-            }
-            _ => return Err(format!("unknown required tool: '{name}'")),
-        };
+        if !NATIVE_TOOLS.contains(&name.as_str()) {
+            return Err(format!("unknown required tool: '{name}'"));
+        }
+        builder = builder.register_native_tool(name)?;
     }
     Ok(builder)
 }
@@ -238,7 +217,9 @@ mod tests {
 
     #[test]
     fn register_required_tools_known() {
-        let yaml = r#"
+        let dir = tempfile::TempDir::new().unwrap();
+        let yaml = format!(
+            r#"
 organism:
   name: child-test
 
@@ -258,14 +239,24 @@ profiles:
     linux_user: agentos-child
     listeners: all
     journal: prune_on_delivery
-"#;
-        let org = parse_organism(yaml).unwrap();
-        let dir = tempfile::TempDir::new().unwrap();
-        let builder = AgentPipelineBuilder::new(org, dir.path());
+    workspace:
+      root: {}
+"#,
+            dir.path().display()
+        );
+        let org = parse_organism(&yaml).unwrap();
+        let builder = AgentPipelineBuilder::new(org, &dir.path().join("data"));
 
         let requires = vec!["file-read".to_string(), "command-exec".to_string()];
         let result = register_required_tools(builder, &requires);
         assert!(result.is_ok());
+
+        // Without a workspace the child's file tools refuse to start
+        let workspace = format!("    workspace:\n      root: {}\n", dir.path().display());
+        let org = parse_organism(&yaml.replace(&workspace, "")).unwrap();
+        let builder = AgentPipelineBuilder::new(org, &dir.path().join("data-unjailed"));
+        let err = register_required_tools(builder, &requires).err().unwrap();
+        assert!(err.contains("declares no workspace"), "{err}");
     }
 
     #[test]
@@ -292,6 +283,45 @@ profiles:
         }
     }
 
+    #[test]
+    fn child_profile_is_explicit() {
+        let yaml = r#"
+organism:
+  name: child-test
+listeners: []
+profiles:
+  jailed:
+    linux_user: agentos-child
+    listeners: all
+    journal: prune_on_delivery
+    workspace:
+      root: .
+  open:
+    linux_user: agentos-child
+    listeners: all
+    journal: prune_on_delivery
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let mut config = BufferConfig {
+            organism: "child.yaml".into(),
+            max_concurrency: 1,
+            timeout_secs: 10,
+            profile: None,
+        };
+        let err = child_profile(&org, &config).unwrap_err();
+        assert!(err.contains("2 profiles"), "{err}");
+
+        config.profile = Some("jailed".into());
+        assert_eq!(child_profile(&org, &config).unwrap(), "jailed");
+        config.profile = Some("missing".into());
+        assert!(child_profile(&org, &config).is_err());
+
+        // A lone profile needs no naming
+        let single = parse_organism(&yaml[..yaml.find("  open:").unwrap()]).unwrap();
+        config.profile = None;
+        assert_eq!(child_profile(&single, &config).unwrap(), "jailed");
+    }
+
     #[test]
     fn resolve_organism_path_relative() {
         let base = std::path::Path::new("/home/user/project");
@@ -0,0 +1,375 @@
+//! Checkpoint store — pre-images of files the agent mutates.
+//!
+//! Before a `file-write` or `file-edit` runs, the file's current content is
+//! saved as a content-addressed blob (`<dir>/<sha256>`) and a checkpoint
+//! referencing it is logged to the WAL, keyed by thread and message id.
+//! Restoring a checkpoint writes the pre-image back (or deletes the file if
+//! it didn't exist) and logs which checkpoints were restored.
+//!
+//! The blobs are immutable and shared; the checkpoint list itself is
+//! rebuilt from the WAL on open, like the journal.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::error::{KernelError, KernelResult};
+use super::wal::{EntryType, WalEntry};
+
+/// A recorded pre-image of one file mutation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Monotonic sequence number (global order of mutations).
+    pub seq: u64,
+    pub thread_id: String,
+    /// Agent transcript message that issued the mutation (e.g. "msg-0004").
+    pub message_id: String,
+    /// Path that was mutated.
+    pub path: String,
+    /// Blob hash of the content before the mutation. None = file was created.
+    pub pre_image: Option<String>,
+    pub created_at: u64,
+    /// Whether this checkpoint has already been restored.
+    pub restored: bool,
+}
+
+/// A file with unrestored checkpoints (one row of the changed-files panel).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub path: String,
+    /// Number of unrestored mutations.
+    pub changes: usize,
+    /// Whether the first mutation created the file.
+    pub created: bool,
+    /// Thread and message of the most recent mutation.
+    pub thread_id: String,
+    pub message_id: String,
+}
+
+/// The checkpoint store.
+pub struct CheckpointStore {
+    /// seq → Checkpoint
+    checkpoints: BTreeMap<u64, Checkpoint>,
+    /// Blob directory.
+    dir: PathBuf,
+}
+
+impl CheckpointStore {
+    /// Open or create the store. Blobs live in `dir`.
+    pub fn open(dir: &Path) -> KernelResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            checkpoints: BTreeMap::new(),
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Apply a WAL entry during replay.
+    pub fn apply_wal_entry(&mut self, entry: &WalEntry) {
+        match entry.entry_type {
+            EntryType::CheckpointRecord => {
+                if let Some(cp) = Self::parse_record_payload(&entry.payload) {
+                    self.checkpoints.insert(cp.seq, cp);
+                }
+            }
+            EntryType::CheckpointRestore => {
+                self.mark_restored(&Self::parse_restore_payload(&entry.payload));
+            }
+            _ => {} // not a checkpoint op
+        }
+    }
+
+    /// Save content as a blob. Returns its hash; existing blobs are reused.
+    ///
+    /// The blob is written to a temp file, synced and renamed into place,
+    /// so a crash never leaves a truncated blob behind a WAL record.
+    pub fn store_blob(&self, content: &[u8]) -> KernelResult<String> {
+        let hash = format!("{:x}", Sha256::digest(content));
+        let path = self.dir.join(&hash);
+        if !path.exists() {
+            let tmp = path.with_extension("tmp");
+            let mut file = std::fs::File::create(&tmp)?;
+            file.write_all(content)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp, &path)?;
+        }
+        Ok(hash)
+    }
+
+    /// Load a blob by hash.
+    pub fn load_blob(&self, hash: &str) -> KernelResult<Vec<u8>> {
+        std::fs::read(self.dir.join(hash))
+            .map_err(|e| KernelError::InvalidData(format!("checkpoint blob {hash}: {e}")))
+    }
+
+    /// Next sequence number.
+    pub fn next_seq(&self) -> u64 {
+        self.checkpoints.keys().next_back().map_or(1, |s| s + 1)
+    }
+
+    /// Insert a checkpoint (after its WAL entry is written).
+    pub fn record(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.insert(checkpoint.seq, checkpoint);
+    }
+
+    /// Mark checkpoints as restored.
+    pub fn mark_restored(&mut self, seqs: &[u64]) {
+        for seq in seqs {
+            if let Some(cp) = self.checkpoints.get_mut(seq) {
+                cp.restored = true;
+            }
+        }
+    }
+
+    /// Build a WAL entry for a checkpoint.
+    pub fn wal_entry_record(cp: &Checkpoint) -> WalEntry {
+        // Payload: seq\0thread_id\0message_id\0path\0pre_image\0created_at
+        let payload = format!(
+            "{}\0{}\0{}\0{}\0{}\0{}",
+            cp.seq,
+            cp.thread_id,
+            cp.message_id,
+            cp.path,
+            cp.pre_image.as_deref().unwrap_or(""),
+            cp.created_at
+        );
+        WalEntry::new(EntryType::CheckpointRecord, payload.into_bytes())
+    }
+
+    /// Build a WAL entry marking checkpoints as restored.
+    pub fn wal_entry_restore(seqs: &[u64]) -> WalEntry {
+        let payload = seqs.iter().flat_map(|s| s.to_le_bytes()).collect();
+        WalEntry::new(EntryType::CheckpointRestore, payload)
+    }
+
+    /// Write a checkpoint's pre-image back to disk.
+    ///
+    /// A checkpoint without a pre-image means the mutation created the file,
+    /// so restoring removes it.
+    pub fn restore_file(&self, cp: &Checkpoint) -> KernelResult<()> {
+        let path = Path::new(&cp.path);
+        match cp.pre_image {
+            Some(ref hash) => {
+                let content = self.load_blob(hash)?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, content)?;
+            }
+            None => {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The most recent unrestored checkpoint.
+    pub fn last_active(&self) -> Option<&Checkpoint> {
+        self.checkpoints.values().rev().find(|cp| !cp.restored)
+    }
+
+    /// Unrestored checkpoints of a thread, newest first.
+    pub fn active_for_thread(&self, thread_id: &str) -> Vec<&Checkpoint> {
+        self.checkpoints
+            .values()
+            .rev()
+            .filter(|cp| !cp.restored && cp.thread_id == thread_id)
+            .collect()
+    }
+
+    /// Unrestored checkpoints of a thread from a message onward, newest first.
+    ///
+    /// Message ids are numbered per thread, so the message is looked up
+    /// within `thread_id`. Finds the earliest unrestored checkpoint recorded
+    /// for it and returns it plus every later one in the same thread. Empty
+    /// if the message has no unrestored checkpoints.
+    pub fn active_since_message(&self, thread_id: &str, message_id: &str) -> Vec<&Checkpoint> {
+        let Some(first) = self
+            .checkpoints
+            .values()
+            .find(|cp| !cp.restored && cp.thread_id == thread_id && cp.message_id == message_id)
+        else {
+            return Vec::new();
+        };
+        self.checkpoints
+            .range(first.seq..)
+            .rev()
+            .map(|(_, cp)| cp)
+            .filter(|cp| !cp.restored && cp.thread_id == thread_id)
+            .collect()
+    }
+
+    /// Files with unrestored checkpoints, most recently changed first.
+    pub fn changed_files(&self) -> Vec<ChangedFile> {
+        let mut files: Vec<ChangedFile> = Vec::new();
+        for cp in self.checkpoints.values().filter(|cp| !cp.restored) {
+            match files.iter_mut().position(|f| f.path == cp.path) {
+                Some(i) => {
+                    let mut f = files.remove(i);
+                    f.changes += 1;
+                    f.thread_id = cp.thread_id.clone();
+                    f.message_id = cp.message_id.clone();
+                    files.push(f);
+                }
+                None => files.push(ChangedFile {
+                    path: cp.path.clone(),
+                    changes: 1,
+                    created: cp.pre_image.is_none(),
+                    thread_id: cp.thread_id.clone(),
+                    message_id: cp.message_id.clone(),
+                }),
+            }
+        }
+        files.reverse();
+        files
+    }
+
+    /// Iterate over all checkpoints in sequence order.
+    pub fn all(&self) -> impl Iterator<Item = &Checkpoint> {
+        self.checkpoints.values()
+    }
+
+    /// Number of checkpoints (restored included).
+    pub fn count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    fn parse_record_payload(payload: &[u8]) -> Option<Checkpoint> {
+        let s = String::from_utf8_lossy(payload);
+        let parts: Vec<&str> = s.splitn(6, '\0').collect();
+        if parts.len() < 6 {
+            return None;
+        }
+        Some(Checkpoint {
+            seq: parts[0].parse().ok()?,
+            thread_id: parts[1].to_string(),
+            message_id: parts[2].to_string(),
+            path: parts[3].to_string(),
+            pre_image: (!parts[4].is_empty()).then(|| parts[4].to_string()),
+            created_at: parts[5].parse().unwrap_or(0),
+            restored: false,
+        })
+    }
+
+    fn parse_restore_payload(payload: &[u8]) -> Vec<u64> {
+        payload
+            .chunks_exact(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cp(seq: u64, thread: &str, msg: &str, path: &str, pre: Option<&str>) -> Checkpoint {
+        Checkpoint {
+            seq,
+            thread_id: thread.into(),
+            message_id: msg.into(),
+            path: path.into(),
+            pre_image: pre.map(String::from),
+            created_at: 1000 + seq,
+            restored: false,
+        }
+    }
+
+    #[test]
+    fn blobs_are_content_addressed() {
+        let dir = TempDir::new().unwrap();
+        let store = CheckpointStore::open(dir.path()).unwrap();
+        let a = store.store_blob(b"fn main() {}").unwrap();
+        let b = store.store_blob(b"fn main() {}").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+        assert!(!dir.path().join(format!("{a}.tmp")).exists());
+        assert_eq!(store.load_blob(&a).unwrap(), b"fn main() {}");
+        assert!(store.load_blob("missing").is_err());
+    }
+
+    #[test]
+    fn wal_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let mut store = CheckpointStore::open(dir.path()).unwrap();
+        let original = cp(7, "t1", "msg-0003", "/w/src/lib.rs", Some("abc"));
+        let created = cp(8, "t1", "msg-0003", "/w/new.rs", None);
+
+        store.apply_wal_entry(&CheckpointStore::wal_entry_record(&original));
+        store.apply_wal_entry(&CheckpointStore::wal_entry_record(&created));
+        store.apply_wal_entry(&CheckpointStore::wal_entry_restore(&[8]));
+
+        let all: Vec<_> = store.all().cloned().collect();
+        assert_eq!(all[0], original);
+        assert_eq!(all[1].pre_image, None);
+        assert!(all[1].restored);
+        assert_eq!(store.next_seq(), 9);
+    }
+
+    #[test]
+    fn since_message_stays_in_thread() {
+        let dir = TempDir::new().unwrap();
+        let mut store = CheckpointStore::open(dir.path()).unwrap();
+        store.record(cp(1, "t1", "msg-0001", "a.rs", Some("h1")));
+        store.record(cp(2, "t1", "msg-0003", "b.rs", Some("h2")));
+        store.record(cp(3, "t2", "msg-0001", "c.rs", None));
+        store.record(cp(4, "t1", "msg-0005", "a.rs", Some("h3")));
+
+        let seqs: Vec<u64> = store
+            .active_since_message("t1", "msg-0003")
+            .iter()
+            .map(|cp| cp.seq)
+            .collect();
+        assert_eq!(seqs, vec![4, 2]);
+        assert!(store.active_since_message("t1", "msg-0009").is_empty());
+
+        // Both threads have a msg-0001; each resolves within its own thread
+        let seqs: Vec<u64> = store
+            .active_since_message("t2", "msg-0001")
+            .iter()
+            .map(|cp| cp.seq)
+            .collect();
+        assert_eq!(seqs, vec![3]);
+        assert_eq!(store.active_since_message("t1", "msg-0001").len(), 3);
+        assert_eq!(store.active_for_thread("t2").len(), 1);
+        assert_eq!(store.last_active().unwrap().seq, 4);
+    }
+
+    #[test]
+    fn changed_files_groups_by_path() {
+        let dir = TempDir::new().unwrap();
+        let mut store = CheckpointStore::open(dir.path()).unwrap();
+        store.record(cp(1, "t1", "msg-0001", "a.rs", None));
+        store.record(cp(2, "t1", "msg-0002", "b.rs", Some("h")));
+        store.record(cp(3, "t1", "msg-0004", "a.rs", Some("h")));
+        store.mark_restored(&[2]);
+
+        let files = store.changed_files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "a.rs");
+        assert_eq!(files[0].changes, 2);
+        assert!(files[0].created);
+        assert_eq!(files[0].message_id, "msg-0004");
+    }
+
+    #[test]
+    fn restore_file_writes_or_removes() {
+        let dir = TempDir::new().unwrap();
+        let store = CheckpointStore::open(&dir.path().join("blobs")).unwrap();
+        let file = dir.path().join("x.txt");
+        let path = file.to_str().unwrap();
+
+        let hash = store.store_blob(b"before").unwrap();
+        std::fs::write(&file, "after").unwrap();
+        store.restore_file(&cp(1, "t", "m", path, Some(&hash))).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before");
+
+        store.restore_file(&cp(2, "t", "m", path, None)).unwrap();
+        assert!(!file.exists());
+    }
+}
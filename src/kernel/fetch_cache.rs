@@ -0,0 +1,149 @@
+//! Fetch cache — web pages `http-fetch` has fetched, keyed by URL.
+//!
+//! Each page is a JSON file `<dir>/<sha256(url)>.json` holding the URL, when
+//! it was fetched, its status and content type, and the text the agent was
+//! shown. Readers pass the age they accept; past [`MAX_ENTRIES`] pages the
+//! oldest are evicted. Unlike the rest of the kernel the cache isn't
+//! WAL-logged — losing it only costs a refetch.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::error::{KernelError, KernelResult};
+
+/// Pages kept before the oldest are evicted.
+pub const MAX_ENTRIES: usize = 256;
+
+/// A fetched page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedPage {
+    pub url: String,
+    /// Seconds since the Unix epoch.
+    pub fetched_at: u64,
+    pub status: u16,
+    pub content_type: String,
+    /// The page as text (HTML already converted to markdown).
+    pub text: String,
+    /// Whether the body was cut at the size cap.
+    pub truncated: bool,
+}
+
+/// The fetch cache.
+pub struct FetchCache {
+    dir: PathBuf,
+}
+
+impl FetchCache {
+    /// Open or create the cache in `dir`.
+    pub fn open(dir: &Path) -> KernelResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:x}.json", Sha256::digest(url.as_bytes())))
+    }
+
+    /// The cached page for `url`, if fetched at most `max_age` seconds before `now`.
+    pub fn get(&self, url: &str, max_age: u64, now: u64) -> Option<CachedPage> {
+        let data = std::fs::read(self.entry_path(url)).ok()?;
+        let page: CachedPage = serde_json::from_slice(&data).ok()?;
+        (page.url == url && now.saturating_sub(page.fetched_at) <= max_age).then_some(page)
+    }
+
+    /// Store a page, replacing any earlier fetch of its URL.
+    pub fn put(&self, page: &CachedPage) -> KernelResult<()> {
+        let data = serde_json::to_vec(page)
+            .map_err(|e| KernelError::InvalidData(format!("fetch cache entry: {e}")))?;
+        // Write then rename, so a reader never sees half an entry
+        let path = self.entry_path(&page.url);
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &path)?;
+        self.evict()
+    }
+
+    /// Drop the oldest entries past [`MAX_ENTRIES`].
+    fn evict(&self) -> KernelResult<()> {
+        let mut entries: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect();
+        if entries.len() <= MAX_ENTRIES {
+            return Ok(());
+        }
+        entries.sort();
+        for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    /// Number of cached pages.
+    pub fn count(&self) -> usize {
+        std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn page(url: &str, fetched_at: u64) -> CachedPage {
+        CachedPage {
+            url: url.into(),
+            fetched_at,
+            status: 200,
+            content_type: "text/html".into(),
+            text: format!("# {url}"),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn get_honours_max_age_and_replaces() {
+        let dir = TempDir::new().unwrap();
+        let cache = FetchCache::open(dir.path()).unwrap();
+        cache.put(&page("https://docs.rs/a", 1000)).unwrap();
+
+        assert_eq!(
+            cache.get("https://docs.rs/a", 60, 1030),
+            Some(page("https://docs.rs/a", 1000))
+        );
+        assert!(cache.get("https://docs.rs/a", 60, 1061).is_none());
+        assert!(cache.get("https://docs.rs/b", 60, 1030).is_none());
+
+        cache.put(&page("https://docs.rs/a", 2000)).unwrap();
+        assert_eq!(
+            cache.get("https://docs.rs/a", 60, 2000).unwrap().fetched_at,
+            2000
+        );
+        assert_eq!(cache.count(), 1);
+    }
+
+    #[test]
+    fn oldest_entries_evicted() {
+        let dir = TempDir::new().unwrap();
+        let cache = FetchCache::open(dir.path()).unwrap();
+        for i in 0..MAX_ENTRIES + 3 {
+            cache
+                .put(&page(&format!("https://docs.rs/{i}"), 0))
+                .unwrap();
+        }
+        assert_eq!(cache.count(), MAX_ENTRIES);
+    }
+}
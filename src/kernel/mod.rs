@@ -5,19 +5,32 @@
 //! - Context store (VMM)
 //! - Message journal (audit/tape)
 //!
+//! Plus the checkpoint store (pre-images of mutated files, for undo), the
+//! read-version table (which file versions each thread has seen) and the
+//! fetch cache (web pages `http-fetch` has fetched, by URL).
+//!
 //! One WAL, atomic ops. Everything else is ephemeral userspace.
 
+pub mod checkpoints;
 pub mod context_store;
 pub mod error;
+pub mod fetch_cache;
 pub mod journal;
+pub mod read_versions;
 pub mod thread_table;
 pub mod wal;
 
 use std::path::{Path, PathBuf};
 
+use tokio::sync::broadcast;
+
+use crate::pipeline::events::{KernelOpType, PipelineEvent};
+use checkpoints::{Checkpoint, CheckpointStore};
 use context_store::ContextStore;
 use error::KernelResult;
+use fetch_cache::FetchCache;
 use journal::Journal;
+use read_versions::ReadVersions;
 use thread_table::ThreadTable;
 use wal::Wal;
 
@@ -27,7 +40,11 @@ pub struct Kernel {
     pub threads: ThreadTable,
     pub contexts: ContextStore,
     pub journal: Journal,
+    pub checkpoints: CheckpointStore,
+    pub read_versions: ReadVersions,
+    pub fetch_cache: FetchCache,
     data_dir: PathBuf,
+    event_tx: Option<broadcast::Sender<PipelineEvent>>,
 }
 
 impl Kernel {
@@ -40,6 +57,9 @@ impl Kernel {
         let mut threads = ThreadTable::open(&data_dir.join("threads.bin"))?;
         let mut contexts = ContextStore::open(&data_dir.join("contexts"))?;
         let mut journal = Journal::open(&data_dir.join("journal.bin"))?;
+        let mut checkpoints = CheckpointStore::open(&data_dir.join("checkpoints"))?;
+        let mut read_versions = ReadVersions::new();
+        let fetch_cache = FetchCache::open(&data_dir.join("fetch-cache"))?;
 
         // Replay WAL and apply any entries not yet reflected in state
         let entries = wal.replay()?;
@@ -47,6 +67,8 @@ impl Kernel {
             threads.apply_wal_entry(entry);
             contexts.apply_wal_entry(entry);
             journal.apply_wal_entry(entry);
+            checkpoints.apply_wal_entry(entry);
+            read_versions.apply_wal_entry(entry);
         }
 
         Ok(Self {
@@ -54,10 +76,28 @@ impl Kernel {
             threads,
             contexts,
             journal,
+            checkpoints,
+            read_versions,
+            fetch_cache,
             data_dir: data_dir.to_path_buf(),
+            event_tx: None,
         })
     }
 
+    /// Report prunes and folds on the pipeline event channel.
+    pub fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.event_tx = Some(tx);
+    }
+
+    fn emit(&self, op: KernelOpType, thread_id: &str) {
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(PipelineEvent::KernelOp {
+                op,
+                thread_id: thread_id.to_string(),
+            });
+        }
+    }
+
     /// Initialize the root thread with WAL logging.
     pub fn initialize_root(&mut self, organism_name: &str, profile: &str) -> KernelResult<String> {
         let uuid = self.threads.initialize_root(organism_name, profile);
@@ -97,6 +137,7 @@ impl Kernel {
         let result = self.threads.prune_for_response(thread_id);
         self.contexts.release(thread_id)?;
         self.journal.mark_delivered_by_thread(thread_id);
+        self.emit(KernelOpType::ThreadPruned, thread_id);
 
         Ok(result)
     }
@@ -178,6 +219,7 @@ impl Kernel {
                 let _ = self.contexts.add_segment(parent_id, fold_seg);
             }
         }
+        self.emit(KernelOpType::ContextFolded, thread_id);
 
         Ok(result)
     }
@@ -225,6 +267,139 @@ impl Kernel {
         Ok(new_uuid)
     }
 
+    /// Record a file's pre-image before it is mutated.
+    ///
+    /// A missing file is recorded without a pre-image, so undoing the
+    /// mutation deletes the file again. Returns the checkpoint's sequence number.
+    pub fn record_checkpoint(
+        &mut self,
+        thread_id: &str,
+        message_id: &str,
+        path: &Path,
+    ) -> KernelResult<u64> {
+        let pre_image = match std::fs::read(path) {
+            Ok(content) => Some(self.checkpoints.store_blob(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let checkpoint = Checkpoint {
+            seq: self.checkpoints.next_seq(),
+            thread_id: thread_id.to_string(),
+            message_id: message_id.to_string(),
+            path: path.to_string_lossy().to_string(),
+            pre_image,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            restored: false,
+        };
+        let seq = checkpoint.seq;
+
+        // WAL first (blob is already on disk), then apply to state
+        self.wal.append(&CheckpointStore::wal_entry_record(&checkpoint))?;
+        self.checkpoints.record(checkpoint);
+        Ok(seq)
+    }
+
+    /// Drop checkpoints whose mutation never happened, leaving the files
+    /// alone. They are logged as restored, so undo skips them.
+    pub fn discard_checkpoints(&mut self, seqs: &[u64]) -> KernelResult<()> {
+        if seqs.is_empty() {
+            return Ok(());
+        }
+        self.wal.append(&CheckpointStore::wal_entry_restore(seqs))?;
+        self.checkpoints.mark_restored(seqs);
+        Ok(())
+    }
+
+    /// Undo the most recent file mutation. Returns the restored paths.
+    pub fn undo_last(&mut self) -> KernelResult<Vec<String>> {
+        let targets = self.checkpoints.last_active().cloned().into_iter().collect();
+        self.restore_checkpoints(targets)
+    }
+
+    /// Undo every file mutation a thread made. Returns the restored paths.
+    pub fn undo_thread(&mut self, thread_id: &str) -> KernelResult<Vec<String>> {
+        let targets = self
+            .checkpoints
+            .active_for_thread(thread_id)
+            .into_iter()
+            .cloned()
+            .collect();
+        self.restore_checkpoints(targets)
+    }
+
+    /// Undo the file mutations of a thread's message and everything after it
+    /// in that thread. Returns the restored paths.
+    pub fn rewind(&mut self, thread_id: &str, message_id: &str) -> KernelResult<Vec<String>> {
+        let targets = self
+            .checkpoints
+            .active_since_message(thread_id, message_id)
+            .into_iter()
+            .cloned()
+            .collect();
+        self.restore_checkpoints(targets)
+    }
+
+    /// Restore checkpoints (given newest first) and log them as restored.
+    ///
+    /// Newest-first order leaves each file at its earliest pre-image. If a
+    /// restore fails, the ones already written are still logged.
+    fn restore_checkpoints(&mut self, targets: Vec<Checkpoint>) -> KernelResult<Vec<String>> {
+        let mut restored = Vec::new();
+        let mut paths: Vec<String> = Vec::new();
+        let mut failure = None;
+        for cp in &targets {
+            if let Err(e) = self.checkpoints.restore_file(cp) {
+                failure = Some(e);
+                break;
+            }
+            restored.push(cp.seq);
+            if !paths.contains(&cp.path) {
+                paths.push(cp.path.clone());
+            }
+        }
+
+        if !restored.is_empty() {
+            self.wal.append(&CheckpointStore::wal_entry_restore(&restored))?;
+            self.checkpoints.mark_restored(&restored);
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(paths),
+        }
+    }
+
+    /// Get a reference to the checkpoint store.
+    pub fn checkpoints(&self) -> &CheckpointStore {
+        &self.checkpoints
+    }
+
+    /// Record that a thread has seen a version of a file (WAL-logged).
+    pub fn record_file_version(
+        &mut self,
+        thread_id: &str,
+        path: &Path,
+        version: &str,
+    ) -> KernelResult<()> {
+        let path = path.to_string_lossy();
+        self.wal
+            .append(&ReadVersions::wal_entry(thread_id, &path, version))?;
+        self.read_versions.record(thread_id, &path, version);
+        Ok(())
+    }
+
+    /// The version of a file a thread last saw, if it has seen one.
+    pub fn file_version(&self, thread_id: &str, path: &Path) -> Option<&str> {
+        self.read_versions.get(thread_id, &path.to_string_lossy())
+    }
+
+    /// Get a reference to the fetch cache.
+    pub fn fetch_cache(&self) -> &FetchCache {
+        &self.fetch_cache
+    }
+
     /// Get a reference to the thread table.
     pub fn threads(&self) -> &ThreadTable {
         &self.threads
@@ -641,6 +816,35 @@ mod tests {
         assert!(!kernel.contexts().exists(&child));
     }
 
+    #[test]
+    fn prune_and_fold_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let mut kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let (tx, mut rx) = broadcast::channel(16);
+        kernel.set_event_sender(tx);
+        let root = kernel.initialize_root("org", "admin").unwrap();
+        let pruned = kernel.dispatch_message("console", "handler", &root, "msg-e1").unwrap();
+        let folded = kernel.dispatch_message("console", "handler", &root, "msg-e2").unwrap();
+
+        kernel.prune_thread(&pruned).unwrap();
+        kernel.fold_thread(&folded, b"summary").unwrap();
+        kernel.prune_thread("no-such-thread").unwrap();
+
+        match rx.try_recv().unwrap() {
+            PipelineEvent::KernelOp { op: KernelOpType::ThreadPruned, thread_id } => {
+                assert_eq!(thread_id, pruned)
+            }
+            other => panic!("expected ThreadPruned, got {other:?}"),
+        }
+        match rx.try_recv().unwrap() {
+            PipelineEvent::KernelOp { op: KernelOpType::ContextFolded, thread_id } => {
+                assert_eq!(thread_id, folded)
+            }
+            other => panic!("expected ContextFolded, got {other:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
     #[test]
     fn fold_thread_child_context_released() {
         let dir = TempDir::new().unwrap();
@@ -659,6 +863,98 @@ mod tests {
         assert!(!kernel.contexts().exists(&child));
     }
 
+    #[test]
+    fn undo_restores_pre_images() {
+        let dir = TempDir::new().unwrap();
+        let mut kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let file = dir.path().join("lib.rs");
+        let created = dir.path().join("new.rs");
+
+        std::fs::write(&file, "v1").unwrap();
+        kernel.record_checkpoint("t1", "msg-0001", &file).unwrap();
+        std::fs::write(&file, "v2").unwrap();
+        kernel.record_checkpoint("t1", "msg-0003", &file).unwrap();
+        std::fs::write(&file, "v3").unwrap();
+        kernel.record_checkpoint("t1", "msg-0003", &created).unwrap();
+        std::fs::write(&created, "fresh").unwrap();
+
+        // Last mutation: the created file goes away
+        let paths = kernel.undo_last().unwrap();
+        assert_eq!(paths, vec![created.to_string_lossy().to_string()]);
+        assert!(!created.exists());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v3");
+
+        // Whole thread: back to the first pre-image
+        kernel.undo_thread("t1").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1");
+        assert!(kernel.checkpoints().changed_files().is_empty());
+        assert!(kernel.undo_last().unwrap().is_empty());
+    }
+
+    #[test]
+    fn discarded_checkpoints_are_not_undone() {
+        let dir = TempDir::new().unwrap();
+        let mut kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let file = dir.path().join("lib.rs");
+
+        std::fs::write(&file, "v1").unwrap();
+        kernel.record_checkpoint("t1", "msg-0001", &file).unwrap();
+        std::fs::write(&file, "v2").unwrap();
+        let seq = kernel.record_checkpoint("t1", "msg-0003", &file).unwrap();
+        kernel.discard_checkpoints(&[seq]).unwrap();
+
+        // The discarded mutation never happened; undo reaches the one before
+        assert_eq!(kernel.checkpoints().last_active().unwrap().message_id, "msg-0001");
+        kernel.undo_last().unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1");
+    }
+
+    #[test]
+    fn rewind_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        let file = dir.path().join("main.rs");
+
+        {
+            let mut kernel = Kernel::open(&data_dir).unwrap();
+            std::fs::write(&file, "original").unwrap();
+            kernel.record_checkpoint("t1", "msg-0002", &file).unwrap();
+            std::fs::write(&file, "edited").unwrap();
+            kernel.record_checkpoint("t1", "msg-0004", &file).unwrap();
+            std::fs::write(&file, "edited twice").unwrap();
+        }
+
+        // Checkpoints come back from the WAL
+        let mut kernel = Kernel::open(&data_dir).unwrap();
+        assert_eq!(kernel.checkpoints().count(), 2);
+
+        kernel.rewind("t1", "msg-0004").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "edited");
+        kernel.rewind("t1", "msg-0002").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+
+        // Restores are logged too
+        let kernel = Kernel::open(&data_dir).unwrap();
+        assert!(kernel.checkpoints().all().all(|cp| cp.restored));
+    }
+
+    #[test]
+    fn file_versions_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        let file = dir.path().join("lib.rs");
+
+        {
+            let mut kernel = Kernel::open(&data_dir).unwrap();
+            kernel.record_file_version("t1", &file, "aaaa").unwrap();
+            kernel.record_file_version("t1", &file, "bbbb").unwrap();
+        }
+
+        let kernel = Kernel::open(&data_dir).unwrap();
+        assert_eq!(kernel.file_version("t1", &file), Some("bbbb"));
+        assert_eq!(kernel.file_version("t2", &file), None);
+    }
+
     #[test]
     fn kernel_op_context_folded_variant() {
         // Verify the KernelOpType::ContextFolded variant constructs
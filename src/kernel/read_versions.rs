@@ -0,0 +1,85 @@
+//! Read-version table — the version of each file a thread last saw.
+//!
+//! A version is the short content hash the file tools report
+//! (`[version: …]`). It is recorded when `file-read` returns and when one
+//! of the thread's own writes lands (a file tool, `replace`, `diagnostics`,
+//! or a `command-exec` redirecting into a file it had seen), so a later
+//! write can be checked against what the agent actually looked at, and a
+//! write to a file the thread never read can be flagged. Rebuilt from the
+//! WAL on open.
+
+use std::collections::HashMap;
+
+use super::wal::{EntryType, WalEntry};
+
+/// Last-seen file versions, keyed by (thread, path).
+#[derive(Debug, Default)]
+pub struct ReadVersions {
+    versions: HashMap<(String, String), String>,
+}
+
+impl ReadVersions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a WAL entry during replay.
+    pub fn apply_wal_entry(&mut self, entry: &WalEntry) {
+        if entry.entry_type != EntryType::FileVersionSeen {
+            return;
+        }
+        let s = String::from_utf8_lossy(&entry.payload);
+        let parts: Vec<&str> = s.splitn(3, '\0').collect();
+        if let [thread_id, path, version] = parts[..] {
+            self.record(thread_id, path, version);
+        }
+    }
+
+    /// Record that `thread_id` has seen `version` of `path`.
+    pub fn record(&mut self, thread_id: &str, path: &str, version: &str) {
+        self.versions.insert(
+            (thread_id.to_string(), path.to_string()),
+            version.to_string(),
+        );
+    }
+
+    /// The version of `path` the thread last saw, if any.
+    pub fn get(&self, thread_id: &str, path: &str) -> Option<&str> {
+        self.versions
+            .get(&(thread_id.to_string(), path.to_string()))
+            .map(String::as_str)
+    }
+
+    /// Build a WAL entry for a seen version.
+    pub fn wal_entry(thread_id: &str, path: &str, version: &str) -> WalEntry {
+        // Payload: thread_id\0path\0version
+        let payload = format!("{thread_id}\0{path}\0{version}");
+        WalEntry::new(EntryType::FileVersionSeen, payload.into_bytes())
+    }
+
+    /// Number of (thread, path) pairs tracked.
+    pub fn count(&self) -> usize {
+        self.versions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_keeps_latest_version_per_thread() {
+        let mut table = ReadVersions::new();
+        for entry in [
+            ReadVersions::wal_entry("t1", "/src/a.rs", "v1"),
+            ReadVersions::wal_entry("t2", "/src/a.rs", "v1"),
+            ReadVersions::wal_entry("t1", "/src/a.rs", "v2"),
+        ] {
+            table.apply_wal_entry(&entry);
+        }
+        assert_eq!(table.get("t1", "/src/a.rs"), Some("v2"));
+        assert_eq!(table.get("t2", "/src/a.rs"), Some("v1"));
+        assert_eq!(table.get("t3", "/src/a.rs"), None);
+        assert_eq!(table.count(), 2);
+    }
+}
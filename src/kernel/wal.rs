@@ -46,6 +46,13 @@ pub enum EntryType {
     JournalDelivered = 21,
     JournalFailed = 22,
 
+    // Checkpoint ops (file pre-images for undo)
+    CheckpointRecord = 30,
+    CheckpointRestore = 31,
+
+    // Read-version ops (optimistic concurrency for file writes)
+    FileVersionSeen = 32,
+
     // Compound
     AtomicBatch = 50,
 }
@@ -70,6 +77,9 @@ impl EntryType {
             20 => Some(Self::JournalDispatched),
             21 => Some(Self::JournalDelivered),
             22 => Some(Self::JournalFailed),
+            30 => Some(Self::CheckpointRecord),
+            31 => Some(Self::CheckpointRestore),
+            32 => Some(Self::FileVersionSeen),
             50 => Some(Self::AtomicBatch),
             _ => None,
         }
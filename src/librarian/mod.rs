@@ -10,6 +10,7 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
+use crate::agent::context::is_transcript_segment;
 use crate::kernel::Kernel;
 use crate::llm::types::Message;
 use crate::llm::LlmPool;
@@ -147,7 +148,8 @@ impl Librarian {
                 None
             } else {
                 let mut ctx = String::new();
-                for seg in &working_set {
+                // Mirrored agent turns reach the model as messages, not here
+                for seg in working_set.iter().filter(|s| !is_transcript_segment(&s.id)) {
                     if let Ok(text) = std::str::from_utf8(&seg.content) {
                         ctx.push_str(&format!("[{}: {}]\n{}\n\n", seg.tag, seg.id, text));
                     }
@@ -190,6 +190,7 @@ mod tests {
             system: None,
             temperature: Some(0.7),
             tools: None,
+            thinking: None,
         };
 
         let json = serde_json::to_value(&req).unwrap();
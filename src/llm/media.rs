@@ -0,0 +1,137 @@
+//! Media content — images and PDFs as content blocks.
+//!
+//! File type is sniffed from magic bytes, not the extension, so a misnamed
+//! screenshot still loads and a `.png` that isn't one is rejected.
+
+use std::path::Path;
+
+use base64::Engine;
+
+use super::types::{ContentBlock, MediaSource};
+
+/// Largest image the API accepts inline (5 MB).
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest PDF the API accepts inline (32 MB).
+pub const MAX_DOCUMENT_BYTES: usize = 32 * 1024 * 1024;
+
+/// Detect a supported media type from the leading bytes.
+pub fn sniff_media_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// Whether a media type is sent as an image block (vs. a document block).
+pub fn is_image(media_type: &str) -> bool {
+    media_type.starts_with("image/")
+}
+
+/// Size limit for a media type.
+pub fn max_bytes(media_type: &str) -> usize {
+    if is_image(media_type) {
+        MAX_IMAGE_BYTES
+    } else {
+        MAX_DOCUMENT_BYTES
+    }
+}
+
+/// Base64-encode raw bytes (standard alphabet, padded).
+pub fn encode_base64(data: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(data)
+}
+
+/// Build an image or document block from already-encoded data.
+pub fn block_from_base64(media_type: &str, data: String) -> ContentBlock {
+    let source = MediaSource::base64(media_type, data);
+    if is_image(media_type) {
+        ContentBlock::Image { source }
+    } else {
+        ContentBlock::Document { source }
+    }
+}
+
+/// Build an image or document block from raw bytes.
+pub fn block_from_bytes(media_type: &str, data: &[u8]) -> ContentBlock {
+    block_from_base64(media_type, encode_base64(data))
+}
+
+/// Check that raw bytes are a supported media file within the size limit.
+/// Returns the media type.
+pub fn check_media(data: &[u8]) -> Result<&'static str, String> {
+    let media_type = sniff_media_type(data)
+        .ok_or("unsupported file type (expected PNG, JPEG, GIF, WebP or PDF)")?;
+    let limit = max_bytes(media_type);
+    if data.len() > limit {
+        return Err(format!(
+            "{media_type} is {} bytes, over the {} byte limit",
+            data.len(),
+            limit
+        ));
+    }
+    Ok(media_type)
+}
+
+/// Load a file as an image or document block.
+pub fn load_media(path: &Path) -> Result<ContentBlock, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let media_type = check_media(&data).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(block_from_bytes(media_type, &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+
+    #[test]
+    fn sniffs_known_types() {
+        assert_eq!(sniff_media_type(PNG_HEADER), Some("image/png"));
+        assert_eq!(sniff_media_type(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("image/jpeg"));
+        assert_eq!(sniff_media_type(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(sniff_media_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_media_type(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_media_type(b"fn main() {}"), None);
+        assert_eq!(sniff_media_type(&[0x00, 0x01, 0x02]), None);
+    }
+
+    #[test]
+    fn image_and_document_blocks() {
+        assert!(matches!(block_from_bytes("image/png", PNG_HEADER), ContentBlock::Image { .. }));
+        match block_from_bytes("application/pdf", b"%PDF-1.4") {
+            ContentBlock::Document { source } => {
+                assert_eq!(source.media_type, "application/pdf");
+                assert_eq!(source.data, "JVBERi0xLjQ=");
+            }
+            other => panic!("expected Document, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn check_media_rejects_text_and_oversized() {
+        assert!(check_media(b"hello").unwrap_err().contains("unsupported"));
+        let mut big = PNG_HEADER.to_vec();
+        big.resize(MAX_IMAGE_BYTES + 1, 0);
+        assert!(check_media(&big).unwrap_err().contains("limit"));
+    }
+
+    #[test]
+    fn load_media_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mockup.bin");
+        std::fs::write(&path, PNG_HEADER).unwrap();
+        assert!(matches!(load_media(&path).unwrap(), ContentBlock::Image { .. }));
+        assert!(load_media(&dir.path().join("missing.png")).is_err());
+    }
+}
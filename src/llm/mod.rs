@@ -5,6 +5,7 @@
 
 pub mod client;
 pub mod handler;
+pub mod media;
 pub mod types;
 
 pub use client::{AnthropicClient, LlmError, ModelInfo};
@@ -97,6 +98,7 @@ impl LlmPool {
             system: system.map(|s| s.to_string()),
             temperature: None,
             tools: None,
+            thinking: None,
         };
 
         self.client.messages(&request).await
@@ -110,6 +112,23 @@ impl LlmPool {
         max_tokens: u32,
         system: Option<&str>,
         tools: Vec<types::ToolDefinition>,
+    ) -> Result<MessagesResponse, LlmError> {
+        self.complete_with_thinking(model, messages, max_tokens, system, tools, None)
+            .await
+    }
+
+    /// Send a completion request with tool definitions and optional extended thinking.
+    ///
+    /// - `thinking_budget`: Some(n) enables extended thinking with an n-token budget
+    ///   (must be below `max_tokens`).
+    pub async fn complete_with_thinking(
+        &self,
+        model: Option<&str>,
+        messages: Vec<Message>,
+        max_tokens: u32,
+        system: Option<&str>,
+        tools: Vec<types::ToolDefinition>,
+        thinking_budget: Option<u32>,
     ) -> Result<MessagesResponse, LlmError> {
         let resolved_model = model
             .map(|m| resolve_model(m).to_string())
@@ -122,6 +141,7 @@ impl LlmPool {
             system: system.map(|s| s.to_string()),
             temperature: None,
             tools: if tools.is_empty() { None } else { Some(tools) },
+            thinking: thinking_budget.map(types::ThinkingConfig::enabled),
         };
 
         self.client.messages(&request).await
@@ -43,6 +43,9 @@ pub struct ToolDefinition {
 
 /// A content block in a response (or assistant message).
 /// The API returns these as `{"type": "text", ...}` or `{"type": "tool_use", ...}`.
+///
+/// Thinking blocks (extended thinking) must be sent back unmodified — including
+/// the signature / redacted data — when replaying an assistant turn that used tools.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ContentBlock {
@@ -57,11 +60,63 @@ pub enum ContentBlock {
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
+        /// Plain text, or text + image/document blocks.
         #[serde(skip_serializing_if = "Option::is_none")]
-        content: Option<String>,
+        content: Option<MessageContent>,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String, signature: String },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+    #[serde(rename = "image")]
+    Image { source: MediaSource },
+    #[serde(rename = "document")]
+    Document { source: MediaSource },
+}
+
+/// Inline data for an image or document block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaSource {
+    /// Always "base64" for inline data.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// MIME type: "image/png", "image/jpeg", "image/gif", "image/webp", "application/pdf".
+    pub media_type: String,
+    /// Base64-encoded bytes.
+    pub data: String,
+}
+
+impl MediaSource {
+    /// Inline base64 source for already-encoded data.
+    pub fn base64(media_type: &str, data: String) -> Self {
+        Self {
+            kind: "base64".into(),
+            media_type: media_type.to_string(),
+            data,
+        }
+    }
+}
+
+/// Extended thinking configuration for a request.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThinkingConfig {
+    /// Always "enabled" when present.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Max tokens the model may spend reasoning (>= 1024, < max_tokens).
+    pub budget_tokens: u32,
+}
+
+impl ThinkingConfig {
+    /// Enable extended thinking with the given token budget.
+    pub fn enabled(budget_tokens: u32) -> Self {
+        Self {
+            kind: "enabled".into(),
+            budget_tokens,
+        }
+    }
 }
 
 // ── Message Content ──
@@ -186,14 +241,37 @@ impl Message {
         }
     }
 
+    /// Create a user message with content blocks (e.g. attachments + task text).
+    pub fn user_blocks(blocks: Vec<ContentBlock>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
     /// Create a user message with tool results.
+    ///
+    /// Results carrying media are sent as a block list (text first);
+    /// plain results stay a string.
     pub fn tool_results(results: Vec<ToolResultBlock>) -> Self {
         let blocks = results
             .into_iter()
-            .map(|r| ContentBlock::ToolResult {
-                tool_use_id: r.tool_use_id,
-                content: Some(r.content),
-                is_error: if r.is_error { Some(true) } else { None },
+            .map(|r| {
+                let content = if r.media.is_empty() {
+                    MessageContent::Text(r.content)
+                } else {
+                    let mut blocks = Vec::with_capacity(r.media.len() + 1);
+                    if !r.content.is_empty() {
+                        blocks.push(ContentBlock::Text { text: r.content });
+                    }
+                    blocks.extend(r.media);
+                    MessageContent::Blocks(blocks)
+                };
+                ContentBlock::ToolResult {
+                    tool_use_id: r.tool_use_id,
+                    content: Some(content),
+                    is_error: if r.is_error { Some(true) } else { None },
+                }
             })
             .collect();
         Self {
@@ -217,6 +295,8 @@ pub struct ToolResultBlock {
     pub tool_use_id: String,
     pub content: String,
     pub is_error: bool,
+    /// Image/document blocks returned alongside the text (e.g. file-read of a PNG).
+    pub media: Vec<ContentBlock>,
 }
 
 // ── Request / Response ──
@@ -233,6 +313,8 @@ pub struct MessagesRequest {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
 }
 
 /// Response from the Anthropic Messages API.
@@ -261,6 +343,25 @@ impl MessagesResponse {
         })
     }
 
+    /// Concatenated extended-thinking text, if the response has any.
+    ///
+    /// Redacted thinking is encrypted and contributes nothing here.
+    pub fn thinking(&self) -> Option<String> {
+        let parts: Vec<&str> = self
+            .content
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Thinking { thinking, .. } => Some(thinking.as_str()),
+                _ => None,
+            })
+            .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("\n\n"))
+        }
+    }
+
     /// Check if the response contains tool_use blocks.
     pub fn has_tool_use(&self) -> bool {
         self.content
@@ -307,6 +408,7 @@ mod tests {
             system: Some("You are helpful.".into()),
             temperature: None,
             tools: None,
+            thinking: None,
         };
 
         let json = serde_json::to_string(&req).unwrap();
@@ -338,6 +440,7 @@ mod tests {
                     "required": ["expression"]
                 }),
             }]),
+            thinking: None,
         };
 
         let json = serde_json::to_string(&req).unwrap();
@@ -441,6 +544,7 @@ mod tests {
             tool_use_id: "toolu_123".into(),
             content: "4".into(),
             is_error: false,
+            media: vec![],
         }]);
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"tool_use_id\":\"toolu_123\""));
@@ -455,6 +559,7 @@ mod tests {
             tool_use_id: "toolu_err".into(),
             content: "file not found".into(),
             is_error: true,
+            media: vec![],
         }]);
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"is_error\":true"));
@@ -602,4 +707,120 @@ mod tests {
             _ => panic!("expected ToolUse"),
         }
     }
+
+    #[test]
+    fn request_with_thinking_serializes() {
+        let req = MessagesRequest {
+            model: "opus".into(),
+            max_tokens: 8192,
+            messages: vec![Message::text("user", "Think hard.")],
+            system: None,
+            temperature: None,
+            tools: None,
+            thinking: Some(ThinkingConfig::enabled(2048)),
+        };
+
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["thinking"]["type"], "enabled");
+        assert_eq!(json["thinking"]["budget_tokens"], 2048);
+    }
+
+    #[test]
+    fn response_deserializes_thinking_blocks() {
+        let json = r#"{
+            "id": "msg_think",
+            "model": "claude-opus-4-6",
+            "content": [
+                {"type": "thinking", "thinking": "Check the parser first.", "signature": "sig=="},
+                {"type": "redacted_thinking", "data": "opaque"},
+                {"type": "tool_use", "id": "toolu_1", "name": "file-read", "input": {"path": "a.rs"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 30, "output_tokens": 25}
+        }"#;
+
+        let resp: MessagesResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(resp.thinking().as_deref(), Some("Check the parser first."));
+        assert!(resp.text().is_none());
+        assert_eq!(resp.tool_use_blocks().len(), 1);
+    }
+
+    #[test]
+    fn thinking_blocks_roundtrip_unmodified() {
+        let blocks = vec![
+            ContentBlock::Thinking {
+                thinking: "reasoning".into(),
+                signature: "sig==".into(),
+            },
+            ContentBlock::RedactedThinking {
+                data: "opaque".into(),
+            },
+        ];
+        let json = serde_json::to_value(Message::assistant_blocks(blocks)).unwrap();
+        assert_eq!(json["content"][0]["type"], "thinking");
+        assert_eq!(json["content"][0]["signature"], "sig==");
+        assert_eq!(json["content"][1]["type"], "redacted_thinking");
+        assert_eq!(json["content"][1]["data"], "opaque");
+    }
+
+    #[test]
+    fn image_block_serializes_with_base64_source() {
+        let block = ContentBlock::Image {
+            source: MediaSource::base64("image/png", "iVBORw==".into()),
+        };
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["type"], "image");
+        assert_eq!(json["source"]["type"], "base64");
+        assert_eq!(json["source"]["media_type"], "image/png");
+        assert_eq!(json["source"]["data"], "iVBORw==");
+    }
+
+    #[test]
+    fn tool_result_with_media_serializes_as_blocks() {
+        let msg = Message::tool_results(vec![ToolResultBlock {
+            tool_use_id: "toolu_img".into(),
+            content: "mockup.png".into(),
+            is_error: false,
+            media: vec![ContentBlock::Image {
+                source: MediaSource::base64("image/png", "AAAA".into()),
+            }],
+        }]);
+        let json = serde_json::to_value(&msg).unwrap();
+        let content = &json["content"][0]["content"];
+        assert_eq!(content[0]["type"], "text");
+        assert_eq!(content[1]["type"], "image");
+
+        let back: Message = serde_json::from_value(json).unwrap();
+        match &back.content {
+            MessageContent::Blocks(blocks) => match &blocks[0] {
+                ContentBlock::ToolResult {
+                    content: Some(MessageContent::Blocks(inner)),
+                    ..
+                } => assert!(matches!(inner[1], ContentBlock::Image { .. })),
+                other => panic!("expected tool_result with blocks, got {other:?}"),
+            },
+            _ => panic!("expected Blocks"),
+        }
+    }
+
+    #[test]
+    fn document_block_roundtrip() {
+        let msg = Message::user_blocks(vec![
+            ContentBlock::Document {
+                source: MediaSource::base64("application/pdf", "JVBERi0=".into()),
+            },
+            ContentBlock::Text {
+                text: "Implement this spec.".into(),
+            },
+        ]);
+        let json = serde_json::to_string(&msg).unwrap();
+        let back: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.content.text(), Some("Implement this spec.".into()));
+        match &back.content {
+            MessageContent::Blocks(blocks) => {
+                assert!(matches!(&blocks[0], ContentBlock::Document { source } if source.media_type == "application/pdf"))
+            }
+            _ => panic!("expected Blocks"),
+        }
+    }
 }
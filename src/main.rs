@@ -1,17 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 use clap::Parser;
 use tracing::info;
 
+use agentos::agent::schema;
 use agentos::config::{AgentsConfig, ModelsConfig};
 use agentos::llm::LlmPool;
-use agentos::organism::parser::parse_organism;
-use agentos::pipeline::AgentPipelineBuilder;
-use agentos::tools::{
-    command_exec::CommandExecTool, file_edit::FileEditTool, file_read::FileReadTool,
-    file_write::FileWriteTool, glob_tool::GlobTool, grep::GrepTool,
-};
+use agentos::organism::parser::{load_response_schema, parse_organism};
+use agentos::organism::ResponseSchema;
+use agentos::pipeline::{AgentPipelineBuilder, NATIVE_TOOLS};
 use agentos::tui::runner::run_tui;
 
 /// Default organism configuration embedded in the binary.
@@ -52,7 +51,7 @@ listeners:
       max_tokens: 4096
       max_agentic_iterations: 25
     librarian: true
-    peers: [file-read, file-write, file-edit, glob, grep, command-exec, codebase-index]
+    peers: [file-read, file-write, file-edit, glob, grep, command-exec, git, test-run, diagnostics, lsp, list-dir, notebook-edit, http-fetch, replace, output-page, codebase-index]
 
   - name: llm-pool
     payload_class: llm.LlmRequest
@@ -106,12 +105,64 @@ listeners:
     handler: tools.command_exec.handle
     description: "Command execution"
 
+  - name: git
+    payload_class: tools.GitRequest
+    handler: tools.git.handle
+    description: "Git operations"
+
+  - name: test-run
+    payload_class: tools.TestRunRequest
+    handler: tools.test_run.handle
+    description: "Test runs with parsed results"
+
+  - name: diagnostics
+    payload_class: tools.DiagnosticsRequest
+    handler: tools.diagnostics.handle
+    description: "Compiler diagnostics and fixes"
+
+  - name: lsp
+    payload_class: tools.LspRequest
+    handler: tools.lsp_client.handle
+    description: "Semantic navigation via language servers"
+
+  - name: list-dir
+    payload_class: tools.ListDirRequest
+    handler: tools.list_dir.handle
+    description: "Directory tree with file metadata"
+
+  - name: notebook-edit
+    payload_class: tools.NotebookEditRequest
+    handler: tools.notebook.handle
+    description: "Cell-level Jupyter notebook edits"
+
+  - name: http-fetch
+    payload_class: tools.HttpFetchRequest
+    handler: tools.http_fetch.handle
+    description: "Fetch web pages within the profile's network ports"
+    ports:
+      - port: 443
+        direction: outbound
+        protocol: https
+        hosts: [docs.rs, crates.io, doc.rust-lang.org, docs.python.org, pypi.org, raw.githubusercontent.com]
+
+  - name: replace
+    payload_class: tools.ReplaceRequest
+    handler: tools.replace.handle
+    description: "Previewed search and replace across files"
+
+  - name: output-page
+    payload_class: tools.OutputPageRequest
+    handler: tools.output.handle
+    description: "Page through long tool outputs"
+
 profiles:
   coding:
     linux_user: agentos
-    listeners: [coding-agent, file-read, file-write, file-edit, glob, grep, command-exec, codebase-index, llm-pool, librarian]
-    network: [llm-pool]
+    listeners: [coding-agent, file-read, file-write, file-edit, glob, grep, command-exec, git, test-run, diagnostics, lsp, list-dir, notebook-edit, http-fetch, replace, output-page, codebase-index, llm-pool, librarian]
+    network: [llm-pool, http-fetch]
     journal: retain_forever
+    workspace:
+      root: .
 "#;
 
 /// Extension trait to convert Result<T, String> to anyhow::Result<T>.
@@ -147,6 +198,50 @@ struct Cli {
     /// Enable debug tab (activity trace, diagnostics)
     #[arg(long)]
     debug: bool,
+
+    /// Run one task without the TUI and print the agent's answer on stdout
+    #[arg(long)]
+    task: Option<String>,
+
+    /// JSON Schema (or .wit interface) the answer must match; printed as JSON
+    #[arg(long, requires = "task")]
+    schema: Option<String>,
+
+    /// Agent listener --task is sent to (default: coding-agent)
+    #[arg(long, requires = "task")]
+    agent: Option<String>,
+
+    /// Seconds to wait for the --task answer before giving up
+    #[arg(long, default_value_t = 1800)]
+    timeout: u64,
+}
+
+/// Agent a headless task goes to unless `--agent` names another.
+const HEADLESS_AGENT: &str = "coding-agent";
+
+/// The headless answer for an agent's response text.
+///
+/// An error the agent reports becomes `Err`. With a schema, the answer is
+/// the submitted JSON, checked against the schema once more and printed
+/// compact, without any verification summary.
+fn headless_output(text: &str, schema: Option<&ResponseSchema>) -> Result<String, String> {
+    if let Some(error) = text.strip_prefix("Error: ") {
+        return Err(error.to_string());
+    }
+    let Some(schema) = schema else {
+        return Ok(text.to_string());
+    };
+    let answer = text.split("\n\nVerification:\n").next().unwrap_or(text);
+    let value: serde_json::Value =
+        serde_json::from_str(answer).map_err(|e| format!("answer is not JSON: {e}"))?;
+    let errors = schema::validate(&schema.schema, &value);
+    if !errors.is_empty() {
+        return Err(format!(
+            "answer does not match the schema:\n{}",
+            errors.join("\n")
+        ));
+    }
+    Ok(value.to_string())
 }
 
 #[tokio::main]
@@ -185,7 +280,16 @@ async fn main() -> Result<()> {
     } else {
         DEFAULT_ORGANISM.to_string()
     };
-    let org = parse_organism(&yaml).to_anyhow()?;
+    let mut org = parse_organism(&yaml).to_anyhow()?;
+    let agent = cli.agent.as_deref().unwrap_or(HEADLESS_AGENT);
+    let response_schema = match cli.schema {
+        Some(ref path) => {
+            let rs = load_response_schema(agent, Path::new(path)).to_anyhow()?;
+            org.set_response_schema(agent, rs.clone()).to_anyhow()?;
+            Some(rs)
+        }
+        None => None,
+    };
 
     // Load models config (user + project + env fallback)
     let models_config = ModelsConfig::load();
@@ -218,6 +322,10 @@ async fn main() -> Result<()> {
         }
     };
 
+    if cli.task.is_some() && pool.is_none() {
+        anyhow::bail!("--task needs a model: set ANTHROPIC_API_KEY or configure models");
+    }
+
     info!("Building pipeline with model {model}");
 
     // Build pipeline — LLM pool is optional (user may configure via TUI)
@@ -232,21 +340,12 @@ async fn main() -> Result<()> {
     }
     // Try to load local inference engine (optional — graceful if missing)
     builder = builder.with_local_inference().to_anyhow()?;
+    builder = builder.with_code_index().to_anyhow()?;
+    for name in NATIVE_TOOLS {
+        // One instance per profile, each jailed to that profile's workspace
+        builder = builder.register_native_tool(name).to_anyhow()?;
+    }
     let mut pipeline = builder
-        .with_code_index()
-        .to_anyhow()?
-        .register_tool("file-read", FileReadTool)
-        .to_anyhow()?
-        .register_tool("file-write", FileWriteTool)
-        .to_anyhow()?
-        .register_tool("file-edit", FileEditTool)
-        .to_anyhow()?
-        .register_tool("glob", GlobTool)
-        .to_anyhow()?
-        .register_tool("grep", GrepTool)
-        .to_anyhow()?
-        .register_tool("command-exec", CommandExecTool::new())
-        .to_anyhow()?
         .with_buffer_nodes(&PathBuf::from(&work_dir))
         .to_anyhow()?;
     if has_pool {
@@ -255,11 +354,24 @@ async fn main() -> Result<()> {
     let mut pipeline = pipeline.build().to_anyhow()?;
 
     // Initialize root thread
-    pipeline
+    let root_uuid = pipeline
         .initialize_root("agentos", "coding")
         .await
         .to_anyhow()?;
 
+    // Headless: one task, answer on stdout
+    if let Some(ref task) = cli.task {
+        info!("Pipeline ready, running task on {agent}");
+        pipeline.run();
+        let outcome = pipeline
+            .ask_agent(agent, &root_uuid, task, Duration::from_secs(cli.timeout))
+            .await
+            .and_then(|text| headless_output(&text, response_schema.as_ref()));
+        pipeline.shutdown().await;
+        println!("{}", outcome.to_anyhow()?);
+        return Ok(());
+    }
+
     info!("Pipeline ready, starting TUI");
 
     // Start pipeline
@@ -274,3 +386,54 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentos::ports::{egress::EgressPolicy, PortManager};
+
+    fn verdict_schema() -> ResponseSchema {
+        ResponseSchema {
+            description: "Report the verdict.".into(),
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": { "verdict": { "type": "string", "enum": ["pass", "fail"] } },
+                "required": ["verdict"]
+            }),
+            max_retries: 1,
+        }
+    }
+
+    #[test]
+    fn headless_output_checks_the_answer() {
+        let rs = verdict_schema();
+        // Free text passes through without a schema
+        assert_eq!(headless_output("All good.", None).unwrap(), "All good.");
+        // The submitted JSON, without the verification summary
+        let text = "{\"verdict\":\"pass\"}\n\nVerification:\ncargo test: passed";
+        assert_eq!(headless_output(text, Some(&rs)).unwrap(), r#"{"verdict":"pass"}"#);
+
+        let err = headless_output(r#"{"verdict":"maybe"}"#, Some(&rs)).unwrap_err();
+        assert!(err.contains("does not match the schema"), "{err}");
+        let err = headless_output("It passes.", Some(&rs)).unwrap_err();
+        assert!(err.contains("not JSON"), "{err}");
+        let err = headless_output("Error: no valid submission after 2 attempts", Some(&rs));
+        assert_eq!(err.unwrap_err(), "no valid submission after 2 attempts");
+    }
+
+    #[test]
+    fn default_organism_parses() {
+        let org = parse_organism(DEFAULT_ORGANISM).unwrap();
+        let coding = org.get_profile("coding").unwrap();
+        let agent = org.get_listener("coding-agent").unwrap();
+        for peer in &agent.peers {
+            assert!(org.get_listener(peer).is_some(), "peer {peer} has no listener");
+            assert!(coding.allowed_listeners.contains(peer.as_str()), "{peer} not in coding");
+        }
+
+        let ports = PortManager::from_organism(&org).unwrap();
+        assert!(ports.validate().is_ok());
+        let egress = EgressPolicy::for_profile(&ports, &org, "coding").unwrap();
+        assert!(egress.check("https", "docs.rs", 443).is_ok());
+    }
+}
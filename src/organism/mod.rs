@@ -32,6 +32,12 @@ pub struct AgentConfig {
     pub max_agentic_iterations: usize,
     /// Model override. None = pool default.
     pub model: Option<String>,
+    /// Structured final-answer schema. None = free-text replies.
+    pub response_schema: Option<ResponseSchema>,
+    /// Extended thinking budget in tokens. None = thinking disabled.
+    pub thinking_budget: Option<u32>,
+    /// Self-verification commands run before accepting end_turn. None = off.
+    pub verify: Option<VerifyConfig>,
 }
 
 impl Default for AgentConfig {
@@ -42,10 +48,42 @@ impl Default for AgentConfig {
             max_routing_iterations: 5,
             max_agentic_iterations: 25,
             model: None,
+            response_schema: None,
+            thinking_budget: None,
+            verify: None,
         }
     }
 }
 
+/// Structured final-answer schema for an agent.
+///
+/// When present, the agent is given a terminal `submit` tool whose input
+/// schema is `schema`, and must finish by calling it with a valid answer.
+#[derive(Debug, Clone)]
+pub struct ResponseSchema {
+    /// Description shown to the model on the `submit` tool.
+    pub description: String,
+    /// JSON Schema the submitted answer must satisfy (WIT records are converted).
+    pub schema: serde_json::Value,
+    /// Max re-prompts on a missing or invalid submission before giving up.
+    pub max_retries: usize,
+}
+
+/// Self-verification block for an agent.
+///
+/// After the agent has run file-write or file-edit, each command is sent
+/// through command-exec before the final reply is released. Failures are fed
+/// back as a user turn, up to `max_retries` times.
+#[derive(Debug, Clone)]
+pub struct VerifyConfig {
+    /// Commands to run, in order (e.g. "cargo check", "cargo test -q").
+    pub commands: Vec<String>,
+    /// Max failed rounds fed back to the model before replying anyway.
+    pub max_retries: usize,
+    /// Per-command timeout in seconds. None = command-exec default.
+    pub timeout_secs: Option<u32>,
+}
+
 /// Callable interface — declares a buffer node as a typed tool for LLM invocation.
 #[derive(Debug, Clone)]
 pub struct CallableConfig {
@@ -70,6 +108,7 @@ pub struct BufferConfig {
     pub organism: String,    // path to child organism YAML
     pub max_concurrency: usize, // default 5
     pub timeout_secs: u64,   // default 300
+    pub profile: Option<String>, // child profile to run under (default: its only one)
 }
 
 impl CallableConfig {
@@ -274,6 +313,26 @@ impl Organism {
         self.listeners.values().filter(|l| l.is_agent).collect()
     }
 
+    /// Give an agent listener a response schema, replacing any it has.
+    ///
+    /// Used to impose a schema from outside the YAML (the headless CLI's
+    /// `--schema`). Errors if `name` isn't an agent listener.
+    pub fn set_response_schema(
+        &mut self,
+        name: &str,
+        schema: ResponseSchema,
+    ) -> Result<(), String> {
+        let def = self
+            .listeners
+            .get_mut(name)
+            .filter(|l| l.is_agent)
+            .ok_or_else(|| format!("no agent listener '{name}'"))?;
+        def.agent_config
+            .get_or_insert_with(AgentConfig::default)
+            .response_schema = Some(schema);
+        Ok(())
+    }
+
     /// Get all listeners that are buffer nodes (have both callable + buffer config).
     pub fn buffer_listeners(&self) -> Vec<&ListenerDef> {
         self.listeners
@@ -381,6 +440,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::Forever,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         }
     }
 
@@ -437,6 +501,11 @@ mod tests {
             allow_all: true,
             journal_retention: RetentionPolicy::Forever,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         };
         org.add_profile(profile).unwrap();
 
@@ -597,6 +666,9 @@ mod tests {
             max_routing_iterations: 10,
             max_agentic_iterations: 30,
             model: Some("haiku".into()),
+            response_schema: None,
+            thinking_budget: Some(2048),
+            verify: None,
         });
 
         let cfg = def.agent_config.as_ref().unwrap();
@@ -605,6 +677,30 @@ mod tests {
         assert_eq!(cfg.max_routing_iterations, 10);
         assert_eq!(cfg.max_agentic_iterations, 30);
         assert_eq!(cfg.model.as_deref(), Some("haiku"));
+        assert_eq!(cfg.thinking_budget, Some(2048));
+    }
+
+    #[test]
+    fn set_response_schema_on_agent() {
+        let mut org = Organism::new("test");
+        let mut agent = sample_listener("agent");
+        agent.is_agent = true;
+        org.register_listener(agent).unwrap();
+        org.register_listener(sample_listener("tool")).unwrap();
+        let schema = ResponseSchema {
+            description: "Report.".into(),
+            schema: serde_json::json!({"type": "object"}),
+            max_retries: 2,
+        };
+
+        org.set_response_schema("agent", schema.clone()).unwrap();
+        let cfg = org.get_listener("agent").unwrap().agent_config.as_ref().unwrap();
+        assert_eq!(cfg.response_schema.as_ref().unwrap().max_retries, 2);
+        // The rest of the config is the default
+        assert_eq!(cfg.max_tokens, 4096);
+
+        let err = org.set_response_schema("tool", schema).unwrap_err();
+        assert!(err.contains("no agent listener 'tool'"), "{err}");
     }
 
     #[test]
@@ -615,6 +711,9 @@ mod tests {
         assert_eq!(cfg.max_routing_iterations, 5);
         assert_eq!(cfg.max_agentic_iterations, 25);
         assert_eq!(cfg.model, None);
+        assert!(cfg.response_schema.is_none());
+        assert_eq!(cfg.thinking_budget, None);
+        assert!(cfg.verify.is_none());
     }
 
     #[test]
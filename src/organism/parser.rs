@@ -3,15 +3,18 @@
 //! Parses `organism.yaml` into an `Organism` struct by calling the
 //! imperative API (register_listener, add_profile, etc.).
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 
 use serde::Deserialize;
 
-use super::profile::{RetentionPolicy, SecurityProfile};
+use super::profile::{
+    CommandPolicySpec, CommandRule, GitPolicySpec, LspServerSpec, LspSpec, RetentionPolicy,
+    SandboxSpec, SecurityProfile, WorkspaceSpec,
+};
 use super::{
     AgentConfig, BufferConfig, CallableConfig, CallableParam, ListenerDef, Organism, PortDef,
-    WasmToolConfig,
+    ResponseSchema, VerifyConfig, WasmToolConfig,
 };
 use crate::wasm::capabilities::{EnvGrant, FsGrant, WasmCapabilities};
 
@@ -87,6 +90,37 @@ struct AgentConfigYaml {
     max_agentic_iterations: Option<usize>,
     #[serde(default)]
     model: Option<String>,
+    #[serde(default)]
+    response_schema: Option<ResponseSchemaYaml>,
+    #[serde(default)]
+    thinking_budget: Option<u32>,
+    #[serde(default)]
+    verify: Option<VerifyYaml>,
+}
+
+/// Self-verification commands run before accepting end_turn.
+#[derive(Debug, Deserialize)]
+struct VerifyYaml {
+    commands: Vec<String>,
+    #[serde(default)]
+    max_retries: Option<usize>,
+    #[serde(default)]
+    timeout: Option<u32>,
+}
+
+/// Structured final-answer schema: exactly one of `json` or `wit`.
+#[derive(Debug, Deserialize)]
+struct ResponseSchemaYaml {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    max_retries: Option<usize>,
+    /// Inline JSON Schema (written as YAML).
+    #[serde(default)]
+    json: Option<serde_json::Value>,
+    /// WIT interface whose record describes the answer.
+    #[serde(default)]
+    wit: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -148,6 +182,8 @@ struct BufferYaml {
     max_concurrency: usize,
     #[serde(default = "default_timeout_secs")]
     timeout_secs: u64,
+    #[serde(default)]
+    profile: Option<String>,
 }
 
 fn default_max_concurrency() -> usize {
@@ -175,6 +211,68 @@ struct ProfileYaml {
     journal: JournalSpec,
     #[serde(default)]
     network: Vec<String>,
+    #[serde(default)]
+    workspace: Option<WorkspaceYaml>,
+    #[serde(default)]
+    commands: Option<BTreeMap<String, Option<CommandRuleYaml>>>,
+    #[serde(default)]
+    sandbox: Option<SandboxYaml>,
+    #[serde(default)]
+    git: Option<GitPolicyYaml>,
+    #[serde(default)]
+    lsp: Option<BTreeMap<String, LspServerYaml>>,
+}
+
+/// Profile workspace jail for the native tools.
+#[derive(Debug, Deserialize)]
+struct WorkspaceYaml {
+    root: String,
+    #[serde(default)]
+    read_only: Vec<String>,
+}
+
+/// Argument rules for one program. An empty entry (`ls:`) allows any arguments.
+#[derive(Debug, Default, Deserialize)]
+struct CommandRuleYaml {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+/// Execution sandbox for command-exec.
+#[derive(Debug, Deserialize)]
+struct SandboxYaml {
+    #[serde(default)]
+    env: Option<Vec<String>>,
+    #[serde(default)]
+    cpu_secs: Option<u64>,
+    #[serde(default)]
+    memory_mb: Option<u64>,
+    #[serde(default)]
+    file_size_mb: Option<u64>,
+    #[serde(default)]
+    max_processes: Option<u64>,
+    #[serde(default)]
+    deny_network: bool,
+    #[serde(default)]
+    run_as_user: bool,
+}
+
+/// What the git tool may not do.
+#[derive(Debug, Deserialize)]
+struct GitPolicyYaml {
+    #[serde(default)]
+    forbid_rewrite: bool,
+    #[serde(default)]
+    forbid_remote: bool,
+}
+
+/// A language server for the lsp tool.
+#[derive(Debug, Deserialize)]
+struct LspServerYaml {
+    command: Vec<String>,
+    extensions: Vec<String>,
 }
 
 /// Listeners can be "all" or a list of names.
@@ -204,6 +302,72 @@ struct JournalDaysSpec {
     retain_days: u16,
 }
 
+/// Resolve a `response_schema:` block into a JSON Schema.
+fn resolve_response_schema(
+    listener: &str,
+    raw: ResponseSchemaYaml,
+) -> Result<ResponseSchema, String> {
+    let (schema, wit_description) = match (raw.json, raw.wit) {
+        (Some(json), None) => {
+            if json.get("type").and_then(|t| t.as_str()) != Some("object") {
+                return Err(format!(
+                    "listener '{listener}': response_schema.json must have type: object"
+                ));
+            }
+            (json, None)
+        }
+        (None, Some(wit)) => {
+            let iface = crate::wit::parser::parse_wit(&wit)
+                .map_err(|e| format!("listener '{listener}': response_schema.wit: {e}"))?;
+            let def = iface.to_tool_definition();
+            (def.input_schema, Some(def.description))
+        }
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "listener '{listener}': response_schema takes either json or wit, not both"
+            ))
+        }
+        (None, None) => {
+            return Err(format!(
+                "listener '{listener}': response_schema requires json or wit"
+            ))
+        }
+    };
+
+    let description = raw
+        .description
+        .or(wit_description)
+        .unwrap_or_else(|| "Submit the final answer.".to_string());
+
+    Ok(ResponseSchema {
+        description,
+        schema,
+        max_retries: raw.max_retries.unwrap_or(3),
+    })
+}
+
+/// Validate a `verify:` block. Commands run through command-exec, so the
+/// agent must have it as a peer.
+fn resolve_verify(
+    listener: &str,
+    peers: &[String],
+    raw: VerifyYaml,
+) -> Result<VerifyConfig, String> {
+    if raw.commands.is_empty() {
+        return Err(format!("listener '{listener}': verify.commands is empty"));
+    }
+    if !peers.iter().any(|p| p == "command-exec") {
+        return Err(format!(
+            "listener '{listener}': verify requires command-exec in peers"
+        ));
+    }
+    Ok(VerifyConfig {
+        commands: raw.commands,
+        max_retries: raw.max_retries.unwrap_or(2),
+        timeout_secs: raw.timeout,
+    })
+}
+
 /// Load an organism from a YAML file.
 pub fn load_organism(path: &Path) -> Result<Organism, String> {
     let contents = std::fs::read_to_string(path)
@@ -211,6 +375,31 @@ pub fn load_organism(path: &Path) -> Result<Organism, String> {
     parse_organism(&contents)
 }
 
+/// Load a response schema from a file, for `listener`: a WIT interface if
+/// it ends in `.wit`, otherwise a JSON Schema (which must be an object).
+pub fn load_response_schema(listener: &str, path: &Path) -> Result<ResponseSchema, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let raw = if path.extension().is_some_and(|ext| ext == "wit") {
+        ResponseSchemaYaml {
+            description: None,
+            max_retries: None,
+            json: None,
+            wit: Some(contents),
+        }
+    } else {
+        let json = serde_json::from_str(&contents)
+            .map_err(|e| format!("{}: invalid JSON: {e}", path.display()))?;
+        ResponseSchemaYaml {
+            description: None,
+            max_retries: None,
+            json: Some(json),
+            wit: None,
+        }
+    };
+    resolve_response_schema(listener, raw)
+}
+
 /// Parse an organism from a YAML string.
 pub fn parse_organism(yaml: &str) -> Result<Organism, String> {
     let raw: OrganismYaml =
@@ -251,12 +440,30 @@ pub fn parse_organism(yaml: &str) -> Result<Organism, String> {
         // Resolve agent field: bool or config block
         let (is_agent, agent_config) = match l.agent {
             AgentFieldYaml::Config(cfg) => {
+                let max_tokens = cfg.max_tokens.unwrap_or(4096);
+                if let Some(budget) = cfg.thinking_budget {
+                    if budget < 1024 || budget >= max_tokens {
+                        return Err(format!(
+                            "listener '{}': thinking_budget must be >= 1024 and below max_tokens ({max_tokens}), got {budget}",
+                            l.name
+                        ));
+                    }
+                }
                 let config = AgentConfig {
                     prompt: cfg.prompt,
-                    max_tokens: cfg.max_tokens.unwrap_or(4096),
+                    max_tokens,
                     max_routing_iterations: cfg.max_iterations.unwrap_or(5),
                     max_agentic_iterations: cfg.max_agentic_iterations.unwrap_or(25),
                     model: cfg.model,
+                    response_schema: cfg
+                        .response_schema
+                        .map(|rs| resolve_response_schema(&l.name, rs))
+                        .transpose()?,
+                    thinking_budget: cfg.thinking_budget,
+                    verify: cfg
+                        .verify
+                        .map(|v| resolve_verify(&l.name, &l.peers, v))
+                        .transpose()?,
                 };
                 (true, Some(config))
             }
@@ -332,6 +539,7 @@ pub fn parse_organism(yaml: &str) -> Result<Organism, String> {
                 organism: b.organism,
                 max_concurrency: b.max_concurrency,
                 timeout_secs: b.timeout_secs,
+                profile: b.profile,
             }),
         })?;
     }
@@ -360,6 +568,14 @@ pub fn parse_organism(yaml: &str) -> Result<Organism, String> {
             JournalSpec::WithDays(spec) => RetentionPolicy::RetainDays(spec.retain_days),
         };
 
+        for (server, spec) in p.lsp.iter().flatten() {
+            if spec.command.is_empty() {
+                return Err(format!(
+                    "profile '{name}': lsp server '{server}' has an empty command"
+                ));
+            }
+        }
+
         org.add_profile(SecurityProfile {
             name,
             linux_user: p.linux_user,
@@ -367,6 +583,44 @@ pub fn parse_organism(yaml: &str) -> Result<Organism, String> {
             allow_all,
             journal_retention,
             network: p.network,
+            workspace: p.workspace.map(|w| WorkspaceSpec {
+                root: w.root.into(),
+                read_only: w.read_only.into_iter().map(Into::into).collect(),
+            }),
+            commands: p.commands.map(|rules| CommandPolicySpec {
+                rules: rules
+                    .into_iter()
+                    .map(|(program, rule)| {
+                        let rule = rule.unwrap_or_default();
+                        (program, CommandRule { allow: rule.allow, deny: rule.deny })
+                    })
+                    .collect(),
+            }),
+            sandbox: p.sandbox.map(|s| SandboxSpec {
+                env: s.env,
+                cpu_secs: s.cpu_secs,
+                memory_mb: s.memory_mb,
+                file_size_mb: s.file_size_mb,
+                max_processes: s.max_processes,
+                deny_network: s.deny_network,
+                run_as_user: s.run_as_user,
+            }),
+            git: p.git.map(|g| GitPolicySpec {
+                forbid_rewrite: g.forbid_rewrite,
+                forbid_remote: g.forbid_remote,
+            }),
+            lsp: p.lsp.map(|servers| LspSpec {
+                servers: servers
+                    .into_iter()
+                    .map(|(name, s)| {
+                        let server = LspServerSpec {
+                            command: s.command,
+                            extensions: s.extensions,
+                        };
+                        (name, server)
+                    })
+                    .collect(),
+            }),
         })?;
     }
 
@@ -520,6 +774,182 @@ profiles:
         assert!(public.network.is_empty());
     }
 
+    #[test]
+    fn parse_profile_workspace() {
+        let yaml = r#"
+organism:
+  name: jailed
+
+listeners:
+  - name: file-read
+    payload_class: tools.FileReadRequest
+    handler: tools.file_read.handle
+    description: "Read files"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [file-read]
+    workspace:
+      root: ./project
+      read_only: [/usr/share/doc]
+  open:
+    linux_user: agentos
+    listeners: all
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let ws = org.get_profile("coding").unwrap().workspace.as_ref().unwrap();
+        assert_eq!(ws.root, std::path::PathBuf::from("./project"));
+        assert_eq!(ws.read_only, vec![std::path::PathBuf::from("/usr/share/doc")]);
+        assert!(org.get_profile("open").unwrap().workspace.is_none());
+    }
+
+    #[test]
+    fn parse_profile_commands() {
+        let yaml = r#"
+organism:
+  name: policed
+
+listeners:
+  - name: command-exec
+    payload_class: tools.CommandExecRequest
+    handler: tools.command_exec.handle
+    description: "Run commands"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [command-exec]
+    commands:
+      git:
+        allow: [status, diff, log, commit]
+        deny: ["push --force"]
+      ls:
+  open:
+    linux_user: agentos
+    listeners: all
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let spec = org.get_profile("coding").unwrap().commands.as_ref().unwrap();
+        let git = &spec.rules["git"];
+        assert_eq!(git.allow, vec!["status", "diff", "log", "commit"]);
+        assert_eq!(git.deny, vec!["push --force"]);
+        assert_eq!(spec.rules["ls"], CommandRule::default());
+        assert!(org.get_profile("open").unwrap().commands.is_none());
+    }
+
+    #[test]
+    fn parse_profile_sandbox() {
+        let yaml = r#"
+organism:
+  name: boxed
+
+listeners:
+  - name: command-exec
+    payload_class: tools.CommandExecRequest
+    handler: tools.command_exec.handle
+    description: "Run commands"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [command-exec]
+    sandbox:
+      env: [PATH, HOME]
+      cpu_secs: 120
+      memory_mb: 4096
+      deny_network: true
+  open:
+    linux_user: agentos
+    listeners: all
+    sandbox: {}
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let sandbox = org.get_profile("coding").unwrap().sandbox.as_ref().unwrap();
+        assert_eq!(sandbox.env.as_deref(), Some(&["PATH".to_string(), "HOME".to_string()][..]));
+        assert_eq!(sandbox.cpu_secs, Some(120));
+        assert_eq!(sandbox.memory_mb, Some(4096));
+        assert_eq!(sandbox.file_size_mb, None);
+        assert!(sandbox.deny_network);
+        assert!(!sandbox.run_as_user);
+        let open = org.get_profile("open").unwrap().sandbox.as_ref().unwrap();
+        assert_eq!(*open, SandboxSpec::default());
+    }
+
+    #[test]
+    fn parse_profile_git_policy() {
+        let yaml = r#"
+organism:
+  name: test-git
+
+listeners:
+  - name: git
+    payload_class: tools.GitRequest
+    handler: tools.git.handle
+    description: "Git"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [git]
+    git:
+      forbid_rewrite: true
+  open:
+    linux_user: agentos
+    listeners: all
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let git = org.get_profile("coding").unwrap().git.as_ref().unwrap();
+        assert_eq!(
+            *git,
+            GitPolicySpec {
+                forbid_rewrite: true,
+                forbid_remote: false
+            }
+        );
+        assert!(org.get_profile("open").unwrap().git.is_none());
+    }
+
+    #[test]
+    fn parse_profile_lsp_servers() {
+        let yaml = r#"
+organism:
+  name: test-lsp
+
+listeners:
+  - name: lsp
+    payload_class: tools.LspRequest
+    handler: tools.lsp.handle
+    description: "Language servers"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [lsp]
+    lsp:
+      pyright:
+        command: [pyright-langserver, --stdio]
+        extensions: [py, pyi]
+  open:
+    linux_user: agentos
+    listeners: all
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let spec = org.get_profile("coding").unwrap().lsp.as_ref().unwrap();
+        assert_eq!(
+            spec.servers["pyright"],
+            LspServerSpec {
+                command: vec!["pyright-langserver".into(), "--stdio".into()],
+                extensions: vec!["py".into(), "pyi".into()],
+            }
+        );
+        assert!(org.get_profile("open").unwrap().lsp.is_none());
+
+        let empty = yaml.replace("[pyright-langserver, --stdio]", "[]");
+        let err = parse_organism(&empty).unwrap_err();
+        assert!(err.contains("lsp server 'pyright' has an empty command"), "{err}");
+    }
+
     #[test]
     fn parse_librarian_flag() {
         let yaml = r#"
@@ -929,6 +1359,249 @@ profiles:
         assert_eq!(cfg.max_agentic_iterations, 50);
     }
 
+    #[test]
+    fn parse_thinking_budget() {
+        let yaml = r#"
+organism:
+  name: test-thinking
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    agent:
+      max_tokens: 16000
+      thinking_budget: 8000
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let cfg = org.get_listener("agent").unwrap().agent_config.as_ref().unwrap();
+        assert_eq!(cfg.max_tokens, 16000);
+        assert_eq!(cfg.thinking_budget, Some(8000));
+    }
+
+    #[test]
+    fn parse_thinking_budget_must_fit_max_tokens() {
+        let yaml = r#"
+organism:
+  name: test-thinking
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    agent:
+      thinking_budget: 4096
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let err = parse_organism(yaml).unwrap_err();
+        assert!(err.contains("thinking_budget"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_verify_block() {
+        let yaml = r#"
+organism:
+  name: test-verify
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    peers: [file-edit, command-exec]
+    agent:
+      verify:
+        commands: ["cargo check", "cargo test -q"]
+        timeout: 300
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let cfg = org.get_listener("agent").unwrap().agent_config.as_ref().unwrap();
+        let verify = cfg.verify.as_ref().unwrap();
+        assert_eq!(verify.commands, vec!["cargo check", "cargo test -q"]);
+        assert_eq!(verify.max_retries, 2);
+        assert_eq!(verify.timeout_secs, Some(300));
+    }
+
+    #[test]
+    fn parse_verify_requires_command_exec_peer() {
+        let yaml = r#"
+organism:
+  name: test-verify
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    peers: [file-edit]
+    agent:
+      verify:
+        commands: ["cargo check"]
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let err = parse_organism(yaml).unwrap_err();
+        assert!(err.contains("requires command-exec"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_response_schema_json() {
+        let yaml = r#"
+organism:
+  name: test-schema
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    agent:
+      response_schema:
+        description: "Report the verdict"
+        max_retries: 2
+        json:
+          type: object
+          properties:
+            verdict:
+              type: string
+              enum: [pass, fail]
+            files:
+              type: array
+              items:
+                type: string
+          required: [verdict]
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let cfg = org.get_listener("agent").unwrap().agent_config.as_ref().unwrap();
+        let rs = cfg.response_schema.as_ref().unwrap();
+        assert_eq!(rs.description, "Report the verdict");
+        assert_eq!(rs.max_retries, 2);
+        assert_eq!(rs.schema["type"], "object");
+        assert_eq!(rs.schema["properties"]["verdict"]["enum"][1], "fail");
+        assert_eq!(rs.schema["required"][0], "verdict");
+    }
+
+    #[test]
+    fn parse_response_schema_wit() {
+        let yaml = r#"
+organism:
+  name: test-schema
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    agent:
+      response_schema:
+        wit: |
+          /// Summarize the change.
+          interface summary {
+              record summary {
+                  /// One-line title
+                  title: string,
+                  lines-changed: option<u32>,
+              }
+          }
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let org = parse_organism(yaml).unwrap();
+        let cfg = org.get_listener("agent").unwrap().agent_config.as_ref().unwrap();
+        let rs = cfg.response_schema.as_ref().unwrap();
+        assert_eq!(rs.description, "Summarize the change.");
+        assert_eq!(rs.max_retries, 3);
+        assert_eq!(rs.schema["properties"]["title"]["type"], "string");
+        assert_eq!(rs.schema["properties"]["lines_changed"]["type"], "integer");
+        assert_eq!(rs.schema["required"], serde_json::json!(["title"]));
+    }
+
+    #[test]
+    fn parse_response_schema_requires_one_source() {
+        let yaml = r#"
+organism:
+  name: test-schema
+
+listeners:
+  - name: agent
+    payload_class: agent.Task
+    handler: agent.handle
+    description: "Agent"
+    agent:
+      response_schema:
+        description: "nothing here"
+
+profiles:
+  admin:
+    linux_user: agentos-admin
+    listeners: [agent]
+    journal: retain_forever
+"#;
+        let err = parse_organism(yaml).unwrap_err();
+        assert!(err.contains("requires json or wit"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn load_response_schema_from_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let json = dir.path().join("verdict.json");
+        std::fs::write(
+            &json,
+            r#"{"type": "object", "properties": {"verdict": {"type": "string"}}}"#,
+        )
+        .unwrap();
+        let rs = load_response_schema("agent", &json).unwrap();
+        assert_eq!(rs.schema["properties"]["verdict"]["type"], "string");
+        assert_eq!(rs.max_retries, 3);
+
+        let wit = dir.path().join("summary.wit");
+        std::fs::write(
+            &wit,
+            "/// Summarize the change.\ninterface summary {\n    record summary {\n        title: string,\n    }\n}\n",
+        )
+        .unwrap();
+        let rs = load_response_schema("agent", &wit).unwrap();
+        assert_eq!(rs.description, "Summarize the change.");
+        assert_eq!(rs.schema["required"], serde_json::json!(["title"]));
+
+        std::fs::write(&json, r#"{"type": "string"}"#).unwrap();
+        let err = load_response_schema("agent", &json).unwrap_err();
+        assert!(err.contains("must have type: object"), "{err}");
+    }
+
     #[test]
     fn parse_agent_false_no_config() {
         let yaml = r#"
@@ -997,6 +1670,7 @@ listeners:
       organism: email-agent.yaml
       max_concurrency: 5
       timeout_secs: 120
+      profile: sender
     callable:
       description: "Send a marketing email to a recipient"
       parameters:
@@ -1032,6 +1706,7 @@ profiles:
         assert_eq!(buffer.organism, "email-agent.yaml");
         assert_eq!(buffer.max_concurrency, 5);
         assert_eq!(buffer.timeout_secs, 120);
+        assert_eq!(buffer.profile.as_deref(), Some("sender"));
     }
 
     #[test]
@@ -1065,6 +1740,7 @@ profiles:
         let buffer = listener.buffer.as_ref().unwrap();
         assert_eq!(buffer.max_concurrency, 5);
         assert_eq!(buffer.timeout_secs, 300);
+        assert!(buffer.profile.is_none());
     }
 
     #[test]
@@ -2,7 +2,8 @@
 //!
 //! A profile = named dispatch table (subset of routing table) + Linux user + retention policy.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 
 use super::ListenerDef;
 
@@ -33,6 +34,84 @@ pub struct SecurityProfile {
     /// Which listeners' ports this profile can use (for network access).
     /// Empty means no network restrictions beyond listener access.
     pub network: Vec<String>,
+    /// Filesystem jail for the native tools. None = unrestricted.
+    pub workspace: Option<WorkspaceSpec>,
+    /// Argument-level command-exec rules. None = the default allowlist.
+    pub commands: Option<CommandPolicySpec>,
+    /// Execution sandbox for command-exec. None = inherit everything.
+    pub sandbox: Option<SandboxSpec>,
+    /// What the git tool may do beyond the working tree. None = anything.
+    pub git: Option<GitPolicySpec>,
+    /// Language servers the lsp tool may start. None = the defaults.
+    pub lsp: Option<LspSpec>,
+}
+
+/// A profile's workspace: where its file tools may read and write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceSpec {
+    /// Root directory tools may read and write under.
+    pub root: PathBuf,
+    /// Extra directories tools may only read.
+    pub read_only: Vec<PathBuf>,
+}
+
+/// A profile's command policy: which programs command-exec may run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandPolicySpec {
+    /// Rules keyed by program name. Unlisted programs are refused.
+    pub rules: BTreeMap<String, CommandRule>,
+}
+
+/// Argument patterns for one program, e.g. `allow: [status, diff]`,
+/// `deny: ["push --force"]`. Deny wins; an empty allow list allows any
+/// arguments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandRule {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// How command-exec children run under a profile.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxSpec {
+    /// Environment variables passed through. None = a default pass-list.
+    pub env: Option<Vec<String>>,
+    /// CPU time per process, in seconds.
+    pub cpu_secs: Option<u64>,
+    /// Address space per process, in MiB.
+    pub memory_mb: Option<u64>,
+    /// Largest file a process may write, in MiB.
+    pub file_size_mb: Option<u64>,
+    /// Processes the executing user may have (counts all of the user's).
+    pub max_processes: Option<u64>,
+    /// Run children in an empty network namespace (Linux).
+    pub deny_network: bool,
+    /// Run children as the profile's `linux_user`.
+    pub run_as_user: bool,
+}
+
+/// Git operations a profile forbids.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GitPolicySpec {
+    /// Refuse amending commits and force-pushing.
+    pub forbid_rewrite: bool,
+    /// Refuse fetch, pull and push.
+    pub forbid_remote: bool,
+}
+
+/// A profile's language servers, keyed by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LspSpec {
+    pub servers: BTreeMap<String, LspServerSpec>,
+}
+
+/// How to start one language server, and the files it serves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LspServerSpec {
+    /// Program and arguments, e.g. `[pyright-langserver, --stdio]`.
+    pub command: Vec<String>,
+    /// File extensions served, without the dot.
+    pub extensions: Vec<String>,
 }
 
 /// A materialized dispatch table for a specific profile.
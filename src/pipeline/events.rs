@@ -3,6 +3,8 @@
 //! Best-effort delivery: if a subscriber falls behind, `Lagged` errors
 //! skip events. The TUI refreshes from kernel truth on the next tick.
 
+use std::path::PathBuf;
+
 /// Events emitted by the pipeline for observation.
 #[derive(Debug, Clone)]
 pub enum PipelineEvent {
@@ -50,12 +52,32 @@ pub enum PipelineEvent {
     AgentThinking {
         thread_id: String,
     },
+    /// Extended-thinking text the model produced on an LLM call.
+    AgentReasoning {
+        thread_id: String,
+        text: String,
+    },
     /// A tool call has been dispatched.
     ToolDispatched {
         thread_id: String,
         tool_name: String,
         detail: String,
     },
+    /// Output a running tool call produced, as it arrives.
+    ToolOutput {
+        thread_id: String,
+        tool_name: String,
+        /// Distinguishes successive calls; every chunk of one call shares it.
+        call_id: u64,
+        chunk: String,
+    },
+    /// A tool wrote a file.
+    FileChanged {
+        thread_id: String,
+        tool_name: String,
+        /// Resolved path of the file written.
+        path: PathBuf,
+    },
     /// A tool call completed (result received).
     ToolCompleted {
         thread_id: String,
@@ -73,7 +95,7 @@ pub enum PipelineEvent {
 /// A conversation entry for TUI display (lightweight, no raw API content).
 #[derive(Debug, Clone)]
 pub struct ConversationEntry {
-    /// "user", "assistant", or "tool_result"
+    /// "user", "assistant", "tool_result", or "thinking"
     pub role: String,
     /// Truncated text or tool description.
     pub summary: String,
@@ -25,6 +25,26 @@ use crate::agent::handler::CodingAgentHandler;
 use crate::agent::prompts;
 use crate::agent::tools as agent_tools;
 use crate::embedding::tfidf::TfIdfProvider;
+use crate::ports::egress::EgressPolicy;
+use crate::tools::command_exec::CommandExecTool;
+use crate::tools::command_policy::CommandPolicy;
+use crate::tools::diagnostics::DiagnosticsTool;
+use crate::tools::file_edit::FileEditTool;
+use crate::tools::file_read::FileReadTool;
+use crate::tools::file_write::FileWriteTool;
+use crate::tools::git::GitTool;
+use crate::tools::glob_tool::GlobTool;
+use crate::tools::grep::GrepTool;
+use crate::tools::http_fetch::HttpFetchTool;
+use crate::tools::list_dir::ListDirTool;
+use crate::tools::lsp_client::LspTool;
+use crate::tools::notebook::NotebookEditTool;
+use crate::tools::output::{OutputPageTool, OutputStore};
+use crate::tools::per_profile::PerProfile;
+use crate::tools::replace::ReplaceTool;
+use crate::tools::sandbox::Sandbox;
+use crate::tools::test_run::TestRunTool;
+use crate::tools::workspace::Workspace;
 use crate::tools::ToolPeer;
 use crate::wit::ToolInterface;
 use crate::embedding::EmbeddingIndex;
@@ -32,8 +52,9 @@ use crate::kernel::Kernel;
 use crate::librarian::handler::LibrarianHandler;
 use crate::librarian::Librarian;
 use crate::llm::{handler::LlmHandler, LlmPool};
+use crate::organism::profile::SecurityProfile;
 use crate::organism::Organism;
-use crate::ports::{Direction, PortDeclaration, PortManager, Protocol};
+use crate::ports::PortManager;
 use crate::routing::{self, form_filler::CloudFormFiller, SemanticRouter, ToolMetadata};
 use crate::security::SecurityResolver;
 use crate::treesitter::handler::CodeIndexHandler;
@@ -42,6 +63,53 @@ use crate::wasm::definitions::WasmToolRegistry;
 use crate::wasm::peer::WasmToolPeer;
 use crate::wasm::runtime::WasmRuntime;
 
+/// Native tool peers [`AgentPipelineBuilder::register_native_tool`] builds,
+/// in registration order.
+pub const NATIVE_TOOLS: &[&str] = &[
+    "file-read",
+    "file-write",
+    "file-edit",
+    "glob",
+    "grep",
+    "command-exec",
+    "git",
+    "test-run",
+    "diagnostics",
+    "lsp",
+    "list-dir",
+    "notebook-edit",
+    "http-fetch",
+    "replace",
+    "output-page",
+];
+
+/// Native tools that touch the filesystem, and so only run jailed to the
+/// calling profile's workspace.
+const JAILED_TOOLS: &[&str] = &[
+    "file-read",
+    "file-write",
+    "file-edit",
+    "glob",
+    "grep",
+    "command-exec",
+    "git",
+    "test-run",
+    "diagnostics",
+    "lsp",
+    "list-dir",
+    "notebook-edit",
+    "replace",
+];
+
+/// The execution sandbox from a profile's `sandbox:` block, if any.
+fn profile_sandbox(profile: &SecurityProfile) -> Result<Option<Sandbox>, String> {
+    profile
+        .sandbox
+        .as_ref()
+        .map(|s| Sandbox::from_spec(&profile.linux_user, s))
+        .transpose()
+}
+
 /// AgentPipeline: wraps rust-pipeline's Pipeline with kernel integration.
 pub struct AgentPipeline {
     /// The inner rust-pipeline.
@@ -176,6 +244,54 @@ impl AgentPipeline {
             .map_err(|e| format!("inject failed: {e}"))
     }
 
+    /// Send a task to an agent listener and wait for its response text.
+    ///
+    /// The task goes in as `<task>` on `thread_id` (bypassing security, like
+    /// [`inject_raw`](Self::inject_raw)); the first `AgentResponse` event on
+    /// that thread is the answer. Replies on other threads are ignored, and
+    /// no answer within `timeout` is an error. Errors the agent reports come
+    /// back as the text (`Error: …`), not as `Err`. The pipeline must be
+    /// running.
+    pub async fn ask_agent(
+        &self,
+        agent: &str,
+        thread_id: &str,
+        task: &str,
+        timeout: std::time::Duration,
+    ) -> Result<String, String> {
+        let def = self
+            .organism
+            .get_listener(agent)
+            .filter(|l| l.is_agent)
+            .ok_or_else(|| format!("no agent listener '{agent}'"))?;
+        let xml = format!(
+            "<{tag}><task>{}</task></{tag}>",
+            crate::tools::xml_escape(task),
+            tag = def.payload_tag
+        );
+        let envelope = build_envelope("user", agent, thread_id, xml.as_bytes())
+            .map_err(|e| format!("envelope build failed: {e}"))?;
+
+        let mut rx = self.subscribe();
+        self.inject_raw(envelope).await?;
+        let answer = async {
+            loop {
+                match rx.recv().await {
+                    Ok(PipelineEvent::AgentResponse { thread_id: t, text }) if t == thread_id => {
+                        return Ok(text)
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err("pipeline event bus closed".to_string())
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(timeout, answer)
+            .await
+            .map_err(|_| format!("agent '{agent}' gave no answer within {}s", timeout.as_secs()))?
+    }
+
     /// Start the pipeline.
     pub fn run(&mut self) {
         self.pipeline.run();
@@ -249,6 +365,14 @@ pub struct AgentPipelineBuilder {
     /// ToolDefinitions generated by buffer nodes (callable organisms).
     /// Appended to peer tool definitions in `with_agents()`.
     buffer_tool_definitions: Vec<crate::llm::types::ToolDefinition>,
+    /// Workspace jails by profile name, built from each profile's
+    /// `workspace:` block as native tools and agents need them.
+    workspaces: std::collections::HashMap<String, Arc<Workspace>>,
+    /// Kernel opened ahead of `build()` when a handler needs it (agents
+    /// record file checkpoints in it). Reused by `build()`.
+    kernel: Option<Arc<Mutex<Kernel>>>,
+    /// Store for long tool outputs, handed to every registered tool.
+    output_store: Option<Arc<OutputStore>>,
 }
 
 impl AgentPipelineBuilder {
@@ -270,9 +394,56 @@ impl AgentPipelineBuilder {
             tool_interfaces: std::collections::HashMap::new(),
             local_engine: None,
             buffer_tool_definitions: Vec::new(),
+            workspaces: std::collections::HashMap::new(),
+            kernel: None,
+            output_store: None,
         }
     }
 
+    /// The pipeline's kernel, opened on first use.
+    fn shared_kernel(&mut self) -> Result<Arc<Mutex<Kernel>>, String> {
+        if let Some(ref kernel) = self.kernel {
+            return Ok(kernel.clone());
+        }
+        let mut kernel =
+            Kernel::open(&self.data_dir).map_err(|e| format!("kernel open failed: {e}"))?;
+        kernel.set_event_sender(self.event_tx.clone());
+        let kernel = Arc::new(Mutex::new(kernel));
+        self.kernel = Some(kernel.clone());
+        Ok(kernel)
+    }
+
+    /// The store long tool outputs are paged from, created on first use.
+    fn shared_output_store(&mut self) -> Result<Arc<OutputStore>, String> {
+        if let Some(ref store) = self.output_store {
+            return Ok(store.clone());
+        }
+        let store = Arc::new(OutputStore::new(self.shared_kernel()?));
+        self.output_store = Some(store.clone());
+        Ok(store)
+    }
+
+    /// The workspace jail for `profile`, built on first use.
+    ///
+    /// None when the profile declares no `workspace:` block. Violations
+    /// are reported on the pipeline event channel as `SecurityBlocked`.
+    fn profile_workspace(
+        &mut self,
+        profile: &SecurityProfile,
+    ) -> Result<Option<Arc<Workspace>>, String> {
+        if let Some(ws) = self.workspaces.get(&profile.name) {
+            return Ok(Some(ws.clone()));
+        }
+        let Some(ref spec) = profile.workspace else {
+            return Ok(None);
+        };
+        let workspace = Arc::new(
+            Workspace::from_spec(&profile.name, spec)?.with_event_sender(self.event_tx.clone()),
+        );
+        self.workspaces.insert(profile.name.clone(), workspace.clone());
+        Ok(Some(workspace))
+    }
+
     /// Register a tool-peer handler for a listener defined in the organism.
     ///
     /// Parses the tool's WIT interface at registration time to derive:
@@ -280,7 +451,15 @@ impl AgentPipelineBuilder {
     /// - ToolInterface stored for later ToolDefinition generation by `with_agents()`
     ///
     /// For WASM tools with empty WIT, falls back to schema-free registration.
-    pub fn register_tool<T: ToolPeer>(mut self, listener_name: &str, tool: T) -> Result<Self, String> {
+    pub fn register_tool<T: ToolPeer>(
+        mut self,
+        listener_name: &str,
+        mut tool: T,
+    ) -> Result<Self, String> {
+        tool.set_event_sender(self.event_tx.clone());
+        tool.set_output_store(self.shared_output_store()?);
+        tool.set_kernel(self.shared_kernel()?);
+
         let wit_str = tool.wit();
         if wit_str.is_empty() {
             // WASM tools — no WIT text, fall back to regular register
@@ -315,6 +494,124 @@ impl AgentPipelineBuilder {
         Ok(self)
     }
 
+    /// Register the native tool peer `name`, one instance per profile.
+    ///
+    /// Every profile that can reach the listener gets its own instance,
+    /// configured from that profile's `workspace:`, `commands:`, `git:`,
+    /// `lsp:` and `sandbox:` blocks and the outbound ports of its
+    /// `network:` listeners; calls are dispatched by the calling thread's
+    /// profile (see [`PerProfile`]). Tools that touch the filesystem refuse
+    /// to start for a profile without a workspace. The main binary and
+    /// buffer children both build their tools here.
+    pub fn register_native_tool(self, name: &str) -> Result<Self, String> {
+        match name {
+            "file-read" => self.register_per_profile(name, |_| Ok(FileReadTool::default())),
+            "file-write" => self.register_per_profile(name, |_| Ok(FileWriteTool::default())),
+            "file-edit" => self.register_per_profile(name, |_| Ok(FileEditTool::default())),
+            "glob" => self.register_per_profile(name, |_| Ok(GlobTool::default())),
+            "grep" => self.register_per_profile(name, |_| Ok(GrepTool::default())),
+            "command-exec" => self.register_per_profile(name, |p| {
+                let mut tool = match p.commands.as_ref() {
+                    Some(commands) => {
+                        CommandExecTool::with_policy(CommandPolicy::from_spec(commands))
+                    }
+                    None => CommandExecTool::new(),
+                };
+                if let Some(sandbox) = profile_sandbox(p)? {
+                    tool = tool.with_sandbox(sandbox);
+                }
+                Ok(tool)
+            }),
+            "git" => self.register_per_profile(name, |p| {
+                let mut tool = p.git.clone().map(GitTool::with_policy).unwrap_or_default();
+                if let Some(sandbox) = profile_sandbox(p)? {
+                    tool = tool.with_sandbox(sandbox);
+                }
+                Ok(tool)
+            }),
+            "test-run" => self.register_per_profile(name, |p| {
+                let mut tool = TestRunTool::new();
+                if let Some(sandbox) = profile_sandbox(p)? {
+                    tool = tool.with_sandbox(sandbox);
+                }
+                Ok(tool)
+            }),
+            "diagnostics" => self.register_per_profile(name, |p| {
+                let mut tool = DiagnosticsTool::new();
+                if let Some(sandbox) = profile_sandbox(p)? {
+                    tool = tool.with_sandbox(sandbox);
+                }
+                Ok(tool)
+            }),
+            "lsp" => self.register_per_profile(name, |p| {
+                let mut tool = match p.lsp.as_ref() {
+                    Some(servers) => LspTool::with_servers(servers),
+                    None => LspTool::new(),
+                };
+                if let Some(sandbox) = profile_sandbox(p)? {
+                    tool = tool.with_sandbox(sandbox);
+                }
+                Ok(tool)
+            }),
+            "list-dir" => self.register_per_profile(name, |_| Ok(ListDirTool::default())),
+            "notebook-edit" => self.register_per_profile(name, |_| Ok(NotebookEditTool::default())),
+            "http-fetch" => {
+                let ports = PortManager::from_organism(&self.organism)?;
+                let organism = self.organism.clone();
+                self.register_per_profile(name, |p| {
+                    Ok(HttpFetchTool::with_policy(EgressPolicy::for_profile(
+                        &ports, &organism, &p.name,
+                    )?))
+                })
+            }
+            "replace" => self.register_per_profile(name, |_| Ok(ReplaceTool::new())),
+            "output-page" => self.register_per_profile(name, |_| Ok(OutputPageTool::default())),
+            _ => Err(format!("unknown native tool: '{name}'")),
+        }
+    }
+
+    /// Build `name` for each profile that can reach it and register the set.
+    ///
+    /// Instances of tools in [`JAILED_TOOLS`] get their profile's workspace;
+    /// a profile that can reach one but declares no workspace is an error.
+    fn register_per_profile<T: ToolPeer>(
+        mut self,
+        name: &str,
+        build: impl Fn(&SecurityProfile) -> Result<T, String>,
+    ) -> Result<Self, String> {
+        let mut profiles: Vec<SecurityProfile> = self
+            .organism
+            .profile_names()
+            .into_iter()
+            .filter_map(|p| self.organism.get_profile(p))
+            .filter(|p| p.allow_all || p.allowed_listeners.contains(name))
+            .cloned()
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        if profiles.is_empty() {
+            // Unreachable listener: nothing to serve
+            tracing::info!("no profile can use {name}; not registering it");
+            return Ok(self);
+        }
+
+        let mut tools = std::collections::HashMap::new();
+        for profile in &profiles {
+            let mut tool = build(profile)?;
+            if JAILED_TOOLS.contains(&name) {
+                let ws = self.profile_workspace(profile)?.ok_or_else(|| {
+                    format!(
+                        "profile '{}' can use {name} but declares no workspace",
+                        profile.name
+                    )
+                })?;
+                tool.set_workspace(ws);
+            }
+            tools.insert(profile.name.clone(), tool);
+        }
+        let tool = PerProfile::new(name, tools)?;
+        self.register_tool(name, tool)
+    }
+
     /// Register a handler for a listener defined in the organism.
     pub fn register<H: Handler>(mut self, listener_name: &str, handler: H) -> Result<Self, String> {
         let def = self
@@ -442,16 +739,8 @@ impl AgentPipelineBuilder {
             }
 
             // Validate: all required tools are known
-            let known_tools = [
-                "file-read",
-                "file-write",
-                "file-edit",
-                "glob",
-                "grep",
-                "command-exec",
-            ];
             for req in &callable.requires {
-                if !known_tools.contains(&req.as_str()) {
+                if !NATIVE_TOOLS.contains(&req.as_str()) {
                     return Err(format!(
                         "buffer '{}': unknown required tool '{}'",
                         def.name, req
@@ -548,38 +837,9 @@ impl AgentPipelineBuilder {
 
     /// Build a PortManager from the organism's listener port declarations.
     ///
-    /// Validates that no two listeners conflict on the same port+direction.
+    /// Validates that no two listeners claim the same inbound port.
     pub fn with_port_manager(mut self) -> Result<Self, String> {
-        let mut pm = PortManager::new();
-
-        for listener in self.organism.listeners().values() {
-            for port_def in &listener.ports {
-                let direction = match port_def.direction.as_str() {
-                    "inbound" => Direction::Inbound,
-                    "outbound" => Direction::Outbound,
-                    other => {
-                        return Err(format!(
-                            "invalid port direction '{}' on listener '{}'",
-                            other, listener.name
-                        ))
-                    }
-                };
-
-                let protocol = Protocol::from_str_lc(&port_def.protocol)
-                    .map_err(|e| format!("listener '{}': {}", listener.name, e))?;
-
-                pm.declare(
-                    &listener.name,
-                    PortDeclaration {
-                        port: port_def.port,
-                        direction,
-                        protocol,
-                        allowed_hosts: port_def.hosts.clone(),
-                    },
-                )?;
-            }
-        }
-
+        let pm = PortManager::from_organism(&self.organism)?;
         pm.validate().map_err(|errs| errs.join("; "))?;
         self.port_manager = Some(pm);
         Ok(self)
@@ -754,6 +1014,9 @@ impl AgentPipelineBuilder {
         // Take the semantic router (can only be given to one agent — first one)
         let mut router_opt = self.semantic_router.take();
 
+        // Agents checkpoint file mutations in the pipeline's own kernel
+        let kernel = self.shared_kernel()?;
+
         for def in &agent_defs {
             // Build tool definitions from WIT interfaces (registered via register_tool),
             // with hand-written fallback for tools without WIT, then WASM registry fallback
@@ -823,6 +1086,20 @@ impl AgentPipelineBuilder {
                 handler = handler.with_router_attached(router);
             }
 
+            // Agents resolve checkpointed paths in their caller's workspace
+            let callers: Vec<SecurityProfile> = self
+                .organism
+                .profile_names()
+                .into_iter()
+                .filter_map(|p| self.organism.get_profile(p))
+                .filter(|p| p.allow_all || p.allowed_listeners.contains(&def.name))
+                .cloned()
+                .collect();
+            for profile in &callers {
+                self.profile_workspace(profile)?;
+            }
+            handler = handler.with_checkpoints_attached(kernel.clone(), self.workspaces.clone());
+
             // Wire the event sender
             handler.set_event_sender(self.event_tx.clone());
 
@@ -867,8 +1144,7 @@ impl AgentPipelineBuilder {
             .schemas
             .register(crate::tools::agent_response_schema());
 
-        let kernel =
-            Kernel::open(&self.data_dir).map_err(|e| format!("kernel open failed: {e}"))?;
+        let kernel = self.shared_kernel()?;
 
         let security = SecurityResolver::from_organism(&self.organism)?;
 
@@ -877,7 +1153,7 @@ impl AgentPipelineBuilder {
 
         Ok(AgentPipeline {
             pipeline,
-            kernel: Arc::new(Mutex::new(kernel)),
+            kernel,
             organism: self.organism,
             security,
             event_tx: self.event_tx,
@@ -925,6 +1201,102 @@ profiles:
         parse_organism(yaml).unwrap()
     }
 
+    #[tokio::test]
+    async fn native_tools_register_from_profile() {
+        let dir = TempDir::new().unwrap();
+        let yaml = format!(
+            r#"
+organism:
+  name: native-test
+
+listeners:
+  - name: command-exec
+    payload_class: tools.CommandExecRequest
+    handler: tools.command_exec.handle
+    description: "Command execution"
+
+  - name: grep
+    payload_class: tools.GrepRequest
+    handler: tools.grep.handle
+    description: "Content search"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [command-exec, grep]
+    journal: retain_forever
+    workspace:
+      root: {}
+  browse:
+    linux_user: agentos-browse
+    listeners: []
+    journal: prune_on_delivery
+"#,
+            dir.path().display()
+        );
+        let builder = AgentPipelineBuilder::new(parse_organism(&yaml).unwrap(), &dir.path().join("data"));
+        let builder = builder
+            .register_native_tool("command-exec")
+            .unwrap()
+            .register_native_tool("grep")
+            .unwrap();
+        assert!(builder.workspaces.contains_key("coding"));
+        assert!(!builder.workspaces.contains_key("browse"));
+
+        let err = builder.register_native_tool("teleport").err().unwrap();
+        assert!(err.contains("unknown native tool"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn file_tools_refuse_profiles_without_workspace() {
+        let dir = TempDir::new().unwrap();
+        let yaml = format!(
+            r#"
+organism:
+  name: jailed
+
+listeners:
+  - name: file-read
+    payload_class: tools.FileReadRequest
+    handler: tools.file_read.handle
+    description: "File read"
+
+  - name: output-page
+    payload_class: tools.OutputPageRequest
+    handler: tools.output.handle
+    description: "Page long outputs"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [file-read, output-page]
+    journal: retain_forever
+    workspace:
+      root: {}
+  admin:
+    linux_user: agentos-admin
+    listeners: all
+    journal: retain_forever
+"#,
+            dir.path().display()
+        );
+        let org = parse_organism(&yaml).unwrap();
+
+        // admin reaches file-read through `listeners: all` but has no jail
+        let err = AgentPipelineBuilder::new(org.clone(), &dir.path().join("data"))
+            .register_native_tool("file-read")
+            .err()
+            .unwrap();
+        assert!(err.contains("profile 'admin' can use file-read but declares no workspace"), "{err}");
+
+        // Tools that don't touch the filesystem need no workspace
+        let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
+            .register_native_tool("output-page")
+            .unwrap()
+            .build();
+        assert!(pipeline.is_ok());
+    }
+
     #[tokio::test]
     async fn build_agent_pipeline() {
         let dir = TempDir::new().unwrap();
@@ -998,6 +1370,62 @@ profiles:
         pipeline.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn ask_agent_waits_for_its_own_thread() {
+        let yaml = r#"
+organism:
+  name: ask-test
+
+listeners:
+  - name: stub-agent
+    payload_class: agent.AgentTask
+    handler: agent.handle
+    description: "Agent that never answers by itself"
+    is_agent: true
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [stub-agent]
+    journal: retain_forever
+"#;
+        let dir = TempDir::new().unwrap();
+        let silent = FnHandler(|_p: ValidatedPayload, _ctx: HandlerContext| {
+            Box::pin(async move { Ok(HandlerResponse::None) })
+        });
+        let mut pipeline = AgentPipelineBuilder::new(parse_organism(yaml).unwrap(), dir.path())
+            .register("stub-agent", silent)
+            .unwrap()
+            .build()
+            .unwrap();
+        pipeline.run();
+
+        // Another thread's reply arrives first; only ours is the answer
+        let tx = pipeline.event_sender().clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            for (thread_id, text) in [("other", "not yours"), ("mine", "yours")] {
+                let _ = tx.send(PipelineEvent::AgentResponse {
+                    thread_id: thread_id.into(),
+                    text: text.into(),
+                });
+            }
+        });
+        let answer = pipeline
+            .ask_agent("stub-agent", "mine", "hi", std::time::Duration::from_secs(5))
+            .await;
+        assert_eq!(answer.unwrap(), "yours");
+
+        // Nobody answers: the deadline turns the wait into an error
+        let err = pipeline
+            .ask_agent("stub-agent", "mine", "hi", std::time::Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(err.contains("no answer within"), "{err}");
+
+        pipeline.shutdown().await;
+    }
+
     #[tokio::test]
     async fn kernel_state_persists() {
         let dir = TempDir::new().unwrap();
@@ -1175,11 +1603,11 @@ profiles:
         builder: AgentPipelineBuilder,
     ) -> Result<AgentPipelineBuilder, String> {
         builder
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)?
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)?
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)?
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)?
-            .register_tool("grep", crate::tools::grep::GrepTool)?
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())?
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())?
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())?
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())?
+            .register_tool("grep", crate::tools::grep::GrepTool::default())?
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
     }
 
@@ -1223,15 +1651,15 @@ profiles:
         let mut pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_llm_pool(pool)
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -1273,6 +1701,51 @@ profiles:
         pipeline.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn native_tools_jail_each_profile_to_its_own_workspace() {
+        let dir = TempDir::new().unwrap();
+        let coding = dir.path().join("coding");
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(&coding).unwrap();
+        std::fs::create_dir_all(&docs).unwrap();
+        let yaml = format!(
+            r#"
+organism:
+  name: jailed
+
+listeners:
+  - name: file-read
+    payload_class: tools.FileReadRequest
+    handler: tools.file_read.handle
+    description: "File read"
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [file-read]
+    journal: retain_forever
+    workspace:
+      root: {}
+  docs:
+    linux_user: agentos-docs
+    listeners: [file-read]
+    journal: retain_forever
+    workspace:
+      root: {}
+"#,
+            coding.display(),
+            docs.display()
+        );
+        let org = parse_organism(&yaml).unwrap();
+
+        let builder = AgentPipelineBuilder::new(org, &dir.path().join("data"))
+            .register_native_tool("file-read")
+            .unwrap();
+        assert_eq!(builder.workspaces["coding"].root(), coding.canonicalize().unwrap());
+        assert_eq!(builder.workspaces["docs"].root(), docs.canonicalize().unwrap());
+        assert!(builder.build().is_ok());
+    }
+
     #[tokio::test]
     async fn security_blocks_llm_for_restricted_profile() {
         let dir = TempDir::new().unwrap();
@@ -1287,15 +1760,15 @@ profiles:
         let mut pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_llm_pool(pool)
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -1425,15 +1898,15 @@ profiles:
         let builder = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_llm_pool(pool)
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -1573,15 +2046,15 @@ profiles:
             .unwrap()
             .with_code_index()
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap();
@@ -1612,15 +2085,15 @@ profiles:
             .unwrap()
             .with_code_index()
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -1666,15 +2139,15 @@ profiles:
             .unwrap()
             .with_code_index()
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -1733,15 +2206,15 @@ profiles:
             .unwrap()
             .with_code_index()
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap();
@@ -2200,7 +2673,7 @@ profiles:
         let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2216,7 +2689,7 @@ profiles:
         let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2234,7 +2707,7 @@ profiles:
         let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2253,7 +2726,7 @@ profiles:
         let mut pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2284,7 +2757,7 @@ profiles:
         let builder = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap();
 
         // WASM registry should have the echo tool definition
@@ -2336,7 +2809,7 @@ profiles:
         let mut pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2380,7 +2853,7 @@ profiles:
         let builder = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap();
 
         let reg = builder.wasm_registry.as_ref().unwrap();
@@ -2395,7 +2868,7 @@ profiles:
         let mut pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_wasm_tools(&echo_wasm_dir())
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
             .build()
             .unwrap();
@@ -2860,15 +3333,15 @@ profiles:
         let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_llm_pool(pool)
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -2964,15 +3437,15 @@ profiles:
         let pipeline = AgentPipelineBuilder::new(org, &dir.path().join("data"))
             .with_llm_pool(pool)
             .unwrap()
-            .register_tool("file-read", crate::tools::file_read::FileReadTool)
+            .register_tool("file-read", crate::tools::file_read::FileReadTool::default())
             .unwrap()
-            .register_tool("file-write", crate::tools::file_write::FileWriteTool)
+            .register_tool("file-write", crate::tools::file_write::FileWriteTool::default())
             .unwrap()
-            .register_tool("file-edit", crate::tools::file_edit::FileEditTool)
+            .register_tool("file-edit", crate::tools::file_edit::FileEditTool::default())
             .unwrap()
-            .register_tool("glob", crate::tools::glob_tool::GlobTool)
+            .register_tool("glob", crate::tools::glob_tool::GlobTool::default())
             .unwrap()
-            .register_tool("grep", crate::tools::grep::GrepTool)
+            .register_tool("grep", crate::tools::grep::GrepTool::default())
             .unwrap()
             .register_tool("command-exec", crate::tools::command_exec::CommandExecTool::new())
             .unwrap()
@@ -0,0 +1,195 @@
+//! Egress policy — which URLs a profile's tools may fetch.
+//!
+//! Built from the outbound http/https port declarations of the listeners a
+//! profile may use for network access (the same selection the firewall rules
+//! make). A URL is allowed when its scheme and port match a declaration and
+//! its host is one of the declaration's `hosts`; `*.example.com` matches any
+//! subdomain. A declaration with no hosts allows any host on its port, as its
+//! firewall rule does. A profile with no such declarations reaches nothing.
+
+use super::{profile_uses_ports, Direction, PortManager, Protocol};
+use crate::organism::Organism;
+
+/// One allowed (protocol, port, hosts) combination.
+#[derive(Debug, Clone, PartialEq)]
+struct EgressRule {
+    protocol: Protocol,
+    port: u16,
+    hosts: Vec<String>,
+}
+
+/// The URLs a profile may reach over http(s).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EgressPolicy {
+    rules: Vec<EgressRule>,
+}
+
+impl EgressPolicy {
+    /// The policy for `profile`: outbound http/https ports of the listeners
+    /// it may use for network access.
+    pub fn for_profile(
+        port_manager: &PortManager,
+        organism: &Organism,
+        profile: &str,
+    ) -> Result<Self, String> {
+        let profile = organism
+            .get_profile(profile)
+            .ok_or_else(|| format!("profile '{profile}' not in organism config"))?;
+        let mut policy = Self::default();
+        for (listener, decl) in port_manager.all_ports() {
+            let web = matches!(decl.protocol, Protocol::Http | Protocol::Https);
+            if web && decl.direction == Direction::Outbound && profile_uses_ports(profile, listener)
+            {
+                policy = policy.allow(decl.protocol, decl.port, &decl.allowed_hosts);
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Also allow `hosts` (any host if empty) on `port` over `protocol`.
+    pub fn allow(mut self, protocol: Protocol, port: u16, hosts: &[String]) -> Self {
+        self.rules.push(EgressRule {
+            protocol,
+            port,
+            hosts: hosts.iter().map(|h| h.to_ascii_lowercase()).collect(),
+        });
+        self
+    }
+
+    /// Whether nothing at all is allowed.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check a request target. `scheme` is `http` or `https`.
+    pub fn check(&self, scheme: &str, host: &str, port: u16) -> Result<(), String> {
+        let host = host.trim_matches(['[', ']']).to_ascii_lowercase();
+        let allowed = self.rules.iter().any(|rule| {
+            rule.protocol.to_string() == scheme
+                && rule.port == port
+                && (rule.hosts.is_empty() || rule.hosts.iter().any(|p| host_matches(p, &host)))
+        });
+        if allowed {
+            return Ok(());
+        }
+        if self.is_empty() {
+            return Err("no network access: the profile declares no outbound http(s) ports".into());
+        }
+        Err(format!(
+            "{scheme}://{host}:{port} is not allowed by the profile's network ports (allowed: {})",
+            self.describe()
+        ))
+    }
+
+    /// `https://docs.rs:443, http://*:8080`, for error messages.
+    pub fn describe(&self) -> String {
+        let mut allowed = Vec::new();
+        for rule in &self.rules {
+            if rule.hosts.is_empty() {
+                allowed.push(format!("{}://*:{}", rule.protocol, rule.port));
+            }
+            for host in &rule.hosts {
+                allowed.push(format!("{}://{host}:{}", rule.protocol, rule.port));
+            }
+        }
+        allowed.join(", ")
+    }
+}
+
+/// `pattern` is a host name, or `*.` and a domain to match its subdomains.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organism::parser::parse_organism;
+
+    const ORGANISM: &str = r#"
+organism:
+  name: egress-test
+
+listeners:
+  - name: llm-pool
+    payload_class: llm.LlmRequest
+    handler: llm.handle
+    description: "LLM inference pool"
+    ports:
+      - port: 443
+        direction: outbound
+        protocol: https
+        hosts: [api.anthropic.com]
+
+  - name: http-fetch
+    payload_class: tools.HttpFetchRequest
+    handler: tools.http_fetch.handle
+    description: "Fetch web pages"
+    ports:
+      - port: 443
+        direction: outbound
+        protocol: https
+        hosts: [docs.rs, "*.python.org"]
+      - port: 8080
+        direction: inbound
+        protocol: http
+
+profiles:
+  coding:
+    linux_user: agentos
+    listeners: [llm-pool, http-fetch]
+    network: [http-fetch]
+    journal: retain_forever
+  offline:
+    linux_user: agentos-offline
+    listeners: [llm-pool]
+    network: [llm-pool]
+    journal: retain_forever
+"#;
+
+    fn policy(profile: &str) -> EgressPolicy {
+        let org = parse_organism(ORGANISM).unwrap();
+        let pm = PortManager::from_organism(&org).unwrap();
+        EgressPolicy::for_profile(&pm, &org, profile).unwrap()
+    }
+
+    #[test]
+    fn profile_reaches_only_its_network_listeners() {
+        let coding = policy("coding");
+        assert!(coding.check("https", "docs.rs", 443).is_ok());
+        assert!(coding.check("https", "DOCS.RS", 443).is_ok());
+        let err = coding.check("https", "api.anthropic.com", 443).unwrap_err();
+        assert!(
+            err.contains("allowed: https://docs.rs:443, https://*.python.org:443"),
+            "{err}"
+        );
+        assert!(coding.check("http", "docs.rs", 443).is_err());
+        assert!(coding.check("https", "docs.rs", 8443).is_err());
+        // Inbound declarations don't grant egress
+        assert!(coding.check("http", "localhost", 8080).is_err());
+
+        let offline = policy("offline");
+        assert!(offline.check("https", "docs.rs", 443).is_err());
+        assert!(offline.check("https", "api.anthropic.com", 443).is_ok());
+    }
+
+    #[test]
+    fn wildcard_hosts_and_empty_policy() {
+        let coding = policy("coding");
+        assert!(coding.check("https", "docs.python.org", 443).is_ok());
+        assert!(coding.check("https", "python.org", 443).is_err());
+        assert!(coding.check("https", "evilpython.org", 443).is_err());
+
+        let err = EgressPolicy::default()
+            .check("https", "docs.rs", 443)
+            .unwrap_err();
+        assert!(err.starts_with("no network access"), "{err}");
+        let any = EgressPolicy::default().allow(Protocol::Http, 8080, &[]);
+        assert!(any.check("http", "127.0.0.1", 8080).is_ok());
+    }
+}
@@ -3,7 +3,7 @@
 //! Generates iptables-syntax rules as strings. Portable — works on any OS
 //! (just strings, no system calls). Applied on Linux deployment only.
 
-use super::{Direction, PortManager, Protocol};
+use super::{profile_uses_ports, Direction, PortManager, Protocol};
 use crate::organism::Organism;
 
 /// Action for a firewall rule.
@@ -92,15 +92,7 @@ pub fn generate_rules(port_manager: &PortManager, organism: &Organism) -> Vec<Fi
         // Find all profiles that grant access to this listener
         for profile_name in organism.profile_names() {
             if let Some(profile) = organism.get_profile(profile_name) {
-                let listener_str = listener_name.to_string();
-                let has_access =
-                    profile.allow_all || profile.allowed_listeners.contains(listener_name);
-
-                // Also check network field if present
-                let has_network =
-                    profile.network.is_empty() || profile.network.contains(&listener_str);
-
-                if has_access && has_network {
+                if profile_uses_ports(profile, listener_name) {
                     rules.push(FirewallRule {
                         linux_user: profile.linux_user.clone(),
                         port: decl.port,
@@ -183,6 +175,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::Forever,
             network: vec!["llm-pool".into()],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -193,6 +190,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::PruneOnDelivery,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -1,12 +1,17 @@
 //! Port Manager — tracks port declarations per listener, validates no conflicts.
 //!
 //! Each listener declares its port requirements (inbound/outbound, protocol, hosts).
-//! The PortManager validates that no two listeners conflict on the same port+direction.
+//! The PortManager validates that no two listeners claim the same inbound port;
+//! outbound ports are shared (several listeners may each reach hosts on 443).
 
+pub mod egress;
 pub mod firewall;
 
 use std::collections::HashMap;
 
+use crate::organism::profile::SecurityProfile;
+use crate::organism::Organism;
+
 /// Direction of network traffic.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
@@ -85,15 +90,50 @@ impl PortManager {
         }
     }
 
+    /// Build a PortManager from the organism's listener port declarations.
+    pub fn from_organism(organism: &Organism) -> Result<Self, String> {
+        let mut pm = Self::new();
+
+        for listener in organism.listeners().values() {
+            for port_def in &listener.ports {
+                let direction = match port_def.direction.as_str() {
+                    "inbound" => Direction::Inbound,
+                    "outbound" => Direction::Outbound,
+                    other => {
+                        return Err(format!(
+                            "invalid port direction '{}' on listener '{}'",
+                            other, listener.name
+                        ))
+                    }
+                };
+
+                let protocol = Protocol::from_str_lc(&port_def.protocol)
+                    .map_err(|e| format!("listener '{}': {}", listener.name, e))?;
+
+                pm.declare(
+                    &listener.name,
+                    PortDeclaration {
+                        port: port_def.port,
+                        direction,
+                        protocol,
+                        allowed_hosts: port_def.hosts.clone(),
+                    },
+                )?;
+            }
+        }
+
+        Ok(pm)
+    }
+
     /// Declare a port for a listener. Returns error on conflict.
     pub fn declare(&mut self, listener: &str, decl: PortDeclaration) -> Result<(), String> {
-        // Check for conflicts: same port + same direction on a different listener
+        // Check for conflicts: same inbound port on a different listener
         for (existing_listener, decls) in &self.allocations {
             if existing_listener == listener {
                 continue;
             }
             for existing in decls {
-                if existing.port == decl.port && existing.direction == decl.direction {
+                if conflicts(existing, &decl) {
                     return Err(format!(
                         "port conflict: {} port {} already declared by '{}', cannot declare for '{}'",
                         decl.direction, decl.port, existing_listener, listener
@@ -119,7 +159,7 @@ impl PortManager {
             for j in (i + 1)..all.len() {
                 let (l1, d1) = &all[i];
                 let (l2, d2) = &all[j];
-                if l1 != l2 && d1.port == d2.port && d1.direction == d2.direction {
+                if l1 != l2 && conflicts(d1, d2) {
                     errors.push(format!(
                         "port conflict: {} port {} declared by both '{}' and '{}'",
                         d1.direction, d1.port, l1, l2
@@ -166,6 +206,19 @@ impl Default for PortManager {
     }
 }
 
+/// Two listeners can't both bind an inbound port; outbound ports are shared.
+fn conflicts(a: &PortDeclaration, b: &PortDeclaration) -> bool {
+    a.port == b.port && a.direction == Direction::Inbound && b.direction == Direction::Inbound
+}
+
+/// Whether a profile may use a listener's ports: it must reach the listener,
+/// and list it under `network:` unless that list is empty.
+pub fn profile_uses_ports(profile: &SecurityProfile, listener: &str) -> bool {
+    let has_access = profile.allow_all || profile.allowed_listeners.contains(listener);
+    let has_network = profile.network.is_empty() || profile.network.iter().any(|n| n == listener);
+    has_access && has_network
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,6 +299,25 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn outbound_ports_are_shared() {
+        let mut pm = PortManager::new();
+        for (listener, host) in [("llm-pool", "api.anthropic.com"), ("http-fetch", "docs.rs")] {
+            pm.declare(
+                listener,
+                PortDeclaration {
+                    port: 443,
+                    direction: Direction::Outbound,
+                    protocol: Protocol::Https,
+                    allowed_hosts: vec![host.into()],
+                },
+            )
+            .unwrap();
+        }
+        assert!(pm.validate().is_ok());
+        assert_eq!(pm.all_ports().len(), 2);
+    }
+
     #[test]
     fn same_listener_same_port_ok() {
         let mut pm = PortManager::new();
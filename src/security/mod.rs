@@ -119,6 +119,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::RetainDays(90),
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -133,6 +138,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::PruneOnDelivery,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -144,6 +154,11 @@ mod tests {
             allow_all: true,
             journal_retention: RetentionPolicy::Forever,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -236,6 +251,11 @@ mod tests {
             allow_all: false,
             journal_retention: RetentionPolicy::PruneOnDelivery,
             network: vec![],
+            workspace: None,
+            commands: None,
+            sandbox: None,
+            git: None,
+            lsp: None,
         })
         .unwrap();
 
@@ -1,13 +1,37 @@
-//! CommandExecTool — execute allowed commands with allowlist, timeout, capture.
+//! CommandExecTool — execute allowed commands with policy, timeout, capture.
+//!
+//! Every command in the line — pipeline stages, `;`/`&&` segments and
+//! substitutions — is checked against the [`CommandPolicy`] before the
+//! line reaches the shell.
+//!
+//! Without a `session`, each call runs in a fresh `sh -c`. With one, the
+//! command goes into a persistent shell owned by the calling thread (see
+//! [`super::shell`]), so working directory and environment carry over, and
+//! long-running commands become jobs to `poll` or `kill`. Jailed, a
+//! session's `cd`/`pushd` targets are checked against the workspace before
+//! the line runs, and a session whose shell ends up outside it anyway is
+//! closed.
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::Command;
+use tokio::sync::broadcast;
 
+use super::command_policy::{self, CommandPolicy};
+use super::output::{self, OutputStore};
+use super::sandbox::Sandbox;
+use super::shell::{JobOutput, JobStatus, ShellSessions};
+use super::workspace::{Access, Workspace};
 use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::pipeline::events::PipelineEvent;
 
-const MAX_OUTPUT: usize = 100 * 1024; // 100KB
+/// Bytes of each stream kept in memory; the model sees a shaped view.
+const MAX_CAPTURE: usize = 4 * 1024 * 1024; // 4MB
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
 /// Default command allowlist.
@@ -16,137 +40,489 @@ const DEFAULT_ALLOWLIST: &[&str] = &[
     "wasm-tools", "ls", "dir", "echo", "where", "which", "tree", "rg", "curl", "mkdir",
 ];
 
+/// Shell builtins that only change session state, allowed in sessions.
+/// `source`/`.` are left out: they run a script the policy never sees.
+const SESSION_BUILTINS: &[&str] = &["cd", "pushd", "popd", "export", "unset"];
+
 /// Execute allowed shell commands with timeout and output capture.
 pub struct CommandExecTool {
-    allowlist: Vec<String>,
+    policy: CommandPolicy,
+    sandbox: Option<Arc<Sandbox>>,
+    workspace: Option<Arc<Workspace>>,
+    sessions: Arc<ShellSessions>,
+    /// Where output is streamed as it arrives.
+    events: Option<broadcast::Sender<PipelineEvent>>,
+    next_call: AtomicU64,
+    output_store: Option<Arc<OutputStore>>,
 }
 
 impl CommandExecTool {
     /// Create with default allowlist.
     pub fn new() -> Self {
-        Self {
-            allowlist: DEFAULT_ALLOWLIST.iter().map(|s| s.to_string()).collect(),
-        }
+        Self::with_allowlist(DEFAULT_ALLOWLIST.iter().map(|s| s.to_string()).collect())
     }
 
-    /// Create with custom allowlist.
+    /// Create with custom allowlist (any arguments).
     pub fn with_allowlist(allowlist: Vec<String>) -> Self {
-        Self { allowlist }
+        Self::with_policy(CommandPolicy::from_allowlist(&allowlist))
     }
 
-    /// Check if a command's first token is in the allowlist.
-    fn is_allowed(&self, command: &str) -> bool {
-        let first_token = command.split_whitespace().next().unwrap_or("");
-        // Extract just the executable name (strip path)
-        let exe_name = std::path::Path::new(first_token)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(first_token);
-
-        // Case-insensitive on Windows, case-sensitive otherwise
-        self.allowlist.iter().any(|allowed| {
-            if cfg!(windows) {
-                exe_name.eq_ignore_ascii_case(allowed)
-                    || exe_name
-                        .strip_suffix(".exe")
-                        .map(|s| s.eq_ignore_ascii_case(allowed))
-                        .unwrap_or(false)
-            } else {
-                exe_name == allowed.as_str()
-            }
-        })
+    /// Create with argument-level rules, e.g. from a profile's `commands:`.
+    pub fn with_policy(policy: CommandPolicy) -> Self {
+        Self {
+            policy,
+            sandbox: None,
+            workspace: None,
+            sessions: Arc::new(ShellSessions::default()),
+            events: None,
+            next_call: AtomicU64::new(0),
+            output_store: None,
+        }
     }
 
-    fn truncate_output(s: &str) -> String {
-        if s.len() > MAX_OUTPUT {
-            format!("{}...\n(truncated at {} bytes)", &s[..MAX_OUTPUT], MAX_OUTPUT)
-        } else {
-            s.to_string()
-        }
+    /// Run every command, one-shot or in a session, inside `sandbox`.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        let sandbox = Arc::new(sandbox);
+        self.sessions = Arc::new(ShellSessions::default().with_sandbox(sandbox.clone()));
+        self.sandbox = Some(sandbox);
+        self
     }
-}
 
-impl Default for CommandExecTool {
-    fn default() -> Self {
-        Self::new()
+    /// Check `command` against the policy; every stage, list segment and
+    /// substitution must pass.
+    fn check(&self, command: &str) -> Result<(), String> {
+        self.policy.check(command, &[])
     }
-}
 
-#[async_trait]
-impl Handler for CommandExecTool {
-    async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
-        let xml_str = String::from_utf8_lossy(&payload.xml);
+    /// Like `check`, plus the state-changing builtins sessions exist for.
+    fn check_in_session(&self, command: &str) -> Result<(), String> {
+        self.policy.check(command, SESSION_BUILTINS)
+    }
 
-        let command = extract_tag(&xml_str, "command").unwrap_or_default();
-        if command.is_empty() {
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err("missing required <command>"),
-            });
+    /// Jailed, check that every `cd`/`pushd` in `command` stays inside the
+    /// workspace, following them in order from the session's `cwd`.
+    fn check_cd_targets(&self, command: &str, cwd: &Path) -> Result<(), String> {
+        match &self.workspace {
+            Some(ws) => follow_cd(ws, command, &mut cwd.to_path_buf()),
+            None => Ok(()),
         }
+    }
 
-        // Allowlist check
-        if !self.is_allowed(&command) {
-            let first = command.split_whitespace().next().unwrap_or("(empty)");
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!(
-                    "command not allowed: {first}. Allowed: {}",
-                    self.allowlist.join(", ")
-                )),
-            });
+    /// Close `session` if its shell has left the workspace anyway (say,
+    /// through a `cd` in a script), so nothing else runs out there.
+    fn confine(&self, thread_id: &str, session: &str, job: JobOutput) -> Result<JobOutput, String> {
+        let (Some(ws), Some(cwd)) = (&self.workspace, &job.cwd) else {
+            return Ok(job);
+        };
+        if ws
+            .resolve("command-exec", &cwd.to_string_lossy(), Access::Write)
+            .is_err()
+        {
+            self.sessions.close(thread_id, session);
+            return Err(format!(
+                "session {session} left the workspace ({}) and was closed
+output:
+{}",
+                cwd.display(),
+                job.output
+            ));
         }
+        Ok(job)
+    }
 
-        let timeout_secs = extract_tag(&xml_str, "timeout")
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+    #[cfg(test)]
+    fn is_allowed(&self, command: &str) -> bool {
+        self.check(command).is_ok()
+    }
 
-        let working_dir = extract_tag(&xml_str, "working_dir");
+    /// Resolve `<working_dir>`. Jailed, it must be inside the workspace and
+    /// defaults to its root rather than the process cwd.
+    fn working_dir(&self, requested: Option<String>) -> Result<Option<PathBuf>, String> {
+        match (&self.workspace, requested) {
+            (Some(ws), dir) => {
+                let dir = dir.unwrap_or_else(|| ".".into());
+                ws.resolve("command-exec", &dir, Access::Write).map(Some)
+            }
+            (None, dir) => Ok(dir.map(PathBuf::from)),
+        }
+    }
 
+    /// A stream for one call's output, if there is anywhere to send it.
+    fn output_stream(&self, thread_id: &str) -> Option<OutputStream> {
+        self.events.clone().map(|tx| OutputStream {
+            tx,
+            thread_id: thread_id.to_string(),
+            call_id: self.next_call.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Run `command` once in a fresh shell.
+    async fn exec_once(
+        &self,
+        thread_id: &str,
+        command: &str,
+        timeout_secs: u64,
+        working_dir: Option<PathBuf>,
+    ) -> Result<String, String> {
         // Build the command
         let mut cmd = if cfg!(windows) {
             let mut c = Command::new("cmd");
-            c.args(["/C", &command]);
+            c.args(["/C", command]);
             c
         } else {
             let mut c = Command::new("sh");
-            c.args(["-c", &command]);
+            c.args(["-c", command]);
             c
         };
 
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(cmd.as_std_mut());
+        }
         if let Some(ref dir) = working_dir {
             cmd.current_dir(dir);
         }
 
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::piped());
+        // Own process group, so a timeout takes down everything it started
+        #[cfg(unix)]
+        cmd.process_group(0);
+        cmd.kill_on_drop(true);
+
+        let mut child = cmd.spawn().map_err(|e| format!("execution error: {e}"))?;
+        let pid = child.id();
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err("execution error: output not captured".into());
+        };
+        let stream = self.output_stream(thread_id);
+
+        // Owns the child, so a timeout drops (and kills) it
+        let run = async move {
+            let (stdout, stderr) =
+                tokio::join!(capture(stdout, stream.as_ref()), capture(stderr, stream.as_ref()));
+            child.wait().await.map(|status| (status, stdout, stderr))
+        };
 
         // Execute with timeout
-        let result = tokio::time::timeout(Duration::from_secs(timeout_secs), cmd.output()).await;
+        let result = tokio::time::timeout(Duration::from_secs(timeout_secs), run).await;
 
         match result {
-            Ok(Ok(output)) => {
-                let stdout = Self::truncate_output(&String::from_utf8_lossy(&output.stdout));
-                let stderr = Self::truncate_output(&String::from_utf8_lossy(&output.stderr));
-                let exit_code = output.status.code().unwrap_or(-1);
-
-                let response = format!(
-                    "exit_code: {exit_code}\nstdout:\n{stdout}\nstderr:\n{stderr}"
-                );
+            Ok(Ok((status, stdout, stderr))) => {
+                let stdout = stdout.finish();
+                let stderr = stderr.finish();
+                let exit_code = status.code().unwrap_or(-1);
 
                 // Report both success and non-zero exit as OK — the caller
                 // sees the exit_code in the response text and decides.
-                Ok(HandlerResponse::Reply {
-                    payload_xml: ToolResponse::ok(&response),
-                })
+                Ok(format!(
+                    "exit_code: {exit_code}\nstdout:\n{stdout}\nstderr:\n{stderr}"
+                ))
+            }
+            Ok(Err(e)) => Err(format!("execution error: {e}")),
+            Err(_) => {
+                // Dropping the wait killed the shell; now its children
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    // SAFETY: signalling the process group we created.
+                    unsafe {
+                        libc::kill(-(pid as i32), libc::SIGKILL);
+                    }
+                }
+                #[cfg(not(unix))]
+                let _ = pid;
+                Err(format!("command timed out after {timeout_secs}s: {command}"))
+            }
+        }
+    }
+
+    /// Describe a session call's outcome for the model.
+    ///
+    /// Finished commands lead with `exit_code: N` like one-shot runs, then
+    /// the shell's `cwd`; the terminal merges stdout and stderr into one
+    /// `output`.
+    fn format_job(session: &str, job: JobOutput) -> String {
+        let output = job.output;
+        match job.status {
+            JobStatus::Exited(code) => match job.cwd {
+                Some(cwd) => format!(
+                    "exit_code: {code}\ncwd: {}\noutput:\n{output}",
+                    cwd.display()
+                ),
+                None => format!("exit_code: {code}\noutput:\n{output}"),
+            },
+            JobStatus::Running { command, elapsed } => format!(
+                "still running in session {session} after {}s: {command}\n\
+                 output so far:\n{output}\n\
+                 (poll the session for more output, or kill it)",
+                elapsed.as_secs()
+            ),
+            JobStatus::Idle => format!("nothing running in session {session}\noutput:\n{output}"),
+            JobStatus::Closed => format!("session {session} has exited\noutput:\n{output}"),
+        }
+    }
+
+    /// Handle a one-shot `run` (no session).
+    async fn handle_once(&self, xml_str: &str, thread_id: &str) -> Result<String, String> {
+        let command = extract_tag(xml_str, "command").unwrap_or_default();
+        if command.is_empty() {
+            return Err("missing required <command>".into());
+        }
+
+        self.check(&command)?;
+
+        let timeout_secs = extract_tag(xml_str, "timeout")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        let working_dir = self.working_dir(extract_tag(xml_str, "working_dir"))?;
+        self.exec_once(thread_id, &command, timeout_secs, working_dir)
+            .await
+    }
+
+    /// Handle a call that names a `<session>`.
+    async fn handle_session(
+        &self,
+        action: &str,
+        session: &str,
+        xml_str: &str,
+        thread_id: &str,
+    ) -> Result<String, String> {
+        let wait = extract_tag(xml_str, "timeout").and_then(|s| s.parse::<u64>().ok());
+        match action {
+            "run" => {
+                let command = extract_tag(xml_str, "command").unwrap_or_default();
+                if command.is_empty() {
+                    return Err("missing required <command>".into());
+                }
+                self.check_in_session(&command)?;
+                let background = extract_tag(xml_str, "background").is_some_and(|s| s == "true");
+                let wait = if background {
+                    0
+                } else {
+                    wait.unwrap_or(DEFAULT_TIMEOUT_SECS)
+                };
+                // Only used when the session is first opened
+                let dir = self.working_dir(extract_tag(xml_str, "working_dir"))?;
+                let cwd = self
+                    .sessions
+                    .cwd(thread_id, session)
+                    .await
+                    .or_else(|| dir.clone());
+                self.check_cd_targets(&command, &cwd.unwrap_or_default())?;
+                let stream = self.output_stream(thread_id);
+                let job = self
+                    .sessions
+                    .run(
+                        thread_id,
+                        session,
+                        &command,
+                        dir.as_deref(),
+                        Duration::from_secs(wait),
+                        stream.as_ref(),
+                    )
+                    .await?;
+                let job = self.confine(thread_id, session, job)?;
+                Ok(Self::format_job(session, job))
+            }
+            "poll" => {
+                let wait = Duration::from_secs(wait.unwrap_or(0));
+                let stream = self.output_stream(thread_id);
+                let job = self
+                    .sessions
+                    .poll(thread_id, session, wait, stream.as_ref())
+                    .await?;
+                let job = self.confine(thread_id, session, job)?;
+                Ok(Self::format_job(session, job))
+            }
+            "kill" => {
+                let job = self.sessions.kill(thread_id, session).await?;
+                let job = self.confine(thread_id, session, job)?;
+                Ok(Self::format_job(session, job))
+            }
+            "close" => {
+                if self.sessions.close(thread_id, session) {
+                    Ok(format!("closed session {session}"))
+                } else {
+                    Err(format!("no shell session named {session}"))
+                }
             }
-            Ok(Err(e)) => Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!("execution error: {e}")),
-            }),
-            Err(_) => Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!(
-                    "command timed out after {timeout_secs}s: {command}"
-                )),
-            }),
+            other => Err(format!(
+                "unknown action: {other} (expected run, poll, kill, close or list)"
+            )),
+        }
+    }
+
+    /// One line per session of `thread_id`.
+    fn list_sessions(&self, thread_id: &str) -> String {
+        let sessions = self.sessions.list(thread_id);
+        if sessions.is_empty() {
+            return "no shell sessions".into();
         }
+        sessions
+            .into_iter()
+            .map(|(name, running)| match running {
+                Some(command) => format!("{name}: running {command}"),
+                None => format!("{name}: idle"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for CommandExecTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Follow the `cd`/`pushd`s of `line` from `cwd`, refusing any that would
+/// leave the workspace. Substitutions run in subshells, so theirs start
+/// from `cwd` and don't move it.
+fn follow_cd(ws: &Workspace, line: &str, cwd: &mut PathBuf) -> Result<(), String> {
+    let parsed = command_policy::parse(line)?;
+    for cmd in &parsed.commands {
+        let Some(target) = command_policy::cd_target(cmd)? else {
+            continue;
+        };
+        let path = cwd.join(target);
+        *cwd = ws
+            .resolve("command-exec", &path.to_string_lossy(), Access::Write)
+            .map_err(|e| format!("command not allowed: {} ({e})", cmd.words.join(" ")))?;
+    }
+    for inner in &parsed.substitutions {
+        follow_cd(ws, inner, &mut cwd.clone())?;
+    }
+    Ok(())
+}
+
+/// `head`, a note that `omitted` bytes were cut, then `tail`.
+fn omitted_join(head: &str, omitted: usize, tail: &str) -> String {
+    format!("{head}\n... ({omitted} bytes omitted) ...\n{tail}")
+}
+
+/// Where a running call's output chunks go, one-shot or in a session.
+pub(super) struct OutputStream {
+    tx: broadcast::Sender<PipelineEvent>,
+    thread_id: String,
+    call_id: u64,
+}
+
+impl OutputStream {
+    pub(super) fn send(&self, chunk: String) {
+        // No subscribers is fine
+        let _ = self.tx.send(PipelineEvent::ToolOutput {
+            thread_id: self.thread_id.clone(),
+            tool_name: "command-exec".into(),
+            call_id: self.call_id,
+            chunk,
+        });
+    }
+}
+
+/// One output pipe, bounded to its first and last `MAX_CAPTURE / 2` bytes.
+#[derive(Default)]
+struct Captured {
+    head: Vec<u8>,
+    tail: Vec<u8>,
+    omitted: usize,
+}
+
+impl Captured {
+    fn push(&mut self, mut bytes: &[u8]) {
+        let half = MAX_CAPTURE / 2;
+        if self.head.len() < half {
+            let n = bytes.len().min(half - self.head.len());
+            self.head.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+        }
+        self.tail.extend_from_slice(bytes);
+        // Trim in batches, not on every chunk
+        if self.tail.len() > 2 * half {
+            let excess = self.tail.len() - half;
+            self.tail.drain(..excess);
+            self.omitted += excess;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let half = MAX_CAPTURE / 2;
+        if self.tail.len() > half {
+            let excess = self.tail.len() - half;
+            self.tail.drain(..excess);
+            self.omitted += excess;
+        }
+        let head = String::from_utf8_lossy(&self.head);
+        let tail = String::from_utf8_lossy(&self.tail);
+        if self.omitted == 0 {
+            format!("{head}{tail}")
+        } else {
+            omitted_join(&head, self.omitted, &tail)
+        }
+    }
+}
+
+/// Read `pipe` to the end, streaming whole UTF-8 characters as they come.
+async fn capture<R: AsyncRead + Unpin>(mut pipe: R, stream: Option<&OutputStream>) -> Captured {
+    let mut captured = Captured::default();
+    let mut buf = vec![0u8; 8192];
+    // Bytes of a character split across reads
+    let mut partial: Vec<u8> = Vec::new();
+    while let Ok(n) = pipe.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        captured.push(&buf[..n]);
+        if let Some(stream) = stream {
+            partial.extend_from_slice(&buf[..n]);
+            let complete = match std::str::from_utf8(&partial) {
+                Ok(_) => partial.len(),
+                // Invalid bytes go out (lossily) now; only a truncated
+                // character at the end waits for the next read
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => partial.len(),
+            };
+            if complete > 0 {
+                stream.send(String::from_utf8_lossy(&partial[..complete]).into_owned());
+                partial.drain(..complete);
+            }
+        }
+    }
+    if let (Some(stream), false) = (stream, partial.is_empty()) {
+        stream.send(String::from_utf8_lossy(&partial).into_owned());
+    }
+    captured
+}
+
+#[async_trait]
+impl Handler for CommandExecTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+
+        let action = extract_tag(&xml_str, "action").unwrap_or_else(|| "run".into());
+        let session = extract_tag(&xml_str, "session").filter(|s| !s.trim().is_empty());
+        let result = match (action.trim(), session) {
+            ("list", _) => Ok(self.list_sessions(&ctx.thread_id)),
+            (action, Some(session)) => {
+                self.handle_session(action, session.trim(), &xml_str, &ctx.thread_id)
+                    .await
+            }
+            ("run", None) => self.handle_once(&xml_str, &ctx.thread_id).await,
+            (action @ ("poll" | "kill" | "close"), None) => {
+                Err(format!("action {action} needs a <session>"))
+            }
+            (other, None) => Err(format!(
+                "unknown action: {other} (expected run, poll, kill, close or list)"
+            )),
+        };
+
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
     }
 }
 
@@ -156,17 +532,36 @@ impl ToolPeer for CommandExecTool {
         "command-exec"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.sessions.set_event_sender(tx.clone());
+        self.events = Some(tx);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Execute a shell command. Only allowed commands can be run (cargo, git, npm, etc). Captures stdout, stderr, and exit code.
+/// Execute a shell command. Only allowed commands can be run (cargo, git, npm, etc). Captures stdout, stderr, and exit code. Name a session to run in a persistent shell that keeps cd and exported variables between calls; commands still running at the timeout keep running there, to poll or kill.
 interface command-exec {
     record request {
-        /// The command to execute
-        command: string,
-        /// Timeout in seconds (default: 30)
+        /// The command to execute (for run)
+        command: option<string>,
+        /// Timeout in seconds (default: 30); for poll, how long to wait for the job (default: 0)
         timeout: option<u32>,
-        /// Working directory for the command
+        /// Working directory for the command (for a session: where it starts)
         working-dir: option<string>,
+        /// Persistent shell session to use, e.g. main or dev-server
+        session: option<string>,
+        /// run (default), poll (new output from a session's job), kill (interrupt it), close (end the session) or list (this thread's sessions)
+        action: option<string>,
+        /// Start the command in the session and return without waiting (default: false)
+        background: option<bool>,
     }
     exec: func(req: request) -> result<string, string>;
 }
@@ -177,6 +572,7 @@ interface command-exec {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::organism::profile::{CommandPolicySpec, CommandRule};
     use tempfile::TempDir;
 
     fn make_ctx() -> HandlerContext {
@@ -235,6 +631,31 @@ mod tests {
         assert!(!tool.is_allowed("echo hello"));
     }
 
+    #[test]
+    fn chained_commands_are_checked() {
+        let tool = CommandExecTool::new();
+        assert!(tool.is_allowed("cargo build 2>&1 | rg error"));
+        assert!(!tool.is_allowed("echo hi; rm -rf ~"));
+        assert!(!tool.is_allowed("echo hi && rm -rf ~"));
+        assert!(!tool.is_allowed("ls | sh"));
+        assert!(!tool.is_allowed("git log $(rm -rf ~)"));
+        assert!(!tool.is_allowed("echo `rm -rf ~`"));
+    }
+
+    #[tokio::test]
+    async fn exec_policy_denies_arguments() {
+        let mut spec = CommandPolicySpec::default();
+        spec.rules.insert(
+            "git".into(),
+            CommandRule { allow: vec!["status".into(), "push".into()], deny: vec!["push --force".into()] },
+        );
+        let tool = CommandExecTool::with_policy(CommandPolicy::from_spec(&spec));
+        let xml = "<CommandExecRequest><command>git push --force origin main</command></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("denied: git push --force"), "{content}");
+    }
+
     #[tokio::test]
     async fn exec_echo() {
         let tool = CommandExecTool::new();
@@ -270,6 +691,29 @@ mod tests {
         assert!(content.contains("marker.txt"));
     }
 
+    #[tokio::test]
+    async fn exec_jailed_working_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("marker.txt"), "found").unwrap();
+
+        let mut tool = CommandExecTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        // Defaults to the workspace root
+        let cmd = if cfg!(windows) { "dir" } else { "ls" };
+        let xml = format!("<CommandExecRequest><command>{cmd}</command></CommandExecRequest>");
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("marker.txt"));
+
+        let xml = format!(
+            "<CommandExecRequest><command>{cmd}</command><working_dir>..</working_dir></CommandExecRequest>"
+        );
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+    }
+
     #[tokio::test]
     async fn exec_missing_command() {
         let tool = CommandExecTool::new();
@@ -314,6 +758,211 @@ mod tests {
         assert!(content.contains("exit_code:"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_streams_output_events() {
+        let (tx, mut rx) = broadcast::channel(64);
+        let mut tool = CommandExecTool::new();
+        tool.set_event_sender(tx);
+        let xml = "<CommandExecRequest><command>echo one &amp;&amp; echo two</command></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("one"));
+
+        let mut streamed = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let PipelineEvent::ToolOutput { thread_id, tool_name, call_id, chunk } = event {
+                assert_eq!((thread_id.as_str(), tool_name.as_str(), call_id), ("t1", "command-exec", 0));
+                streamed.push_str(&chunk);
+            }
+        }
+        assert_eq!(streamed.replace('\r', ""), "one\ntwo\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_streams_session_output_events() {
+        let (tx, mut rx) = broadcast::channel(64);
+        let mut tool = CommandExecTool::with_allowlist(vec!["echo".into(), "sleep".into()]);
+        tool.set_event_sender(tx);
+        let xml = "<CommandExecRequest><command>echo one; sleep 0.2; echo two</command><session>main</session></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+
+        let mut streamed = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let PipelineEvent::ToolOutput { thread_id, call_id, chunk, .. } = event {
+                assert_eq!((thread_id.as_str(), call_id), ("t1", 0));
+                streamed.push_str(&chunk);
+            }
+        }
+        // Everything but the sentinel
+        assert_eq!(streamed, "one\ntwo\n");
+    }
+
+    #[test]
+    fn long_output_keeps_head_and_tail() {
+        let mut captured = Captured::default();
+        captured.push(b"BEGIN\n");
+        for _ in 0..3 * MAX_CAPTURE / 1024 {
+            captured.push(&[b'x'; 1024]);
+        }
+        captured.push(b"\nEND");
+        let text = captured.finish();
+        assert!(text.starts_with("BEGIN\n"));
+        assert!(text.ends_with("\nEND"));
+        assert!(text.contains("bytes omitted"));
+        assert!(text.len() < MAX_CAPTURE + 100);
+
+        let long = format!("start{}finish", "é".repeat(MAX_CAPTURE));
+        let text = output::shape_inline(long);
+        assert!(text.starts_with("start") && text.ends_with("finish"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_timeout_kills_background_children() {
+        let dir = TempDir::new().unwrap();
+        let tool = CommandExecTool::with_allowlist(vec!["echo".into(), "sleep".into()]);
+        let xml = format!("<CommandExecRequest><command>sleep 2 &amp;&amp; echo late &gt; marker &amp; sleep 10</command><timeout>1</timeout><working_dir>{}</working_dir></CommandExecRequest>", dir.path().display());
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("timed out"));
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert!(!dir.path().join("marker").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_in_sandbox_scrubs_environment() {
+        std::env::set_var("AGENTOS_EXEC_SECRET", "hunter2");
+        let spec = crate::organism::profile::SandboxSpec {
+            env: Some(vec!["PATH".into()]),
+            ..Default::default()
+        };
+        let tool = CommandExecTool::with_allowlist(vec!["env".into()])
+            .with_sandbox(Sandbox::from_spec("nobody", &spec).unwrap());
+        let xml = "<CommandExecRequest><command>env</command></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("PATH="));
+        assert!(!content.contains("AGENTOS_EXEC_SECRET"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_in_session_keeps_state() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/marker.txt"), "found").unwrap();
+
+        let mut tool = CommandExecTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        let xml = "<CommandExecRequest><command>cd sub</command><session>main</session></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert!(content.starts_with("exit_code: 0"));
+
+        let xml = "<CommandExecRequest><command>ls</command><session>main</session></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("marker.txt"));
+
+        // Builtins are only allowed where they persist
+        let xml = "<CommandExecRequest><command>cd sub</command></CommandExecRequest>";
+        let (ok, _) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+
+        // Nor can a session run unchecked scripts or poison its environment
+        for cmd in [
+            "source evil.sh",
+            ". ./evil.sh",
+            "export PATH=/tmp/evil:$PATH",
+            "export LD_PRELOAD=/tmp/evil.so",
+            "unset PATH",
+        ] {
+            let xml = format!("<CommandExecRequest><command>{cmd}</command><session>main</session></CommandExecRequest>");
+            let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+            assert!(!ok, "{cmd}: {content}");
+        }
+
+        let xml = "<CommandExecRequest><action>list</action></CommandExecRequest>";
+        let (_, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert_eq!(content, "main: idle");
+
+        let xml = "<CommandExecRequest><action>close</action><session>main</session></CommandExecRequest>";
+        let (ok, _) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        let xml = "<CommandExecRequest><action>list</action></CommandExecRequest>";
+        let (_, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert_eq!(content, "no shell sessions");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_session_cd_stays_in_workspace() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        // eval runs text the policy never parses
+        let mut tool = CommandExecTool::with_allowlist(vec!["eval".into()]);
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+        let run = |cmd: &str| {
+            format!("<CommandExecRequest><command>{cmd}</command><session>main</session></CommandExecRequest>")
+        };
+
+        let (ok, content) = get_result(tool.handle(make_payload(&run("cd sub")), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        let sub = dir.path().canonicalize().unwrap().join("sub");
+        assert!(content.contains(&format!("cwd: {}", sub.display())), "{content}");
+
+        // Targets are followed from the session's directory, in order
+        for cmd in [
+            "cd ../..",
+            "cd ..; cd ..",
+            "cd /",
+            "cd",
+            "cd -",
+            "cd ~",
+            "cd $HOME",
+            "pushd /tmp",
+            "CDPATH=/ cd etc",
+        ] {
+            let (ok, content) = get_result(tool.handle(make_payload(&run(cmd)), make_ctx()).await.unwrap());
+            assert!(!ok, "{cmd}: {content}");
+        }
+        let (ok, content) = get_result(tool.handle(make_payload(&run("cd -P .. &amp;&amp; cd sub")), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+
+        // A cd the check can't see closes the session
+        let (ok, content) = get_result(tool.handle(make_payload(&run("eval 'cd /'")), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("left the workspace"), "{content}");
+        assert!(tool.sessions.list("t1").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_session_background_and_poll() {
+        let tool = CommandExecTool::with_allowlist(vec!["echo".into(), "sleep".into()]);
+        let xml = "<CommandExecRequest><command>echo booting; sleep 0.3; echo ready</command><session>dev</session><background>true</background></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.starts_with("still running in session dev"));
+
+        let xml = "<CommandExecRequest><action>poll</action><session>dev</session><timeout>10</timeout></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.starts_with("exit_code: 0"));
+        assert!(content.contains("ready"));
+
+        let xml = "<CommandExecRequest><action>poll</action></CommandExecRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("needs a <session>"));
+    }
+
     #[cfg(windows)]
     #[test]
     fn allowlist_case_insensitive_windows() {
@@ -339,5 +988,6 @@ mod tests {
         assert_eq!(iface.name, "command-exec");
         assert_eq!(iface.request_tag(), "CommandExecRequest");
         assert!(iface.request.fields.iter().any(|f| f.name == "command"));
+        assert!(iface.request.fields.iter().any(|f| f.name == "session"));
     }
 }
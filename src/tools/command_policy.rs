@@ -0,0 +1,766 @@
+//! Command policy — argument-level rules for command-exec.
+//!
+//! A command line is tokenized the way `sh` would split it: quotes,
+//! escapes, `;` / `&&` / `||` / `|` / `&` separators, redirections, and
+//! `$(…)`, backtick and `<(…)` substitutions. Every simple command found —
+//! each pipeline stage, each list segment, and every command inside a
+//! substitution — must pass the policy on its own. Constructs the policy
+//! can't see through (subshells, groups, here-documents, arithmetic) are
+//! rejected outright.
+//!
+//! Rules are per program, e.g. from a profile's `commands:` block:
+//!
+//! ```yaml
+//! commands:
+//!   git:
+//!     allow: [status, diff, log, commit]
+//!     deny: ["push --force"]
+//!   ls:
+//! ```
+//!
+//! A pattern's first word must equal the command's first argument and each
+//! further word must appear among the remaining arguments, so
+//! `push --force` denies `git push origin main --force`. Deny wins; an empty
+//! allow list allows any arguments. A program with rules only takes literal
+//! arguments, since a variable or substitution could expand to a denied word.
+
+use std::collections::BTreeMap;
+
+use crate::organism::profile::{CommandPolicySpec, CommandRule};
+
+/// Substitutions nested deeper than this are rejected.
+const MAX_DEPTH: usize = 8;
+
+/// Variables a command may not set in its environment prefix.
+const PROTECTED_ENV: &[&str] = &[
+    "PATH",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "CDPATH",
+];
+
+/// One simple command: environment assignments, then program and arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct SimpleCommand {
+    pub assignments: Vec<String>,
+    pub words: Vec<String>,
+    /// Per word, where its first expansion or substitution ends (a byte
+    /// offset into the word), or `None` if the word is literal.
+    pub dynamic: Vec<Option<usize>>,
+    /// Literal targets of its `>`, `>>` and `&>` redirections, in order.
+    pub write_targets: Vec<String>,
+}
+
+/// A command line split into simple commands, plus the text of every
+/// substitution found in it (checked separately).
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedLine {
+    pub commands: Vec<SimpleCommand>,
+    pub substitutions: Vec<String>,
+}
+
+/// Split a command line the way `sh` would.
+pub fn parse(line: &str) -> Result<ParsedLine, String> {
+    Lexer::new(line).run()
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    parsed: ParsedLine,
+    current: SimpleCommand,
+    word: String,
+    /// Whether a word is being built (distinguishes `''` from no word).
+    in_word: bool,
+    word_dynamic: Option<usize>,
+    /// The next word is a redirection target, not an argument.
+    redirect_target: bool,
+    /// The pending redirection writes to its target.
+    redirect_writes: bool,
+}
+
+impl Lexer {
+    fn new(line: &str) -> Self {
+        Self {
+            chars: line.chars().collect(),
+            pos: 0,
+            parsed: ParsedLine::default(),
+            current: SimpleCommand::default(),
+            word: String::new(),
+            in_word: false,
+            word_dynamic: None,
+            redirect_target: false,
+            redirect_writes: false,
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn run(mut self) -> Result<ParsedLine, String> {
+        while let Some(c) = self.peek(0) {
+            match c {
+                ' ' | '\t' => {
+                    self.end_word()?;
+                    self.pos += 1;
+                }
+                '\n' | ';' | '|' => {
+                    self.end_command()?;
+                    self.pos += 1;
+                    // `||`, `;;`, `|&`
+                    if matches!(self.peek(0), Some('|' | ';' | '&')) && c != '\n' {
+                        self.pos += 1;
+                    }
+                }
+                '&' if self.peek(1) == Some('>') => {
+                    self.end_word()?;
+                    self.pos += 2;
+                    if self.peek(0) == Some('>') {
+                        self.pos += 1;
+                    }
+                    self.redirect_target = true;
+                    self.redirect_writes = true;
+                }
+                '&' => {
+                    self.end_command()?;
+                    self.pos += 1;
+                    if self.peek(0) == Some('&') {
+                        self.pos += 1;
+                    }
+                }
+                '>' | '<' if self.peek(1) == Some('(') => {
+                    self.pos += 2;
+                    let inner = self.take_balanced(')')?;
+                    self.parsed.substitutions.push(inner);
+                    self.mark_dynamic();
+                }
+                '>' | '<' => self.redirect(c)?,
+                '(' | ')' => return Err("subshells and ( ) grouping are not allowed".into()),
+                '#' if !self.in_word => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek(0) {
+                        // Line continuation
+                        Some('\n') => {}
+                        Some(next) => self.push(next),
+                        None => return Err("trailing backslash".into()),
+                    }
+                    self.pos += 1;
+                }
+                '\'' => {
+                    self.pos += 1;
+                    self.in_word = true;
+                    loop {
+                        match self.peek(0) {
+                            Some('\'') => break,
+                            Some(ch) => self.push(ch),
+                            None => return Err("unterminated single quote".into()),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '"' => {
+                    self.pos += 1;
+                    self.in_word = true;
+                    self.double_quoted()?;
+                }
+                '$' | '`' => self.expansion()?,
+                _ => {
+                    self.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.end_command()?;
+        Ok(self.parsed)
+    }
+
+    fn push(&mut self, c: char) {
+        self.word.push(c);
+        self.in_word = true;
+    }
+
+    fn mark_dynamic(&mut self) {
+        self.in_word = true;
+        if self.word_dynamic.is_none() {
+            self.word_dynamic = Some(self.word.len());
+        }
+    }
+
+    /// Inside `"…"`, up to and past the closing quote.
+    fn double_quoted(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek(0) {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek(0) {
+                        Some(c @ ('$' | '`' | '"' | '\\')) => self.push(c),
+                        Some('\n') => {}
+                        Some(c) => {
+                            self.push('\\');
+                            self.push(c);
+                        }
+                        None => return Err("unterminated double quote".into()),
+                    }
+                    self.pos += 1;
+                }
+                Some('$' | '`') => self.expansion()?,
+                Some(c) => {
+                    self.push(c);
+                    self.pos += 1;
+                }
+                None => return Err("unterminated double quote".into()),
+            }
+        }
+    }
+
+    /// `$name`, `${…}`, `$(…)` or `` `…` `` at the current position.
+    fn expansion(&mut self) -> Result<(), String> {
+        if self.peek(0) == Some('`') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.peek(0).is_some_and(|c| c != '`') {
+                if self.peek(0) == Some('\\') {
+                    self.pos += 1;
+                }
+                self.pos += 1;
+            }
+            if self.peek(0).is_none() {
+                return Err("unterminated backtick substitution".into());
+            }
+            let inner: String = self.chars[start..self.pos].iter().collect();
+            self.pos += 1;
+            self.parsed.substitutions.push(inner);
+            self.mark_dynamic();
+            return Ok(());
+        }
+
+        // At `$`
+        match self.peek(1) {
+            Some('(') if self.peek(2) == Some('(') => {
+                Err("arithmetic expansion $(( )) is not supported".into())
+            }
+            Some('(') => {
+                self.pos += 2;
+                let inner = self.take_balanced(')')?;
+                self.parsed.substitutions.push(inner);
+                self.mark_dynamic();
+                Ok(())
+            }
+            Some('{') => {
+                self.pos += 2;
+                let inner = self.take_balanced('}')?;
+                if inner.contains("$(") || inner.contains('`') {
+                    return Err("command substitution inside ${…} is not supported".into());
+                }
+                self.word.push_str(&format!("${{{inner}}}"));
+                self.mark_dynamic();
+                Ok(())
+            }
+            Some(c) if c.is_ascii_alphanumeric() || "_?#@*!$-".contains(c) => {
+                self.word.push('$');
+                self.pos += 1;
+                if c.is_ascii_alphabetic() || c == '_' {
+                    while self
+                        .peek(0)
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        self.word.push(self.chars[self.pos]);
+                        self.pos += 1;
+                    }
+                } else {
+                    self.word.push(c);
+                    self.pos += 1;
+                }
+                self.mark_dynamic();
+                Ok(())
+            }
+            // A lone `$` is literal
+            _ => {
+                self.push('$');
+                self.pos += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Text up to the `close` matching an already-consumed opener; leaves
+    /// the position after it. Quotes are respected.
+    fn take_balanced(&mut self, close: char) -> Result<String, String> {
+        let open = if close == ')' { '(' } else { '{' };
+        let start = self.pos;
+        let mut depth = 1;
+        let mut quote: Option<char> = None;
+        while let Some(c) = self.peek(0) {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') | (None, '\\') => self.pos += 1,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, c) if c == open => depth += 1,
+                (None, c) if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let inner = self.chars[start..self.pos].iter().collect();
+                        self.pos += 1;
+                        return Ok(inner);
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(format!("unterminated substitution (missing `{close}`)"))
+    }
+
+    /// `>`, `>>`, `<`, `>&`, `N>`… at the current position.
+    fn redirect(&mut self, c: char) -> Result<(), String> {
+        // A word of digits right before is the fd, not an argument
+        if self.in_word
+            && self.word_dynamic.is_none()
+            && self.word.chars().all(|d| d.is_ascii_digit())
+        {
+            self.word.clear();
+            self.in_word = false;
+        }
+        self.end_word()?;
+        self.pos += 1;
+        if c == '<' && self.peek(0) == Some('<') {
+            return Err("here-documents are not supported".into());
+        }
+        if self.peek(0) == Some(c) || (c == '>' && self.peek(0) == Some('|')) {
+            self.pos += 1;
+        }
+        if self.peek(0) == Some('&') {
+            // `>&2`, `2>&1`, `>&-`: duplicating an fd needs no target
+            self.pos += 1;
+            while self.peek(0).is_some_and(|d| d.is_ascii_digit() || d == '-') {
+                self.pos += 1;
+            }
+            return Ok(());
+        }
+        self.redirect_target = true;
+        self.redirect_writes = c == '>';
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), String> {
+        if !self.in_word {
+            return Ok(());
+        }
+        let word = std::mem::take(&mut self.word);
+        let dynamic = std::mem::take(&mut self.word_dynamic);
+        self.in_word = false;
+
+        if std::mem::take(&mut self.redirect_target) {
+            if std::mem::take(&mut self.redirect_writes) && dynamic.is_none() {
+                self.current.write_targets.push(word);
+            }
+            return Ok(());
+        }
+        let cmd = &mut self.current;
+        if cmd.words.is_empty() {
+            if is_assignment(&word) {
+                cmd.assignments.push(word);
+                return Ok(());
+            }
+            if word == "{" || word == "}" {
+                return Err("{ } grouping is not allowed".into());
+            }
+        }
+        cmd.words.push(word);
+        cmd.dynamic.push(dynamic);
+        Ok(())
+    }
+
+    fn end_command(&mut self) -> Result<(), String> {
+        self.end_word()?;
+        if self.redirect_target {
+            return Err("redirection without a target".into());
+        }
+        let cmd = std::mem::take(&mut self.current);
+        if !cmd.words.is_empty() || !cmd.assignments.is_empty() || !cmd.write_targets.is_empty() {
+            self.parsed.commands.push(cmd);
+        }
+        Ok(())
+    }
+}
+
+/// `NAME=value`
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Per-program argument rules.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    rules: BTreeMap<String, CommandRule>,
+}
+
+impl CommandPolicy {
+    /// Allow each listed program with any arguments.
+    pub fn from_allowlist(programs: &[String]) -> Self {
+        let rules = programs
+            .iter()
+            .map(|p| (normalize_program(p), CommandRule::default()))
+            .collect();
+        Self { rules }
+    }
+
+    /// A profile's `commands:` rules.
+    pub fn from_spec(spec: &CommandPolicySpec) -> Self {
+        let rules = spec
+            .rules
+            .iter()
+            .map(|(p, rule)| (normalize_program(p), rule.clone()))
+            .collect();
+        Self { rules }
+    }
+
+    /// Allowed program names, sorted.
+    pub fn programs(&self) -> Vec<&str> {
+        self.rules.keys().map(String::as_str).collect()
+    }
+
+    /// Check every command in `line`. `builtins` are extra programs allowed
+    /// with any arguments (the state-changing shell builtins, in sessions).
+    pub fn check(&self, line: &str, builtins: &[&str]) -> Result<(), String> {
+        self.check_depth(line, builtins, 0)
+    }
+
+    fn check_depth(&self, line: &str, builtins: &[&str], depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err("substitutions are nested too deeply".into());
+        }
+        let parsed = parse(line)?;
+        if depth == 0 && parsed.commands.is_empty() {
+            return Err("empty command".into());
+        }
+        for cmd in &parsed.commands {
+            self.check_command(cmd, builtins)?;
+        }
+        for inner in &parsed.substitutions {
+            self.check_depth(inner, builtins, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn check_command(&self, cmd: &SimpleCommand, builtins: &[&str]) -> Result<(), String> {
+        for assignment in &cmd.assignments {
+            let name = assignment.split('=').next().unwrap_or_default();
+            if PROTECTED_ENV.contains(&name) {
+                return Err(format!("command not allowed: setting {name}"));
+            }
+        }
+        let Some(program) = cmd.words.first() else {
+            // Bare assignments only set shell variables
+            return Ok(());
+        };
+        if cmd.dynamic[0].is_some() {
+            return Err(format!(
+                "command not allowed: {program} (the command name must be a literal word)"
+            ));
+        }
+        if builtins.contains(&program.as_str()) {
+            return check_builtin(program, &cmd.words[1..], &cmd.dynamic[1..]);
+        }
+        let name = normalize_program(program);
+        let Some(rule) = self.rules.get(&name) else {
+            return Err(format!(
+                "command not allowed: {program}. Allowed: {}",
+                self.programs().join(", ")
+            ));
+        };
+
+        let args = &cmd.words[1..];
+        let shown = || cmd.words.join(" ");
+        // Rules match literal words; an expansion could become anything
+        let ruled = !rule.allow.is_empty() || !rule.deny.is_empty();
+        if ruled && cmd.dynamic[1..].iter().any(Option::is_some) {
+            return Err(format!(
+                "command not allowed: {} (arguments to {name} must be literal words, \
+                 not variables or substitutions)",
+                shown()
+            ));
+        }
+        if let Some(deny) = rule.deny.iter().find(|p| pattern_matches(p, args)) {
+            return Err(format!(
+                "command not allowed: {} (denied: {name} {deny})",
+                shown()
+            ));
+        }
+        if !rule.allow.is_empty() && !rule.allow.iter().any(|p| pattern_matches(p, args)) {
+            return Err(format!(
+                "command not allowed: {} (allowed: {name} {})",
+                shown(),
+                rule.allow.join(" | ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Where a `cd` or `pushd` goes, or None for other commands. Only one
+/// literal directory is accepted: no target (`$HOME`), `-`, `~`, stack
+/// entries and expansions can't be checked before the shell runs them.
+pub fn cd_target(cmd: &SimpleCommand) -> Result<Option<&str>, String> {
+    if !matches!(cmd.words.first().map(String::as_str), Some("cd" | "pushd")) {
+        return Ok(None);
+    }
+    let mut args = cmd
+        .words
+        .iter()
+        .zip(&cmd.dynamic)
+        .skip(1)
+        .skip_while(|(w, d)| d.is_none() && matches!(w.as_str(), "-P" | "-L" | "-e" | "-@"))
+        .peekable();
+    args.next_if(|(w, d)| d.is_none() && w.as_str() == "--");
+    match (args.next(), args.next()) {
+        (Some((target, None)), None)
+            if !target.is_empty() && !target.starts_with(['-', '+', '~']) =>
+        {
+            Ok(Some(target.as_str()))
+        }
+        _ => Err(format!(
+            "command not allowed: {} (cd and pushd take one literal directory in the workspace)",
+            cmd.words.join(" ")
+        )),
+    }
+}
+
+/// `export` and `unset` may only name literal, unprotected variables: what
+/// they set sticks for every later command in the session.
+fn check_builtin(program: &str, args: &[String], dynamic: &[Option<usize>]) -> Result<(), String> {
+    if !matches!(program, "export" | "unset") {
+        return Ok(());
+    }
+    for (arg, dynamic) in args
+        .iter()
+        .zip(dynamic)
+        .filter(|(a, _)| !a.starts_with('-'))
+    {
+        let name = arg.split('=').next().unwrap_or_default();
+        // `export X=$HOME` is fine; an expansion in or before the name is not
+        let name_dynamic = dynamic.is_some_and(|end| end <= name.len());
+        if name_dynamic || !is_assignment(&format!("{name}=")) {
+            return Err(format!(
+                "command not allowed: {program} {arg} (the variable name must be a literal word)"
+            ));
+        }
+        if PROTECTED_ENV.contains(&name) {
+            return Err(format!("command not allowed: {program} {name}"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `args` match `pattern`: the first word is the first argument,
+/// the rest appear somewhere after it (`--flag` also matches `--flag=x`).
+fn pattern_matches(pattern: &str, args: &[String]) -> bool {
+    let mut words = pattern.split_whitespace();
+    let Some(first) = words.next() else {
+        return false;
+    };
+    if args.first().map(String::as_str) != Some(first) {
+        return false;
+    }
+    words.all(|w| {
+        args[1..]
+            .iter()
+            .any(|a| a == w || a.strip_prefix(w).is_some_and(|rest| rest.starts_with('=')))
+    })
+}
+
+/// The executable name a rule is keyed by: path stripped, and on Windows
+/// lowercased without `.exe`.
+fn normalize_program(program: &str) -> String {
+    let name = std::path::Path::new(program)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(program);
+    if cfg!(windows) {
+        let lower = name.to_ascii_lowercase();
+        lower.strip_suffix(".exe").unwrap_or(&lower).to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<Vec<String>> {
+        parse(line)
+            .unwrap()
+            .commands
+            .into_iter()
+            .map(|c| c.words)
+            .collect()
+    }
+
+    fn git_policy() -> CommandPolicy {
+        let mut spec = CommandPolicySpec::default();
+        spec.rules.insert(
+            "git".into(),
+            CommandRule {
+                allow: vec![
+                    "status".into(),
+                    "diff".into(),
+                    "log".into(),
+                    "commit".into(),
+                    "push".into(),
+                ],
+                deny: vec!["push --force".into(), "push -f".into()],
+            },
+        );
+        spec.rules.insert("echo".into(), CommandRule::default());
+        spec.rules.insert("grep".into(), CommandRule::default());
+        CommandPolicy::from_spec(&spec)
+    }
+
+    #[test]
+    fn tokenizes_like_sh() {
+        assert_eq!(
+            words(r#"git commit -m "fix: it's \"done\"" && echo 'a  b'|grep a; ls"#),
+            vec![
+                vec!["git", "commit", "-m", r#"fix: it's "done""#],
+                vec!["echo", "a  b"],
+                vec!["grep", "a"],
+                vec!["ls"],
+            ]
+        );
+        // Redirect targets and fds aren't arguments
+        assert_eq!(
+            words("cargo test 2>&1 > out.log"),
+            vec![vec!["cargo", "test"]]
+        );
+        assert_eq!(
+            words("RUST_LOG=debug cargo run # comment"),
+            vec![vec!["cargo", "run"]]
+        );
+
+        // Only literal write redirections name targets
+        let parsed = parse("a > out.txt 2>> err.log < in.txt &> all.log > \"$F\"; > b").unwrap();
+        assert_eq!(
+            parsed.commands[0].write_targets,
+            vec!["out.txt", "err.log", "all.log"]
+        );
+        assert_eq!(parsed.commands[1].write_targets, vec!["b"]);
+
+        let parsed = parse("echo $(whoami) `date` \"$(id -u)\"").unwrap();
+        assert_eq!(parsed.substitutions, vec!["whoami", "date", "id -u"]);
+
+        assert!(parse("(rm -rf /)").is_err());
+        assert!(parse("echo 'open").is_err());
+        assert!(parse("cat <<EOF").is_err());
+        assert!(parse("echo >").is_err());
+    }
+
+    #[test]
+    fn every_segment_and_substitution_is_checked() {
+        let policy = git_policy();
+        assert!(policy.check("git status && echo ok | grep ok", &[]).is_ok());
+
+        let err = policy.check("echo hi; rm -rf ~", &[]).unwrap_err();
+        assert!(err.starts_with("command not allowed: rm"), "{err}");
+        assert!(policy.check("git log $(curl evil)", &[]).is_err());
+        assert!(policy.check("echo `rm -rf ~`", &[]).is_err());
+        assert!(policy.check("echo \"$(rm x)\"", &[]).is_err());
+        assert!(policy.check("$CMD status", &[]).is_err());
+        assert!(policy.check("PATH=/tmp/evil git status", &[]).is_err());
+        assert!(policy.check("git status &", &[]).is_ok());
+        assert!(policy.check("", &[]).is_err());
+
+        assert!(policy.check("cd sub && git status", &[]).is_err());
+        assert!(policy.check("cd sub && git status", &["cd"]).is_ok());
+    }
+
+    #[test]
+    fn session_builtins_cannot_touch_protected_env() {
+        let policy = git_policy();
+        let builtins = &["cd", "export", "unset"];
+        assert!(policy.check("export RUST_LOG=debug", builtins).is_ok());
+        assert!(policy.check("export -n FOO; unset BAR", builtins).is_ok());
+
+        let err = policy
+            .check("export PATH=/tmp/evil:$PATH", builtins)
+            .unwrap_err();
+        assert!(err.contains("export PATH"), "{err}");
+        assert!(policy
+            .check("export LD_PRELOAD=/tmp/x.so", builtins)
+            .is_err());
+        assert!(policy
+            .check("export FOO=1 LD_LIBRARY_PATH=/tmp", builtins)
+            .is_err());
+        assert!(policy.check("unset PATH", builtins).is_err());
+        assert!(policy.check("export $NAME=x", builtins).is_err());
+        assert!(policy
+            .check("export P`echo ATH`=/tmp/evil", builtins)
+            .is_err());
+        assert!(policy.check("export GOPATH=$HOME/go", builtins).is_ok());
+        assert!(policy.check("source evil.sh", builtins).is_err());
+    }
+
+    #[test]
+    fn argument_rules() {
+        let policy = git_policy();
+        assert!(policy.check("git commit -m wip", &[]).is_ok());
+        assert!(policy.check("git push origin main", &[]).is_ok());
+
+        let err = policy
+            .check("git push origin main --force", &[])
+            .unwrap_err();
+        assert!(err.contains("denied: git push --force"), "{err}");
+        assert!(policy.check("git push -f", &[]).is_err());
+        assert!(policy.check("git push --force=true", &[]).is_err());
+
+        let err = policy.check("git rebase -i HEAD~3", &[]).unwrap_err();
+        assert!(err.contains("allowed: git status | diff"), "{err}");
+        // Global options ahead of the subcommand don't slip past
+        assert!(policy.check("git -c core.pager=sh log", &[]).is_err());
+    }
+
+    #[test]
+    fn expansions_cannot_dodge_argument_rules() {
+        let policy = git_policy();
+        let err = policy
+            .check("F=--force; git push origin main $F", &[])
+            .unwrap_err();
+        assert!(err.contains("must be literal words"), "{err}");
+        assert!(policy.check("git push origin \"${BRANCH}\"", &[]).is_err());
+        assert!(policy.check("git log `echo --all`", &[]).is_err());
+        assert!(policy.check("git commit -m \"$(echo wip)\"", &[]).is_err());
+        // Programs without rules take any arguments, expanded or not
+        assert!(policy.check("echo $HOME", &[]).is_ok());
+    }
+
+    #[test]
+    fn cd_targets_are_single_literal_directories() {
+        let target = |line: &str| cd_target(&parse(line).unwrap().commands[0]);
+        assert_eq!(target("cd src").unwrap(), Some("src"));
+        assert_eq!(target("pushd -P -- ../lib").unwrap(), Some("../lib"));
+        assert_eq!(target("ls src").unwrap(), None);
+        for line in ["cd", "cd -", "cd ~/x", "pushd +1", "cd $DIR", "cd a b"] {
+            assert!(target(line).is_err(), "{line}");
+        }
+    }
+}
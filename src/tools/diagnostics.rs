@@ -0,0 +1,1048 @@
+//! Diagnostics — compiler and linter findings as structured results.
+//!
+//! `check` runs a checker with JSON output and returns its diagnostics
+//! deduplicated and grouped by file, each numbered with its span and any
+//! suggested replacements:
+//!
+//! - `cargo`: `cargo check --message-format=json`, with rustc's rendering
+//! - `ruff`: `ruff check --output-format=json`, with ruff's fixes
+//! - `mypy`: `mypy -O json`
+//!
+//! `apply_suggestion` applies suggestions from the thread's last check by
+//! id, writing through the same path as file-edit. Only fixes the checker
+//! marks as safe to apply mechanically are accepted. It checkpoints the
+//! files it changes itself, under the issuing message, and puts back the
+//! files already written if a later one fails.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use serde_json::Value;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+
+use super::encoding::TextFile;
+use super::file_edit;
+use super::output::{self, OutputStore};
+use super::sandbox::Sandbox;
+use super::version;
+use super::workspace::{self, Access, Workspace};
+use super::{announce_file_changed, extract_tag, restore_originals, ToolPeer, ToolResponse};
+use crate::kernel::Kernel;
+use crate::pipeline::events::{KernelOpType, PipelineEvent};
+
+const TIMEOUT_SECS: u64 = 600;
+
+/// Diagnostics listed in full; the rest are only counted.
+const MAX_LISTED: usize = 50;
+
+/// Lines of each rendered diagnostic kept.
+const MAX_RENDERED_LINES: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Checker {
+    Cargo,
+    Ruff,
+    Mypy,
+}
+
+impl Checker {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "cargo" => Ok(Self::Cargo),
+            "ruff" => Ok(Self::Ruff),
+            "mypy" => Ok(Self::Mypy),
+            other => Err(format!(
+                "unknown checker: {other} (expected cargo, ruff or mypy)"
+            )),
+        }
+    }
+
+    /// Pick a checker from the project files in `dir`.
+    fn detect(dir: &Path) -> Result<Self, String> {
+        if dir.join("Cargo.toml").is_file() {
+            return Ok(Self::Cargo);
+        }
+        let python = ["pyproject.toml", "ruff.toml", ".ruff.toml", "setup.cfg"];
+        if python.iter().any(|f| dir.join(f).is_file()) {
+            return Ok(Self::Ruff);
+        }
+        Err(format!(
+            "no Cargo.toml or Python project in {}; name a checker",
+            dir.display()
+        ))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo check",
+            Self::Ruff => "ruff",
+            Self::Mypy => "mypy",
+        }
+    }
+}
+
+/// One finding, positioned by 1-based line and character column.
+#[derive(Debug, Clone, PartialEq)]
+struct Diagnostic {
+    file: PathBuf,
+    line: usize,
+    column: usize,
+    /// error, warning, note, ...
+    level: String,
+    code: Option<String>,
+    message: String,
+    /// The checker's own rendering, without its first line.
+    rendered: Option<String>,
+    suggestions: Vec<Suggestion>,
+}
+
+/// A suggested change: one or more replacements in the diagnostic's file.
+#[derive(Debug, Clone, PartialEq)]
+struct Suggestion {
+    label: String,
+    /// Safe to apply without review (rustc MachineApplicable, ruff safe).
+    applicable: bool,
+    applicability: String,
+    edits: Vec<SpanEdit>,
+}
+
+/// Replace the text between two 1-based (line, column) positions.
+#[derive(Debug, Clone, PartialEq)]
+struct SpanEdit {
+    start: (usize, usize),
+    end: (usize, usize),
+    replacement: String,
+}
+
+/// A numbered suggestion remembered for `apply_suggestion`.
+#[derive(Debug, Clone)]
+struct Stored {
+    id: String,
+    file: PathBuf,
+    /// The file's version when it was checked.
+    version: String,
+    suggestion: Suggestion,
+}
+
+/// Compiler and linter diagnostics, with suggested fixes to apply.
+#[derive(Default)]
+pub struct DiagnosticsTool {
+    workspace: Option<Arc<Workspace>>,
+    sandbox: Option<Arc<Sandbox>>,
+    output_store: Option<Arc<OutputStore>>,
+    suggestions: Arc<Mutex<HashMap<String, Vec<Stored>>>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+    /// Journal for the pre-images of applied files.
+    kernel: Option<Arc<tokio::sync::Mutex<Kernel>>>,
+    /// Started on the first check, to forget pruned threads' suggestions.
+    reaper: OnceLock<()>,
+}
+
+impl DiagnosticsTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run checkers inside `sandbox`, like command-exec.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(Arc::new(sandbox));
+        self
+    }
+
+    fn root(&self) -> PathBuf {
+        match &self.workspace {
+            Some(ws) => ws.root().to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    }
+
+    fn command(&self, program: &str, dir: &Path) -> Command {
+        let mut cmd = Command::new(program);
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(cmd.as_std_mut());
+        }
+        cmd.current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        cmd
+    }
+
+    async fn output(mut cmd: Command, program: &str) -> Result<std::process::Output, String> {
+        match tokio::time::timeout(Duration::from_secs(TIMEOUT_SECS), cmd.output()).await {
+            Ok(out) => out.map_err(|e| format!("failed to run {program}: {e}")),
+            Err(_) => Err(format!("{program} timed out after {TIMEOUT_SECS}s")),
+        }
+    }
+
+    async fn check(&self, xml: &str, thread_id: &str) -> Result<String, String> {
+        let text = |tag: &str| extract_tag(xml, tag).filter(|s| !s.trim().is_empty());
+        let path = match text("path") {
+            Some(p) => Some(workspace::resolve_in(
+                self.workspace.as_deref(),
+                "diagnostics",
+                p.trim(),
+                Access::Read,
+            )?),
+            None => None,
+        };
+        let root = self.root();
+        let dir = match &path {
+            Some(p) if p.is_dir() => p.clone(),
+            _ => root.clone(),
+        };
+        let checker = match text("checker") {
+            Some(name) => Checker::parse(name.trim())?,
+            None => Checker::detect(&dir)?,
+        };
+
+        let (diagnostics, base) = match checker {
+            Checker::Cargo => {
+                let mut cmd = self.command("cargo", &dir);
+                cmd.args(["check", "--all-targets", "--message-format=json"]);
+                if let Some(package) = text("package") {
+                    cmd.args(["-p", package.trim()]);
+                }
+                let out = Self::output(cmd, "cargo check").await?;
+                let stdout = String::from_utf8_lossy(&out.stdout);
+                let diagnostics = parse_cargo(&stdout);
+                if diagnostics.is_empty() && !out.status.success() {
+                    return Err(format!(
+                        "cargo check failed:\n{}",
+                        String::from_utf8_lossy(&out.stderr).trim_end()
+                    ));
+                }
+                // rustc's paths are relative to the cargo workspace root
+                (diagnostics, self.cargo_root(&dir).await)
+            }
+            Checker::Ruff => {
+                let mut cmd = self.command("ruff", &root);
+                cmd.args(["check", "--output-format=json", "--exit-zero"]);
+                cmd.args(path.iter());
+                let out = Self::output(cmd, "ruff").await?;
+                let stdout = String::from_utf8_lossy(&out.stdout);
+                let diagnostics = parse_ruff(&stdout).map_err(|e| {
+                    format!(
+                        "ruff: {e}\n{}",
+                        String::from_utf8_lossy(&out.stderr).trim_end()
+                    )
+                })?;
+                (diagnostics, root.clone())
+            }
+            Checker::Mypy => {
+                let mut cmd = self.command("mypy", &root);
+                cmd.args(["-O", "json"]);
+                match &path {
+                    Some(p) => cmd.arg(p),
+                    None => cmd.arg("."),
+                };
+                let out = Self::output(cmd, "mypy").await?;
+                let diagnostics = parse_mypy(&String::from_utf8_lossy(&out.stdout));
+                if diagnostics.is_empty() && out.status.code() == Some(2) {
+                    return Err(format!(
+                        "mypy failed:\n{}",
+                        String::from_utf8_lossy(&out.stderr).trim_end()
+                    ));
+                }
+                (diagnostics, root.clone())
+            }
+        };
+
+        let mut diagnostics = dedup(diagnostics);
+        for d in &mut diagnostics {
+            if d.file.is_relative() {
+                d.file = base.join(&d.file);
+            }
+        }
+        let stored = self.remember(thread_id, &diagnostics);
+        Ok(render(checker, &diagnostics, &stored, &root))
+    }
+
+    /// The cargo workspace root of the package in `dir`.
+    async fn cargo_root(&self, dir: &Path) -> PathBuf {
+        let mut cmd = self.command("cargo", dir);
+        cmd.args(["locate-project", "--workspace", "--message-format", "plain"]);
+        match Self::output(cmd, "cargo").await {
+            Ok(out) if out.status.success() => {
+                let manifest = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+                manifest
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| dir.to_path_buf())
+            }
+            _ => dir.to_path_buf(),
+        }
+    }
+
+    /// Number the suggestions and keep them as the thread's last check.
+    fn remember(&self, thread_id: &str, diagnostics: &[Diagnostic]) -> Vec<Stored> {
+        self.start_reaper();
+        let mut versions: HashMap<PathBuf, String> = HashMap::new();
+        let mut stored = Vec::new();
+        for (i, d) in diagnostics.iter().enumerate() {
+            for (k, suggestion) in d.suggestions.iter().enumerate() {
+                let version = versions
+                    .entry(d.file.clone())
+                    .or_insert_with(|| {
+                        std::fs::read(&d.file)
+                            .map(|data| version::content_version(&data))
+                            .unwrap_or_default()
+                    })
+                    .clone();
+                stored.push(Stored {
+                    id: format!("{}.{}", i + 1, k + 1),
+                    file: d.file.clone(),
+                    version,
+                    suggestion: suggestion.clone(),
+                });
+            }
+        }
+        self.suggestions
+            .lock()
+            .unwrap()
+            .insert(thread_id.to_string(), stored.clone());
+        stored
+    }
+
+    /// Apply suggestions from the thread's last check, file by file.
+    ///
+    /// Every file's pre-image is checkpointed before any is written.
+    async fn apply(&self, xml: &str, thread_id: &str) -> Result<String, String> {
+        let ids: Vec<String> = match extract_tag(xml, "ids").filter(|s| !s.trim().is_empty()) {
+            Some(json) => serde_json::from_str(&json).map_err(|e| format!("invalid <ids>: {e}"))?,
+            None => return Err("apply_suggestion requires ids".into()),
+        };
+        if ids.is_empty() {
+            return Err("apply_suggestion requires ids".into());
+        }
+
+        let all = match self.suggestions.lock().unwrap().get(thread_id) {
+            Some(all) => all.clone(),
+            None => return Err("no diagnostics recorded for this thread; run check first".into()),
+        };
+        let mut by_file: BTreeMap<PathBuf, Vec<Stored>> = BTreeMap::new();
+        for id in &ids {
+            let Some(stored) = all.iter().find(|s| &s.id == id) else {
+                return Err(format!("no suggestion {id} in the last check"));
+            };
+            if !stored.suggestion.applicable {
+                return Err(format!(
+                    "suggestion {id} is {} and may be wrong; make the change with file-edit",
+                    stored.suggestion.applicability
+                ));
+            }
+            by_file
+                .entry(stored.file.clone())
+                .or_default()
+                .push(stored.clone());
+        }
+
+        // Check every file before writing any
+        let mut planned = Vec::new();
+        for (file, chosen) in &by_file {
+            let display = file.display().to_string();
+            let path = workspace::resolve_in(
+                self.workspace.as_deref(),
+                "diagnostics",
+                &display,
+                Access::Write,
+            )?;
+            let raw = std::fs::read(&path).map_err(|e| format!("read error: {display}: {e}"))?;
+            if version::content_version(&raw) != chosen[0].version {
+                return Err(format!(
+                    "{display} changed since the check; run check again"
+                ));
+            }
+            let file = TextFile::decode(&raw).ok_or_else(|| format!("{display}: not a text file"))?;
+            let edits: Vec<&SpanEdit> = chosen
+                .iter()
+                .flat_map(|s| s.suggestion.edits.iter())
+                .collect();
+            let new_content =
+                apply_span_edits(&file.text, &edits).map_err(|e| format!("{display}: {e}"))?;
+            planned.push((display, path, raw, file, new_content));
+        }
+
+        let mut kernel = match &self.kernel {
+            Some(kernel) => Some(kernel.lock().await),
+            None => None,
+        };
+        let mut checkpoints = Vec::new();
+        if let Some(kernel) = kernel.as_mut() {
+            let message_id = extract_tag(xml, "message_id").unwrap_or_default();
+            for (display, path, _, _, _) in &planned {
+                match kernel.record_checkpoint(thread_id, &message_id, path) {
+                    Ok(seq) => checkpoints.push(seq),
+                    Err(e) => {
+                        discard_checkpoints(kernel, &checkpoints);
+                        return Err(format!("journal error: {display}: {e}; nothing written"));
+                    }
+                }
+            }
+        }
+
+        let mut written = Vec::new();
+        for (i, (display, path, _, file, new_content)) in planned.iter().enumerate() {
+            match file_edit::write_edit(path, file, new_content, &[], false) {
+                Ok(diff) => written.push(diff),
+                Err(e) => {
+                    // The failed write may have truncated its file too
+                    let restored = restore_originals(
+                        planned[..=i]
+                            .iter()
+                            .map(|(d, p, raw, _, _)| (d.as_str(), p.as_path(), raw.as_slice())),
+                    );
+                    if let Some(kernel) = kernel.as_mut() {
+                        discard_checkpoints(kernel, &checkpoints);
+                    }
+                    return Err(format!("{display}: {e}; {restored}"));
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for ((_, path, _, _, _), written) in planned.iter().zip(written) {
+            out.push_str(&format!("{}\n", path.display()));
+            announce_file_changed(self.events.as_ref(), thread_id, "diagnostics", path);
+            // Keep the thread's view of the file current, as file-edit does
+            if let (Some(kernel), Some(seen)) = (kernel.as_mut(), version::parse(&written)) {
+                let _ = kernel.record_file_version(thread_id, path, seen);
+            }
+            out.push_str(&written);
+        }
+        // Positions in the edited files have moved; their suggestions are stale
+        if let Some(all) = self.suggestions.lock().unwrap().get_mut(thread_id) {
+            all.retain(|s| !by_file.contains_key(&s.file));
+        }
+        Ok(out)
+    }
+
+    /// Forget a thread's suggestions when the kernel prunes it.
+    fn start_reaper(&self) {
+        let Some(tx) = &self.events else {
+            return;
+        };
+        self.reaper.get_or_init(|| {
+            let mut rx = tx.subscribe();
+            let suggestions: Weak<Mutex<HashMap<String, Vec<Stored>>>> =
+                Arc::downgrade(&self.suggestions);
+            tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(PipelineEvent::KernelOp {
+                            op: KernelOpType::ThreadPruned,
+                            thread_id,
+                        }) => match suggestions.upgrade() {
+                            Some(s) => {
+                                s.lock().unwrap().remove(&thread_id);
+                            }
+                            None => break,
+                        },
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Drop the checkpoints of an apply that was rolled back.
+fn discard_checkpoints(kernel: &mut Kernel, seqs: &[u64]) {
+    if let Err(e) = kernel.discard_checkpoints(seqs) {
+        tracing::warn!("dropping diagnostics checkpoints failed: {e}");
+    }
+}
+
+/// Diagnostics from `cargo check --message-format=json`.
+fn parse_cargo(out: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for line in out.lines() {
+        let Ok(msg) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if msg["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &msg["message"];
+        let spans = message["spans"].as_array().cloned().unwrap_or_default();
+        let Some(primary) = spans.iter().find(|s| s["is_primary"] == true) else {
+            // "aborting due to ..." and "N warnings emitted" summaries
+            continue;
+        };
+
+        let mut suggestions = Vec::new();
+        let children = message["children"].as_array().cloned().unwrap_or_default();
+        for child in std::iter::once(message).chain(children.iter()) {
+            let edits: Vec<&Value> = child["spans"]
+                .as_array()
+                .map(|spans| {
+                    spans
+                        .iter()
+                        .filter(|s| s["suggested_replacement"].is_string())
+                        .filter(|s| s["file_name"] == primary["file_name"])
+                        .collect()
+                })
+                .unwrap_or_default();
+            let Some(first) = edits.first() else {
+                continue;
+            };
+            let applicability = first["suggestion_applicability"]
+                .as_str()
+                .unwrap_or("Unspecified")
+                .to_string();
+            suggestions.push(Suggestion {
+                label: child["message"].as_str().unwrap_or_default().to_string(),
+                applicable: applicability == "MachineApplicable",
+                applicability: applicability_name(&applicability),
+                edits: edits.iter().map(|s| rustc_edit(s)).collect(),
+            });
+        }
+
+        let rendered = message["rendered"]
+            .as_str()
+            .and_then(|r| r.split_once('\n'))
+            .map(|(_, rest)| rest.trim_end().to_string())
+            .filter(|r| !r.is_empty());
+        diagnostics.push(Diagnostic {
+            file: PathBuf::from(primary["file_name"].as_str().unwrap_or_default()),
+            line: primary["line_start"].as_u64().unwrap_or(1) as usize,
+            column: primary["column_start"].as_u64().unwrap_or(1) as usize,
+            level: message["level"].as_str().unwrap_or("error").to_string(),
+            code: message["code"]["code"].as_str().map(str::to_string),
+            message: message["message"].as_str().unwrap_or_default().to_string(),
+            rendered,
+            suggestions,
+        });
+    }
+    diagnostics
+}
+
+fn rustc_edit(span: &Value) -> SpanEdit {
+    let pos = |line: &str, column: &str| {
+        (
+            span[line].as_u64().unwrap_or(1) as usize,
+            span[column].as_u64().unwrap_or(1) as usize,
+        )
+    };
+    SpanEdit {
+        start: pos("line_start", "column_start"),
+        end: pos("line_end", "column_end"),
+        replacement: span["suggested_replacement"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// rustc's `MaybeIncorrect` as `maybe-incorrect`.
+fn applicability_name(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('-');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+/// Diagnostics from `ruff check --output-format=json`.
+fn parse_ruff(out: &str) -> Result<Vec<Diagnostic>, String> {
+    let findings: Vec<Value> =
+        serde_json::from_str(out.trim()).map_err(|e| format!("unreadable output: {e}"))?;
+    let pos = |loc: &Value| {
+        (
+            loc["row"].as_u64().unwrap_or(1) as usize,
+            loc["column"].as_u64().unwrap_or(1) as usize,
+        )
+    };
+    Ok(findings
+        .iter()
+        .map(|f| {
+            let (line, column) = pos(&f["location"]);
+            let fix = &f["fix"];
+            let suggestions = match fix["edits"].as_array() {
+                Some(edits) if !edits.is_empty() => {
+                    let applicability = fix["applicability"].as_str().unwrap_or("unsafe");
+                    vec![Suggestion {
+                        label: fix["message"].as_str().unwrap_or("fix").to_string(),
+                        applicable: applicability == "safe",
+                        applicability: applicability.to_string(),
+                        edits: edits
+                            .iter()
+                            .map(|e| SpanEdit {
+                                start: pos(&e["location"]),
+                                end: pos(&e["end_location"]),
+                                replacement: e["content"].as_str().unwrap_or_default().into(),
+                            })
+                            .collect(),
+                    }]
+                }
+                _ => Vec::new(),
+            };
+            Diagnostic {
+                file: PathBuf::from(f["filename"].as_str().unwrap_or_default()),
+                line,
+                column,
+                level: "error".into(),
+                code: f["code"].as_str().map(str::to_string),
+                message: f["message"].as_str().unwrap_or_default().to_string(),
+                rendered: None,
+                suggestions,
+            }
+        })
+        .collect())
+}
+
+/// Diagnostics from `mypy -O json`, one object per line. mypy's columns
+/// are 0-based.
+fn parse_mypy(out: &str) -> Vec<Diagnostic> {
+    out.lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .map(|d| Diagnostic {
+            file: PathBuf::from(d["file"].as_str().unwrap_or_default()),
+            line: d["line"].as_u64().unwrap_or(1) as usize,
+            column: d["column"].as_u64().unwrap_or(0) as usize + 1,
+            level: d["severity"].as_str().unwrap_or("error").to_string(),
+            code: d["code"].as_str().map(str::to_string),
+            message: d["message"].as_str().unwrap_or_default().to_string(),
+            rendered: d["hint"].as_str().map(str::to_string),
+            suggestions: Vec::new(),
+        })
+        .collect()
+}
+
+/// Drop repeats — cargo reports a library's warnings once per target.
+fn dedup(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut seen = HashSet::new();
+    diagnostics
+        .into_iter()
+        .filter(|d| {
+            seen.insert((
+                d.file.clone(),
+                d.line,
+                d.column,
+                d.level.clone(),
+                d.message.clone(),
+            ))
+        })
+        .collect()
+}
+
+/// Byte offset of a 1-based (line, character column) position.
+fn offset_of(content: &str, (line, column): (usize, usize)) -> Option<usize> {
+    let mut start = 0;
+    for _ in 1..line {
+        start += content[start..].find('\n')? + 1;
+    }
+    let rest = &content[start..];
+    let line_len = rest.find('\n').unwrap_or(rest.len());
+    if column <= 1 {
+        return Some(start);
+    }
+    match rest[..line_len].char_indices().nth(column - 1) {
+        Some((i, _)) => Some(start + i),
+        // one past the last character
+        None if rest[..line_len].chars().count() == column - 1 => Some(start + line_len),
+        None => None,
+    }
+}
+
+/// Apply span replacements, which must not overlap.
+fn apply_span_edits(content: &str, edits: &[&SpanEdit]) -> Result<String, String> {
+    let mut ranges = Vec::new();
+    for edit in edits {
+        let start = offset_of(content, edit.start);
+        let end = offset_of(content, edit.end);
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => {
+                ranges.push((start, end, edit.replacement.as_str()))
+            }
+            _ => {
+                return Err(format!(
+                    "span {}:{}-{}:{} is not in the file",
+                    edit.start.0, edit.start.1, edit.end.0, edit.end.1
+                ))
+            }
+        }
+    }
+    ranges.sort_by_key(|&(start, end, _)| (start, end));
+    if ranges.windows(2).any(|w| w[1].0 < w[0].1) {
+        return Err("the chosen suggestions overlap; apply them one at a time".into());
+    }
+    let mut out = content.to_string();
+    for (start, end, replacement) in ranges.into_iter().rev() {
+        out.replace_range(start..end, replacement);
+    }
+    Ok(out)
+}
+
+/// Counts, then diagnostics grouped by file with their suggestions.
+fn render(checker: Checker, diagnostics: &[Diagnostic], stored: &[Stored], root: &Path) -> String {
+    let count = |level: &str| diagnostics.iter().filter(|d| d.level == level).count();
+    let mut by_file: BTreeMap<&Path, Vec<(usize, &Diagnostic)>> = BTreeMap::new();
+    for (i, d) in diagnostics.iter().enumerate() {
+        by_file.entry(&d.file).or_default().push((i + 1, d));
+    }
+    let mut out = format!(
+        "{}: {} errors, {} warnings in {} files",
+        checker.name(),
+        count("error"),
+        count("warning"),
+        by_file.len()
+    );
+    if diagnostics.is_empty() {
+        return out;
+    }
+
+    let root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut listed = 0;
+    for (file, entries) in &by_file {
+        if listed >= MAX_LISTED {
+            break;
+        }
+        let shown = file.strip_prefix(&root).unwrap_or(file);
+        out.push_str(&format!("\n\n{}", shown.display()));
+        for (n, d) in entries {
+            if listed >= MAX_LISTED {
+                break;
+            }
+            listed += 1;
+            let code = d
+                .code
+                .as_ref()
+                .map(|c| format!("[{c}]"))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "\n  [{n}] {}{code} {}:{}: {}",
+                d.level, d.line, d.column, d.message
+            ));
+            if let Some(rendered) = &d.rendered {
+                for line in rendered.lines().take(MAX_RENDERED_LINES) {
+                    out.push_str("\n      ");
+                    out.push_str(line);
+                }
+            }
+            let prefix = format!("{n}.");
+            for s in stored.iter().filter(|s| s.id.starts_with(&prefix)) {
+                let edit = &s.suggestion.edits[0];
+                let more = match s.suggestion.edits.len() {
+                    1 => String::new(),
+                    k => format!(" (+{} more spans)", k - 1),
+                };
+                out.push_str(&format!(
+                    "\n    fix {} ({}): {} — {}:{}-{}:{} -> {:?}{more}",
+                    s.id,
+                    s.suggestion.applicability,
+                    s.suggestion.label,
+                    edit.start.0,
+                    edit.start.1,
+                    edit.end.0,
+                    edit.end.1,
+                    edit.replacement
+                ));
+            }
+        }
+    }
+    if diagnostics.len() > listed {
+        out.push_str(&format!("\n\n... and {} more", diagnostics.len() - listed));
+    }
+    if stored.iter().any(|s| s.suggestion.applicable) {
+        out.push_str("\n\napply fixes with action apply_suggestion and their ids");
+    }
+    out
+}
+
+#[async_trait]
+impl Handler for DiagnosticsTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+        let action = extract_tag(&xml_str, "action").unwrap_or_default();
+
+        let result = match action.trim() {
+            "" | "check" => self.check(&xml_str, &ctx.thread_id).await,
+            "apply_suggestion" => self.apply(&xml_str, &ctx.thread_id).await,
+            other => Err(format!(
+                "unknown action: {other} (expected check or apply_suggestion)"
+            )),
+        };
+
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, e).await;
+                ToolResponse::err(&text)
+            }
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for DiagnosticsTool {
+    fn name(&self) -> &str {
+        "diagnostics"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn set_kernel(&mut self, kernel: Arc<tokio::sync::Mutex<Kernel>>) {
+        self.kernel = Some(kernel);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Compiler and linter diagnostics. check runs cargo check, ruff or mypy (picked from the project unless checker is given) and returns numbered diagnostics grouped by file, with spans and suggested fixes. apply_suggestion applies fixes from the last check by id (e.g. 3.1); only fixes marked machine-applicable or safe are accepted.
+interface diagnostics {
+    record request {
+        /// check (default) or apply_suggestion
+        action: option<string>,
+        /// cargo, ruff or mypy (default: detected from the project)
+        checker: option<string>,
+        /// Package directory (cargo) or file or directory to check (ruff, mypy)
+        path: option<string>,
+        /// cargo: the package to check
+        package: option<string>,
+        /// apply_suggestion: fix ids from the last check
+        ids: option<list<string>>,
+        /// Transcript message the fixes are checkpointed under; filled in by the agent loop
+        message-id: option<string>,
+    }
+    run: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "diagnostics".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "DiagnosticsRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[test]
+    fn ruff_findings_and_fixes() {
+        let out = r#"[{"cell":null,"code":"F401","end_location":{"column":10,"row":1},"filename":"/p/app.py","fix":{"applicability":"safe","edits":[{"content":"","end_location":{"column":1,"row":2},"location":{"column":1,"row":1}}],"message":"Remove unused import: `os`"},"location":{"column":8,"row":1},"message":"`os` imported but unused","noqa_row":1,"url":"https://docs.astral.sh/ruff/rules/unused-import"},
+{"cell":null,"code":"E741","end_location":{"column":2,"row":3},"filename":"/p/app.py","fix":null,"location":{"column":1,"row":3},"message":"Ambiguous variable name: `l`","noqa_row":3,"url":null}]"#;
+        let diagnostics = parse_ruff(out).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 8));
+        assert_eq!(diagnostics[0].code.as_deref(), Some("F401"));
+        let fix = &diagnostics[0].suggestions[0];
+        assert!(fix.applicable);
+        assert_eq!(fix.edits[0].end, (2, 1));
+        assert!(diagnostics[1].suggestions.is_empty());
+
+        let content = "import os\nprint(1)\nl = 2\n";
+        let edited = apply_span_edits(content, &[&fix.edits[0]]).unwrap();
+        assert_eq!(edited, "print(1)\nl = 2\n");
+    }
+
+    #[test]
+    fn span_edits_use_character_columns() {
+        let content = "let é = fóo;\n";
+        let edit = SpanEdit {
+            start: (1, 9),
+            end: (1, 12),
+            replacement: "bar".into(),
+        };
+        assert_eq!(
+            apply_span_edits(content, &[&edit]).unwrap(),
+            "let é = bar;\n"
+        );
+        let past = SpanEdit {
+            start: (1, 20),
+            end: (1, 21),
+            replacement: String::new(),
+        };
+        assert!(apply_span_edits(content, &[&past]).is_err());
+        let overlap = SpanEdit {
+            start: (1, 10),
+            end: (1, 11),
+            replacement: String::new(),
+        };
+        assert!(apply_span_edits(content, &[&edit, &overlap])
+            .unwrap_err()
+            .contains("overlap"));
+    }
+
+    #[tokio::test]
+    async fn cargo_check_and_apply_suggestion() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "pub fn demo() -> u32 {\n    let mut x = 1;\n    x\n}\n",
+        )
+        .unwrap();
+        let kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(tokio::sync::Mutex::new(kernel));
+        let mut tool = DiagnosticsTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+        tool.set_kernel(kernel.clone());
+
+        let xml = "<DiagnosticsRequest></DiagnosticsRequest>";
+        let (ok, report) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{report}");
+        // reported once, though it's checked for both lib and test targets
+        assert!(
+            report.starts_with("cargo check: 0 errors, 1 warnings in 1 files"),
+            "{report}"
+        );
+        assert!(
+            report.contains(
+                "\n\nsrc/lib.rs\n  [1] warning[unused_mut] 2:9: variable does not need to be mutable"
+            ),
+            "{report}"
+        );
+        assert!(report.contains("fix 1.1 (machine-applicable)"), "{report}");
+
+        let xml = r#"<DiagnosticsRequest><action>apply_suggestion</action><ids>["1.1"]</ids><message_id>msg-0005</message_id></DiagnosticsRequest>"#;
+        let (ok, diff) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{diff}");
+        assert!(
+            diff.contains("-    let mut x = 1;\n+    let x = 1;"),
+            "{diff}"
+        );
+        assert!(diff.contains("[version: "));
+        let lib = dir.path().join("src/lib.rs");
+        assert_eq!(
+            std::fs::read_to_string(&lib).unwrap(),
+            "pub fn demo() -> u32 {\n    let x = 1;\n    x\n}\n"
+        );
+
+        // The pre-image is journalled under the issuing message, and the
+        // thread's view of the file follows the fix
+        {
+            let k = kernel.lock().await;
+            let cp = k.checkpoints().last_active().unwrap();
+            assert_eq!(
+                (cp.thread_id.as_str(), cp.message_id.as_str()),
+                ("t1", "msg-0005")
+            );
+            assert_eq!(
+                k.file_version("t1", Path::new(&cp.path)),
+                version::parse(&diff)
+            );
+        }
+
+        // applied suggestions can't be applied twice
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(err.contains("no suggestion 1.1"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn failed_apply_restores_files_and_drops_checkpoints() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "alpha\n").unwrap();
+        // Latin-1: the second fix can't be encoded, after a.txt is written
+        std::fs::write(dir.path().join("b.txt"), b"caf\xe9\n").unwrap();
+        let kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(tokio::sync::Mutex::new(kernel));
+        let mut tool = DiagnosticsTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+        tool.set_kernel(kernel.clone());
+
+        let stored = |id: &str, file: &str, raw: &[u8], replacement: &str| Stored {
+            id: id.into(),
+            file: file.into(),
+            version: version::content_version(raw),
+            suggestion: Suggestion {
+                label: "fix".into(),
+                applicable: true,
+                applicability: "machine-applicable".into(),
+                edits: vec![SpanEdit {
+                    start: (1, 1),
+                    end: (1, 2),
+                    replacement: replacement.into(),
+                }],
+            },
+        };
+        tool.suggestions.lock().unwrap().insert(
+            "t1".into(),
+            vec![
+                stored("1.1", "a.txt", b"alpha\n", "A"),
+                stored("2.1", "b.txt", b"caf\xe9\n", "\u{65e5}"),
+            ],
+        );
+
+        let xml = r#"<DiagnosticsRequest><action>apply_suggestion</action><ids>["1.1", "2.1"]</ids><message_id>msg-0003</message_id></DiagnosticsRequest>"#;
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(err.starts_with("b.txt: "), "{err}");
+        assert!(
+            err.ends_with("the files already written were restored"),
+            "{err}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "alpha\n"
+        );
+        assert!(kernel.lock().await.checkpoints().last_active().is_none());
+    }
+
+    #[test]
+    fn diagnostics_metadata() {
+        let tool = DiagnosticsTool::new();
+        assert_eq!(tool.name(), "diagnostics");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "DiagnosticsRequest");
+    }
+}
@@ -0,0 +1,241 @@
+//! Text encodings — how the file tools decode files and write them back.
+//!
+//! A file is decoded once into a [`TextFile`]: its text with `\n` line
+//! endings, plus what it takes to write it back the same way — encoding
+//! (UTF-8, UTF-16 with a BOM, or Latin-1 when the bytes aren't valid
+//! UTF-8), whether it had a BOM, and its dominant line ending. Edits work on
+//! the normalized text and [`TextFile::encode`] restores the rest, refusing
+//! characters the encoding can't hold unless the caller accepts the loss.
+//!
+//! A file that mixes line endings is written back with the dominant one.
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: every byte is a character, so any file round-trips.
+    Latin1,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Latin1 => "latin-1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "lf",
+            Self::Crlf => "crlf",
+        }
+    }
+}
+
+/// A decoded text file and how to write it back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFile {
+    /// The content, with `\n` line endings.
+    pub text: String,
+    pub encoding: Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    /// Lines that end the other way.
+    pub mixed: usize,
+}
+
+impl TextFile {
+    /// Decode a file's bytes; None if they look binary.
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        let (encoding, bom, body) = if let Some(body) = raw.strip_prefix(UTF8_BOM) {
+            (Encoding::Utf8, true, body)
+        } else if let Some(body) = raw.strip_prefix(UTF16LE_BOM) {
+            (Encoding::Utf16Le, true, body)
+        } else if let Some(body) = raw.strip_prefix(UTF16BE_BOM) {
+            (Encoding::Utf16Be, true, body)
+        } else {
+            (Encoding::Utf8, false, raw)
+        };
+
+        let (encoding, bom, decoded) = match encoding {
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if body.len() % 2 != 0 {
+                    return None;
+                }
+                let units = body.chunks_exact(2).map(|pair| match encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+                let text: String = char::decode_utf16(units).collect::<Result<_, _>>().ok()?;
+                (encoding, bom, text)
+            }
+            _ => {
+                // A NUL byte early on means binary, as for grep
+                if body[..body.len().min(8192)].contains(&0) {
+                    return None;
+                }
+                match std::str::from_utf8(body) {
+                    Ok(text) => (Encoding::Utf8, bom, text.to_string()),
+                    Err(_) => {
+                        let text = raw.iter().map(|&b| char::from(b)).collect();
+                        (Encoding::Latin1, false, text)
+                    }
+                }
+            }
+        };
+
+        let crlf = decoded.matches("\r\n").count();
+        let lf = decoded.matches('\n').count() - crlf;
+        let (line_ending, mixed) = if crlf > lf {
+            (LineEnding::Crlf, lf)
+        } else {
+            (LineEnding::Lf, crlf)
+        };
+        let text = if crlf > 0 {
+            decoded.replace("\r\n", "\n")
+        } else {
+            decoded
+        };
+        Some(Self {
+            text,
+            encoding,
+            bom,
+            line_ending,
+            mixed,
+        })
+    }
+
+    /// Encode `text` the way this file was: same encoding, BOM and (dominant)
+    /// line ending. Returns the bytes and how many characters were replaced
+    /// with `?`, which only happens with `allow_lossy`.
+    pub fn encode(&self, text: &str, allow_lossy: bool) -> Result<(Vec<u8>, usize), String> {
+        let text = text.replace("\r\n", "\n");
+        let text = match self.line_ending {
+            LineEnding::Lf => text,
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        };
+        let mut out = Vec::with_capacity(text.len() + 3);
+        let mut replaced = 0;
+        match self.encoding {
+            Encoding::Utf8 => {
+                if self.bom {
+                    out.extend_from_slice(UTF8_BOM);
+                }
+                out.extend_from_slice(text.as_bytes());
+            }
+            Encoding::Utf16Le => {
+                out.extend_from_slice(UTF16LE_BOM);
+                out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            }
+            Encoding::Utf16Be => {
+                out.extend_from_slice(UTF16BE_BOM);
+                out.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            }
+            Encoding::Latin1 => {
+                for (i, c) in text.char_indices() {
+                    match u8::try_from(u32::from(c)) {
+                        Ok(byte) => out.push(byte),
+                        Err(_) if allow_lossy => {
+                            out.push(b'?');
+                            replaced += 1;
+                        }
+                        Err(_) => {
+                            let line = text[..i].matches('\n').count() + 1;
+                            return Err(format!(
+                                "{c:?} on line {line} cannot be written as {}; \
+                                 pass allow_lossy to write '?' in its place",
+                                self.encoding.name()
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok((out, replaced))
+    }
+
+    /// `[encoding: utf-8 line-endings: lf]`, for tool output.
+    pub fn describe(&self) -> String {
+        let bom = if self.bom { "+bom" } else { "" };
+        let mixed = match (self.mixed, self.line_ending) {
+            (0, _) => String::new(),
+            (n, LineEnding::Lf) => format!(" (mixed: {n} crlf)"),
+            (n, LineEnding::Crlf) => format!(" (mixed: {n} lf)"),
+        };
+        format!(
+            "[encoding: {}{bom} line-endings: {}{mixed}]",
+            self.encoding.name(),
+            self.line_ending.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_bom_crlf_and_utf16() {
+        let raw = b"\xEF\xBB\xBFa\r\nb\r\n";
+        let file = TextFile::decode(raw).unwrap();
+        assert_eq!(file.text, "a\nb\n");
+        assert_eq!(file.describe(), "[encoding: utf-8+bom line-endings: crlf]");
+        let (bytes, _) = file.encode("a\nb\nc\n", false).unwrap();
+        assert_eq!(bytes, b"\xEF\xBB\xBFa\r\nb\r\nc\r\n");
+
+        let raw: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("hé\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let file = TextFile::decode(&raw).unwrap();
+        assert_eq!(
+            (file.encoding, file.text.as_str()),
+            (Encoding::Utf16Le, "hé\n")
+        );
+        assert_eq!(file.encode("hé\n", false).unwrap().0, raw);
+    }
+
+    #[test]
+    fn latin1_refuses_lossy_writes_unless_allowed() {
+        let file = TextFile::decode(b"caf\xE9\n").unwrap();
+        assert_eq!(file.encoding, Encoding::Latin1);
+        assert_eq!(file.text, "café\n");
+        assert_eq!(file.encode("café!\n", false).unwrap().0, b"caf\xE9!\n");
+
+        let err = file.encode("café\n5 €\n", false).unwrap_err();
+        assert!(
+            err.contains("'€' on line 2 cannot be written as latin-1"),
+            "{err}"
+        );
+        let (bytes, replaced) = file.encode("5 €\n", true).unwrap();
+        assert_eq!((bytes.as_slice(), replaced), (&b"5 ?\n"[..], 1));
+    }
+
+    #[test]
+    fn dominant_line_ending_and_binary() {
+        let file = TextFile::decode(b"a\nb\nc\r\n").unwrap();
+        assert_eq!(file.line_ending, LineEnding::Lf);
+        assert_eq!(
+            file.describe(),
+            "[encoding: utf-8 line-endings: lf (mixed: 1 crlf)]"
+        );
+        assert_eq!(file.encode(&file.text, false).unwrap().0, b"a\nb\nc\n");
+        assert!(TextFile::decode(b"\x7fELF\0\0").is_none());
+    }
+}
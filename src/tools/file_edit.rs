@@ -1,18 +1,49 @@
 //! FileEditTool — surgical old→new text replacement with unified diff output.
+//!
+//! Three modes, exactly one per call:
+//! - `old_string`/`new_string` — one replacement (unique unless `replace_all`)
+//! - `edits` — an ordered batch of replacements, applied all-or-nothing
+//! - `patch` — a unified diff, applied with fuzzy hunk matching
+//!
+//! Edits see the file's text with `\n` line endings, and the result is
+//! written back in the file's own encoding, BOM and line ending (see
+//! [`super::encoding`]).
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
+use serde::Deserialize;
 use similar::{ChangeTag, TextDiff};
 use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 
-use super::{extract_tag, ToolPeer, ToolResponse};
+use super::encoding::TextFile;
+use super::patch;
+use super::version;
+use super::workspace::{self, Access, Workspace};
+use super::{announce_file_changed, extract_tag, ToolPeer, ToolResponse};
+use crate::pipeline::events::PipelineEvent;
 
 /// Surgical text replacement in files. Returns unified diff.
-pub struct FileEditTool;
+#[derive(Default)]
+pub struct FileEditTool {
+    workspace: Option<Arc<Workspace>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+}
+
+/// One old→new replacement (a batch entry, or the single-edit fields).
+#[derive(Debug, Deserialize)]
+struct Edit {
+    old_string: String,
+    #[serde(default)]
+    new_string: String,
+    #[serde(default)]
+    replace_all: bool,
+}
 
 #[async_trait]
 impl Handler for FileEditTool {
-    async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
         let xml_str = String::from_utf8_lossy(&payload.xml);
 
         let path = extract_tag(&xml_str, "path").unwrap_or_default();
@@ -22,83 +53,211 @@ impl Handler for FileEditTool {
             });
         }
 
-        let old_string = extract_tag(&xml_str, "old_string").unwrap_or_default();
-        if old_string.is_empty() {
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err("missing required <old_string>"),
-            });
-        }
+        let old_string = extract_tag(&xml_str, "old_string").filter(|s| !s.is_empty());
+        let edits = extract_tag(&xml_str, "edits").filter(|s| !s.trim().is_empty());
+        let patch = extract_tag(&xml_str, "patch").filter(|s| !s.trim().is_empty());
 
-        let new_string = extract_tag(&xml_str, "new_string").unwrap_or_default();
+        let modes = [old_string.is_some(), edits.is_some(), patch.is_some()];
+        match modes.iter().filter(|m| **m).count() {
+            0 => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(
+                        "missing required <old_string>, <edits> or <patch>",
+                    ),
+                });
+            }
+            1 => {}
+            _ => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(
+                        "give exactly one of <old_string>, <edits> or <patch>",
+                    ),
+                });
+            }
+        }
 
-        let file_path = Path::new(&path);
+        let file_path =
+            match workspace::resolve_in(self.workspace.as_deref(), "file-edit", &path, Access::Write) {
+                Ok(p) => p,
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            };
+        let file_path = file_path.as_path();
         if !file_path.exists() {
             return Ok(HandlerResponse::Reply {
                 payload_xml: ToolResponse::err(&format!("file not found: {path}")),
             });
         }
 
-        let content = match std::fs::read_to_string(file_path) {
-            Ok(s) => s,
+        // Optimistic concurrency: refuse to edit a file changed since it was read
+        if let Some(expected) = extract_tag(&xml_str, "expected_hash").filter(|h| !h.is_empty()) {
+            if let Err(e) = version::check_expected(file_path, &path, &expected) {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        }
+
+        let file = match std::fs::read(file_path) {
+            Ok(raw) => match TextFile::decode(&raw) {
+                Some(file) => file,
+                None => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&format!("cannot edit binary file: {path}")),
+                    });
+                }
+            },
             Err(e) => {
                 return Ok(HandlerResponse::Reply {
                     payload_xml: ToolResponse::err(&format!("read error: {e}")),
                 });
             }
         };
+        let content = file.text.as_str();
+
+        let applied = if let Some(patch) = patch {
+            patch::apply_unified(content, &patch)
+                .map(|outcome| (outcome.content, outcome.notes))
+                .map_err(|failures| {
+                    format!("patch not applied, file unchanged:\n{}", failures.join("\n"))
+                })
+        } else if let Some(json) = edits {
+            serde_json::from_str::<Vec<Edit>>(&json)
+                .map_err(|e| format!("invalid <edits>: {e}"))
+                .and_then(|edits| apply_edits(content, &edits))
+                .map(|new_content| (new_content, Vec::new()))
+        } else {
+            let edit = Edit {
+                old_string: old_string.unwrap_or_default(),
+                new_string: extract_tag(&xml_str, "new_string").unwrap_or_default(),
+                replace_all: extract_tag(&xml_str, "replace_all").is_some_and(|v| v == "true"),
+            };
+            apply_edit(content, &edit).map(|new_content| (new_content, Vec::new()))
+        };
 
-        // Count matches
-        let match_count = content.matches(&old_string).count();
-
-        if match_count == 0 {
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err("old_string not found in file"),
-            });
-        }
+        let (new_content, notes) = match applied {
+            Ok(applied) => applied,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        };
 
-        if match_count > 1 {
-            // Find line numbers of each occurrence
-            let mut line_numbers = Vec::new();
-            let mut search_start = 0;
-            while let Some(pos) = content[search_start..].find(&old_string) {
-                let abs_pos = search_start + pos;
-                let line_num = content[..abs_pos].lines().count() + 1;
-                line_numbers.push(line_num);
-                search_start = abs_pos + 1;
+        let allow_lossy = extract_tag(&xml_str, "allow_lossy").is_some_and(|v| v == "true");
+        let payload_xml = match write_edit(file_path, &file, &new_content, &notes, allow_lossy) {
+            Ok(diff) => {
+                announce_file_changed(self.events.as_ref(), &ctx.thread_id, "file-edit", file_path);
+                ToolResponse::ok(&diff)
             }
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!(
-                    "old_string has {match_count} matches (must be unique). Found at lines: {:?}",
-                    line_numbers
-                )),
-            });
-        }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+/// Write an edited file in `file`'s encoding and line endings, and
+/// describe the change: any notes, the diff, and the file's new version.
+///
+/// Every edit to an existing file ends here, whether it came from
+/// file-edit's own modes or from a compiler suggestion.
+pub(crate) fn write_edit(
+    file_path: &Path,
+    file: &TextFile,
+    new_content: &str,
+    notes: &[String],
+    allow_lossy: bool,
+) -> Result<String, String> {
+    let new_content = new_content.replace("\r\n", "\n");
+    let (bytes, replaced) = file.encode(&new_content, allow_lossy)?;
+    std::fs::write(file_path, &bytes).map_err(|e| format!("write error: {e}"))?;
+
+    // Generate unified diff, prefixed with any fuzzy-match notes
+    let diff = TextDiff::from_lines(&file.text, &new_content);
+    let mut diff_output = String::new();
+    for note in notes {
+        diff_output.push_str(&format!("# {note}\n"));
+    }
+    if file.mixed > 0 {
+        diff_output.push_str(&format!(
+            "# line endings normalized to {} ({} lines ended otherwise)\n",
+            file.line_ending.name(),
+            file.mixed
+        ));
+    }
+    if replaced > 0 {
+        diff_output.push_str(&format!(
+            "# {replaced} characters written as '?' ({} can't hold them)\n",
+            file.encoding.name()
+        ));
+    }
+    for change in diff.iter_all_changes() {
+        let sign = match change.tag() {
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+            ChangeTag::Equal => " ",
+        };
+        diff_output.push_str(&format!("{sign}{change}"));
+    }
+    if !diff_output.ends_with('\n') {
+        diff_output.push('\n');
+    }
+    diff_output.push_str(&version::footer(file_path, &bytes));
+    Ok(diff_output)
+}
 
-        // Exactly one match — perform replacement
-        let new_content = content.replacen(&old_string, &new_string, 1);
+/// Apply a batch of edits in order; the first failure aborts the whole batch.
+fn apply_edits(content: &str, edits: &[Edit]) -> Result<String, String> {
+    if edits.is_empty() {
+        return Err("<edits> is empty".into());
+    }
+    let mut current = content.to_string();
+    for (i, edit) in edits.iter().enumerate() {
+        current = apply_edit(&current, edit).map_err(|e| {
+            format!("edit {} of {}: {e} (no edits applied)", i + 1, edits.len())
+        })?;
+    }
+    Ok(current)
+}
 
-        if let Err(e) = std::fs::write(file_path, &new_content) {
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!("write error: {e}")),
-            });
-        }
+/// Apply one replacement, enforcing uniqueness unless `replace_all` is set.
+fn apply_edit(content: &str, edit: &Edit) -> Result<String, String> {
+    if edit.old_string.is_empty() {
+        return Err("old_string is empty".into());
+    }
 
-        // Generate unified diff
-        let diff = TextDiff::from_lines(&content, &new_content);
-        let mut diff_output = String::new();
-        for change in diff.iter_all_changes() {
-            let sign = match change.tag() {
-                ChangeTag::Delete => "-",
-                ChangeTag::Insert => "+",
-                ChangeTag::Equal => " ",
-            };
-            diff_output.push_str(&format!("{sign}{change}"));
-        }
+    // Count matches
+    let match_count = content.matches(&edit.old_string).count();
+
+    if match_count == 0 {
+        return Err("old_string not found in file".into());
+    }
+
+    if edit.replace_all {
+        return Ok(content.replace(&edit.old_string, &edit.new_string));
+    }
 
-        Ok(HandlerResponse::Reply {
-            payload_xml: ToolResponse::ok(&diff_output),
-        })
+    if match_count > 1 {
+        // Find line numbers of each occurrence
+        let mut line_numbers = Vec::new();
+        let mut search_start = 0;
+        while let Some(pos) = content[search_start..].find(&edit.old_string) {
+            let abs_pos = search_start + pos;
+            let line_num = content[..abs_pos].lines().count() + 1;
+            line_numbers.push(line_num);
+            search_start = abs_pos + 1;
+        }
+        return Err(format!(
+            "old_string has {match_count} matches (must be unique). Found at lines: {:?}",
+            line_numbers
+        ));
     }
+
+    // Exactly one match — perform replacement
+    Ok(content.replacen(&edit.old_string, &edit.new_string, 1))
 }
 
 #[async_trait]
@@ -107,17 +266,43 @@ impl ToolPeer for FileEditTool {
         "file-edit"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Surgical text replacement in a file. Replaces old_string with new_string. The old_string must match exactly once. Returns unified diff.
+/// Surgical text replacement in a file. Give exactly one of: old_string/new_string (one replacement; old_string must match exactly once unless replace_all), edits (ordered batch applied all-or-nothing), or patch (unified diff, hunks matched fuzzily). The file keeps its encoding, BOM and line endings; characters its encoding can't hold are refused unless allow_lossy. Returns unified diff and the new [version: <hash> mtime: <secs>].
 interface file-edit {
+    record edit {
+        /// The exact text to find
+        old-string: string,
+        /// The replacement text
+        new-string: string,
+        /// Replace every occurrence instead of requiring a unique match (default: false)
+        replace-all: option<bool>,
+    }
     record request {
         /// The file path to edit
         path: string,
-        /// The exact text to find and replace (must be unique in the file)
-        old-string: string,
+        /// The exact text to find and replace (must be unique in the file unless replace_all)
+        old-string: option<string>,
         /// The replacement text
-        new-string: string,
+        new-string: option<string>,
+        /// Replace every occurrence of old_string (default: false)
+        replace-all: option<bool>,
+        /// Ordered edits applied in sequence; if any fails, none are written
+        edits: option<list<edit>>,
+        /// Unified diff to apply (@@ hunks; line numbers may be approximate)
+        patch: option<string>,
+        /// Version hash from file-read; the edit is rejected if the file has changed since
+        expected-hash: option<string>,
+        /// Write '?' for characters the file's encoding can't hold instead of failing (default: false)
+        allow-lossy: option<bool>,
     }
     edit: func(req: request) -> result<string, string>;
 }
@@ -171,7 +356,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>fn hello()</old_string><new_string>fn world()</new_string></FileEditRequest>"
         );
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("-fn hello()"));
         assert!(content.contains("+fn world()"));
@@ -192,7 +377,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>nonexistent</old_string><new_string>replacement</new_string></FileEditRequest>"
         );
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("not found"));
     }
@@ -207,7 +392,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>foo</old_string><new_string>replaced</new_string></FileEditRequest>"
         );
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("3 matches"));
         assert!(content.contains("lines:"));
@@ -223,7 +408,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>bbb\nccc</old_string><new_string>BBB\nCCC\nEEE</new_string></FileEditRequest>"
         );
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("-bbb"));
         assert!(content.contains("+BBB"));
@@ -235,7 +420,7 @@ mod tests {
     #[tokio::test]
     async fn edit_missing_file() {
         let xml = "<FileEditRequest><path>/nonexistent/file.txt</path><old_string>x</old_string><new_string>y</new_string></FileEditRequest>";
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("file not found"));
     }
@@ -243,7 +428,7 @@ mod tests {
     #[tokio::test]
     async fn edit_missing_old_string() {
         let xml = "<FileEditRequest><path>/tmp/x</path><new_string>y</new_string></FileEditRequest>";
-        let (ok, content) = get_result(FileEditTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("missing required"));
     }
@@ -258,7 +443,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>remove this\n</old_string><new_string></new_string></FileEditRequest>"
         );
-        let (ok, _) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert_eq!(
             std::fs::read_to_string(&path).unwrap(),
@@ -276,7 +461,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>a &lt; b</old_string><new_string>a &gt; b</new_string></FileEditRequest>"
         );
-        let (ok, _) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert_eq!(
             std::fs::read_to_string(&path).unwrap(),
@@ -294,7 +479,7 @@ mod tests {
         let xml = format!(
             "<FileEditRequest><path>{path_str}</path><old_string>beta</old_string><new_string>BETA</new_string></FileEditRequest>"
         );
-        let (ok, diff) = get_result(FileEditTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, diff) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         // Diff should have context lines (space prefix) and changes (+/-)
         assert!(diff.contains(" alpha\n"));
@@ -303,13 +488,181 @@ mod tests {
         assert!(diff.contains(" gamma\n"));
     }
 
+    #[tokio::test]
+    async fn edit_read_only_root_rejected() {
+        let root = TempDir::new().unwrap();
+        let docs = TempDir::new().unwrap();
+        let path = docs.path().join("notes.md");
+        std::fs::write(&path, "draft\n").unwrap();
+
+        let mut tool = FileEditTool::default();
+        let ws = Workspace::new("coding", root.path(), &[docs.path().to_path_buf()]).unwrap();
+        tool.set_workspace(Arc::new(ws));
+
+        let xml = format!(
+            "<FileEditRequest><path>{}</path><old_string>draft</old_string><new_string>final</new_string></FileEditRequest>",
+            path.display()
+        );
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "draft\n");
+    }
+
+    #[tokio::test]
+    async fn edit_replace_all() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.txt");
+        std::fs::write(&path, "foo bar\nfoo baz\nfoo qux\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><old_string>foo</old_string><new_string>qux</new_string><replace_all>true</replace_all></FileEditRequest>"
+        );
+        let (ok, _) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "qux bar\nqux baz\nqux qux\n");
+    }
+
+    #[tokio::test]
+    async fn edit_batch_applies_in_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "fn a() {}\nfn b() {}\nlet x = a();\nlet y = a();\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        // The second edit sees the result of the first
+        let edits = r#"[{"old_string":"fn a()","new_string":"fn alpha()"},{"old_string":" a();","new_string":" alpha();","replace_all":true},{"old_string":"fn b() {}\n","new_string":""}]"#;
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><edits>{}</edits></FileEditRequest>",
+            crate::tools::xml_escape(edits)
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn alpha() {}\nlet x = alpha();\nlet y = alpha();\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_batch_is_all_or_nothing() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let edits = r#"[{"old_string":"one","new_string":"1"},{"old_string":"three","new_string":"3"}]"#;
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><edits>{edits}</edits></FileEditRequest>"
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("edit 2 of 2"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+    }
+
+    #[tokio::test]
+    async fn edit_patch_mode() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "fn main() {\n    run();\n}\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let patch = "--- a/main.rs\n+++ b/main.rs\n@@ -1,3 +1,4 @@\n fn main() {\n+    init();\n     run();\n }\n";
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><patch>{patch}</patch></FileEditRequest>"
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert!(content.contains("+    init();"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn main() {\n    init();\n    run();\n}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_patch_failure_names_hunk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let patch = "@@ -1 +1 @@\n-fn start() {}\n+fn begin() {}\n";
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><patch>{patch}</patch></FileEditRequest>"
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("hunk 1 (@@ -1 +1 @@)"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fn main() {}\n");
+    }
+
+    #[tokio::test]
+    async fn edit_rejects_stale_expected_hash() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "fn a() {}\n").unwrap();
+        let path_str = path.to_str().unwrap();
+        let seen = version::content_version(b"fn a() {}\n");
+
+        // Someone else saves the file after the agent read it
+        std::fs::write(&path, "fn a() {}\nfn b() {}\n").unwrap();
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><old_string>fn a()</old_string><new_string>fn x()</new_string><expected_hash>{seen}</expected_hash></FileEditRequest>"
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("conflict"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fn a() {}\nfn b() {}\n");
+
+        // With the current version the edit goes through and reports the new one
+        let current = version::content_version(b"fn a() {}\nfn b() {}\n");
+        let xml = xml.replace(&seen, &current);
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        let expected = version::content_version(b"fn x() {}\nfn b() {}\n");
+        assert_eq!(version::parse(&content), Some(expected.as_str()));
+    }
+
+    #[tokio::test]
+    async fn edit_preserves_bom_and_crlf() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Program.cs");
+        std::fs::write(&path, b"\xEF\xBB\xBFclass A\r\n{\r\n}\r\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        // The agent sees and sends plain \n; the file keeps its BOM and CRLF
+        let xml = format!(
+            "<FileEditRequest><path>{path_str}</path><old_string>class A\n{{</old_string><new_string>class B\n{{\n    int x;</new_string></FileEditRequest>"
+        );
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert!(content.contains("+class B\n"), "{content}");
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"\xEF\xBB\xBFclass B\r\n{\r\n    int x;\r\n}\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_rejects_mixed_modes() {
+        let xml = "<FileEditRequest><path>/tmp/x</path><old_string>a</old_string><patch>@@ -1 +1 @@</patch></FileEditRequest>";
+        let (ok, content) = get_result(FileEditTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("exactly one of"));
+    }
+
     #[test]
     fn file_edit_metadata() {
-        let tool = FileEditTool;
+        let tool = FileEditTool::default();
         assert_eq!(tool.name(), "file-edit");
         let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
         assert_eq!(iface.name, "file-edit");
         assert_eq!(iface.request_tag(), "FileEditRequest");
         assert!(iface.request.fields.iter().any(|f| f.name == "path"));
+        assert!(iface.request.fields.iter().any(|f| f.name == "edits"));
+        assert!(iface.request.fields.iter().any(|f| f.name == "patch"));
     }
 }
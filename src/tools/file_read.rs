@@ -1,25 +1,32 @@
 //! FileReadTool — read file contents with line numbers, offset/limit.
+//!
+//! Images (PNG, JPEG, GIF, WebP) and PDFs are returned whole as media
+//! rather than rejected as binary. Text is decoded by [`super::encoding`],
+//! and the output says which encoding and line endings the file uses.
+//! Jupyter notebooks are rendered as numbered cells (see [`super::notebook`]).
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
-use std::path::Path;
+use std::sync::Arc;
 
+use super::encoding::TextFile;
+use super::notebook;
+use super::output::{self, OutputStore};
+use super::version;
+use super::workspace::{self, Access, Workspace};
 use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::llm::media;
 
 /// Read file contents with optional offset and limit.
-pub struct FileReadTool;
-
-impl FileReadTool {
-    /// Check if a byte slice looks like binary (contains null bytes in first 8KB).
-    fn is_binary(data: &[u8]) -> bool {
-        let check_len = data.len().min(8192);
-        data[..check_len].contains(&0)
-    }
+#[derive(Default)]
+pub struct FileReadTool {
+    workspace: Option<Arc<Workspace>>,
+    output_store: Option<Arc<OutputStore>>,
 }
 
 #[async_trait]
 impl Handler for FileReadTool {
-    async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
         let xml_str = String::from_utf8_lossy(&payload.xml);
 
         let path = extract_tag(&xml_str, "path").unwrap_or_default();
@@ -36,7 +43,16 @@ impl Handler for FileReadTool {
             .and_then(|s| s.parse::<usize>().ok())
             .unwrap_or(2000);
 
-        let file_path = Path::new(&path);
+        let file_path =
+            match workspace::resolve_in(self.workspace.as_deref(), "file-read", &path, Access::Read) {
+                Ok(p) => p,
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            };
+        let file_path = file_path.as_path();
         if !file_path.exists() {
             return Ok(HandlerResponse::Reply {
                 payload_xml: ToolResponse::err(&format!("file not found: {path}")),
@@ -59,17 +75,43 @@ impl Handler for FileReadTool {
             }
         };
 
-        if Self::is_binary(&raw) {
+        // Images and PDFs go back as content blocks the model can see
+        if let Some(media_type) = media::sniff_media_type(&raw) {
+            return Ok(HandlerResponse::Reply {
+                payload_xml: match media::check_media(&raw) {
+                    Ok(_) => ToolResponse::ok_media(
+                        &format!("{path} ({media_type}, {} bytes)", raw.len()),
+                        media_type,
+                        &raw,
+                    ),
+                    Err(e) => ToolResponse::err(&format!("{path}: {e}")),
+                },
+            });
+        }
+
+        let Some(file) = TextFile::decode(&raw) else {
             return Ok(HandlerResponse::Reply {
                 payload_xml: ToolResponse::err(&format!(
                     "binary file detected: {path} ({} bytes)",
                     raw.len()
                 )),
             });
+        };
+
+        // Notebooks read as cells; malformed ones fall through to raw JSON
+        if notebook::is_notebook(file_path) {
+            if let Ok(nb) = notebook::parse(&file.text) {
+                let mut output = notebook::render(&nb);
+                output.push_str(&version::footer(file_path, &raw));
+                let output =
+                    output::shape(self.output_store.as_deref(), &ctx.thread_id, output).await;
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::ok(&output),
+                });
+            }
         }
 
-        let content = String::from_utf8_lossy(&raw);
-        let lines: Vec<&str> = content.lines().collect();
+        let lines: Vec<&str> = file.text.lines().collect();
         let total_lines = lines.len();
 
         // offset is 1-based
@@ -80,8 +122,8 @@ impl Handler for FileReadTool {
         for (i, line) in lines.iter().enumerate().skip(start).take(end - start) {
             let line_num = i + 1;
             // Truncate long lines at 2000 chars
-            if line.len() > 2000 {
-                output.push_str(&format!("{line_num}| {}...\n", &line[..2000]));
+            if let Some((cut, _)) = line.char_indices().nth(2000) {
+                output.push_str(&format!("{line_num}| {}...\n", &line[..cut]));
             } else {
                 output.push_str(&format!("{line_num}| {line}\n"));
             }
@@ -89,11 +131,14 @@ impl Handler for FileReadTool {
 
         if end < total_lines {
             output.push_str(&format!(
-                "\n... ({} more lines, {} total)",
+                "\n... ({} more lines, {} total)\n",
                 total_lines - end,
                 total_lines
             ));
         }
+        output.push_str(&format!("{}\n", file.describe()));
+        output.push_str(&version::footer(file_path, &raw));
+        let output = output::shape(self.output_store.as_deref(), &ctx.thread_id, output).await;
 
         Ok(HandlerResponse::Reply {
             payload_xml: ToolResponse::ok(&output),
@@ -107,9 +152,17 @@ impl ToolPeer for FileReadTool {
         "file-read"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Read file contents with line numbers. Supports offset and limit for large files. Detects binary files.
+/// Read file contents with line numbers. Supports offset and limit for large files. Text output ends with the file's [encoding: … line-endings: …], which edits preserve, and [version: <hash> mtime: <secs>]; pass the hash as expected_hash to file-write/file-edit to reject stale writes. Images (PNG, JPEG, GIF, WebP) and PDFs are returned as viewable content; other binary files are rejected. Jupyter notebooks (.ipynb) are shown as numbered cells with their outputs truncated; offset and limit don't apply to them.
 interface file-read {
     record request {
         /// The file path to read
@@ -171,7 +224,7 @@ mod tests {
 
         let path = f.path().to_str().unwrap();
         let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("1| line one"));
         assert!(content.contains("2| line two"));
@@ -189,7 +242,7 @@ mod tests {
         let xml = format!(
             "<FileReadRequest><path>{path}</path><offset>5</offset><limit>3</limit></FileReadRequest>"
         );
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("5| line 5"));
         assert!(content.contains("6| line 6"));
@@ -208,7 +261,7 @@ mod tests {
         let xml = format!(
             "<FileReadRequest><path>{path}</path><limit>10</limit></FileReadRequest>"
         );
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("... (90 more lines, 100 total)"));
     }
@@ -216,7 +269,7 @@ mod tests {
     #[tokio::test]
     async fn read_missing_file() {
         let xml = "<FileReadRequest><path>/nonexistent/file.txt</path></FileReadRequest>";
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("file not found"));
     }
@@ -224,7 +277,7 @@ mod tests {
     #[tokio::test]
     async fn read_missing_path_tag() {
         let xml = "<FileReadRequest></FileReadRequest>";
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("missing required"));
     }
@@ -234,7 +287,7 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().to_str().unwrap();
         let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("directory"));
     }
@@ -246,17 +299,34 @@ mod tests {
 
         let path = f.path().to_str().unwrap();
         let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("binary file"));
     }
 
+    #[tokio::test]
+    async fn read_image_returns_media() {
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR").unwrap();
+
+        let path = f.path().to_str().unwrap();
+        let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
+        let resp = FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap();
+        let HandlerResponse::Reply { payload_xml } = resp else {
+            panic!("expected Reply");
+        };
+        let xml = String::from_utf8(payload_xml).unwrap();
+        assert!(xml.contains("<success>true</success>"));
+        assert!(extract_tag(&xml, "result").unwrap().contains("image/png, 16 bytes"));
+        assert_eq!(extract_tag(&xml, "media_type").unwrap(), "image/png");
+    }
+
     #[tokio::test]
     async fn read_empty_file() {
         let f = NamedTempFile::new().unwrap();
         let path = f.path().to_str().unwrap();
         let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
-        let (ok, _content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
     }
 
@@ -269,16 +339,76 @@ mod tests {
 
         let path = f.path().to_str().unwrap();
         let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
-        let (ok, content) = get_result(FileReadTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("2000| line 2000"));
         assert!(!content.contains("2001| line 2001"));
         assert!(content.contains("500 more lines"));
     }
 
+    #[tokio::test]
+    async fn read_reports_version() {
+        let mut f = NamedTempFile::new().unwrap();
+        write!(f, "alpha\nbeta\n").unwrap();
+        let xml = format!(
+            "<FileReadRequest><path>{}</path><limit>1</limit></FileReadRequest>",
+            f.path().display()
+        );
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        // The version covers the whole file, not just the lines shown
+        let expected = version::content_version(b"alpha\nbeta\n");
+        assert_eq!(version::parse(&content), Some(expected.as_str()));
+        assert!(content.contains("mtime: "));
+    }
+
+    #[tokio::test]
+    async fn read_reports_encoding_and_line_endings() {
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(b"na\xEFve\r\ncaf\xE9\r\n").unwrap();
+        let xml = format!("<FileReadRequest><path>{}</path></FileReadRequest>", f.path().display());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("1| naïve\n2| café\n"), "{content}");
+        assert!(content.contains("[encoding: latin-1 line-endings: crlf]\n[version: "), "{content}");
+    }
+
+    #[tokio::test]
+    async fn read_renders_notebook_cells() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("demo.ipynb");
+        std::fs::write(&path, r#"{"cells": [{"cell_type": "code", "execution_count": 1, "id": "a1", "metadata": {}, "outputs": [{"name": "stdout", "output_type": "stream", "text": "hi\n"}], "source": ["print('hi')"]}], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"#).unwrap();
+        let xml = format!("<FileReadRequest><path>{}</path></FileReadRequest>", path.display());
+        let (ok, content) = get_result(FileReadTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.starts_with("[cell 0] code id=a1 exec=1\n1| print('hi')\n  -> stdout\n     hi\n"), "{content}");
+        assert!(content.contains("[notebook: 1 cells (1 code) nbformat: 4.5]\n[version: "), "{content}");
+    }
+
+    #[tokio::test]
+    async fn read_outside_workspace_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("inside.txt"), "hello").unwrap();
+        let outside = NamedTempFile::new().unwrap();
+
+        let mut tool = FileReadTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", root.path(), &[]).unwrap()));
+
+        let xml = "<FileReadRequest><path>inside.txt</path></FileReadRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("1| hello"));
+
+        let path = outside.path().to_str().unwrap();
+        let xml = format!("<FileReadRequest><path>{path}</path></FileReadRequest>");
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+    }
+
     #[test]
     fn file_read_metadata() {
-        let tool = FileReadTool;
+        let tool = FileReadTool::default();
         assert_eq!(tool.name(), "file-read");
         let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
         assert_eq!(iface.name, "file-read");
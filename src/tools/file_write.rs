@@ -1,17 +1,29 @@
 //! FileWriteTool — write/create files with automatic parent directory creation.
+//!
+//! Overwriting a text file keeps its encoding, BOM and line endings (see
+//! [`super::encoding`]); new files are written as given, in UTF-8.
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
-use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 
-use super::{extract_tag, ToolPeer, ToolResponse};
+use super::encoding::TextFile;
+use super::version;
+use super::workspace::{self, Access, Workspace};
+use super::{announce_file_changed, extract_tag, ToolPeer, ToolResponse};
+use crate::pipeline::events::PipelineEvent;
 
 /// Write or create files. Auto-creates parent directories.
-pub struct FileWriteTool;
+#[derive(Default)]
+pub struct FileWriteTool {
+    workspace: Option<Arc<Workspace>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+}
 
 #[async_trait]
 impl Handler for FileWriteTool {
-    async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
         let xml_str = String::from_utf8_lossy(&payload.xml);
 
         let path = extract_tag(&xml_str, "path").unwrap_or_default();
@@ -23,7 +35,25 @@ impl Handler for FileWriteTool {
 
         let content = extract_tag(&xml_str, "content").unwrap_or_default();
 
-        let file_path = Path::new(&path);
+        let file_path =
+            match workspace::resolve_in(self.workspace.as_deref(), "file-write", &path, Access::Write) {
+                Ok(p) => p,
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            };
+        let file_path = file_path.as_path();
+
+        // Optimistic concurrency: refuse to clobber a file changed since it was read
+        if let Some(expected) = extract_tag(&xml_str, "expected_hash").filter(|h| !h.is_empty()) {
+            if let Err(e) = version::check_expected(file_path, &path, &expected) {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        }
 
         // Auto-create parent directories
         if let Some(parent) = file_path.parent() {
@@ -38,14 +68,43 @@ impl Handler for FileWriteTool {
             }
         }
 
-        let bytes = content.as_bytes();
-        match std::fs::write(file_path, bytes) {
-            Ok(()) => Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::ok(&format!(
-                    "wrote {} bytes to {path}",
-                    bytes.len()
-                )),
-            }),
+        // An existing text file keeps its encoding, BOM and line endings
+        let existing = std::fs::read(file_path)
+            .ok()
+            .and_then(|raw| TextFile::decode(&raw));
+        let allow_lossy = extract_tag(&xml_str, "allow_lossy").is_some_and(|v| v == "true");
+        let (bytes, kept) = match &existing {
+            Some(file) => match file.encode(&content, allow_lossy) {
+                Ok((bytes, 0)) => (bytes, format!(" {}", file.describe())),
+                Ok((bytes, replaced)) => (
+                    bytes,
+                    format!(" {} ({replaced} characters written as '?')", file.describe()),
+                ),
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&format!("{path}: {e}")),
+                    });
+                }
+            },
+            None => (content.into_bytes(), String::new()),
+        };
+
+        match std::fs::write(file_path, &bytes) {
+            Ok(()) => {
+                announce_file_changed(
+                    self.events.as_ref(),
+                    &ctx.thread_id,
+                    "file-write",
+                    file_path,
+                );
+                Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::ok(&format!(
+                        "wrote {} bytes to {path}{kept} {}",
+                        bytes.len(),
+                        version::footer(file_path, &bytes)
+                    )),
+                })
+            }
             Err(e) => Ok(HandlerResponse::Reply {
                 payload_xml: ToolResponse::err(&format!("write error: {e}")),
             }),
@@ -59,15 +118,27 @@ impl ToolPeer for FileWriteTool {
         "file-write"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Write or create a file. Auto-creates parent directories.
+/// Write or create a file. Auto-creates parent directories. An existing file keeps its encoding, BOM and line endings; characters its encoding can't hold are refused unless allow_lossy. Returns the new [version: <hash> mtime: <secs>].
 interface file-write {
     record request {
         /// The file path to write
         path: string,
         /// The content to write to the file
         content: string,
+        /// Version hash from file-read; the write is rejected if the file has changed since
+        expected-hash: option<string>,
+        /// Write '?' for characters the file's encoding can't hold instead of failing (default: false)
+        allow-lossy: option<bool>,
     }
     write: func(req: request) -> result<string, string>;
 }
@@ -120,7 +191,7 @@ mod tests {
         let xml = format!(
             "<FileWriteRequest><path>{path_str}</path><content>hello world</content></FileWriteRequest>"
         );
-        let (ok, content) = get_result(FileWriteTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("11 bytes"));
         assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
@@ -135,7 +206,7 @@ mod tests {
         let xml = format!(
             "<FileWriteRequest><path>{path_str}</path><content>deep content</content></FileWriteRequest>"
         );
-        let (ok, _) = get_result(FileWriteTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert_eq!(std::fs::read_to_string(&path).unwrap(), "deep content");
     }
@@ -150,7 +221,7 @@ mod tests {
         let xml = format!(
             "<FileWriteRequest><path>{path_str}</path><content>new content</content></FileWriteRequest>"
         );
-        let (ok, _) = get_result(FileWriteTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert_eq!(std::fs::read_to_string(&path).unwrap(), "new content");
     }
@@ -164,7 +235,7 @@ mod tests {
         let xml = format!(
             "<FileWriteRequest><path>{path_str}</path><content></content></FileWriteRequest>"
         );
-        let (ok, content) = get_result(FileWriteTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("0 bytes"));
     }
@@ -172,7 +243,7 @@ mod tests {
     #[tokio::test]
     async fn write_missing_path() {
         let xml = "<FileWriteRequest><content>hello</content></FileWriteRequest>";
-        let (ok, content) = get_result(FileWriteTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("missing required"));
     }
@@ -186,14 +257,88 @@ mod tests {
         let xml = format!(
             "<FileWriteRequest><path>{path_str}</path><content>a &lt; b &amp; c</content></FileWriteRequest>"
         );
-        let (ok, _) = get_result(FileWriteTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, _) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert_eq!(std::fs::read_to_string(&path).unwrap(), "a < b & c");
     }
 
+    #[tokio::test]
+    async fn write_outside_workspace_rejected() {
+        let root = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("escape.txt");
+
+        let mut tool = FileWriteTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", root.path(), &[]).unwrap()));
+
+        let xml = "<FileWriteRequest><path>src/new.rs</path><content>ok</content></FileWriteRequest>";
+        let (ok, _) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert_eq!(std::fs::read_to_string(root.path().join("src/new.rs")).unwrap(), "ok");
+
+        let xml = format!(
+            "<FileWriteRequest><path>{}</path><content>x</content></FileWriteRequest>",
+            target.display()
+        );
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn write_rejects_stale_expected_hash() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "draft").unwrap();
+        let path_str = path.to_str().unwrap();
+        let seen = version::content_version(b"draft");
+
+        std::fs::write(&path, "draft, edited in the IDE").unwrap();
+        let xml = format!(
+            "<FileWriteRequest><path>{path_str}</path><content>agent version</content><expected_hash>{seen}</expected_hash></FileWriteRequest>"
+        );
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("changed since it was read"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "draft, edited in the IDE");
+    }
+
+    #[tokio::test]
+    async fn overwrite_keeps_encoding_and_line_endings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("legacy.txt");
+        std::fs::write(&path, b"caf\xE9\r\nbar\r\n").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let xml = format!(
+            "<FileWriteRequest><path>{path_str}</path><content>café\nbaz\n</content></FileWriteRequest>"
+        );
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("[encoding: latin-1 line-endings: crlf]"), "{content}");
+        assert_eq!(std::fs::read(&path).unwrap(), b"caf\xE9\r\nbaz\r\n");
+
+        let xml = format!(
+            "<FileWriteRequest><path>{path_str}</path><content>5 €\n</content></FileWriteRequest>"
+        );
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("cannot be written as latin-1"), "{content}");
+        assert_eq!(std::fs::read(&path).unwrap(), b"caf\xE9\r\nbaz\r\n");
+
+        let xml = format!(
+            "<FileWriteRequest><path>{path_str}</path><content>5 €\n</content><allow_lossy>true</allow_lossy></FileWriteRequest>"
+        );
+        let (ok, content) = get_result(FileWriteTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("1 characters written as '?'"), "{content}");
+        assert_eq!(std::fs::read(&path).unwrap(), b"5 ?\r\n");
+    }
+
     #[test]
     fn file_write_metadata() {
-        let tool = FileWriteTool;
+        let tool = FileWriteTool::default();
         assert_eq!(tool.name(), "file-write");
         let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
         assert_eq!(iface.name, "file-write");
@@ -0,0 +1,1160 @@
+//! Git tool — repository operations with structured (JSON) results.
+//!
+//! Wraps the `git` CLI so the agent doesn't have to drive it through
+//! command-exec and parse porcelain text. Every action runs in the workspace
+//! root (or the current directory when unjailed):
+//!
+//! - Reads (`status`, `diff`, `log`, `show`) are scoped to that directory,
+//!   and paths go through the workspace jail like the file tools.
+//! - Actions that change the repository (`stage`, `commit`, `branch`,
+//!   `switch`, `fetch`, `pull`, `push`) are refused when jailed unless the
+//!   whole repository lies inside the workspace.
+//! - A profile's `git:` block can forbid rewriting history (amend, force
+//!   push) and remote operations.
+//!
+//! git runs inside the profile's sandbox like command-exec, with hooks,
+//! fsmonitor and `ext::` remotes turned off, so nothing in the repository's
+//! config can make it run a program.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::output::{self, OutputStore};
+use super::sandbox::Sandbox;
+use super::workspace::{self, Access, Workspace};
+use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::organism::profile::GitPolicySpec;
+
+const DEFAULT_LOG_LIMIT: usize = 20;
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(60);
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Actions that talk to a remote.
+const REMOTE_ACTIONS: &[&str] = &["fetch", "pull", "push"];
+
+/// Actions that change the repository, its refs or the working tree.
+const MUTATING_ACTIONS: &[&str] = &["stage", "commit", "switch", "fetch", "pull", "push"];
+
+/// `--format` for commits: hash, short hash, author, email, date, subject, body.
+const COMMIT_FORMAT: &str = "--format=%H%x1f%h%x1f%an%x1f%ae%x1f%aI%x1f%s%x1f%b%x1e";
+
+/// Structured git operations for the agent.
+#[derive(Default)]
+pub struct GitTool {
+    policy: GitPolicySpec,
+    workspace: Option<Arc<Workspace>>,
+    sandbox: Option<Arc<Sandbox>>,
+    output_store: Option<Arc<OutputStore>>,
+}
+
+/// A parsed `<GitRequest>`.
+#[derive(Debug, Default)]
+struct Request {
+    action: String,
+    path: Option<String>,
+    rev: Option<String>,
+    staged: bool,
+    hunks: Option<Vec<usize>>,
+    name: Option<String>,
+    create: bool,
+    message: Option<String>,
+    amend: bool,
+    force: bool,
+    remote: Option<String>,
+    limit: Option<usize>,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+}
+
+impl Request {
+    fn parse(xml: &str) -> Result<Self, String> {
+        let text = |tag: &str| extract_tag(xml, tag).filter(|s| !s.trim().is_empty());
+        let flag = |tag: &str| extract_tag(xml, tag).is_some_and(|s| s.trim() == "true");
+        let number = |tag: &str| extract_tag(xml, tag).and_then(|s| s.trim().parse().ok());
+        let hunks = match text("hunks") {
+            Some(list) => Some(
+                serde_json::from_str::<Vec<usize>>(&list)
+                    .map_err(|e| format!("invalid <hunks>: {e}"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            action: text("action").unwrap_or_default().trim().to_string(),
+            path: text("path"),
+            rev: text("rev"),
+            staged: flag("staged"),
+            hunks,
+            name: text("name"),
+            create: flag("create"),
+            message: extract_tag(xml, "message"),
+            amend: flag("amend"),
+            force: flag("force"),
+            remote: text("remote"),
+            limit: number("limit"),
+            start_line: number("start_line"),
+            end_line: number("end_line"),
+        })
+    }
+}
+
+/// One file of a unified diff.
+#[derive(Debug, Default)]
+struct FileDiff {
+    path: String,
+    old_path: Option<String>,
+    status: &'static str,
+    binary: bool,
+    /// Lines from `diff --git` up to the first hunk, kept to rebuild patches.
+    header: Vec<String>,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug)]
+struct Hunk {
+    header: String,
+    lines: Vec<String>,
+}
+
+impl GitTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create with a profile's `git:` restrictions.
+    pub fn with_policy(policy: GitPolicySpec) -> Self {
+        Self {
+            policy,
+            ..Self::default()
+        }
+    }
+
+    /// Run git inside `sandbox`, like command-exec.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(Arc::new(sandbox));
+        self
+    }
+
+    /// Refuse what the profile forbids.
+    fn check_policy(&self, req: &Request) -> Result<(), String> {
+        let action = req.action.as_str();
+        if self.policy.forbid_remote && REMOTE_ACTIONS.contains(&action) {
+            return Err(format!(
+                "git {action} not allowed: this profile forbids remote operations"
+            ));
+        }
+        let rewrite = match action {
+            "commit" if req.amend => Some("commit --amend"),
+            "push" if req.force => Some("push --force"),
+            _ => None,
+        };
+        if let (true, Some(op)) = (self.policy.forbid_rewrite, rewrite) {
+            return Err(format!(
+                "git {op} not allowed: this profile forbids rewriting history"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Where git runs: the workspace root, or the current directory.
+    fn repo_dir(&self) -> PathBuf {
+        match &self.workspace {
+            Some(ws) => ws.root().to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    }
+
+    /// A pathspec for `path` (default: the whole directory git runs in).
+    fn pathspec(&self, path: Option<&str>, access: Access) -> Result<String, String> {
+        match path {
+            Some(path) => {
+                let resolved =
+                    workspace::resolve_in(self.workspace.as_deref(), "git", path, access)?;
+                Ok(resolved.to_string_lossy().into_owned())
+            }
+            None => Ok(".".into()),
+        }
+    }
+
+    /// Jailed, changing the repository must not touch files outside the
+    /// workspace, so its whole working tree has to be inside.
+    async fn check_repo_inside(&self) -> Result<(), String> {
+        let Some(ws) = &self.workspace else {
+            return Ok(());
+        };
+        let top = self.git(&["rev-parse", "--show-toplevel"]).await?;
+        let top = Path::new(top.trim());
+        if ws.contains(top, Access::Write) {
+            Ok(())
+        } else {
+            Err(format!(
+                "repository {} extends outside the workspace; only read actions are allowed",
+                top.display()
+            ))
+        }
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String, String> {
+        self.git_with(args, None, LOCAL_TIMEOUT)
+            .await
+            .map(|(stdout, _)| stdout)
+    }
+
+    /// Run git with `args`, returning stdout and stderr. A non-zero exit is
+    /// an error carrying git's message.
+    async fn git_with(
+        &self,
+        args: &[&str],
+        stdin: Option<String>,
+        timeout: Duration,
+    ) -> Result<(String, String), String> {
+        let mut cmd = Command::new("git");
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(cmd.as_std_mut());
+        }
+        cmd.arg("-C")
+            .arg(self.repo_dir())
+            .args([
+                "--no-pager",
+                "-c",
+                "core.quotepath=off",
+                "-c",
+                "color.ui=never",
+                // Nothing the repository configures may run a program
+                "-c",
+                "core.hooksPath=/dev/null",
+                "-c",
+                "core.fsmonitor=false",
+                "-c",
+                "protocol.ext.allow=never",
+            ])
+            .args(args)
+            // Never wait on a credential prompt; don't refresh the index on reads
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = cmd.spawn().map_err(|e| format!("failed to run git: {e}"))?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input.as_bytes())
+                .await
+                .map_err(|e| format!("git {}: {e}", args[0]))?;
+        }
+        let output = tokio::time::timeout(timeout, child.wait_with_output())
+            .await
+            .map_err(|_| format!("git {} timed out after {}s", args[0], timeout.as_secs()))?
+            .map_err(|e| format!("git {}: {e}", args[0]))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if !output.status.success() {
+            let message = if stderr.trim().is_empty() {
+                &stdout
+            } else {
+                &stderr
+            };
+            return Err(format!("git {}: {}", args[0], message.trim()));
+        }
+        Ok((stdout, stderr))
+    }
+
+    async fn run(&self, req: &Request) -> Result<Value, String> {
+        self.check_policy(req)?;
+        let mutating = MUTATING_ACTIONS.contains(&req.action.as_str())
+            || (req.action == "branch" && req.name.is_some());
+        if mutating {
+            self.check_repo_inside().await?;
+        }
+        match req.action.as_str() {
+            "status" => self.status().await,
+            "diff" => self.diff(req).await,
+            "log" => self.log(req).await,
+            "blame" => self.blame(req).await,
+            "show" => self.show(req).await,
+            "branch" => self.branch(req).await,
+            "switch" => self.switch(req).await,
+            "stage" => self.stage(req).await,
+            "commit" => self.commit(req).await,
+            "fetch" | "pull" | "push" => self.remote(req).await,
+            "" => Err("missing required <action>".into()),
+            other => Err(format!(
+                "unknown action: {other} (expected status, diff, log, blame, show, branch, \
+                 switch, stage, commit, fetch, pull or push)"
+            )),
+        }
+    }
+
+    async fn status(&self) -> Result<Value, String> {
+        let out = self
+            .git(&["status", "--porcelain=v2", "--branch", "-z", "--", "."])
+            .await?;
+        Ok(parse_status(&out))
+    }
+
+    async fn diff(&self, req: &Request) -> Result<Value, String> {
+        let pathspec = self.pathspec(req.path.as_deref(), Access::Read)?;
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff", "-U3"];
+        if req.staged {
+            args.push("--cached");
+        }
+        if let Some(rev) = &req.rev {
+            args.push(option_safe(rev, "rev")?);
+        }
+        args.extend(["--", &pathspec]);
+        let out = self.git(&args).await?;
+        Ok(json!({ "files": diff_json(&parse_diff(&out)) }))
+    }
+
+    async fn log(&self, req: &Request) -> Result<Value, String> {
+        let pathspec = self.pathspec(req.path.as_deref(), Access::Read)?;
+        let limit = format!("-n{}", req.limit.unwrap_or(DEFAULT_LOG_LIMIT).max(1));
+        let mut args = vec!["log", COMMIT_FORMAT, &limit];
+        if let Some(rev) = &req.rev {
+            args.push(option_safe(rev, "rev")?);
+        }
+        args.extend(["--", &pathspec]);
+        let out = self.git(&args).await?;
+        let commits: Vec<Value> = out
+            .split('\x1e')
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| commit_json(r, false))
+            .collect();
+        Ok(json!({ "commits": commits }))
+    }
+
+    async fn blame(&self, req: &Request) -> Result<Value, String> {
+        let path = req.path.as_deref().ok_or("blame needs a <path>")?;
+        let pathspec = self.pathspec(Some(path), Access::Read)?;
+        let mut args = vec!["blame".to_string(), "--porcelain".to_string()];
+        match (req.start_line, req.end_line) {
+            (Some(start), Some(end)) => args.push(format!("-L{start},{end}")),
+            (Some(start), None) => args.push(format!("-L{start},")),
+            (None, Some(end)) => args.push(format!("-L1,{end}")),
+            (None, None) => {}
+        }
+        if let Some(rev) = &req.rev {
+            args.push(option_safe(rev, "rev")?.to_string());
+        }
+        args.extend(["--".to_string(), pathspec]);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let out = self.git(&args).await?;
+        Ok(parse_blame(&out))
+    }
+
+    async fn show(&self, req: &Request) -> Result<Value, String> {
+        let rev = option_safe(req.rev.as_deref().unwrap_or("HEAD"), "rev")?;
+        if let Some(path) = req.path.as_deref() {
+            // One file as of `rev`
+            let resolved = self.pathspec(Some(path), Access::Read)?;
+            let relative = self.relative_path(Path::new(&resolved))?;
+            let content = self.git(&["show", &format!("{rev}:./{relative}")]).await?;
+            return Ok(json!({ "rev": rev, "path": relative, "content": content }));
+        }
+        let out = self
+            .git(&[
+                "show",
+                COMMIT_FORMAT,
+                "--patch",
+                "--diff-merges=first-parent",
+                "--no-color",
+                "--no-ext-diff",
+                rev,
+                "--",
+                ".",
+            ])
+            .await?;
+        let (meta, patch) = out.split_once('\x1e').unwrap_or((&out, ""));
+        Ok(json!({
+            "commit": commit_json(meta, true),
+            "files": diff_json(&parse_diff(patch.trim_start_matches('\n'))),
+        }))
+    }
+
+    /// `path` relative to the directory git runs in.
+    fn relative_path(&self, path: &Path) -> Result<String, String> {
+        if path.is_relative() {
+            return Ok(path.to_string_lossy().into_owned());
+        }
+        let dir = self
+            .repo_dir()
+            .canonicalize()
+            .map_err(|e| format!("git: {e}"))?;
+        path.strip_prefix(&dir)
+            .map(|p| p.to_string_lossy().into_owned())
+            .map_err(|_| format!("path is outside the repository: {}", path.display()))
+    }
+
+    async fn branch(&self, req: &Request) -> Result<Value, String> {
+        let Some(name) = &req.name else {
+            let out = self
+                .git(&[
+                    "branch",
+                    "--format=%(HEAD)%1f%(refname:short)%1f%(objectname:short)%1f%(upstream:short)",
+                ])
+                .await?;
+            return Ok(parse_branches(&out));
+        };
+        let name = option_safe(name, "name")?;
+        let mut args = vec!["branch", name];
+        if let Some(rev) = &req.rev {
+            args.push(option_safe(rev, "rev")?);
+        }
+        self.git(&args).await?;
+        let commit = self.git(&["rev-parse", "--short", name]).await?;
+        Ok(json!({ "created": name, "commit": commit.trim() }))
+    }
+
+    async fn switch(&self, req: &Request) -> Result<Value, String> {
+        let name = option_safe(req.name.as_deref().unwrap_or_default(), "name")?;
+        let mut args = vec!["switch"];
+        if req.create {
+            args.push("-c");
+        }
+        args.push(name);
+        if let (true, Some(rev)) = (req.create, &req.rev) {
+            args.push(option_safe(rev, "rev")?);
+        }
+        self.git(&args).await?;
+        self.status().await
+    }
+
+    /// Stage a path (or everything), or only some of a file's hunks as
+    /// numbered by an unstaged `diff` of it.
+    async fn stage(&self, req: &Request) -> Result<Value, String> {
+        let pathspec = self.pathspec(req.path.as_deref(), Access::Write)?;
+        match &req.hunks {
+            None => {
+                self.git(&["add", "-A", "--", &pathspec]).await?;
+            }
+            Some(hunks) => {
+                if req.path.is_none() {
+                    return Err("staging hunks needs a <path>".into());
+                }
+                let out = self
+                    .git(&[
+                        "diff",
+                        "--no-color",
+                        "--no-ext-diff",
+                        "-U3",
+                        "--",
+                        &pathspec,
+                    ])
+                    .await?;
+                let files = parse_diff(&out);
+                let [file] = files.as_slice() else {
+                    return Err(format!(
+                        "no unstaged changes in {} to stage hunks from",
+                        pathspec
+                    ));
+                };
+                let patch = hunk_patch(file, hunks)?;
+                self.git_with(&["apply", "--cached", "-"], Some(patch), LOCAL_TIMEOUT)
+                    .await?;
+            }
+        }
+        self.status().await
+    }
+
+    async fn commit(&self, req: &Request) -> Result<Value, String> {
+        let message = req
+            .message
+            .as_deref()
+            .filter(|m| !m.trim().is_empty())
+            .ok_or("commit needs a <message>")?;
+        let mut args = vec!["commit", "-m", message];
+        if req.amend {
+            args.push("--amend");
+        }
+        self.git(&args).await?;
+        let out = self.git(&["log", "-n1", COMMIT_FORMAT]).await?;
+        let mut commit = commit_json(out.trim_end_matches(['\n', '\x1e']), false);
+        commit["amended"] = json!(req.amend);
+        Ok(commit)
+    }
+
+    async fn remote(&self, req: &Request) -> Result<Value, String> {
+        let action = req.action.as_str();
+        let mut args = vec![action];
+        match action {
+            "pull" => args.push("--ff-only"),
+            "push" if req.force => args.push("--force-with-lease"),
+            _ => {}
+        }
+        // A branch needs a remote in front of it
+        let remote = match (&req.remote, &req.name) {
+            (Some(remote), _) => Some(remote.as_str()),
+            (None, Some(_)) => Some("origin"),
+            (None, None) => None,
+        };
+        if let Some(remote) = remote {
+            args.push(option_safe(remote, "remote")?);
+        }
+        if let Some(name) = &req.name {
+            args.push(option_safe(name, "name")?);
+        }
+        let (stdout, stderr) = self.git_with(&args, None, REMOTE_TIMEOUT).await?;
+        // git reports progress and ref updates on stderr
+        let output = format!("{}\n{}", stdout.trim(), stderr.trim());
+        Ok(json!({ "action": action, "output": output.trim() }))
+    }
+}
+
+/// `value` if it can't be mistaken for an option.
+fn option_safe<'a>(value: &'a str, what: &str) -> Result<&'a str, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("missing required <{what}>"));
+    }
+    if value.starts_with('-') {
+        return Err(format!("invalid <{what}>: {value}"));
+    }
+    Ok(value)
+}
+
+/// Name a porcelain status letter.
+fn status_word(code: char) -> &'static str {
+    match code {
+        'M' => "modified",
+        'A' => "added",
+        'D' => "deleted",
+        'R' => "renamed",
+        'C' => "copied",
+        'T' => "type changed",
+        _ => "changed",
+    }
+}
+
+/// Parse `git status --porcelain=v2 --branch -z`.
+fn parse_status(out: &str) -> Value {
+    let mut branch = Value::Null;
+    let mut upstream = Value::Null;
+    let (mut ahead, mut behind) = (0i64, 0i64);
+    let (mut staged, mut unstaged, mut untracked, mut conflicted) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+
+    let mut records = out.split('\0');
+    while let Some(record) = records.next() {
+        if let Some(head) = record.strip_prefix("# branch.head ") {
+            if head != "(detached)" {
+                branch = json!(head);
+            }
+        } else if let Some(name) = record.strip_prefix("# branch.upstream ") {
+            upstream = json!(name);
+        } else if let Some(counts) = record.strip_prefix("# branch.ab ") {
+            for count in counts.split(' ') {
+                if let Some(n) = count.strip_prefix('+') {
+                    ahead = n.parse().unwrap_or(0);
+                } else if let Some(n) = count.strip_prefix('-') {
+                    behind = n.parse().unwrap_or(0);
+                }
+            }
+        } else if let Some(path) = record.strip_prefix("? ") {
+            untracked.push(json!(path));
+        } else if record.starts_with("1 ") || record.starts_with("2 ") {
+            let renamed = record.starts_with('2');
+            let fields: Vec<&str> = record.splitn(if renamed { 10 } else { 9 }, ' ').collect();
+            let (Some(xy), Some(path)) = (fields.get(1), fields.last()) else {
+                continue;
+            };
+            // A rename's source path is the next record
+            let from = if renamed { records.next() } else { None };
+            let mut codes = xy.chars();
+            let (x, y) = (codes.next().unwrap_or('.'), codes.next().unwrap_or('.'));
+            if x != '.' {
+                let mut entry = json!({ "path": path, "status": status_word(x) });
+                if let Some(from) = from {
+                    entry["from"] = json!(from);
+                }
+                staged.push(entry);
+            }
+            if y != '.' {
+                unstaged.push(json!({ "path": path, "status": status_word(y) }));
+            }
+        } else if record.starts_with("u ") {
+            if let Some(path) = record.splitn(11, ' ').nth(10) {
+                conflicted.push(json!(path));
+            }
+        }
+    }
+    let clean =
+        staged.is_empty() && unstaged.is_empty() && untracked.is_empty() && conflicted.is_empty();
+    json!({
+        "branch": branch,
+        "upstream": upstream,
+        "ahead": ahead,
+        "behind": behind,
+        "staged": staged,
+        "unstaged": unstaged,
+        "untracked": untracked,
+        "conflicted": conflicted,
+        "clean": clean,
+    })
+}
+
+/// The path of a `diff --git a/X b/X` header, when both sides agree.
+fn header_path(rest: &str) -> String {
+    let half = rest.len() / 2;
+    if rest.len() % 2 == 1 && rest.is_char_boundary(half) {
+        let (a, b) = (&rest[..half], &rest[half + 1..]);
+        if let (Some(a), Some(b)) = (a.strip_prefix("a/"), b.strip_prefix("b/")) {
+            if a == b {
+                return b.to_string();
+            }
+        }
+    }
+    rest.rsplit(" b/").next().unwrap_or(rest).to_string()
+}
+
+/// Split unified diff output into files and hunks.
+fn parse_diff(out: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    if out.is_empty() {
+        return files;
+    }
+    for line in out.strip_suffix('\n').unwrap_or(out).split('\n') {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.push(FileDiff {
+                path: header_path(rest),
+                status: "modified",
+                header: vec![line.to_string()],
+                ..FileDiff::default()
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        } else {
+            if let Some(path) = line.strip_prefix("+++ b/") {
+                file.path = path.to_string();
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                file.old_path = Some(from.to_string());
+                file.status = "renamed";
+            } else if let Some(to) = line.strip_prefix("rename to ") {
+                file.path = to.to_string();
+            } else if line.starts_with("new file mode") {
+                file.status = "added";
+            } else if line.starts_with("deleted file mode") {
+                file.status = "deleted";
+            } else if line.starts_with("Binary files") {
+                file.binary = true;
+            }
+            file.header.push(line.to_string());
+        }
+    }
+    files
+}
+
+fn diff_json(files: &[FileDiff]) -> Vec<Value> {
+    files
+        .iter()
+        .map(|file| {
+            let count = |prefix: char| {
+                file.hunks
+                    .iter()
+                    .flat_map(|h| &h.lines)
+                    .filter(|l| l.starts_with(prefix))
+                    .count()
+            };
+            let hunks: Vec<Value> = file
+                .hunks
+                .iter()
+                .enumerate()
+                .map(|(i, h)| json!({ "index": i, "header": h.header, "lines": h.lines }))
+                .collect();
+            let mut entry = json!({
+                "path": file.path,
+                "status": file.status,
+                "additions": count('+'),
+                "deletions": count('-'),
+                "hunks": hunks,
+            });
+            if let Some(old) = &file.old_path {
+                entry["old_path"] = json!(old);
+            }
+            if file.binary {
+                entry["binary"] = json!(true);
+            }
+            entry
+        })
+        .collect()
+}
+
+/// A patch of `file` holding only the hunks at `indices`.
+fn hunk_patch(file: &FileDiff, indices: &[usize]) -> Result<String, String> {
+    if file.binary {
+        return Err(format!("{} is binary; stage it whole", file.path));
+    }
+    if indices.is_empty() {
+        return Err("<hunks> is empty".into());
+    }
+    if let Some(bad) = indices.iter().find(|&&i| i >= file.hunks.len()) {
+        return Err(format!(
+            "no hunk {bad} in {} (it has {})",
+            file.path,
+            file.hunks.len()
+        ));
+    }
+    let mut patch = String::new();
+    for line in &file.header {
+        patch.push_str(line);
+        patch.push('\n');
+    }
+    for (_, hunk) in file
+        .hunks
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| indices.contains(i))
+    {
+        patch.push_str(&hunk.header);
+        patch.push('\n');
+        for line in &hunk.lines {
+            patch.push_str(line);
+            patch.push('\n');
+        }
+    }
+    Ok(patch)
+}
+
+/// One record of [`COMMIT_FORMAT`] output.
+fn commit_json(record: &str, with_body: bool) -> Value {
+    let fields: Vec<&str> = record.trim_start_matches('\n').split('\x1f').collect();
+    let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+    let mut commit = json!({
+        "hash": field(0),
+        "short": field(1),
+        "author": field(2),
+        "email": field(3),
+        "date": field(4),
+        "subject": field(5),
+    });
+    if with_body {
+        commit["body"] = json!(field(6).trim());
+    }
+    commit
+}
+
+/// Parse `git branch --format=%(HEAD)%1f%(refname:short)%1f…`.
+fn parse_branches(out: &str) -> Value {
+    let mut current = Value::Null;
+    let branches: Vec<Value> = out
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\x1f').collect();
+            let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+            if field(0) == "*" {
+                current = json!(field(1));
+            }
+            let mut branch = json!({ "name": field(1), "commit": field(2) });
+            if !field(3).is_empty() {
+                branch["upstream"] = json!(field(3));
+            }
+            branch
+        })
+        .collect();
+    json!({ "current": current, "branches": branches })
+}
+
+/// Parse `git blame --porcelain` into per-line commits and a commit table.
+fn parse_blame(out: &str) -> Value {
+    let mut commits: BTreeMap<String, Value> = BTreeMap::new();
+    let mut lines = Vec::new();
+    // (commit, final line number) of the line being described
+    let mut current: Option<(String, u64)> = None;
+    for line in out.lines() {
+        if let Some(text) = line.strip_prefix('\t') {
+            if let Some((sha, number)) = current.take() {
+                let short: String = sha.chars().take(8).collect();
+                lines.push(json!({ "line": number, "commit": short, "text": text }));
+            }
+            continue;
+        }
+        match &current {
+            Some((sha, _)) => {
+                let short: String = sha.chars().take(8).collect();
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                let info = commits.entry(short).or_insert_with(|| json!({}));
+                match key {
+                    "author" => info["author"] = json!(value),
+                    "author-time" => info["time"] = json!(value.parse::<i64>().unwrap_or(0)),
+                    "summary" => info["summary"] = json!(value),
+                    _ => {}
+                }
+            }
+            None => {
+                let mut parts = line.split(' ');
+                let sha = parts.next().unwrap_or_default();
+                let number = parts.nth(1).and_then(|n| n.parse().ok()).unwrap_or(0);
+                current = Some((sha.to_string(), number));
+            }
+        }
+    }
+    json!({ "commits": commits, "lines": lines })
+}
+
+#[async_trait]
+impl Handler for GitTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+        let result = match Request::parse(&xml_str) {
+            Ok(req) => self.run(&req).await,
+            Err(e) => Err(e),
+        };
+        let payload_xml = match result {
+            Ok(value) => {
+                let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for GitTool {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Git operations with JSON results. Actions: status; diff (unstaged, staged, or against rev; hunks are numbered); log; blame; show (a commit, or a file at rev); branch (list, or create name); switch (to name, create to make it); stage (a path, everything, or some hunks of a path by their unstaged diff numbers); commit; fetch, pull (fast-forward only), push.
+interface git {
+    record request {
+        /// status, diff, log, blame, show, branch, switch, stage, commit, fetch, pull or push
+        action: string,
+        /// File or directory, relative to the workspace
+        path: option<string>,
+        /// Commit, branch or range: diff against it, log from it, blame or show at it, start a branch from it
+        rev: option<string>,
+        /// diff: staged changes instead of unstaged
+        staged: option<bool>,
+        /// stage: hunk numbers from an unstaged diff of path
+        hunks: option<list<u32>>,
+        /// branch, switch: branch name; fetch, pull, push: remote branch
+        name: option<string>,
+        /// switch: create the branch first
+        create: option<bool>,
+        /// commit: commit message
+        message: option<string>,
+        /// commit: amend the last commit (rewrites history)
+        amend: option<bool>,
+        /// push: force with lease (rewrites remote history)
+        force: option<bool>,
+        /// fetch, pull, push: remote name
+        remote: option<string>,
+        /// log: most commits to list (default: 20)
+        limit: option<u32>,
+        /// blame: first line (1-based)
+        start-line: option<u32>,
+        /// blame: last line
+        end-line: option<u32>,
+    }
+    run: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "git".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "GitRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    /// A repository with one commit of `a.txt`, and a tool jailed to it.
+    fn repo() -> (TempDir, GitTool) {
+        let dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["config", "user.email", "test@example.com"]);
+        let lines: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        std::fs::write(dir.path().join("a.txt"), lines).unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-q", "-m", "first"]);
+        let mut tool = GitTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+        (dir, tool)
+    }
+
+    async fn call(tool: &GitTool, fields: &str) -> (bool, Value) {
+        let xml = format!("<GitRequest>{fields}</GitRequest>");
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        match serde_json::from_str(&content) {
+            Ok(value) => (ok, value),
+            Err(_) => (ok, json!(content)),
+        }
+    }
+
+    #[tokio::test]
+    async fn status_diff_and_hunk_staging() {
+        let (dir, tool) = repo();
+        let edited = std::fs::read_to_string(dir.path().join("a.txt"))
+            .unwrap()
+            .replace("line 2\n", "line two\n")
+            .replace("line 19\n", "line nineteen\n");
+        std::fs::write(dir.path().join("a.txt"), edited).unwrap();
+        std::fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+
+        let (ok, status) = call(&tool, "<action>status</action>").await;
+        assert!(ok, "{status}");
+        assert_eq!(status["branch"], "main");
+        assert_eq!(
+            status["unstaged"],
+            json!([{ "path": "a.txt", "status": "modified" }])
+        );
+        assert_eq!(status["untracked"], json!(["new.txt"]));
+
+        let (ok, diff) = call(&tool, "<action>diff</action><path>a.txt</path>").await;
+        assert!(ok, "{diff}");
+        let file = &diff["files"][0];
+        assert_eq!(file["path"], "a.txt");
+        assert_eq!(
+            (file["additions"].as_u64(), file["deletions"].as_u64()),
+            (Some(2), Some(2))
+        );
+        assert_eq!(file["hunks"].as_array().unwrap().len(), 2);
+
+        // Stage only the second hunk
+        let (ok, status) = call(
+            &tool,
+            "<action>stage</action><path>a.txt</path><hunks>[1]</hunks>",
+        )
+        .await;
+        assert!(ok, "{status}");
+        let (_, staged) = call(&tool, "<action>diff</action><staged>true</staged>").await;
+        let lines = staged["files"][0]["hunks"][0]["lines"].to_string();
+        assert!(lines.contains("+line nineteen") && !lines.contains("line two"));
+        assert_eq!(status["staged"][0]["path"], "a.txt");
+        assert_eq!(status["unstaged"][0]["path"], "a.txt");
+
+        let (ok, err) = call(
+            &tool,
+            "<action>stage</action><path>a.txt</path><hunks>[5]</hunks>",
+        )
+        .await;
+        assert!(!ok);
+        assert!(err.as_str().unwrap().contains("no hunk 5"));
+    }
+
+    #[tokio::test]
+    async fn commit_log_blame_show_and_branches() {
+        let (dir, tool) = repo();
+        std::fs::write(dir.path().join("b.txt"), "bee\n").unwrap();
+        call(&tool, "<action>stage</action><path>b.txt</path>").await;
+        let (ok, commit) = call(&tool, "<action>commit</action><message>add b</message>").await;
+        assert!(ok, "{commit}");
+        assert_eq!(commit["subject"], "add b");
+        assert_eq!(commit["amended"], false);
+
+        let (_, log) = call(&tool, "<action>log</action>").await;
+        let subjects: Vec<&str> = log["commits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["subject"].as_str().unwrap())
+            .collect();
+        assert_eq!(subjects, vec!["add b", "first"]);
+
+        let (ok, blame) = call(
+            &tool,
+            "<action>blame</action><path>a.txt</path><start_line>3</start_line><end_line>4</end_line>",
+        )
+        .await;
+        assert!(ok, "{blame}");
+        assert_eq!(
+            (&blame["lines"][0]["line"], &blame["lines"][0]["text"]),
+            (&json!(3), &json!("line 3"))
+        );
+        assert_eq!(blame["lines"].as_array().unwrap().len(), 2);
+        let short = blame["lines"][0]["commit"].as_str().unwrap();
+        assert_eq!(blame["commits"][short]["summary"], "first");
+
+        let (_, show) = call(&tool, "<action>show</action>").await;
+        assert_eq!(show["commit"]["subject"], "add b");
+        assert_eq!(show["files"][0]["status"], "added");
+        let (_, old) = call(
+            &tool,
+            "<action>show</action><rev>HEAD~1</rev><path>a.txt</path>",
+        )
+        .await;
+        assert!(old["content"].as_str().unwrap().starts_with("line 1\n"));
+
+        let (ok, _) = call(
+            &tool,
+            "<action>switch</action><name>topic</name><create>true</create>",
+        )
+        .await;
+        assert!(ok);
+        let (_, branches) = call(&tool, "<action>branch</action>").await;
+        assert_eq!(branches["current"], "topic");
+        assert_eq!(branches["branches"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn policy_and_jail_are_enforced() {
+        let (_dir, tool) = repo();
+        let tool = GitTool {
+            policy: GitPolicySpec {
+                forbid_rewrite: true,
+                forbid_remote: true,
+            },
+            ..tool
+        };
+        let (ok, err) = call(
+            &tool,
+            "<action>commit</action><message>x</message><amend>true</amend>",
+        )
+        .await;
+        assert!(!ok);
+        assert!(err.as_str().unwrap().contains("forbids rewriting history"));
+        let (ok, err) = call(&tool, "<action>push</action>").await;
+        assert!(!ok);
+        assert!(err.as_str().unwrap().contains("forbids remote operations"));
+
+        let (ok, err) = call(&tool, "<action>diff</action><path>../outside</path>").await;
+        assert!(!ok);
+        assert!(err.as_str().unwrap().contains("outside workspace"));
+        let (ok, err) = call(&tool, "<action>log</action><rev>--output=/tmp/x</rev>").await;
+        assert!(!ok);
+        assert!(err.as_str().unwrap().contains("invalid <rev>"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn repository_hooks_never_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, tool) = repo();
+        let hook = dir.path().join(".git/hooks/pre-commit");
+        let marker = dir.path().join("hook-ran");
+        std::fs::write(
+            &hook,
+            format!("#!/bin/sh\ntouch {}\nexit 1\n", marker.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        std::fs::write(dir.path().join("b.txt"), "bee\n").unwrap();
+        call(&tool, "<action>stage</action><path>b.txt</path>").await;
+        let (ok, commit) = call(&tool, "<action>commit</action><message>add b</message>").await;
+        assert!(ok, "{commit}");
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn jailed_subdirectory_is_read_only() {
+        let (dir, _) = repo();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let mut tool = GitTool::new();
+        let sub = dir.path().join("sub");
+        tool.set_workspace(Arc::new(Workspace::new("coding", &sub, &[]).unwrap()));
+        let (ok, status) = call(&tool, "<action>status</action>").await;
+        assert!(ok, "{status}");
+        assert_eq!(status["clean"], true);
+        let (ok, err) = call(&tool, "<action>commit</action><message>x</message>").await;
+        assert!(!ok);
+        assert!(err
+            .as_str()
+            .unwrap()
+            .contains("extends outside the workspace"));
+    }
+
+    #[test]
+    fn parse_status_renames_and_conflicts() {
+        let out = "# branch.oid abc\0# branch.head main\0# branch.upstream origin/main\0\
+                   # branch.ab +2 -1\0\
+                   2 R. N... 100644 100644 100644 1111 1111 R100 new name.rs\0old.rs\0\
+                   u UU N... 100644 100644 100644 100644 1 2 3 both.rs\0";
+        let status = parse_status(out);
+        assert_eq!(status["upstream"], "origin/main");
+        assert_eq!(
+            (status["ahead"].as_i64(), status["behind"].as_i64()),
+            (Some(2), Some(1))
+        );
+        assert_eq!(
+            status["staged"],
+            json!([{ "path": "new name.rs", "status": "renamed", "from": "old.rs" }])
+        );
+        assert_eq!(status["conflicted"], json!(["both.rs"]));
+        assert_eq!(status["clean"], false);
+    }
+
+    #[test]
+    fn git_metadata() {
+        let tool = GitTool::new();
+        assert_eq!(tool.name(), "git");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "GitRequest");
+    }
+}
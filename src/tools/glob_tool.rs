@@ -1,15 +1,291 @@
 //! GlobTool — find files by glob pattern.
+//!
+//! The pattern's literal leading directories are split off as the base, and
+//! the base is walked honouring `.gitignore` / `.ignore` (see
+//! [`super::ignore`]) unless asked not to. `.git` and symlinked directories
+//! are never entered, and a pattern without `**` is not walked deeper than
+//! it can match. Matches come back most recently modified first, so the file
+//! just touched is at the top, or sorted by path.
 
 use async_trait::async_trait;
+use glob::{MatchOptions, Pattern};
 use rust_pipeline::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
+use super::ignore::IgnoreStack;
+use super::workspace::{Access, Workspace};
 use super::{extract_tag, ToolPeer, ToolResponse};
 
 /// Find files matching a glob pattern.
-pub struct GlobTool;
+#[derive(Default)]
+pub struct GlobTool {
+    workspace: Option<Arc<Workspace>>,
+}
 
 const MAX_RESULTS: usize = 1000;
 
+/// Entries visited per call before the walk gives up.
+const MAX_VISITED: usize = 200_000;
+
+/// `*` and `?` never cross a `/`; `**` does.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Result order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sort {
+    /// Most recently modified first.
+    Mtime,
+    Path,
+}
+
+/// Which matches are kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Any,
+    File,
+    Dir,
+}
+
+/// An exclude pattern. Without a `/` it matches a name at any depth, like
+/// a gitignore rule; with one, a path relative to the base. Excluded
+/// directories are not entered.
+struct Exclude {
+    pattern: Pattern,
+    by_name: bool,
+}
+
+impl Exclude {
+    fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim().trim_end_matches('/');
+        let pattern = Pattern::new(s).map_err(|e| format!("invalid exclude pattern {s}: {e}"))?;
+        Ok(Self {
+            pattern,
+            by_name: !s.contains('/'),
+        })
+    }
+
+    fn matches(&self, rel: &str, name: &str) -> bool {
+        let subject = if self.by_name { name } else { rel };
+        self.pattern.matches_with(subject, MATCH_OPTIONS)
+    }
+}
+
+/// What one search looks for.
+struct Query {
+    pattern: Pattern,
+    excludes: Vec<Exclude>,
+    kind: Kind,
+    sort: Sort,
+    use_ignores: bool,
+    /// Path components the pattern can match; None with `**`.
+    max_depth: Option<usize>,
+}
+
+/// State of one walk.
+struct Walk<'a> {
+    query: &'a Query,
+    jail: Option<&'a Workspace>,
+    visited: usize,
+    unreadable: usize,
+    found: Vec<(PathBuf, SystemTime)>,
+}
+
+impl Walk<'_> {
+    /// Collect matches under `dir`, whose path relative to the base is `rel`.
+    fn walk(&mut self, dir: &Path, rel: &Path, ignores: &IgnoreStack) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            self.unreadable += 1;
+            return;
+        };
+        let depth = rel.components().count() + 1;
+        for entry in entries.filter_map(|e| e.ok()) {
+            if self.visited >= MAX_VISITED {
+                return;
+            }
+            self.visited += 1;
+
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_link = entry.file_type().map_or(true, |t| t.is_symlink());
+            if let Some(ws) = self.jail {
+                if is_link && !ws.contains(&path, Access::Read) {
+                    continue;
+                }
+            }
+            let is_dir = path.is_dir();
+            if is_dir && name == ".git" {
+                continue;
+            }
+            if self.query.use_ignores && ignores.is_ignored(&path, is_dir) {
+                continue;
+            }
+            let entry_rel = rel.join(&name);
+            let rel_str = entry_rel.to_string_lossy();
+            if self
+                .query
+                .excludes
+                .iter()
+                .any(|x| x.matches(&rel_str, &name))
+            {
+                continue;
+            }
+
+            let wanted = match self.query.kind {
+                Kind::Any => true,
+                Kind::File => !is_dir,
+                Kind::Dir => is_dir,
+            };
+            if wanted && self.query.pattern.matches_with(&rel_str, MATCH_OPTIONS) {
+                let mtime = std::fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                self.found.push((entry_rel.clone(), mtime));
+            }
+
+            let deeper = self.query.max_depth.is_none_or(|max| depth < max);
+            if is_dir && !is_link && deeper {
+                let nested = if self.query.use_ignores {
+                    ignores.descend(&path)
+                } else {
+                    IgnoreStack::default()
+                };
+                self.walk(&path, &entry_rel, &nested);
+            }
+        }
+    }
+}
+
+/// Split a pattern into its literal leading directories and the glob below
+/// them. The last component always stays in the glob.
+fn split_pattern(pattern: &str) -> (String, String) {
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let literal = parts[..parts.len() - 1]
+        .iter()
+        .take_while(|p| !p.contains(['*', '?', '[']))
+        .count();
+    let mut base = parts[..literal].join("/");
+    if base.is_empty() && pattern.starts_with('/') {
+        base.push('/');
+    }
+    let rest = parts[literal..].join("/");
+    let rest = if literal == 0 {
+        rest.trim_start_matches('/').to_string()
+    } else {
+        rest
+    };
+    (base, rest)
+}
+
+impl GlobTool {
+    /// The search options of a request.
+    fn query(xml: &str, pattern: Pattern, glob: &str) -> Result<Query, String> {
+        let sort = match extract_tag(xml, "sort").as_deref().map(str::trim) {
+            None | Some("") | Some("mtime") => Sort::Mtime,
+            Some("path") => Sort::Path,
+            Some(other) => return Err(format!("invalid <sort>: {other} (expected mtime or path)")),
+        };
+        let kind = match extract_tag(xml, "kind").as_deref().map(str::trim) {
+            None | Some("") | Some("any") => Kind::Any,
+            Some("file") => Kind::File,
+            Some("dir") => Kind::Dir,
+            Some(other) => {
+                return Err(format!(
+                    "invalid <kind>: {other} (expected file, dir or any)"
+                ));
+            }
+        };
+        let excludes: Vec<String> =
+            match extract_tag(xml, "exclude").filter(|s| !s.trim().is_empty()) {
+                Some(json) => {
+                    serde_json::from_str(&json).map_err(|e| format!("invalid <exclude>: {e}"))?
+                }
+                None => Vec::new(),
+            };
+        let excludes = excludes
+            .iter()
+            .map(|x| Exclude::parse(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let use_ignores = extract_tag(xml, "include_ignored").is_none_or(|v| v.trim() != "true");
+        let max_depth = (!glob.contains("**")).then(|| glob.split('/').count());
+        Ok(Query {
+            pattern,
+            excludes,
+            kind,
+            sort,
+            use_ignores,
+            max_depth,
+        })
+    }
+
+    /// Search `root` (shown as `shown`) for `query`. Blocking.
+    fn run(root: &Path, shown: &Path, query: &Query, jail: Option<&Workspace>) -> String {
+        let mut walk = Walk {
+            query,
+            jail,
+            visited: 0,
+            unreadable: 0,
+            found: Vec::new(),
+        };
+        // A base that doesn't exist simply matches nothing, as with glob(3)
+        if let Some(root) = root.canonicalize().ok().filter(|r| r.is_dir()) {
+            let ignores = if query.use_ignores {
+                IgnoreStack::for_dir(&root)
+            } else {
+                IgnoreStack::default()
+            };
+            walk.walk(&root, Path::new(""), &ignores);
+        }
+
+        let mut found = walk.found;
+        match query.sort {
+            Sort::Mtime => found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))),
+            Sort::Path => found.sort_by(|a, b| a.0.cmp(&b.0)),
+        }
+        let total = found.len();
+        let results: Vec<String> = found
+            .iter()
+            .take(MAX_RESULTS)
+            .map(|(rel, _)| shown.join(rel).display().to_string())
+            .collect();
+
+        let noun = match query.kind {
+            Kind::Dir => "directories",
+            Kind::Any | Kind::File => "files",
+        };
+        let mut output = results.join("\n");
+        if total > MAX_RESULTS {
+            let order = match query.sort {
+                Sort::Mtime => "most recent",
+                Sort::Path => "first",
+            };
+            output.push_str(&format!(
+                "\n\n... {} more not shown ({total} {noun} matched, showing the {MAX_RESULTS} {order})",
+                total - MAX_RESULTS
+            ));
+        } else {
+            output.push_str(&format!("\n\n{total} {noun} matched"));
+        }
+        if walk.visited >= MAX_VISITED {
+            output.push_str(&format!(
+                "\n(search stopped after {MAX_VISITED} entries; narrow the pattern or base_path)"
+            ));
+        }
+        if walk.unreadable > 0 {
+            output.push_str(&format!(
+                "\n({} directories could not be read)",
+                walk.unreadable
+            ));
+        }
+        output
+    }
+}
+
 #[async_trait]
 impl Handler for GlobTool {
     async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
@@ -24,52 +300,75 @@ impl Handler for GlobTool {
 
         let base_path = extract_tag(&xml_str, "base_path").unwrap_or_default();
 
-        // Build full pattern
-        let full_pattern = if base_path.is_empty() {
-            pattern.clone()
+        // Find the directory to walk. When jailed, the base is resolved inside
+        // the workspace and the pattern itself may not climb out of it.
+        let (root, glob) = if let Some(ref ws) = self.workspace {
+            if pattern.starts_with('/') || pattern.starts_with('\\') || pattern.contains("..") {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&format!(
+                        "pattern must be relative to the workspace: {pattern}"
+                    )),
+                });
+            }
+            let base = if base_path.is_empty() {
+                "."
+            } else {
+                base_path.as_str()
+            };
+            let (prefix, glob) = split_pattern(&pattern);
+            let dir = if prefix.is_empty() {
+                base.to_string()
+            } else {
+                format!("{base}/{prefix}")
+            };
+            match ws.resolve("glob", &dir, Access::Read) {
+                Ok(dir) => (dir, glob),
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            }
         } else {
-            let base = base_path.trim_end_matches('/').trim_end_matches('\\');
-            format!("{base}/{pattern}")
+            let full = if base_path.is_empty() {
+                pattern.clone()
+            } else {
+                let base = base_path.trim_end_matches('/').trim_end_matches('\\');
+                format!("{base}/{pattern}")
+            };
+            let (prefix, glob) = split_pattern(&full);
+            (PathBuf::from(prefix), glob)
+        };
+        let shown = root.clone();
+        let root = if root.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            root
         };
 
-        let entries = match glob::glob(&full_pattern) {
-            Ok(paths) => paths,
+        let compiled = match Pattern::new(&glob) {
+            Ok(p) => p,
             Err(e) => {
                 return Ok(HandlerResponse::Reply {
                     payload_xml: ToolResponse::err(&format!("invalid glob pattern: {e}")),
                 });
             }
         };
-
-        let mut results: Vec<String> = Vec::new();
-        let mut total = 0usize;
-
-        for entry in entries {
-            match entry {
-                Ok(path) => {
-                    total += 1;
-                    if results.len() < MAX_RESULTS {
-                        results.push(path.display().to_string());
-                    }
-                }
-                Err(e) => {
-                    // Skip unreadable entries
-                    total += 1;
-                    if results.len() < MAX_RESULTS {
-                        results.push(format!("(error: {e})"));
-                    }
-                }
+        let query = match Self::query(&xml_str, compiled, &glob) {
+            Ok(q) => q,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
             }
-        }
+        };
 
-        results.sort();
-
-        let mut output = results.join("\n");
-        if total > MAX_RESULTS {
-            output.push_str(&format!("\n\n... ({total} total, showing first {MAX_RESULTS})"));
-        } else {
-            output.push_str(&format!("\n\n{total} files matched"));
-        }
+        // The walk is blocking; keep it off the runtime.
+        let jail = self.workspace.clone();
+        let output =
+            tokio::task::spawn_blocking(move || Self::run(&root, &shown, &query, jail.as_deref()))
+                .await
+                .map_err(|e| PipelineError::Handler(format!("glob task panicked: {e}")))?;
 
         Ok(HandlerResponse::Reply {
             payload_xml: ToolResponse::ok(&output),
@@ -83,22 +382,33 @@ impl ToolPeer for GlobTool {
         "glob"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Find files matching a glob pattern (e.g. **/*.rs, src/*.txt).
+/// Find files matching a glob pattern (e.g. **/*.rs, src/*.txt). Honours .gitignore and .ignore unless include-ignored is set, and never looks inside .git. Results are most recently modified first unless sort is path; at most 1000 are listed and the rest counted.
 interface glob {
     record request {
         /// The glob pattern to match (e.g. **/*.rs)
         pattern: string,
         /// Base directory for the pattern (default: current directory)
         base-path: option<string>,
+        /// mtime (default, newest first) or path
+        sort: option<string>,
+        /// file, dir or any (default)
+        kind: option<string>,
+        /// JSON list of glob patterns to leave out; a pattern without / matches a name at any depth, and excluded directories are skipped whole
+        exclude: option<string>,
+        /// Also match files ignored by .gitignore and .ignore
+        include-ignored: option<bool>,
     }
     search: func(req: request) -> result<string, string>;
 }
 "#
     }
 }
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,7 +454,7 @@ mod tests {
 
         let pattern = dir.path().join("*.rs").to_str().unwrap().to_string();
         let xml = format!("<GlobRequest><pattern>{pattern}</pattern></GlobRequest>");
-        let (ok, content) = get_result(GlobTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("a.rs"));
         assert!(content.contains("b.rs"));
@@ -161,7 +471,7 @@ mod tests {
         let xml = format!(
             "<GlobRequest><pattern>*.txt</pattern><base_path>{base}</base_path></GlobRequest>"
         );
-        let (ok, content) = get_result(GlobTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("test.txt"));
     }
@@ -171,7 +481,7 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let pattern = dir.path().join("*.nonexistent").to_str().unwrap().to_string();
         let xml = format!("<GlobRequest><pattern>{pattern}</pattern></GlobRequest>");
-        let (ok, content) = get_result(GlobTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("0 files matched"));
     }
@@ -179,7 +489,7 @@ mod tests {
     #[tokio::test]
     async fn glob_invalid_pattern() {
         let xml = "<GlobRequest><pattern>[invalid</pattern></GlobRequest>";
-        let (ok, content) = get_result(GlobTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("invalid glob"));
     }
@@ -187,7 +497,7 @@ mod tests {
     #[tokio::test]
     async fn glob_missing_pattern() {
         let xml = "<GlobRequest></GlobRequest>";
-        let (ok, content) = get_result(GlobTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("missing required"));
     }
@@ -202,14 +512,118 @@ mod tests {
 
         let pattern = dir.path().join("**/*.rs").to_str().unwrap().to_string();
         let xml = format!("<GlobRequest><pattern>{pattern}</pattern></GlobRequest>");
-        let (ok, content) = get_result(GlobTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("nested.rs"));
     }
 
+    #[tokio::test]
+    async fn glob_jailed_to_workspace() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+
+        let mut tool = GlobTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        let xml = "<GlobRequest><pattern>**/*.rs</pattern></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("lib.rs"));
+
+        let xml = "<GlobRequest><pattern>../*</pattern></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("relative to the workspace"));
+
+        let xml = "<GlobRequest><pattern>*</pattern><base_path>/etc</base_path></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+    }
+
+    fn touch(path: &Path, secs_ago: u64) {
+        let when = SystemTime::now() - std::time::Duration::from_secs(secs_ago);
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(when).unwrap();
+    }
+
+    #[tokio::test]
+    async fn glob_sorts_newest_first_or_by_path() {
+        let dir = TempDir::new().unwrap();
+        for (name, age) in [("a.rs", 300), ("b.rs", 10), ("c.rs", 100)] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+            touch(&dir.path().join(name), age);
+        }
+        let mut tool = GlobTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        let xml = "<GlobRequest><pattern>*.rs</pattern></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        let names: Vec<&str> = content.lines().take(3).map(|l| l.rsplit('/').next().unwrap()).collect();
+        assert_eq!(names, ["b.rs", "c.rs", "a.rs"]);
+
+        let xml = "<GlobRequest><pattern>*.rs</pattern><sort>path</sort></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        let names: Vec<&str> = content.lines().take(3).map(|l| l.rsplit('/').next().unwrap()).collect();
+        assert_eq!(names, ["a.rs", "b.rs", "c.rs"]);
+    }
+
+    #[tokio::test]
+    async fn glob_honours_ignores_excludes_and_type() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for d in ["src/gen", "target/debug", "node_modules/x", ".git/objects"] {
+            std::fs::create_dir_all(root.join(d)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\nnode_modules/\n").unwrap();
+        for f in ["src/lib.rs", "src/gen/api.rs", "src/lib_test.rs", "target/debug/build.rs", "node_modules/x/y.rs"] {
+            std::fs::write(root.join(f), "").unwrap();
+        }
+        std::fs::write(root.join(".git/objects/z.rs"), "").unwrap();
+        let mut tool = GlobTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", root, &[]).unwrap()));
+
+        let xml = "<GlobRequest><pattern>**/*.rs</pattern><sort>path</sort></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.ends_with("3 files matched"), "{content}");
+        assert!(!content.contains("target") && !content.contains("node_modules") && !content.contains(".git"));
+
+        let xml = r#"<GlobRequest><pattern>**/*.rs</pattern><exclude>["gen", "*_test.rs"]</exclude></GlobRequest>"#;
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.starts_with(&format!("{}/src/lib.rs\n\n1 files matched", root.canonicalize().unwrap().display())), "{content}");
+
+        let xml = "<GlobRequest><pattern>**/*.rs</pattern><include_ignored>true</include_ignored></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.ends_with("5 files matched"), "{content}");
+
+        let xml = "<GlobRequest><pattern>src/*</pattern><kind>dir</kind></GlobRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.ends_with("src/gen\n\n1 directories matched"), "{content}");
+    }
+
+    #[tokio::test]
+    async fn glob_reports_how_many_were_not_shown() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..(MAX_RESULTS + 3) {
+            std::fs::write(dir.path().join(format!("f{i:04}.txt")), "").unwrap();
+        }
+        let base = dir.path().to_str().unwrap();
+        let xml = format!("<GlobRequest><pattern>*.txt</pattern><base_path>{base}</base_path><sort>path</sort></GlobRequest>");
+        let (ok, content) = get_result(GlobTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert_eq!(content.lines().filter(|l| l.ends_with(".txt")).count(), MAX_RESULTS);
+        assert!(content.ends_with("... 3 more not shown (1003 files matched, showing the 1000 first)"), "{content}");
+    }
+
     #[test]
     fn glob_metadata() {
-        let tool = GlobTool;
+        let tool = GlobTool::default();
         assert_eq!(tool.name(), "glob");
         let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
         assert_eq!(iface.name, "glob");
@@ -1,17 +1,94 @@
 //! GrepTool — regex search across files.
+//!
+//! The walk honours `.gitignore` / `.ignore` (see [`super::ignore`]) and
+//! skips dotfiles and binaries. Both the walk and the search of the files it
+//! finds run on a small pool of scoped threads, on the blocking pool rather
+//! than the async runtime. Output is ordered by path regardless of which
+//! thread finished first.
+//!
+//! Content output follows ripgrep: `path:N:line` for matching lines,
+//! `path-N-line` for context lines, `--` between non-adjacent groups.
 
 use async_trait::async_trait;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use rust_pipeline::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 
+use super::ignore::IgnoreStack;
+use super::output::{self, OutputStore};
+use super::workspace::{self, Access, Workspace};
 use super::{extract_tag, ToolPeer, ToolResponse};
 
 /// Regex search across files in a directory tree.
-pub struct GrepTool;
+#[derive(Default)]
+pub struct GrepTool {
+    workspace: Option<Arc<Workspace>>,
+    output_store: Option<Arc<OutputStore>>,
+}
 
+/// Matches (or files, or counts) shown before the output is truncated.
 const MAX_MATCHES: usize = 500;
 
+/// Upper bound on walk and search threads per call.
+const MAX_WORKERS: usize = 8;
+
+/// What the search reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputMode {
+    /// Matching lines, with any requested context.
+    Content,
+    /// Only the paths of files that match.
+    FilesWithMatches,
+    /// Per-file match counts.
+    Count,
+}
+
+impl OutputMode {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().replace('-', "_").as_str() {
+            "content" => Ok(Self::Content),
+            "files_with_matches" => Ok(Self::FilesWithMatches),
+            "count" => Ok(Self::Count),
+            other => Err(format!(
+                "invalid <output_mode>: {other} (expected content, files_with_matches or count)"
+            )),
+        }
+    }
+}
+
+/// Directories still to walk, shared by the walk workers.
+struct WalkQueue {
+    dirs: Vec<(PathBuf, IgnoreStack)>,
+    /// Workers scanning a directory, which may queue more.
+    busy: usize,
+}
+
+/// Everything a worker needs to search one file.
+struct SearchOptions {
+    re: Regex,
+    before: usize,
+    after: usize,
+    multiline: bool,
+    mode: OutputMode,
+}
+
+/// One line of content output.
+struct Line {
+    no: usize,
+    text: String,
+    is_match: bool,
+}
+
+/// Matches found in one file.
+struct FileHits {
+    display: String,
+    count: usize,
+    /// Match and context lines in order; empty unless the mode is content.
+    lines: Vec<Line>,
+}
+
 impl GrepTool {
     /// Check if a byte slice looks like binary (contains null bytes in first 8KB).
     fn is_binary(data: &[u8]) -> bool {
@@ -19,29 +96,106 @@ impl GrepTool {
         data[..check_len].contains(&0)
     }
 
-    /// Recursively walk a directory, searching each text file.
-    fn search_dir(
+    /// Threads to use for `jobs` units of work, at most [`MAX_WORKERS`].
+    fn worker_count(jobs: usize) -> usize {
+        std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_WORKERS)
+            .min(jobs)
+            .max(1)
+    }
+
+    /// Recursively walk a directory, collecting the files to search.
+    ///
+    /// Hidden and ignored entries are skipped, and ignored directories are
+    /// not entered. With a workspace, symlinks that lead outside it are
+    /// skipped too. Directories are scanned on up to [`MAX_WORKERS`] threads
+    /// that share a queue; the files come back sorted by path.
+    pub(crate) fn collect_files(
+        dir: &Path,
+        ignores: &IgnoreStack,
+        glob_filter: Option<&glob::Pattern>,
+        jail: Option<&Workspace>,
+    ) -> Vec<PathBuf> {
+        let queue = Mutex::new(WalkQueue {
+            dirs: vec![(dir.to_path_buf(), ignores.clone())],
+            busy: 0,
+        });
+        let ready = Condvar::new();
+
+        let mut files: Vec<PathBuf> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..Self::worker_count(usize::MAX))
+                .map(|_| s.spawn(|| Self::walk_worker(&queue, &ready, glob_filter, jail)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        // Component-wise order: the same as a depth-first walk in name order
+        files.sort();
+        files
+    }
+
+    /// One walk thread: scan queued directories until the queue is empty
+    /// and no other worker can add to it. Returns the files it found.
+    fn walk_worker(
+        queue: &Mutex<WalkQueue>,
+        ready: &Condvar,
+        glob_filter: Option<&glob::Pattern>,
+        jail: Option<&Workspace>,
+    ) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        loop {
+            let (dir, ignores) = {
+                let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+                loop {
+                    if let Some(next) = q.dirs.pop() {
+                        q.busy += 1;
+                        break next;
+                    }
+                    if q.busy == 0 {
+                        return files;
+                    }
+                    q = ready.wait(q).unwrap_or_else(PoisonError::into_inner);
+                }
+            };
+            let mut subdirs = Vec::new();
+            Self::scan_dir(&dir, &ignores, glob_filter, jail, &mut subdirs, &mut files);
+            let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+            q.dirs.extend(subdirs);
+            q.busy -= 1;
+            // Wake idle workers for the new directories, or to finish
+            ready.notify_all();
+        }
+    }
+
+    /// Scan one directory: files to search go to `files`, directories to
+    /// enter (with their ignore rules) to `subdirs`.
+    fn scan_dir(
         dir: &Path,
-        re: &Regex,
+        ignores: &IgnoreStack,
         glob_filter: Option<&glob::Pattern>,
-        results: &mut Vec<String>,
+        jail: Option<&Workspace>,
+        subdirs: &mut Vec<(PathBuf, IgnoreStack)>,
+        files: &mut Vec<PathBuf>,
     ) {
         let entries = match std::fs::read_dir(dir) {
             Ok(e) => e,
             Err(_) => return,
         };
 
-        let mut entries_vec: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-        entries_vec.sort_by_key(|e| e.path());
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
 
-        for entry in entries_vec {
-            if results.len() >= MAX_MATCHES {
-                return;
+            if let Some(ws) = jail {
+                let is_link = entry.file_type().map(|t| t.is_symlink()).unwrap_or(true);
+                if is_link && !ws.contains(&path, Access::Read) {
+                    continue;
+                }
             }
 
-            let path = entry.path();
-
-            // Skip hidden directories/files
+            // Skip hidden directories/files (including .git)
             if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
                 if name.starts_with('.') {
                     continue;
@@ -49,8 +203,15 @@ impl GrepTool {
             }
 
             if path.is_dir() {
-                Self::search_dir(&path, re, glob_filter, results);
+                if ignores.is_ignored(&path, true) {
+                    continue;
+                }
+                let nested = ignores.descend(&path);
+                subdirs.push((path, nested));
             } else if path.is_file() {
+                if ignores.is_ignored(&path, false) {
+                    continue;
+                }
                 // Apply glob filter if present
                 if let Some(filter) = glob_filter {
                     if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
@@ -59,39 +220,206 @@ impl GrepTool {
                         }
                     }
                 }
-
-                Self::search_file(&path, re, results);
+                files.push(path);
             }
         }
     }
 
-    fn search_file(path: &Path, re: &Regex, results: &mut Vec<String>) {
-        let raw = match std::fs::read(path) {
-            Ok(d) => d,
-            Err(_) => return,
-        };
+    /// Search `files` on up to [`MAX_WORKERS`] threads.
+    ///
+    /// Workers pull the next file index from a shared counter, so a few large
+    /// files don't hold up the rest. Results come back in `files` order.
+    fn search_files(files: &[PathBuf], opts: &SearchOptions) -> Vec<FileHits> {
+        let workers = Self::worker_count(files.len());
+        let next = AtomicUsize::new(0);
+
+        let mut found: Vec<(usize, FileHits)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut local = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(path) = files.get(i) else { break };
+                            if let Some(hits) = Self::search_file(path, opts) {
+                                local.push((i, hits));
+                            }
+                        }
+                        local
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        found.sort_by_key(|(i, _)| *i);
+        found.into_iter().map(|(_, hits)| hits).collect()
+    }
 
+    /// Search one file; `None` if it is unreadable, binary or has no match.
+    fn search_file(path: &Path, opts: &SearchOptions) -> Option<FileHits> {
+        let raw = std::fs::read(path).ok()?;
         if raw.is_empty() || Self::is_binary(&raw) {
-            return;
+            return None;
         }
 
         let content = String::from_utf8_lossy(&raw);
-        let display = path.display();
+        let (count, lines) = Self::search_text(&content, opts);
+        (count > 0).then(|| FileHits {
+            display: path.display().to_string(),
+            count,
+            lines,
+        })
+    }
 
-        for (i, line) in content.lines().enumerate() {
-            if results.len() >= MAX_MATCHES {
-                return;
+    /// Find matches in `content`, returning the match count and, in content
+    /// mode, the match and context lines to show.
+    ///
+    /// A line counts as one match however many times it matches. In
+    /// multiline mode each regex match counts once and marks every line it
+    /// spans.
+    fn search_text(content: &str, opts: &SearchOptions) -> (usize, Vec<Line>) {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut matched = vec![false; lines.len()];
+        let mut count = 0;
+
+        if opts.multiline {
+            let starts: Vec<usize> = std::iter::once(0)
+                .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+                .collect();
+            let line_of = |byte: usize| {
+                let line = starts.partition_point(|&s| s <= byte) - 1;
+                line.min(lines.len().saturating_sub(1))
+            };
+            for m in opts.re.find_iter(content) {
+                count += 1;
+                let last_byte = if m.end() > m.start() { m.end() - 1 } else { m.start() };
+                for flag in &mut matched[line_of(m.start())..=line_of(last_byte)] {
+                    *flag = true;
+                }
             }
-            if re.is_match(line) {
-                results.push(format!("{display}:{}:{line}", i + 1));
+        } else {
+            for (flag, line) in matched.iter_mut().zip(&lines) {
+                if opts.re.is_match(line) {
+                    *flag = true;
+                    count += 1;
+                }
+            }
+        }
+
+        if opts.mode != OutputMode::Content || count == 0 {
+            return (count, Vec::new());
+        }
+
+        // Each match pulls in its context; overlapping groups merge.
+        let mut out = Vec::new();
+        let mut next_line = 0;
+        let hits = matched.iter().enumerate().filter(|(_, m)| **m).map(|(i, _)| i);
+        for i in hits.take(MAX_MATCHES) {
+            let from = i.saturating_sub(opts.before).max(next_line);
+            let to = (i + opts.after).min(lines.len() - 1);
+            for (j, text) in lines.iter().enumerate().take(to + 1).skip(from) {
+                out.push(Line {
+                    no: j + 1,
+                    text: text.to_string(),
+                    is_match: matched[j],
+                });
+            }
+            next_line = next_line.max(to + 1);
+        }
+        (count, out)
+    }
+
+    /// Format the hits for the model, truncating at [`MAX_MATCHES`] and
+    /// saying how much was left out.
+    fn render(hits: &[FileHits], opts: &SearchOptions) -> String {
+        let total: usize = hits.iter().map(|h| h.count).sum();
+        let files = hits.len();
+
+        match opts.mode {
+            OutputMode::FilesWithMatches | OutputMode::Count => {
+                let mut output: Vec<String> = hits
+                    .iter()
+                    .take(MAX_MATCHES)
+                    .map(|h| match opts.mode {
+                        OutputMode::Count => format!("{}:{}", h.display, h.count),
+                        _ => h.display.clone(),
+                    })
+                    .collect();
+                let summary = if files > MAX_MATCHES {
+                    format!(
+                        "... (showing {MAX_MATCHES} of {files} files; {total} matches in all; \
+                         narrow the pattern or path)"
+                    )
+                } else {
+                    format!("{total} matches in {files} files")
+                };
+                output.push(String::new());
+                output.push(summary);
+                output.join("\n")
+            }
+            OutputMode::Content => {
+                let with_context = opts.before > 0 || opts.after > 0;
+                let mut output = Vec::new();
+                let mut shown = 0;
+                'files: for hit in hits {
+                    let mut prev: Option<usize> = None;
+                    for line in &hit.lines {
+                        if line.is_match {
+                            if shown == MAX_MATCHES {
+                                break 'files;
+                            }
+                            shown += 1;
+                        }
+                        let adjacent = prev.is_some_and(|p| p + 1 == line.no);
+                        if with_context && !output.is_empty() && !adjacent {
+                            output.push("--".to_string());
+                        }
+                        let sep = if line.is_match { ':' } else { '-' };
+                        output.push(format!("{}{sep}{}{sep}{}", hit.display, line.no, line.text));
+                        prev = Some(line.no);
+                    }
+                }
+                let summary = if total > shown {
+                    format!(
+                        "... (showing {shown} of {total} matches in {files} files; \
+                         narrow the pattern or path)"
+                    )
+                } else {
+                    format!("{total} matches")
+                };
+                format!("{}\n\n{summary}", output.join("\n"))
             }
         }
     }
+
+    /// Walk and search `search` (a file or directory). Blocking.
+    fn run(
+        search: &Path,
+        search_path: &str,
+        opts: &SearchOptions,
+        glob_filter: Option<&glob::Pattern>,
+        jail: Option<&Workspace>,
+    ) -> Result<String, String> {
+        let files = if search.is_file() {
+            vec![search.to_path_buf()]
+        } else if search.is_dir() {
+            let ignores = IgnoreStack::for_dir(search);
+            Self::collect_files(search, &ignores, glob_filter, jail)
+        } else {
+            return Err(format!("path not found: {search_path}"));
+        };
+
+        let hits = Self::search_files(&files, opts);
+        Ok(Self::render(&hits, opts))
+    }
 }
 
 #[async_trait]
 impl Handler for GrepTool {
-    async fn handle(&self, payload: ValidatedPayload, _ctx: HandlerContext) -> HandlerResult {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
         let xml_str = String::from_utf8_lossy(&payload.xml);
 
         let pattern = extract_tag(&xml_str, "pattern").unwrap_or_default();
@@ -101,17 +429,17 @@ impl Handler for GrepTool {
             });
         }
 
-        let case_insensitive = extract_tag(&xml_str, "case_insensitive")
-            .map(|s| s == "true")
-            .unwrap_or(false);
-
-        let re = if case_insensitive {
-            Regex::new(&format!("(?i){pattern}"))
-        } else {
-            Regex::new(&pattern)
-        };
-
-        let re = match re {
+        let flag = |tag: &str| extract_tag(&xml_str, tag).map(|s| s == "true").unwrap_or(false);
+        let case_insensitive = flag("case_insensitive");
+        let multiline = flag("multiline");
+
+        // Multiline: ^/$ match at line breaks and . matches newlines
+        let re = match RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(multiline)
+            .dot_matches_new_line(multiline)
+            .build()
+        {
             Ok(r) => r,
             Err(e) => {
                 return Ok(HandlerResponse::Reply {
@@ -120,36 +448,62 @@ impl Handler for GrepTool {
             }
         };
 
+        let mode = match extract_tag(&xml_str, "output_mode").map(|m| OutputMode::parse(&m)) {
+            None => OutputMode::Content,
+            Some(Ok(m)) => m,
+            Some(Err(e)) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        };
+
+        let lines = |tag: &str| extract_tag(&xml_str, tag).and_then(|s| s.trim().parse::<usize>().ok());
+        let context = lines("context");
+        let opts = SearchOptions {
+            re,
+            before: lines("before_context").or(context).unwrap_or(0),
+            after: lines("after_context").or(context).unwrap_or(0),
+            multiline,
+            mode,
+        };
+
         let search_path = extract_tag(&xml_str, "path").unwrap_or_else(|| ".".into());
         let glob_filter_str = extract_tag(&xml_str, "glob_filter");
         let glob_filter = glob_filter_str
             .as_ref()
             .and_then(|g| glob::Pattern::new(g).ok());
 
-        let search = Path::new(&search_path);
-        let mut results = Vec::new();
-
-        if search.is_file() {
-            Self::search_file(search, &re, &mut results);
-        } else if search.is_dir() {
-            Self::search_dir(search, &re, glob_filter.as_ref(), &mut results);
-        } else {
-            return Ok(HandlerResponse::Reply {
-                payload_xml: ToolResponse::err(&format!("path not found: {search_path}")),
-            });
-        }
-
-        let total = results.len();
-        let mut output = results.join("\n");
-        if total >= MAX_MATCHES {
-            output.push_str(&format!("\n\n... (truncated at {MAX_MATCHES} matches)"));
-        } else {
-            output.push_str(&format!("\n\n{total} matches"));
-        }
+        let search = match workspace::resolve_in(
+            self.workspace.as_deref(),
+            "grep",
+            &search_path,
+            Access::Read,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        };
 
-        Ok(HandlerResponse::Reply {
-            payload_xml: ToolResponse::ok(&output),
+        // The walk and the file reads are blocking; keep them off the runtime.
+        let jail = self.workspace.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            Self::run(&search, &search_path, &opts, glob_filter.as_ref(), jail.as_deref())
         })
+        .await
+        .map_err(|e| PipelineError::Handler(format!("grep task panicked: {e}")))?;
+
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
     }
 }
 
@@ -159,9 +513,17 @@ impl ToolPeer for GrepTool {
         "grep"
     }
 
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
     fn wit(&self) -> &str {
         r#"
-/// Regex search across files. Recursively searches directories, skips binary files.
+/// Regex search across files. Recursively searches directories, honouring .gitignore and .ignore; skips hidden and binary files. Results stop at 500 matches and say how many there were in total.
 interface grep {
     record request {
         /// Regex pattern to search for
@@ -172,6 +534,16 @@ interface grep {
         glob-filter: option<string>,
         /// Case insensitive search (default: false)
         case-insensitive: option<bool>,
+        /// Lines of context before and after each match (like grep -C)
+        context: option<u32>,
+        /// Lines of context before each match (like grep -B; overrides context)
+        before-context: option<u32>,
+        /// Lines of context after each match (like grep -A; overrides context)
+        after-context: option<u32>,
+        /// content (matching lines, default), files_with_matches (paths only) or count (matches per file)
+        output-mode: option<string>,
+        /// Let the pattern span lines; . matches newlines and ^/$ match at every line (default: false)
+        multiline: option<bool>,
     }
     search: func(req: request) -> result<string, string>;
 }
@@ -225,7 +597,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>fn \\w+</pattern><path>{path}</path></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("fn hello"));
         assert!(content.contains("fn world"));
@@ -244,7 +616,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>fn \\w+</pattern><path>{base}</path></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("alpha"));
         assert!(content.contains("beta"));
@@ -260,7 +632,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>hello</pattern><path>{base}</path><glob_filter>*.rs</glob_filter></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("code.rs"));
         assert!(!content.contains("readme.md"));
@@ -276,7 +648,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>hello</pattern><path>{path}</path><case_insensitive>true</case_insensitive></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("3 matches"));
     }
@@ -291,7 +663,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>zzz</pattern><path>{path}</path></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("0 matches"));
     }
@@ -299,7 +671,7 @@ mod tests {
     #[tokio::test]
     async fn grep_invalid_regex() {
         let xml = "<GrepRequest><pattern>[invalid</pattern><path>.</path></GrepRequest>";
-        let (ok, content) = get_result(GrepTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("invalid regex"));
     }
@@ -307,7 +679,7 @@ mod tests {
     #[tokio::test]
     async fn grep_missing_pattern() {
         let xml = "<GrepRequest><path>.</path></GrepRequest>";
-        let (ok, content) = get_result(GrepTool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(xml), make_ctx()).await.unwrap());
         assert!(!ok);
         assert!(content.contains("missing required"));
     }
@@ -325,7 +697,7 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>findme</pattern><path>{base}</path></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("text.txt"));
         assert!(!content.contains("binary.bin"));
@@ -343,19 +715,203 @@ mod tests {
         let xml = format!(
             "<GrepRequest><pattern>findme</pattern><path>{base}</path></GrepRequest>"
         );
-        let (ok, content) = get_result(GrepTool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
         assert!(ok);
         assert!(content.contains("visible.txt"));
         assert!(!content.contains("secret.txt"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn grep_skips_symlinks_out_of_workspace() {
+        let dir = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("inside.txt"), "findme\n").unwrap();
+        std::fs::write(outside.path().join("leaked.txt"), "findme\n").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+
+        let mut tool = GrepTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        let xml = "<GrepRequest><pattern>findme</pattern></GrepRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("inside.txt"));
+        assert!(!content.contains("leaked.txt"));
+
+        let xml = "<GrepRequest><pattern>findme</pattern><path>escape</path></GrepRequest>";
+        let (ok, content) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("outside workspace"));
+    }
+
+    #[tokio::test]
+    async fn grep_respects_ignore_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(dir.path().join("main.rs"), "findme\n").unwrap();
+        std::fs::write(dir.path().join("build.log"), "findme\n").unwrap();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "findme\n").unwrap();
+        std::fs::create_dir(dir.path().join("vendor")).unwrap();
+        std::fs::write(dir.path().join("vendor/.ignore"), "*.rs\n").unwrap();
+        std::fs::write(dir.path().join("vendor/lib.rs"), "findme\n").unwrap();
+
+        let base = dir.path().to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>findme</pattern><path>{base}</path></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("main.rs"));
+        assert!(!content.contains("build.log"));
+        assert!(!content.contains("out.rs"));
+        assert!(!content.contains("lib.rs"));
+        assert!(content.contains("1 matches"));
+    }
+
+    #[tokio::test]
+    async fn grep_context_lines() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("test.txt");
+        std::fs::write(&file, "a\nb\nhit one\nc\nd\ne\nf\nhit two\ng\n").unwrap();
+
+        let path = file.to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>hit</pattern><path>{path}</path><context>1</context></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        let expected = format!("{path}-2-b\n{path}:3:hit one\n{path}-4-c\n--\n{path}-7-f\n{path}:8:hit two\n{path}-9-g\n\n2 matches");
+        assert_eq!(content, expected);
+
+        // -A overrides -C on its side only
+        let xml = format!(
+            "<GrepRequest><pattern>hit one</pattern><path>{path}</path><context>1</context><after_context>0</after_context></GrepRequest>"
+        );
+        let (_, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert_eq!(content, format!("{path}-2-b\n{path}:3:hit one\n\n1 matches"));
+    }
+
+    #[tokio::test]
+    async fn grep_output_modes() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "todo\ntodo\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "todo\n").unwrap();
+        std::fs::write(dir.path().join("c.rs"), "done\n").unwrap();
+
+        let base = dir.path().to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>todo</pattern><path>{base}</path><output_mode>files_with_matches</output_mode></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.starts_with(&format!("{base}/a.rs\n{base}/b.rs\n")));
+        assert!(!content.contains("c.rs"));
+        assert!(content.contains("3 matches in 2 files"));
+
+        let xml = format!(
+            "<GrepRequest><pattern>todo</pattern><path>{base}</path><output_mode>count</output_mode></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains(&format!("{base}/a.rs:2\n{base}/b.rs:1")));
+
+        let xml = format!(
+            "<GrepRequest><pattern>todo</pattern><path>{base}</path><output_mode>lines</output_mode></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(content.contains("invalid <output_mode>"));
+    }
+
+    #[tokio::test]
+    async fn grep_multiline() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn open(\n    path: &str,\n) {}\nfn close() {}\n").unwrap();
+
+        let path = file.to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>fn open\\(.*?\\)</pattern><path>{path}</path><multiline>true</multiline></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains(&format!("{path}:1:fn open(")));
+        assert!(content.contains(&format!("{path}:3:) {{}}")));
+        assert!(!content.contains("close"));
+        assert!(content.contains("1 matches"));
+    }
+
+    #[tokio::test]
+    async fn grep_multiline_anchors_match_at_each_line() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "use std::io;\n\nfn open() {}\n  fn nested() {}\nfn close() {}\n").unwrap();
+
+        let path = file.to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>^fn \\w+</pattern><path>{path}</path><multiline>true</multiline></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains(&format!("{path}:3:fn open() {{}}")));
+        assert!(content.contains(&format!("{path}:5:fn close() {{}}")));
+        assert!(!content.contains("nested"));
+        assert!(content.contains("2 matches"));
+    }
+
+    #[test]
+    fn collect_files_walks_the_whole_tree_in_path_order() {
+        let dir = TempDir::new().unwrap();
+        let mut expected = Vec::new();
+        for a in ["b", "a", "c"] {
+            for b in ["y", "x"] {
+                let sub = dir.path().join(a).join(b);
+                std::fs::create_dir_all(&sub).unwrap();
+                for name in ["2.rs", "1.rs"] {
+                    std::fs::write(sub.join(name), "fn f() {}\n").unwrap();
+                    expected.push(sub.join(name));
+                }
+            }
+            std::fs::write(dir.path().join(a).join("top.rs"), "").unwrap();
+            expected.push(dir.path().join(a).join("top.rs"));
+        }
+        expected.sort();
+
+        let ignores = IgnoreStack::for_dir(dir.path());
+        let files = GrepTool::collect_files(dir.path(), &ignores, None, None);
+        assert_eq!(files, expected);
+    }
+
+    #[tokio::test]
+    async fn grep_reports_truncation() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..12 {
+            std::fs::write(dir.path().join(format!("f{i:02}.txt")), "hit\n".repeat(50)).unwrap();
+        }
+
+        let base = dir.path().to_str().unwrap();
+        let xml = format!(
+            "<GrepRequest><pattern>hit</pattern><path>{base}</path></GrepRequest>"
+        );
+        let (ok, content) = get_result(GrepTool::default().handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(content.contains("showing 500 of 600 matches in 12 files"));
+        // Parallel search still reports files in path order
+        assert!(content.find("f00.txt").unwrap() < content.find("f09.txt").unwrap());
+        assert!(!content.contains("f10.txt"));
+    }
+
     #[test]
     fn grep_metadata() {
-        let tool = GrepTool;
+        let tool = GrepTool::default();
         assert_eq!(tool.name(), "grep");
         let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
         assert_eq!(iface.name, "grep");
         assert_eq!(iface.request_tag(), "GrepRequest");
         assert!(iface.request.fields.iter().any(|f| f.name == "pattern"));
+        assert!(iface.request.fields.iter().any(|f| f.name == "output-mode"));
+        assert!(iface.request.fields.iter().any(|f| f.name == "after-context"));
     }
 }
@@ -0,0 +1,638 @@
+//! HTML to markdown, for `http-fetch`.
+//!
+//! A small, forgiving converter for reading documentation pages: headings,
+//! paragraphs, links (resolved against the page URL), emphasis, inline code
+//! and `<pre>` blocks (with their `language-*` class as the fence tag),
+//! lists, blockquotes, images and tables as pipe rows. Scripts, styles and
+//! page chrome (`<head>`, `<nav>`, `<footer>`) are dropped. Malformed markup
+//! degrades to text rather than failing.
+
+use reqwest::Url;
+
+/// Elements whose content is dropped entirely.
+const DROPPED: [&str; 9] = [
+    "head", "script", "style", "noscript", "template", "svg", "nav", "footer", "iframe",
+];
+
+/// Elements that start and end a block (a blank line around them).
+const BLOCKS: [&str; 16] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "aside",
+    "figure",
+    "figcaption",
+    "dl",
+    "dt",
+    "dd",
+    "form",
+    "table",
+    "details",
+    "summary",
+];
+
+/// A converted page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Converted {
+    /// The `<title>`, if the page has one.
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+/// Convert an HTML document to markdown. Relative links resolve against `base`.
+pub fn to_markdown(html: &str, base: Option<&Url>) -> Converted {
+    let mut md = Markdown {
+        base,
+        ..Markdown::default()
+    };
+    for token in tokenize(html) {
+        md.token(token);
+    }
+    md.finish()
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End(String),
+}
+
+/// Split HTML into text and tags. Comments, doctypes and processing
+/// instructions are skipped; `<script>` and `<style>` bodies are not parsed.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = html.as_bytes();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'<' {
+            i += 1;
+            continue;
+        }
+        let rest = &html[i..];
+        let skip_to = |end: &str| rest.find(end).map_or(html.len(), |n| i + n + end.len());
+        let next = if rest.starts_with("<!--") {
+            Some((skip_to("-->"), None))
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            Some((skip_to(">"), None))
+        } else if rest.starts_with("</") && rest[2..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let end = skip_to(">");
+            let name = tag_name(&html[i + 2..end]);
+            Some((end, Some(Token::End(name))))
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (end, name, attrs) = parse_start_tag(html, i);
+            Some((end, Some(Token::Start { name, attrs })))
+        } else {
+            None
+        };
+        let Some((mut end, token)) = next else {
+            i += 1;
+            continue;
+        };
+        if text_start < i {
+            tokens.push(Token::Text(&html[text_start..i]));
+        }
+        if let Some(Token::Start { name, .. }) = &token {
+            if name == "script" || name == "style" {
+                // Raw text: skip to the matching close tag
+                let close = format!("</{name}");
+                let body_end = html[end..]
+                    .to_ascii_lowercase()
+                    .find(&close)
+                    .map_or(html.len(), |n| end + n);
+                end = html[body_end..]
+                    .find('>')
+                    .map_or(html.len(), |n| body_end + n + 1);
+                let close = Token::End(name.clone());
+                tokens.extend(token);
+                tokens.push(close);
+                text_start = end;
+                i = end;
+                continue;
+            }
+        }
+        tokens.extend(token);
+        text_start = end;
+        i = end;
+    }
+    if text_start < html.len() {
+        tokens.push(Token::Text(&html[text_start..]));
+    }
+    tokens
+}
+
+fn tag_name(s: &str) -> String {
+    s.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Parse `<name attr="v" ...>` starting at `start`; returns the end offset.
+fn parse_start_tag(html: &str, start: usize) -> (usize, String, Vec<(String, String)>) {
+    let name = tag_name(&html[start + 1..]);
+    let bytes = html.as_bytes();
+    let mut i = start + 1 + name.len();
+    let mut attrs = Vec::new();
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return (html.len(), name, attrs);
+        }
+        if bytes[i] == b'>' {
+            return (i + 1, name, attrs);
+        }
+        let key_start = i;
+        while i < bytes.len()
+            && !matches!(bytes[i], b'=' | b'>' | b'/')
+            && !bytes[i].is_ascii_whitespace()
+        {
+            i += 1;
+        }
+        let key = html[key_start..i].to_ascii_lowercase();
+        let mut value = String::new();
+        if i < bytes.len() && bytes[i] == b'=' {
+            i += 1;
+            match bytes.get(i) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let end = html[i + 1..]
+                        .find(quote as char)
+                        .map_or(html.len(), |n| i + 1 + n);
+                    value = decode_entities(&html[i + 1..end]);
+                    i = (end + 1).min(html.len());
+                }
+                _ => {
+                    let value_start = i;
+                    while i < bytes.len() && bytes[i] != b'>' && !bytes[i].is_ascii_whitespace() {
+                        i += 1;
+                    }
+                    value = decode_entities(&html[value_start..i]);
+                }
+            }
+        }
+        if key.is_empty() {
+            i += 1;
+        } else {
+            attrs.push((key, value));
+        }
+    }
+}
+
+/// Decode character references; unknown ones are left as they are.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&n| n <= 10)
+            .and_then(|n| entity(&rest[1..n + 1]).map(|c| (c, n + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "mdash" => '—',
+        "ndash" => '–',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "times" => '×',
+        "middot" => '·',
+        "bull" => '•',
+        "rarr" => '→',
+        "larr" => '←',
+        _ => return None,
+    })
+}
+
+/// Converter state.
+#[derive(Default)]
+struct Markdown<'a> {
+    base: Option<&'a Url>,
+    out: String,
+    title: Option<String>,
+    in_title: bool,
+    /// Depth inside dropped elements.
+    skip: usize,
+    /// Depth inside `<pre>`.
+    pre: usize,
+    /// Depth inside `<blockquote>`.
+    quote: usize,
+    /// Open lists: the next number for `<ol>`, None for `<ul>`.
+    lists: Vec<Option<usize>>,
+    /// Open links: the target, or None if the link is written as plain text.
+    links: Vec<Option<String>>,
+    /// Whitespace seen since the last character written.
+    space: bool,
+    /// Cells in the current table row, and whether they were `<th>`.
+    row: usize,
+    header_row: bool,
+    header_done: bool,
+}
+
+impl Markdown<'_> {
+    fn token(&mut self, token: Token<'_>) {
+        match token {
+            Token::Text(text) => {
+                if self.in_title {
+                    let title = self.title.get_or_insert_with(String::new);
+                    title.push_str(&decode_entities(text));
+                } else if self.skip == 0 {
+                    self.text(&decode_entities(text));
+                }
+            }
+            Token::Start { name, attrs } => {
+                if name == "title" {
+                    self.in_title = true;
+                } else if DROPPED.contains(&name.as_str()) {
+                    self.skip += 1;
+                } else if self.skip == 0 {
+                    self.start(&name, &attrs);
+                }
+            }
+            Token::End(name) => {
+                if name == "title" {
+                    self.in_title = false;
+                } else if DROPPED.contains(&name.as_str()) {
+                    self.skip = self.skip.saturating_sub(1);
+                } else if self.skip == 0 {
+                    self.end(&name);
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(String, String)]) {
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.blank_line();
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                self.write(&format!("{} ", "#".repeat(level)));
+            }
+            "br" => self.line_break(),
+            "hr" => {
+                self.blank_line();
+                self.write("---");
+                self.blank_line();
+            }
+            "pre" => {
+                self.blank_line();
+                self.write("```");
+                self.pre += 1;
+                self.raw("\n");
+            }
+            "code" if self.pre > 0 => {
+                // Tag the fence with the language, if it was just opened
+                let lang = attr("class").and_then(|c| {
+                    c.split_whitespace()
+                        .find_map(|c| c.strip_prefix("language-"))
+                });
+                if let Some(lang) = lang {
+                    if self.out.ends_with("```\n") {
+                        self.out.pop();
+                        self.out.push_str(lang);
+                        self.out.push('\n');
+                    }
+                }
+            }
+            "code" | "kbd" | "samp" | "tt" => self.inline("`"),
+            "strong" | "b" => self.inline("**"),
+            "em" | "i" => self.inline("*"),
+            "a" => {
+                let href = attr("href")
+                    .filter(|h| !h.starts_with('#') && !h.starts_with("javascript:"))
+                    .map(|h| self.resolve(h));
+                if href.is_some() {
+                    self.inline("[");
+                }
+                self.links.push(href);
+            }
+            "img" => {
+                let alt = attr("alt").unwrap_or("").trim();
+                if let Some(src) = attr("src").filter(|s| !s.starts_with("data:")) {
+                    let src = self.resolve(src);
+                    self.inline(&format!("![{alt}]({src})"));
+                }
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.blank_line();
+                } else {
+                    self.line_break();
+                }
+                let start = attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+                self.lists.push((name == "ol").then_some(start));
+            }
+            "li" => {
+                self.line_break();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.write(&format!("{}{marker}", "  ".repeat(depth)));
+            }
+            "blockquote" => {
+                self.blank_line();
+                self.quote += 1;
+            }
+            "tr" => {
+                self.line_break();
+                self.row = 0;
+                self.header_row = false;
+            }
+            "td" | "th" => {
+                self.write(if self.row == 0 { "| " } else { " | " });
+                self.row += 1;
+                self.header_row |= name == "th";
+            }
+            _ if BLOCKS.contains(&name) => self.blank_line(),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.blank_line(),
+            "pre" => {
+                self.pre = self.pre.saturating_sub(1);
+                if !self.out.ends_with('\n') {
+                    self.raw("\n");
+                }
+                self.write("```");
+                self.blank_line();
+            }
+            "code" if self.pre > 0 => {}
+            "code" | "kbd" | "samp" | "tt" => self.inline("`"),
+            "strong" | "b" => self.inline("**"),
+            "em" | "i" => self.inline("*"),
+            "a" => {
+                if let Some(Some(href)) = self.links.pop() {
+                    self.inline(&format!("]({href})"));
+                }
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                } else {
+                    self.line_break();
+                }
+            }
+            "blockquote" => {
+                // Drop the quoted blank line a closing paragraph left behind
+                let marker = format!("\n{}\n", ">".repeat(self.quote));
+                if self.out.ends_with(&marker) {
+                    self.out.truncate(self.out.len() - marker.len() + 1);
+                }
+                self.quote = self.quote.saturating_sub(1);
+                self.blank_line();
+            }
+            "tr" => {
+                if self.row > 0 {
+                    self.write(" |");
+                    if self.header_row && !self.header_done {
+                        self.line_break();
+                        self.write(&format!("|{}", " --- |".repeat(self.row)));
+                    }
+                    self.header_done = true;
+                }
+                self.line_break();
+            }
+            "table" => {
+                self.header_done = false;
+                self.blank_line();
+            }
+            _ if BLOCKS.contains(&name) => self.blank_line(),
+            _ => {}
+        }
+    }
+
+    /// Resolve a link target against the page URL.
+    fn resolve(&self, href: &str) -> String {
+        match self.base.and_then(|base| base.join(href).ok()) {
+            Some(url) => url.to_string(),
+            None => href.to_string(),
+        }
+    }
+
+    /// Prefix for a new line: `> ` per quote level, and list indentation.
+    fn prefix(&self) -> String {
+        let mut prefix = "> ".repeat(self.quote);
+        prefix.push_str(&"  ".repeat(self.lists.len()));
+        prefix
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    /// Write text as-is (inside `<pre>`), prefixing each new line.
+    fn raw(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            if !line.is_empty() {
+                if self.at_line_start() {
+                    self.out.push_str(&"> ".repeat(self.quote));
+                }
+                self.out.push_str(line);
+            }
+        }
+    }
+
+    /// Write markup at the current position, starting a line if needed.
+    fn write(&mut self, s: &str) {
+        if self.at_line_start() {
+            self.out.push_str(&"> ".repeat(self.quote));
+        }
+        self.out.push_str(s);
+        self.space = false;
+    }
+
+    /// Inline markup: keeps a pending space in front of it.
+    fn inline(&mut self, s: &str) {
+        if self.space && !self.at_line_start() && !self.out.ends_with(' ') {
+            self.out.push(' ');
+        }
+        self.space = false;
+        if self.at_line_start() {
+            let prefix = self.prefix();
+            self.out.push_str(&prefix);
+        }
+        self.out.push_str(s);
+    }
+
+    /// Text outside `<pre>`: whitespace collapses to single spaces.
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            self.raw(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.space = true;
+                continue;
+            }
+            if self.at_line_start() {
+                let prefix = self.prefix();
+                self.out.push_str(&prefix);
+            } else if self.space && !self.out.ends_with(' ') {
+                self.out.push(' ');
+            }
+            self.space = false;
+            self.out.push(c);
+        }
+    }
+
+    fn trim_line_end(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+    }
+
+    fn line_break(&mut self) {
+        self.trim_line_end();
+        if !self.at_line_start() {
+            self.out.push('\n');
+        }
+        self.space = false;
+    }
+
+    /// End the current block with a blank line (a plain break inside lists).
+    fn blank_line(&mut self) {
+        self.line_break();
+        if self.out.is_empty() || !self.lists.is_empty() || self.out.ends_with("\n\n") {
+            return;
+        }
+        if self.quote > 0 {
+            self.out.push_str(&">".repeat(self.quote));
+        }
+        self.out.push('\n');
+    }
+
+    fn finish(self) -> Converted {
+        let mut markdown = String::with_capacity(self.out.len());
+        let mut blank = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank += 1;
+                if blank > 1 {
+                    continue;
+                }
+            } else {
+                blank = 0;
+            }
+            markdown.push_str(line);
+            markdown.push('\n');
+        }
+        let title = self
+            .title
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|t| !t.is_empty());
+        Converted {
+            title,
+            markdown: markdown.trim().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_common_markup() {
+        let html = r##"<!DOCTYPE html>
+<html><head><title>Vec &mdash; std</title><style>p { color: red }</style></head>
+<body><nav><a href="/">Home</a></nav>
+<h1>Struct <code>Vec</code></h1>
+<p>A <b>contiguous</b> growable array, see
+   <a href="../slice/index.html">slices</a> and <a href="#impl">below</a>.</p>
+<pre><code class="language-rust">let v = vec![1, 2];
+assert_eq!(v.len(), 2);</code></pre>
+<ul><li>push</li><li>pop<ol><li>first</li><li>second</li></ol></li></ul>
+<blockquote><p>Note &amp; caveat</p></blockquote>
+<table><tr><th>Method</th><th>Cost</th></tr><tr><td>push</td><td>O(1)</td></tr></table>
+<script>document.write("<p>nope</p>")</script>
+<footer>Copyright</footer></body></html>"##;
+        let base = Url::parse("https://doc.rust-lang.org/std/vec/struct.Vec.html").unwrap();
+        let page = to_markdown(html, Some(&base));
+        assert_eq!(page.title.as_deref(), Some("Vec — std"));
+        assert_eq!(
+            page.markdown,
+            "# Struct `Vec`\n\n\
+             A **contiguous** growable array, see \
+             [slices](https://doc.rust-lang.org/std/slice/index.html) and below.\n\n\
+             ```rust\nlet v = vec![1, 2];\nassert_eq!(v.len(), 2);\n```\n\n\
+             - push\n- pop\n  1. first\n  2. second\n\n\
+             > Note & caveat\n\n\
+             | Method | Cost |\n| --- | --- |\n| push | O(1) |"
+        );
+    }
+
+    #[test]
+    fn tolerates_malformed_markup() {
+        let page = to_markdown("a < b && <i>c &bogus; <p unclosed", None);
+        assert_eq!(page.markdown, "a < b && *c &bogus;");
+        assert_eq!(decode_entities("&#x41;&#66;&lt;"), "AB<");
+    }
+}
@@ -0,0 +1,604 @@
+//! HttpFetchTool — fetch a web page on the agent's behalf, within the
+//! profile's declared network ports.
+//!
+//! Every request, and every redirect it follows, is checked against an
+//! [`EgressPolicy`] built from the outbound http/https port declarations of
+//! the profile's `network:` listeners; a tool built without one reaches
+//! nothing. The pipeline builds one tool per profile and hands each call to
+//! the calling thread's (see [`super::per_profile`]), so a host one profile
+//! may fetch stays refused to another, cached or not. HTML comes back as markdown (see [`super::html`]), other text
+//! as-is, binary content is refused. Bodies are cut at [`MAX_BYTES`].
+//!
+//! Converted pages are cached in the kernel's fetch cache by URL for
+//! [`CACHE_TTL_SECS`], so rereading documentation doesn't refetch it;
+//! `refresh` skips the cache.
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use rust_pipeline::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use super::encoding::TextFile;
+use super::html;
+use super::output::{self, OutputStore};
+use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::kernel::fetch_cache::CachedPage;
+use crate::kernel::Kernel;
+use crate::ports::egress::EgressPolicy;
+
+/// Bytes of a response body read before it is cut off.
+pub const MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// How long a cached page is served before it is fetched again.
+pub const CACHE_TTL_SECS: u64 = 15 * 60;
+
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// Per-request timeout.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error bodies are quoted up to this many characters.
+const ERROR_BODY_CHARS: usize = 1000;
+
+/// Fetch web pages within the profile's network policy.
+pub struct HttpFetchTool {
+    policy: Arc<EgressPolicy>,
+    client: Client,
+    kernel: Option<Arc<Mutex<Kernel>>>,
+    output_store: Option<Arc<OutputStore>>,
+}
+
+impl HttpFetchTool {
+    /// A tool with no network access; every fetch is refused.
+    pub fn new() -> Self {
+        Self::with_policy(EgressPolicy::default())
+    }
+
+    /// A tool that may reach what `policy` allows.
+    pub fn with_policy(policy: EgressPolicy) -> Self {
+        let policy = Arc::new(policy);
+        let guard = policy.clone();
+        let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("more than {MAX_REDIRECTS} redirects"));
+            }
+            match check_url(&guard, attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(format!("redirect refused: {e}")),
+            }
+        });
+        let client = Client::builder()
+            .redirect(redirects)
+            .timeout(TIMEOUT)
+            .user_agent(concat!("agentos-http-fetch/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("http client");
+        Self {
+            policy,
+            client,
+            kernel: None,
+            output_store: None,
+        }
+    }
+
+    async fn cached(&self, url: &Url, now: u64) -> Option<CachedPage> {
+        let kernel = self.kernel.as_ref()?.lock().await;
+        kernel.fetch_cache().get(url.as_str(), CACHE_TTL_SECS, now)
+    }
+
+    async fn store(&self, page: &CachedPage) {
+        if let Some(kernel) = &self.kernel {
+            if let Err(e) = kernel.lock().await.fetch_cache().put(page) {
+                tracing::warn!("could not cache {}: {e}", page.url);
+            }
+        }
+    }
+
+    /// Fetch `url` and turn the body into text.
+    async fn fetch(&self, url: &Url, raw: bool, now: u64) -> Result<CachedPage, String> {
+        let mut resp = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| error_chain(&e))?;
+        let status = resp.status();
+        let final_url = resp.url().clone();
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = resp.chunk().await.map_err(|e| error_chain(&e))? {
+            let room = MAX_BYTES - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        if truncated {
+            // Don't let a character cut in half make the body look non-UTF-8
+            if let Err(e) = std::str::from_utf8(&body) {
+                if e.error_len().is_none() {
+                    body.truncate(e.valid_up_to());
+                }
+            }
+        }
+
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        if !is_text(&mime) {
+            return Err(format!(
+                "unsupported content type {} ({} bytes): http-fetch returns text only",
+                if mime.is_empty() { "(none)" } else { &mime },
+                body.len()
+            ));
+        }
+        let Some(file) = TextFile::decode(&body) else {
+            return Err(format!("{mime} body is binary ({} bytes)", body.len()));
+        };
+
+        let text = if !raw && (mime == "text/html" || mime == "application/xhtml+xml") {
+            let page = html::to_markdown(&file.text, Some(&final_url));
+            match page.title {
+                Some(title) if !page.markdown.starts_with("# ") => {
+                    format!("# {title}\n\n{}", page.markdown)
+                }
+                _ => page.markdown,
+            }
+        } else {
+            file.text
+        };
+
+        if !status.is_success() {
+            let excerpt: String = text.chars().take(ERROR_BODY_CHARS).collect();
+            return Err(format!("HTTP {status} from {final_url}\n{excerpt}"));
+        }
+
+        Ok(CachedPage {
+            url: url.to_string(),
+            fetched_at: now,
+            status: status.as_u16(),
+            content_type: mime,
+            text,
+            truncated,
+        })
+    }
+}
+
+impl Default for HttpFetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check a URL's scheme, host and port against the policy.
+fn check_url(policy: &EgressPolicy, url: &Url) -> Result<(), String> {
+    let scheme = url.scheme();
+    if scheme != "http" && scheme != "https" {
+        return Err(format!(
+            "unsupported scheme '{scheme}': only http and https"
+        ));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("URLs with credentials are not allowed".into());
+    }
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    policy.check(scheme, host, port)
+}
+
+/// Whether a MIME type is text the agent can read.
+fn is_text(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime,
+            "application/json"
+                | "application/xml"
+                | "application/xhtml+xml"
+                | "application/javascript"
+                | "application/x-yaml"
+                | "application/yaml"
+                | "application/toml"
+        )
+}
+
+/// A reqwest error with its causes, which carry the useful part.
+fn error_chain(e: &reqwest::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        let text = cause.to_string();
+        if !msg.contains(&text) {
+            msg.push_str(": ");
+            msg.push_str(&text);
+        }
+        source = cause.source();
+    }
+    msg
+}
+
+/// `3m ago`, for cache hits.
+fn age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        _ => format!("{}h ago", secs / 3600),
+    }
+}
+
+/// The page, then a footer line with where it came from.
+fn render(page: &CachedPage, cached_age: Option<u64>) -> String {
+    let mut footer = format!("[{} {} from {}", page.status, page.content_type, page.url);
+    if page.truncated {
+        footer.push_str(&format!(", truncated at {MAX_BYTES} bytes"));
+    }
+    if let Some(secs) = cached_age {
+        footer.push_str(&format!(", cached {}", age(secs)));
+    }
+    footer.push(']');
+    format!("{}\n\n{footer}", page.text.trim_end())
+}
+
+#[async_trait]
+impl Handler for HttpFetchTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+
+        let raw_url = extract_tag(&xml_str, "url").unwrap_or_default();
+        if raw_url.trim().is_empty() {
+            return Ok(HandlerResponse::Reply {
+                payload_xml: ToolResponse::err("missing required <url>"),
+            });
+        }
+        let raw = extract_tag(&xml_str, "raw").is_some_and(|v| v == "true");
+        let refresh = extract_tag(&xml_str, "refresh").is_some_and(|v| v == "true");
+
+        let url = match Url::parse(raw_url.trim()) {
+            Ok(url) => url,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&format!("invalid URL {raw_url}: {e}")),
+                });
+            }
+        };
+        if let Err(e) = check_url(&self.policy, &url) {
+            return Ok(HandlerResponse::Reply {
+                payload_xml: ToolResponse::err(&e),
+            });
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        // Raw fetches bypass the cache, which holds converted pages
+        let cached = if raw || refresh {
+            None
+        } else {
+            self.cached(&url, now).await
+        };
+        let text = match cached {
+            Some(page) => render(&page, Some(now.saturating_sub(page.fetched_at))),
+            None => match self.fetch(&url, raw, now).await {
+                Ok(page) => {
+                    if !raw {
+                        self.store(&page).await;
+                    }
+                    render(&page, None)
+                }
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            },
+        };
+
+        let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+        Ok(HandlerResponse::Reply {
+            payload_xml: ToolResponse::ok(&text),
+        })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for HttpFetchTool {
+    fn name(&self) -> &str {
+        "http-fetch"
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn set_kernel(&mut self, kernel: Arc<Mutex<Kernel>>) {
+        self.kernel = Some(kernel);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Fetch a web page over http(s), e.g. documentation or an API reference. Only hosts the profile's network ports allow are reachable, redirects included. HTML is returned as markdown, other text as-is; binary content is refused and bodies are cut at 2 MB. Pages are cached for 15 minutes.
+interface http-fetch {
+    record request {
+        /// The URL to fetch
+        url: string,
+        /// Return HTML as-is instead of converting it to markdown (default: false)
+        raw: option<bool>,
+        /// Fetch again even if the page is cached (default: false)
+        refresh: option<bool>,
+    }
+    fetch: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::Protocol;
+    use crate::tools::per_profile::PerProfile;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A stand-in web server on 127.0.0.1; returns its port and a hit counter.
+    async fn serve() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut conn, _)) = listener.accept().await else {
+                    return;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match conn.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let (status, headers, body): (&str, &str, Vec<u8>) = match path.as_str() {
+                        "/doc" => (
+                            "200 OK",
+                            "Content-Type: text/html; charset=utf-8\r\n",
+                            b"<html><head><title>Guide</title></head><body><h1>Hello</h1>\
+                              <p>See <a href=\"/other\">the other page</a>.</p></body></html>"
+                                .to_vec(),
+                        ),
+                        "/big" => (
+                            "200 OK",
+                            "Content-Type: text/plain\r\n",
+                            vec![b'a'; MAX_BYTES + 10],
+                        ),
+                        "/image" => (
+                            "200 OK",
+                            "Content-Type: image/png\r\n",
+                            b"\x89PNG\r\n".to_vec(),
+                        ),
+                        "/missing" => (
+                            "404 Not Found",
+                            "Content-Type: text/plain\r\n",
+                            b"no such page".to_vec(),
+                        ),
+                        "/away" => ("302 Found", "Location: http://example.com/\r\n", Vec::new()),
+                        _ => ("302 Found", "Location: /doc\r\n", Vec::new()),
+                    };
+                    let head = format!(
+                        "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    );
+                    let _ = conn.write_all(head.as_bytes()).await;
+                    let _ = conn.write_all(&body).await;
+                });
+            }
+        });
+        (port, hits)
+    }
+
+    fn make_tool(port: u16, data: &TempDir) -> HttpFetchTool {
+        let policy = EgressPolicy::default().allow(Protocol::Http, port, &["127.0.0.1".into()]);
+        let mut tool = HttpFetchTool::with_policy(policy);
+        tool.set_kernel(Arc::new(Mutex::new(Kernel::open(data.path()).unwrap())));
+        tool
+    }
+
+    fn make_ctx(thread_id: &str) -> HandlerContext {
+        HandlerContext {
+            thread_id: thread_id.into(),
+            from: "agent".into(),
+            own_name: "http-fetch".into(),
+        }
+    }
+
+    async fn fetch(tool: &HttpFetchTool, fields: &str) -> (bool, String) {
+        fetch_as(tool, "t1", fields).await
+    }
+
+    async fn fetch_as(tool: &impl Handler, thread_id: &str, fields: &str) -> (bool, String) {
+        let xml = format!("<HttpFetchRequest>{fields}</HttpFetchRequest>");
+        let payload = ValidatedPayload {
+            xml: xml.into_bytes(),
+            tag: "HttpFetchRequest".into(),
+        };
+        match tool.handle(payload, make_ctx(thread_id)).await.unwrap() {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_html_as_markdown_and_caches_it() {
+        let (port, hits) = serve().await;
+        let data = TempDir::new().unwrap();
+        let tool = make_tool(port, &data);
+        let url = format!("<url>http://127.0.0.1:{port}/doc</url>");
+
+        let (ok, content) = fetch(&tool, &url).await;
+        assert!(ok, "{content}");
+        assert!(
+            content.starts_with(&format!(
+                "# Hello\n\nSee [the other page](http://127.0.0.1:{port}/other).\n\n\
+                 [200 text/html from http://127.0.0.1:{port}/doc]"
+            )),
+            "{content}"
+        );
+
+        let (ok, content) = fetch(&tool, &url).await;
+        assert!(ok);
+        assert!(content.ends_with(", cached 0s ago]"), "{content}");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (ok, _) = fetch(&tool, &format!("{url}<refresh>true</refresh>")).await;
+        assert!(ok);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Redirects within the policy are followed; raw skips conversion
+        let (ok, content) = fetch(
+            &tool,
+            &format!("<url>http://127.0.0.1:{port}/old</url><raw>true</raw>"),
+        )
+        .await;
+        assert!(ok);
+        assert!(
+            content.starts_with("<html><head><title>Guide</title>"),
+            "{content}"
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_what_the_policy_does_not_allow() {
+        let (port, hits) = serve().await;
+        let data = TempDir::new().unwrap();
+        let tool = make_tool(port, &data);
+
+        let (ok, content) = fetch(&tool, &format!("<url>http://localhost:{port}/doc</url>")).await;
+        assert!(!ok);
+        assert!(
+            content.contains("is not allowed by the profile's network ports"),
+            "{content}"
+        );
+        let (ok, content) = fetch(&tool, "<url>file:///etc/passwd</url>").await;
+        assert!(!ok);
+        assert!(content.contains("unsupported scheme 'file'"), "{content}");
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        let (ok, content) = fetch(&tool, &format!("<url>http://127.0.0.1:{port}/away</url>")).await;
+        assert!(!ok);
+        assert!(
+            content.contains("redirect refused: http://example.com:80"),
+            "{content}"
+        );
+
+        let (ok, content) = fetch(
+            &HttpFetchTool::new(),
+            &format!("<url>http://127.0.0.1:{port}/doc</url>"),
+        )
+        .await;
+        assert!(!ok);
+        assert!(content.starts_with("no network access"), "{content}");
+    }
+
+    #[tokio::test]
+    async fn egress_follows_the_callers_profile() {
+        let (port, hits) = serve().await;
+        let data = TempDir::new().unwrap();
+        let kernel = Arc::new(Mutex::new(Kernel::open(data.path()).unwrap()));
+        {
+            let mut k = kernel.lock().await;
+            k.threads_mut()
+                .register_thread("t-web", "console", "a", "web");
+            k.threads_mut()
+                .register_thread("t-offline", "console", "b", "offline");
+        }
+        let web = EgressPolicy::default().allow(Protocol::Http, port, &["127.0.0.1".into()]);
+        let tools = std::collections::HashMap::from([
+            ("web".to_string(), HttpFetchTool::with_policy(web)),
+            ("offline".to_string(), HttpFetchTool::new()),
+        ]);
+        let mut tool = PerProfile::new("http-fetch", tools).unwrap();
+        tool.set_kernel(kernel);
+        let url = format!("<url>http://127.0.0.1:{port}/doc</url>");
+
+        let (ok, content) = fetch_as(&tool, "t-web", &url).await;
+        assert!(ok, "{content}");
+
+        // The page is cached now, but not for a profile that can't reach it
+        let (ok, content) = fetch_as(&tool, "t-offline", &url).await;
+        assert!(!ok);
+        assert!(content.starts_with("no network access"), "{content}");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn caps_size_and_rejects_binary_and_errors() {
+        let (port, _) = serve().await;
+        let data = TempDir::new().unwrap();
+        let tool = make_tool(port, &data);
+
+        let (ok, content) = fetch(&tool, &format!("<url>http://127.0.0.1:{port}/big</url>")).await;
+        assert!(ok);
+        assert!(
+            content.contains(&format!(", truncated at {MAX_BYTES} bytes]")),
+            "{content}"
+        );
+
+        let (ok, content) =
+            fetch(&tool, &format!("<url>http://127.0.0.1:{port}/image</url>")).await;
+        assert!(!ok);
+        assert!(
+            content.starts_with("unsupported content type image/png"),
+            "{content}"
+        );
+
+        let (ok, content) = fetch(
+            &tool,
+            &format!("<url>http://127.0.0.1:{port}/missing</url>"),
+        )
+        .await;
+        assert!(!ok);
+        assert!(content.starts_with("HTTP 404 Not Found from"), "{content}");
+        assert!(content.ends_with("no such page"), "{content}");
+    }
+
+    #[test]
+    fn http_fetch_metadata() {
+        let tool = HttpFetchTool::new();
+        assert_eq!(tool.name(), "http-fetch");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "HttpFetchRequest");
+    }
+}
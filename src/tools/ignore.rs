@@ -0,0 +1,194 @@
+//! Ignore files — `.gitignore` / `.ignore` rules for the directory walkers.
+//!
+//! A deliberately small subset of gitignore: blank lines and `#` comments
+//! are skipped, `!` re-includes, a trailing `/` matches directories only,
+//! and a pattern with a `/` elsewhere is anchored to its ignore file's
+//! directory (a leading `**/` un-anchors it again). A pattern without a `/`
+//! matches a name at any depth. Later rules win, and rules from deeper
+//! directories win over shallower ones.
+//!
+//! Walkers prune ignored directories, so a path under an ignored directory
+//! never needs checking.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use glob::{MatchOptions, Pattern};
+
+/// Ignore files read in each directory, in increasing precedence.
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// `*` and `?` never cross a `/`, as in gitignore.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (anchored, line) = match line.strip_prefix("**/") {
+            Some(rest) => (false, rest),
+            None => (line.contains('/'), line.strip_prefix('/').unwrap_or(line)),
+        };
+        Some(Self {
+            pattern: Pattern::new(line).ok()?,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Whether the rule matches `rel`, a path relative to the rule's base.
+    fn matches(&self, rel: &Path) -> bool {
+        if self.anchored {
+            return self.pattern.matches_path_with(rel, MATCH_OPTIONS);
+        }
+        // Unanchored: try every suffix (`a/b/c`, `b/c`, `c`)
+        let parts: Vec<Component> = rel.components().collect();
+        (0..parts.len()).any(|i| {
+            let suffix: PathBuf = parts[i..].iter().collect();
+            self.pattern.matches_path_with(&suffix, MATCH_OPTIONS)
+        })
+    }
+}
+
+/// The rules of the ignore files in one directory.
+#[derive(Debug)]
+struct RuleSet {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+/// Ignore rules in effect for a directory and its ancestors.
+///
+/// Cheap to clone; a walker calls [`IgnoreStack::descend`] per directory.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    sets: Vec<Arc<RuleSet>>,
+}
+
+impl IgnoreStack {
+    /// Rules in effect at `dir`: the ignore files of its ancestors up to the
+    /// enclosing git repository root, then those in `dir` itself.
+    ///
+    /// Outside a git repository only `dir`'s own ignore files apply.
+    pub fn for_dir(dir: &Path) -> Self {
+        let mut ancestors = Vec::new();
+        let mut in_repo = dir.join(".git").exists();
+        let mut current = dir.parent();
+        while let Some(d) = current {
+            if in_repo {
+                break;
+            }
+            ancestors.push(d);
+            in_repo = d.join(".git").exists();
+            current = d.parent();
+        }
+        if !in_repo {
+            ancestors.clear();
+        }
+
+        let mut stack = Self::default();
+        for d in ancestors.iter().rev() {
+            stack = stack.descend(d);
+        }
+        stack.descend(dir)
+    }
+
+    /// These rules plus the ignore files found directly in `dir`.
+    pub fn descend(&self, dir: &Path) -> Self {
+        let rules: Vec<Rule> = IGNORE_FILES
+            .iter()
+            .filter_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+            .flat_map(|text| text.lines().filter_map(Rule::parse).collect::<Vec<_>>())
+            .collect();
+        let mut next = self.clone();
+        if !rules.is_empty() {
+            next.sets.push(Arc::new(RuleSet {
+                base: dir.to_path_buf(),
+                rules,
+            }));
+        }
+        next
+    }
+
+    /// Whether `path` is ignored. `is_dir` enables directory-only rules.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for set in &self.sets {
+            let Ok(rel) = path.strip_prefix(&set.base) else {
+                continue;
+            };
+            for rule in &set.rules {
+                if (is_dir || !rule.dir_only) && rule.matches(rel) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn gitignore_subset() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::write(
+            root.join(".gitignore"),
+            "# build output\ntarget/\n*.log\n!keep.log\n/local.toml\ndocs/**/*.tmp\n",
+        )
+        .unwrap();
+        let stack = IgnoreStack::for_dir(root);
+
+        assert!(stack.is_ignored(&root.join("target"), true));
+        assert!(!stack.is_ignored(&root.join("target"), false)); // dir-only rule
+        assert!(stack.is_ignored(&root.join("sub/debug.log"), false));
+        assert!(!stack.is_ignored(&root.join("sub/keep.log"), false));
+        assert!(stack.is_ignored(&root.join("local.toml"), false));
+        assert!(!stack.is_ignored(&root.join("sub/local.toml"), false)); // anchored
+        assert!(stack.is_ignored(&root.join("docs/a/b/x.tmp"), false));
+        assert!(!stack.is_ignored(&root.join("src/main.rs"), false));
+    }
+
+    #[test]
+    fn nested_files_and_repo_root() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("crates/app")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.gen.rs\n").unwrap();
+        std::fs::write(root.join("crates/.gitignore"), "!app.gen.rs\n").unwrap();
+
+        // Starting below the repo root still sees the root's rules
+        let app = root.join("crates/app");
+        let stack = IgnoreStack::for_dir(&app);
+        assert!(stack.is_ignored(&app.join("schema.gen.rs"), false));
+        assert!(!stack.is_ignored(&app.join("app.gen.rs"), false));
+    }
+}
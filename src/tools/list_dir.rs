@@ -0,0 +1,643 @@
+//! ListDirTool — a bounded directory overview.
+//!
+//! The walk honours `.gitignore` / `.ignore` (see [`super::ignore`]) and
+//! skips dotfiles, like grep. Each file shown carries its line count, size,
+//! language and modification time (UTC); each directory its file and size
+//! totals. Directories below the requested depth, and entries past
+//! [`MAX_ENTRIES`] in one directory, are collapsed into those totals, so a
+//! whole repository fits in one cheap call.
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use super::ignore::IgnoreStack;
+use super::output::{self, OutputStore};
+use super::workspace::{self, Access, Workspace};
+use super::{extract_tag, ToolPeer, ToolResponse};
+
+/// Directory tree with per-file metadata.
+#[derive(Default)]
+pub struct ListDirTool {
+    workspace: Option<Arc<Workspace>>,
+    output_store: Option<Arc<OutputStore>>,
+}
+
+const DEFAULT_DEPTH: usize = 2;
+const MAX_DEPTH: usize = 8;
+
+/// Entries shown per directory; the rest are only counted.
+const MAX_ENTRIES: usize = 50;
+
+/// Entries counted per call, across collapsed directories. Past this the
+/// totals are reported as lower bounds.
+const MAX_COUNTED: usize = 100_000;
+
+/// Files larger than this get no line count.
+const MAX_COUNT_LINES_BYTES: u64 = 8 * 1024 * 1024;
+
+/// File and size totals for a directory.
+#[derive(Debug, Default, Clone, Copy)]
+struct Totals {
+    files: usize,
+    dirs: usize,
+    bytes: u64,
+}
+
+impl Totals {
+    fn add(&mut self, other: Totals) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.bytes += other.bytes;
+    }
+}
+
+/// One directory entry that survived the filters.
+struct Entry {
+    name: String,
+    path: std::path::PathBuf,
+    kind: Kind,
+}
+
+#[derive(PartialEq)]
+enum Kind {
+    Dir,
+    /// An ignored directory: shown, never entered.
+    IgnoredDir,
+    File,
+    Symlink,
+}
+
+/// State of one listing.
+struct Walk<'a> {
+    hidden: bool,
+    jail: Option<&'a Workspace>,
+    counted: usize,
+    lines: Vec<String>,
+}
+
+impl Walk<'_> {
+    /// The entries of `dir` to show or count, directories first.
+    fn entries(&self, dir: &Path, ignores: &IgnoreStack) -> Vec<Entry> {
+        let Ok(read) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut entries = Vec::new();
+        for entry in read.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type().ok();
+            let is_link = file_type.is_none_or(|t| t.is_symlink());
+
+            if let Some(ws) = self.jail {
+                if is_link && !ws.contains(&path, Access::Read) {
+                    continue;
+                }
+            }
+            if !self.hidden && name.starts_with('.') {
+                continue;
+            }
+
+            // Symlinked directories are not followed, which also rules out cycles
+            let kind = if is_link {
+                Kind::Symlink
+            } else if path.is_dir() {
+                if !self.hidden && ignores.is_ignored(&path, true) {
+                    Kind::IgnoredDir
+                } else {
+                    Kind::Dir
+                }
+            } else {
+                if !self.hidden && ignores.is_ignored(&path, false) {
+                    continue;
+                }
+                Kind::File
+            };
+            entries.push(Entry { name, path, kind });
+        }
+        entries.sort_by(|a, b| {
+            let rank = |e: &Entry| usize::from(!matches!(e.kind, Kind::Dir | Kind::IgnoredDir));
+            rank(a).cmp(&rank(b)).then_with(|| a.name.cmp(&b.name))
+        });
+        entries
+    }
+
+    /// List `dir`'s entries `depth` levels deep at `indent`, returning the
+    /// directory's totals.
+    fn list(&mut self, dir: &Path, ignores: &IgnoreStack, depth: usize, indent: usize) -> Totals {
+        let entries = self.entries(dir, ignores);
+        let pad = "  ".repeat(indent);
+        let width = entries
+            .iter()
+            .take(MAX_ENTRIES)
+            .filter(|e| e.kind == Kind::File)
+            .map(|e| e.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(40);
+
+        let mut totals = Totals::default();
+        let mut rest = Totals::default();
+        for (i, entry) in entries.iter().enumerate() {
+            let shown = i < MAX_ENTRIES;
+            match entry.kind {
+                Kind::Dir => {
+                    let sub = if shown {
+                        let header = self.lines.len();
+                        self.lines.push(String::new());
+                        let nested = ignores.descend(&entry.path);
+                        let sub = if depth > 1 {
+                            self.list(&entry.path, &nested, depth - 1, indent + 1)
+                        } else {
+                            self.count(&entry.path, &nested)
+                        };
+                        self.lines[header] = format!("{pad}{}/  {}", entry.name, describe(sub));
+                        sub
+                    } else {
+                        self.count(&entry.path, &ignores.descend(&entry.path))
+                    };
+                    let dir = Totals {
+                        dirs: 1,
+                        ..Totals::default()
+                    };
+                    if shown {
+                        totals.add(dir);
+                        totals.add(sub);
+                    } else {
+                        rest.add(dir);
+                        rest.add(sub);
+                    }
+                }
+                Kind::IgnoredDir => {
+                    if shown {
+                        self.lines.push(format!("{pad}{}/  (ignored)", entry.name));
+                    }
+                }
+                Kind::Symlink => {
+                    if shown {
+                        let target = std::fs::read_link(&entry.path)
+                            .map(|t| t.display().to_string())
+                            .unwrap_or_else(|_| "?".into());
+                        self.lines.push(format!("{pad}{} -> {target}", entry.name));
+                    }
+                }
+                Kind::File => {
+                    let meta = std::fs::metadata(&entry.path).ok();
+                    let bytes = meta.as_ref().map_or(0, |m| m.len());
+                    let file = Totals {
+                        files: 1,
+                        bytes,
+                        ..Totals::default()
+                    };
+                    if shown {
+                        self.lines.push(format!(
+                            "{pad}{:<width$}  {}",
+                            entry.name,
+                            file_line(&entry.path, bytes, meta.as_ref())
+                        ));
+                        totals.add(file);
+                    } else {
+                        rest.add(file);
+                    }
+                }
+            }
+        }
+        if entries.len() > MAX_ENTRIES {
+            let more = entries.len() - MAX_ENTRIES;
+            self.lines
+                .push(format!("{pad}... {more} more entries: {}", describe(rest)));
+            totals.add(rest);
+        }
+        totals
+    }
+
+    /// Totals of a collapsed directory, without listing it.
+    fn count(&mut self, dir: &Path, ignores: &IgnoreStack) -> Totals {
+        let mut totals = Totals::default();
+        for entry in self.entries(dir, ignores) {
+            if self.counted >= MAX_COUNTED {
+                break;
+            }
+            self.counted += 1;
+            match entry.kind {
+                Kind::Dir => {
+                    totals.dirs += 1;
+                    let nested = ignores.descend(&entry.path);
+                    totals.add(self.count(&entry.path, &nested));
+                }
+                Kind::File => {
+                    totals.files += 1;
+                    totals.bytes += std::fs::metadata(&entry.path).map_or(0, |m| m.len());
+                }
+                Kind::IgnoredDir | Kind::Symlink => {}
+            }
+        }
+        totals
+    }
+}
+
+/// `12 files, 3 dirs, 48.2 KB`.
+fn describe(totals: Totals) -> String {
+    let plural = |n: usize, what: &str| {
+        if n == 1 {
+            format!("1 {what}")
+        } else {
+            format!("{n} {what}s")
+        }
+    };
+    let mut parts = vec![plural(totals.files, "file")];
+    if totals.dirs > 0 {
+        parts.push(plural(totals.dirs, "dir"));
+    }
+    parts.push(human_size(totals.bytes));
+    parts.join(", ")
+}
+
+/// Line count, size, language and mtime of one file.
+fn file_line(path: &Path, bytes: u64, meta: Option<&std::fs::Metadata>) -> String {
+    let lines = match line_count(path, bytes) {
+        Some(n) => format!("{n} lines"),
+        None if bytes > MAX_COUNT_LINES_BYTES => "-".into(),
+        None => "binary".into(),
+    };
+    let modified = meta
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or_else(|| "-".into(), |d| format_utc(d.as_secs()));
+    format!(
+        "{lines:>11}  {:>9}  {:<10}  {modified}",
+        human_size(bytes),
+        language(path).unwrap_or("-")
+    )
+}
+
+/// Lines in a text file; None for binaries and very large files.
+fn line_count(path: &Path, bytes: u64) -> Option<usize> {
+    if bytes > MAX_COUNT_LINES_BYTES {
+        return None;
+    }
+    let mut data = Vec::with_capacity(bytes as usize);
+    std::fs::File::open(path)
+        .ok()?
+        .read_to_end(&mut data)
+        .ok()?;
+    if data[..data.len().min(8192)].contains(&0) {
+        return None;
+    }
+    let newlines = data.iter().filter(|&&b| b == b'\n').count();
+    Some(newlines + usize::from(data.last().is_some_and(|&b| b != b'\n')))
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// `YYYY-MM-DD HH:MM` (UTC) of a Unix timestamp.
+fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (hour, minute) = (secs % 86_400 / 3600, secs % 3600 / 60);
+    // Civil-from-days (Howard Hinnant), shifted so years start in March
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}")
+}
+
+/// The language of a file, from its name.
+fn language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    let by_name = match name {
+        "Makefile" | "GNUmakefile" => Some("make"),
+        "Dockerfile" => Some("docker"),
+        "CMakeLists.txt" => Some("cmake"),
+        "Cargo.lock" | "poetry.lock" | "uv.lock" => Some("lock"),
+        _ => None,
+    };
+    if by_name.is_some() {
+        return by_name;
+    }
+    let ext = path.extension()?.to_str()?;
+    Some(match ext {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "rb" => "ruby",
+        "php" => "php",
+        "cs" => "csharp",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" | "scss" => "css",
+        "md" | "markdown" => "markdown",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "json" => "json",
+        "xml" => "xml",
+        "proto" => "protobuf",
+        "wit" => "wit",
+        "ipynb" => "notebook",
+        "txt" => "text",
+        _ => return None,
+    })
+}
+
+impl ListDirTool {
+    /// List `dir` (shown as `shown`). Blocking.
+    fn run(
+        dir: &Path,
+        shown: &str,
+        depth: usize,
+        hidden: bool,
+        jail: Option<&Workspace>,
+    ) -> Result<String, String> {
+        if !dir.is_dir() {
+            return Err(format!("not a directory: {shown}"));
+        }
+        let mut walk = Walk {
+            hidden,
+            jail,
+            counted: 0,
+            lines: vec![String::new()],
+        };
+        let totals = walk.list(dir, &IgnoreStack::for_dir(dir), depth, 1);
+        let shown = shown.trim_end_matches('/');
+        walk.lines[0] = format!("{shown}/  {}", describe(totals));
+        if walk.counted >= MAX_COUNTED {
+            walk.lines.push(format!(
+                "(stopped counting after {MAX_COUNTED} entries; collapsed totals are lower bounds)"
+            ));
+        }
+        Ok(walk.lines.join("\n"))
+    }
+}
+
+#[async_trait]
+impl Handler for ListDirTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+
+        let path = extract_tag(&xml_str, "path")
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| ".".into());
+        let depth = match extract_tag(&xml_str, "depth") {
+            Some(d) => match d.trim().parse::<usize>() {
+                Ok(d) if d >= 1 => d.min(MAX_DEPTH),
+                _ => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&format!(
+                            "invalid <depth>: {d} (expected 1 to {MAX_DEPTH})"
+                        )),
+                    });
+                }
+            },
+            None => DEFAULT_DEPTH,
+        };
+        let hidden = extract_tag(&xml_str, "hidden").is_some_and(|h| h.trim() == "true");
+
+        let dir =
+            match workspace::resolve_in(self.workspace.as_deref(), "list-dir", &path, Access::Read)
+            {
+                Ok(p) => p,
+                Err(e) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&e),
+                    });
+                }
+            };
+
+        // The walk and the line counts are blocking; keep them off the runtime.
+        let jail = self.workspace.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            Self::run(&dir, &path, depth, hidden, jail.as_deref())
+        })
+        .await
+        .map_err(|e| PipelineError::Handler(format!("list-dir task panicked: {e}")))?;
+
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for ListDirTool {
+    fn name(&self) -> &str {
+        "list-dir"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Directory overview: a tree to the given depth with each file's line count, size, language and modification time (UTC), and file and size totals per directory. Honours .gitignore and .ignore and skips dotfiles unless hidden is set; ignored directories are shown but not entered. Deeper directories, and entries past 50 in one directory, are summarized as counts.
+interface list-dir {
+    record request {
+        /// Directory to list (default: the current directory)
+        path: option<string>,
+        /// Levels to show (default 2, at most 8)
+        depth: option<u32>,
+        /// Include dotfiles and ignored entries
+        hidden: option<bool>,
+    }
+    list: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "list-dir".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "ListDirRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    fn jailed(root: &Path) -> ListDirTool {
+        let mut tool = ListDirTool::default();
+        tool.set_workspace(Arc::new(Workspace::new("coding", root, &[]).unwrap()));
+        tool
+    }
+
+    #[tokio::test]
+    async fn tree_with_metadata_and_collapsed_depth() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src/agent/deep")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir(root.join(".git")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        std::fs::write(root.join("build.log"), "noise\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n\n// end").unwrap();
+        std::fs::write(root.join("src/agent/mod.rs"), "mod deep;\n").unwrap();
+        std::fs::write(root.join("src/agent/deep/x.rs"), "x\n").unwrap();
+        std::fs::write(root.join("src/logo.png"), [0x89, b'P', 0, 0]).unwrap();
+        std::fs::write(root.join("target/debug/app"), "bin").unwrap();
+
+        let xml = "<ListDirRequest><depth>2</depth></ListDirRequest>";
+        let (ok, out) = get_result(
+            jailed(root)
+                .handle(make_payload(xml), make_ctx())
+                .await
+                .unwrap(),
+        );
+        assert!(ok, "{out}");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "./  5 files, 3 dirs, 57 B");
+        assert_eq!(lines[1], "  src/  4 files, 2 dirs, 36 B");
+        // depth 2 stops here: agent/ is collapsed into its totals
+        assert_eq!(lines[2], "    agent/  2 files, 1 dir, 12 B");
+        assert!(
+            lines[3].starts_with("    logo.png       binary        4 B  -"),
+            "{out}"
+        );
+        assert!(
+            lines[4].starts_with("    main.rs       3 lines       20 B  rust"),
+            "{out}"
+        );
+        assert_eq!(lines[5], "  target/  (ignored)");
+        assert!(
+            lines[6].starts_with("  Cargo.toml      2 lines       21 B  toml"),
+            "{out}"
+        );
+        assert_eq!(lines.len(), 7, "{out}");
+        assert!(!out.contains(".git") && !out.contains("build.log"));
+    }
+
+    #[tokio::test]
+    async fn large_directories_are_summarized() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..(MAX_ENTRIES + 5) {
+            std::fs::write(dir.path().join(format!("f{i:03}.txt")), "ab").unwrap();
+        }
+
+        let xml = "<ListDirRequest><path>.</path><depth>1</depth></ListDirRequest>";
+        let (ok, out) = get_result(
+            jailed(dir.path())
+                .handle(make_payload(xml), make_ctx())
+                .await
+                .unwrap(),
+        );
+        assert!(ok, "{out}");
+        assert!(out.starts_with("./  55 files, 110 B\n"), "{out}");
+        assert!(
+            out.contains("f049.txt") && !out.contains("f050.txt"),
+            "{out}"
+        );
+        assert!(
+            out.ends_with("  ... 5 more entries: 5 files, 10 B"),
+            "{out}"
+        );
+    }
+
+    #[tokio::test]
+    async fn hidden_includes_dotfiles_and_ignored() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(".ignore"), "*.tmp\n").unwrap();
+        std::fs::write(dir.path().join("a.tmp"), "").unwrap();
+
+        let xml = "<ListDirRequest><hidden>true</hidden></ListDirRequest>";
+        let (ok, out) = get_result(
+            jailed(dir.path())
+                .handle(make_payload(xml), make_ctx())
+                .await
+                .unwrap(),
+        );
+        assert!(ok, "{out}");
+        assert!(out.contains(".ignore") && out.contains("a.tmp"), "{out}");
+
+        let xml = "<ListDirRequest><path>/etc</path></ListDirRequest>";
+        let (ok, err) = get_result(
+            jailed(dir.path())
+                .handle(make_payload(xml), make_ctx())
+                .await
+                .unwrap(),
+        );
+        assert!(!ok);
+        assert!(err.contains("outside workspace"), "{err}");
+    }
+
+    #[test]
+    fn utc_dates() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00");
+        assert_eq!(format_utc(951_782_400 + 3_660), "2000-02-29 01:01");
+        assert_eq!(format_utc(1_790_000_000), "2026-09-21 14:13");
+    }
+
+    #[test]
+    fn list_dir_metadata() {
+        let tool = ListDirTool::default();
+        assert_eq!(tool.name(), "list-dir");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.name, "list-dir");
+        assert_eq!(iface.request_tag(), "ListDirRequest");
+    }
+}
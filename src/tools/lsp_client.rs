@@ -0,0 +1,1397 @@
+//! LSP client — semantic navigation through real language servers.
+//!
+//! The lsp tool starts a configured language server (rust-analyzer,
+//! pyright, ...) over stdio JSON-RPC the first time a file it serves is
+//! asked about, and keeps it running for the workspace. Documents the
+//! server has open are mirrored from disk: files announced by file-write
+//! and file-edit are sent as `didChange` straight away, and every open
+//! document is re-checked before each query, which catches other writers.
+//!
+//! Lines and columns in requests and results are 1-based characters, like
+//! the other tools; on the wire they become LSP's 0-based UTF-16 positions.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use serde_json::{json, Value};
+use similar::TextDiff;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, oneshot, Mutex as AsyncMutex, Notify};
+
+use super::output::{self, OutputStore};
+use super::sandbox::Sandbox;
+use super::workspace::{self, Access, Workspace};
+use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::organism::profile::LspSpec;
+use crate::pipeline::events::PipelineEvent;
+
+/// How long one request may take; servers index lazily on first use.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long `diagnostics` waits for the server to publish.
+const DIAGNOSTICS_WAIT: Duration = Duration::from_secs(10);
+
+/// Locations and symbols listed; the rest are only counted.
+const MAX_LISTED: usize = 100;
+
+/// A language server the tool may start.
+#[derive(Debug, Clone, PartialEq)]
+struct ServerConfig {
+    name: String,
+    command: Vec<String>,
+    extensions: Vec<String>,
+}
+
+/// rust-analyzer for Rust, pyright for Python.
+fn default_servers() -> Vec<ServerConfig> {
+    let server = |name: &str, command: &[&str], extensions: &[&str]| ServerConfig {
+        name: name.to_string(),
+        command: command.iter().map(|s| s.to_string()).collect(),
+        extensions: extensions.iter().map(|s| s.to_string()).collect(),
+    };
+    vec![
+        server("rust-analyzer", &["rust-analyzer"], &["rs"]),
+        server(
+            "pyright",
+            &["pyright-langserver", "--stdio"],
+            &["py", "pyi"],
+        ),
+    ]
+}
+
+/// Semantic navigation — definitions, references, hover, symbols, rename
+/// previews and diagnostics from language servers.
+pub struct LspTool {
+    servers: Vec<ServerConfig>,
+    workspace: Option<Arc<Workspace>>,
+    sandbox: Option<Arc<Sandbox>>,
+    output_store: Option<Arc<OutputStore>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+    /// Running servers, by name.
+    clients: Arc<AsyncMutex<HashMap<String, Arc<Client>>>>,
+    /// Started with the first server, to mirror announced file changes.
+    watcher: OnceLock<()>,
+}
+
+impl Default for LspTool {
+    fn default() -> Self {
+        Self {
+            servers: default_servers(),
+            workspace: None,
+            sandbox: None,
+            output_store: None,
+            events: None,
+            clients: Arc::default(),
+            watcher: OnceLock::new(),
+        }
+    }
+}
+
+impl LspTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a profile's language servers instead of the defaults.
+    pub fn with_servers(spec: &LspSpec) -> Self {
+        let servers = spec
+            .servers
+            .iter()
+            .map(|(name, s)| ServerConfig {
+                name: name.clone(),
+                command: s.command.clone(),
+                extensions: s.extensions.clone(),
+            })
+            .collect();
+        Self {
+            servers,
+            ..Self::default()
+        }
+    }
+
+    /// Run language servers inside `sandbox`, like command-exec.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(Arc::new(sandbox));
+        self
+    }
+
+    fn root(&self) -> PathBuf {
+        let root = match &self.workspace {
+            Some(ws) => ws.root().to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        root.canonicalize().unwrap_or(root)
+    }
+
+    /// The running server for `path`'s language, started if need be.
+    async fn client_for(&self, path: &Path) -> Result<Arc<Client>, String> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let server = self
+            .servers
+            .iter()
+            .find(|s| s.extensions.iter().any(|e| e == ext))
+            .ok_or_else(|| format!("no language server configured for .{ext} files"))?;
+
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(&server.name) {
+            if !client.closed.load(Ordering::SeqCst) {
+                return Ok(client.clone());
+            }
+        }
+        let client = self.spawn(server).await?;
+        clients.insert(server.name.clone(), client.clone());
+        drop(clients);
+        self.start_watcher();
+        Ok(client)
+    }
+
+    async fn spawn(&self, server: &ServerConfig) -> Result<Arc<Client>, String> {
+        let root = self.root();
+        let mut cmd = Command::new(&server.command[0]);
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(cmd.as_std_mut());
+        }
+        cmd.args(&server.command[1..])
+            .current_dir(&root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {e}", server.name))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(format!("failed to start {}: no stdio", server.name));
+        };
+        Client::connect(&server.name, stdout, stdin, &root, Some(child)).await
+    }
+
+    /// Mirror files other tools write into the servers that have them open.
+    fn start_watcher(&self) {
+        let Some(tx) = &self.events else {
+            return;
+        };
+        self.watcher.get_or_init(|| {
+            let mut rx = tx.subscribe();
+            let clients = Arc::downgrade(&self.clients);
+            tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(PipelineEvent::FileChanged { path, .. }) => {
+                            let Some(clients) = clients.upgrade() else {
+                                break;
+                            };
+                            // Documents are keyed by canonical path
+                            let path = path.canonicalize().unwrap_or(path);
+                            let running: Vec<Arc<Client>> =
+                                clients.lock().await.values().cloned().collect();
+                            for client in running {
+                                if let Err(e) = client.sync(&path, false).await {
+                                    tracing::warn!("lsp: mirroring {}: {e}", path.display());
+                                }
+                            }
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        });
+    }
+
+    /// Resolve the request's path, and bring its server up to date.
+    async fn open(&self, xml: &str) -> Result<(PathBuf, String, Arc<Client>), String> {
+        let path = extract_tag(xml, "path")
+            .filter(|p| !p.trim().is_empty())
+            .ok_or("this action requires path")?;
+        let resolved =
+            workspace::resolve_in(self.workspace.as_deref(), "lsp", path.trim(), Access::Read)?;
+        let resolved = resolved
+            .canonicalize()
+            .map_err(|e| format!("{}: {e}", path.trim()))?;
+        let text =
+            std::fs::read_to_string(&resolved).map_err(|e| format!("read error: {path}: {e}"))?;
+        let client = self.client_for(&resolved).await?;
+        client.refresh().await?;
+        client.sync(&resolved, true).await?;
+        Ok((resolved, text, client))
+    }
+
+    async fn run(&self, xml: &str) -> Result<String, String> {
+        let action = extract_tag(xml, "action").unwrap_or_default();
+        let root = self.root();
+        let mut files = Files::new(root.clone());
+        match action.trim() {
+            "definition" | "references" | "hover" | "rename-preview" => {
+                let (path, text, client) = self.open(xml).await?;
+                let position = position_param(xml, &text)?;
+                let document = json!({ "uri": file_uri(&path) });
+                files.seed(&path, text);
+                match action.trim() {
+                    "definition" => {
+                        let params = json!({ "textDocument": document, "position": position });
+                        let result = client.request("textDocument/definition", params).await?;
+                        Ok(render_locations(
+                            "definition",
+                            &locations(&result),
+                            &mut files,
+                        ))
+                    }
+                    "references" => {
+                        let params = json!({
+                            "textDocument": document,
+                            "position": position,
+                            "context": { "includeDeclaration": true },
+                        });
+                        let result = client.request("textDocument/references", params).await?;
+                        Ok(render_locations(
+                            "reference",
+                            &locations(&result),
+                            &mut files,
+                        ))
+                    }
+                    "hover" => {
+                        let params = json!({ "textDocument": document, "position": position });
+                        let result = client.request("textDocument/hover", params).await?;
+                        Ok(hover_text(&result["contents"])
+                            .unwrap_or_else(|| "no hover information".into()))
+                    }
+                    _ => {
+                        let new_name = extract_tag(xml, "new_name")
+                            .filter(|n| !n.trim().is_empty())
+                            .ok_or("rename-preview requires new_name")?;
+                        let params = json!({
+                            "textDocument": document,
+                            "position": position,
+                            "newName": new_name.trim(),
+                        });
+                        let result = client.request("textDocument/rename", params).await?;
+                        render_rename(new_name.trim(), &result, &mut files)
+                    }
+                }
+            }
+            "workspace-symbol" => {
+                let query = extract_tag(xml, "query")
+                    .filter(|q| !q.trim().is_empty())
+                    .ok_or("workspace-symbol requires query")?;
+                let client = match extract_tag(xml, "path").filter(|p| !p.trim().is_empty()) {
+                    Some(_) => self.open(xml).await?.2,
+                    None => self.any_client().await?,
+                };
+                let params = json!({ "query": query.trim() });
+                let result = client.request("workspace/symbol", params).await?;
+                Ok(render_symbols(&result, &mut files))
+            }
+            "diagnostics" => {
+                let (path, text, client) = self.open(xml).await?;
+                files.seed(&path, text);
+                let published = client.wait_for_diagnostics(&path).await;
+                Ok(render_diagnostics(&path, &published, &mut files))
+            }
+            "" => Err("missing required <action>".into()),
+            other => Err(format!(
+                "unknown action: {other} (expected definition, references, hover, \
+                 workspace-symbol, rename-preview or diagnostics)"
+            )),
+        }
+    }
+
+    /// A running server, for queries not tied to a file.
+    async fn any_client(&self) -> Result<Arc<Client>, String> {
+        let clients = self.clients.lock().await;
+        let client = self
+            .servers
+            .iter()
+            .filter_map(|s| clients.get(&s.name))
+            .find(|c| !c.closed.load(Ordering::SeqCst))
+            .cloned()
+            .ok_or("no language server is running yet; give a path to pick one")?;
+        drop(clients);
+        client.refresh().await?;
+        Ok(client)
+    }
+}
+
+/// A document as last sent to the server.
+struct Document {
+    version: i64,
+    text: String,
+}
+
+/// Diagnostics a server published for one document.
+#[derive(Debug, Clone, Default)]
+struct Published {
+    /// False from sending new text until the server publishes for it.
+    current: bool,
+    items: Vec<Value>,
+}
+
+/// A JSON-RPC connection to one language server.
+struct Client {
+    name: String,
+    writer: AsyncMutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    next_id: AtomicI64,
+    pending: Mutex<HashMap<i64, oneshot::Sender<Result<Value, String>>>>,
+    documents: AsyncMutex<HashMap<PathBuf, Document>>,
+    diagnostics: Mutex<HashMap<String, Published>>,
+    published: Notify,
+    closed: AtomicBool,
+    /// The server process, killed when the client is dropped.
+    _process: Option<Child>,
+}
+
+impl Client {
+    /// Start a session over `reader`/`writer`: read in the background and
+    /// run the `initialize` handshake.
+    async fn connect(
+        name: &str,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+        root: &Path,
+        process: Option<Child>,
+    ) -> Result<Arc<Self>, String> {
+        let client = Arc::new(Self {
+            name: name.to_string(),
+            writer: AsyncMutex::new(Box::new(writer)),
+            next_id: AtomicI64::new(1),
+            pending: Mutex::default(),
+            documents: AsyncMutex::default(),
+            diagnostics: Mutex::default(),
+            published: Notify::new(),
+            closed: AtomicBool::new(false),
+            _process: process,
+        });
+
+        let weak = Arc::downgrade(&client);
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader).await {
+                let Some(client) = weak.upgrade() else {
+                    return;
+                };
+                client.dispatch(message).await;
+            }
+            if let Some(client) = weak.upgrade() {
+                client.shut();
+            }
+        });
+
+        let root_uri = file_uri(root);
+        let folder = root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": root_uri,
+            "workspaceFolders": [{ "uri": root_uri, "name": folder }],
+            "clientInfo": { "name": "agentos" },
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-16"] },
+                "workspace": {
+                    "configuration": true,
+                    "workspaceEdit": { "documentChanges": true },
+                    "symbol": {},
+                },
+                "textDocument": {
+                    "synchronization": { "dynamicRegistration": false },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "hover": { "contentFormat": ["markdown", "plaintext"] },
+                    "rename": { "prepareSupport": false },
+                    "publishDiagnostics": { "versionSupport": true },
+                },
+            },
+        });
+        client.request("initialize", params).await?;
+        client.notify("initialized", json!({})).await?;
+        Ok(client)
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), String> {
+        let mut writer = self.writer.lock().await;
+        write_message(&mut *writer, message)
+            .await
+            .map_err(|e| format!("{}: {e}", self.name))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        if self.closed.load(Ordering::SeqCst) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(format!("language server {} has exited", self.name));
+        }
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.send(&message).await?;
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(format!("language server {} has exited", self.name)),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(format!("{}: {method} timed out", self.name))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        self.send(&message).await
+    }
+
+    /// Route one message from the server.
+    async fn dispatch(&self, message: Value) {
+        let method = message["method"].as_str();
+        match (method, message.get("id")) {
+            // A request from the server: answer what we must, decline the rest
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let reply = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                let _ = self.send(&reply).await;
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = &message["params"];
+                let uri = params["uri"].as_str().unwrap_or_default().to_string();
+                let items = params["diagnostics"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let published = Published {
+                    current: true,
+                    items,
+                };
+                self.diagnostics.lock().unwrap().insert(uri, published);
+                self.published.notify_waiters();
+            }
+            (Some(_), None) => {}
+            (None, Some(id)) => {
+                let Some(id) = id.as_i64() else {
+                    return;
+                };
+                let Some(tx) = self.pending.lock().unwrap().remove(&id) else {
+                    return;
+                };
+                let result = match message.get("error") {
+                    Some(error) => Err(format!(
+                        "{}: {}",
+                        self.name,
+                        error["message"].as_str().unwrap_or("request failed")
+                    )),
+                    None => Ok(message["result"].clone()),
+                };
+                let _ = tx.send(result);
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// The server went away: fail everything waiting on it.
+    fn shut(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.pending.lock().unwrap().clear();
+        self.published.notify_waiters();
+    }
+
+    /// Bring the server's copy of `path` up to date with the disk.
+    ///
+    /// Unopened files are opened only if `open` is set; a file that has
+    /// gone is closed.
+    async fn sync(&self, path: &Path, open: bool) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).ok();
+        let uri = file_uri(path);
+        let mut documents = self.documents.lock().await;
+        match (documents.get_mut(path), text) {
+            (None, Some(text)) if open => {
+                self.outdate(&uri);
+                let params = json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": language_id(path),
+                        "version": 1,
+                        "text": text,
+                    }
+                });
+                self.notify("textDocument/didOpen", params).await?;
+                documents.insert(path.to_path_buf(), Document { version: 1, text });
+            }
+            (Some(doc), Some(text)) if doc.text != text => {
+                self.outdate(&uri);
+                doc.version += 1;
+                let params = json!({
+                    "textDocument": { "uri": uri, "version": doc.version },
+                    "contentChanges": [{ "text": text }],
+                });
+                self.notify("textDocument/didChange", params).await?;
+                doc.text = text;
+            }
+            (Some(_), None) => {
+                let params = json!({ "textDocument": { "uri": uri } });
+                self.notify("textDocument/didClose", params).await?;
+                documents.remove(path);
+                self.diagnostics.lock().unwrap().remove(&uri);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// New text is on its way: diagnostics for `uri` are out of date until
+    /// the server publishes again.
+    fn outdate(&self, uri: &str) {
+        let mut diagnostics = self.diagnostics.lock().unwrap();
+        diagnostics.entry(uri.to_string()).or_default().current = false;
+    }
+
+    /// Re-check every open document against the disk.
+    async fn refresh(&self) -> Result<(), String> {
+        let open: Vec<PathBuf> = self.documents.lock().await.keys().cloned().collect();
+        for path in open {
+            self.sync(&path, false).await?;
+        }
+        Ok(())
+    }
+
+    /// The diagnostics for open document `path` once the server has
+    /// published for its current text, or whatever it has after a short
+    /// wait.
+    async fn wait_for_diagnostics(&self, path: &Path) -> Vec<Value> {
+        let uri = file_uri(path);
+        let published = || self.diagnostics.lock().unwrap().get(&uri).cloned();
+        let deadline = tokio::time::Instant::now() + DIAGNOSTICS_WAIT;
+        loop {
+            let notified = self.published.notified();
+            let latest = published().unwrap_or_default();
+            if latest.current
+                || self.closed.load(Ordering::SeqCst)
+                || tokio::time::timeout_at(deadline, notified).await.is_err()
+            {
+                return latest.items;
+            }
+        }
+    }
+}
+
+/// Read one `Content-Length`-framed message; `None` at end of stream.
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "missing Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Write one `Content-Length`-framed message.
+async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin + ?Sized),
+    message: &Value,
+) -> std::io::Result<()> {
+    let body = message.to_string();
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes())
+        .await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.flush().await
+}
+
+/// `file://` URI of an absolute path.
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Path of a `file://` URI.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn language_id(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+    {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "java" => "java",
+        _ => "plaintext",
+    }
+}
+
+/// The LSP position of the request's `line` and `column` (or `symbol`).
+fn position_param(xml: &str, text: &str) -> Result<Value, String> {
+    let line = extract_tag(xml, "line")
+        .and_then(|l| l.trim().parse::<usize>().ok())
+        .filter(|&l| l >= 1)
+        .ok_or("this action requires line (1-based)")?;
+    let line_text = text
+        .lines()
+        .nth(line - 1)
+        .ok_or_else(|| format!("line {line} is past the end of the file"))?;
+    let symbol = extract_tag(xml, "symbol").filter(|s| !s.trim().is_empty());
+    let column = match symbol {
+        Some(symbol) => {
+            let at = line_text
+                .find(symbol.trim())
+                .ok_or_else(|| format!("{} is not on line {line}", symbol.trim()))?;
+            line_text[..at].chars().count() + 1
+        }
+        None => extract_tag(xml, "column")
+            .and_then(|c| c.trim().parse::<usize>().ok())
+            .filter(|&c| c >= 1)
+            .ok_or("this action requires column or symbol")?,
+    };
+    let character: usize = line_text
+        .chars()
+        .take(column - 1)
+        .map(char::len_utf16)
+        .sum();
+    Ok(json!({ "line": line - 1, "character": character }))
+}
+
+/// Files read while rendering results, to place positions in them.
+struct Files {
+    root: PathBuf,
+    texts: HashMap<PathBuf, Option<String>>,
+}
+
+impl Files {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            texts: HashMap::new(),
+        }
+    }
+
+    fn seed(&mut self, path: &Path, text: String) {
+        self.texts.insert(path.to_path_buf(), Some(text));
+    }
+
+    fn text(&mut self, path: &Path) -> Option<&str> {
+        self.texts
+            .entry(path.to_path_buf())
+            .or_insert_with(|| std::fs::read_to_string(path).ok())
+            .as_deref()
+    }
+
+    /// A path as the agent should see it: relative to the root if inside.
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// 1-based line and character column of an LSP position in `path`.
+    fn place(&mut self, path: &Path, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let column = match self.text(path).and_then(|t| t.lines().nth(line)) {
+            Some(line_text) => {
+                let mut units = 0;
+                let mut chars = 0;
+                for c in line_text.chars() {
+                    if units >= character {
+                        break;
+                    }
+                    units += c.len_utf16();
+                    chars += 1;
+                }
+                chars + 1
+            }
+            None => character + 1,
+        };
+        (line + 1, column)
+    }
+
+    /// Byte offset of an LSP position in `text`.
+    fn offset(text: &str, position: &Value) -> Option<usize> {
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        let mut start = 0;
+        for _ in 0..line {
+            start += text[start..].find('\n')? + 1;
+        }
+        let line_text = &text[start..];
+        let line_text = &line_text[..line_text.find('\n').unwrap_or(line_text.len())];
+        let mut units = 0;
+        for (i, c) in line_text.char_indices() {
+            if units >= character {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(start + line_text.len())
+    }
+
+    /// `path:line:col: text of the line`.
+    fn location(&mut self, uri: &str, range: &Value) -> String {
+        let Some(path) = uri_path(uri) else {
+            return uri.to_string();
+        };
+        let (line, column) = self.place(&path, &range["start"]);
+        let shown = self.display(&path);
+        match self.text(&path).and_then(|t| t.lines().nth(line - 1)) {
+            Some(text) => format!("{shown}:{line}:{column}: {}", text.trim()),
+            None => format!("{shown}:{line}:{column}"),
+        }
+    }
+}
+
+/// (uri, range) of each Location or LocationLink in a result.
+fn locations(result: &Value) -> Vec<(String, Value)> {
+    let items = match result {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        single => vec![single.clone()],
+    };
+    items
+        .iter()
+        .filter_map(|item| match item.get("targetUri") {
+            Some(uri) => Some((
+                uri.as_str()?.to_string(),
+                item["targetSelectionRange"].clone(),
+            )),
+            None => Some((item["uri"].as_str()?.to_string(), item["range"].clone())),
+        })
+        .collect()
+}
+
+fn render_locations(what: &str, found: &[(String, Value)], files: &mut Files) -> String {
+    if found.is_empty() {
+        return format!("no {what} found");
+    }
+    let plural = if found.len() == 1 { "" } else { "s" };
+    let mut out = format!("{} {what}{plural}:", found.len());
+    for (uri, range) in found.iter().take(MAX_LISTED) {
+        out.push('\n');
+        out.push_str(&files.location(uri, range));
+    }
+    if found.len() > MAX_LISTED {
+        out.push_str(&format!("\n... and {} more", found.len() - MAX_LISTED));
+    }
+    out
+}
+
+/// Text of hover contents: MarkupContent, a MarkedString, or a list.
+fn hover_text(contents: &Value) -> Option<String> {
+    let text = match contents {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(hover_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(o) => {
+            let value = o.get("value")?.as_str()?;
+            match o.get("language").and_then(Value::as_str) {
+                Some(language) => format!("```{language}\n{value}\n```"),
+                None => value.to_string(),
+            }
+        }
+        _ => return None,
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+const SYMBOL_KINDS: [&str; 26] = [
+    "file",
+    "module",
+    "namespace",
+    "package",
+    "class",
+    "method",
+    "property",
+    "field",
+    "constructor",
+    "enum",
+    "interface",
+    "function",
+    "variable",
+    "constant",
+    "string",
+    "number",
+    "boolean",
+    "array",
+    "object",
+    "key",
+    "null",
+    "enum member",
+    "struct",
+    "event",
+    "operator",
+    "type parameter",
+];
+
+fn render_symbols(result: &Value, files: &mut Files) -> String {
+    let symbols = result.as_array().cloned().unwrap_or_default();
+    if symbols.is_empty() {
+        return "no symbols found".into();
+    }
+    let mut out = format!("{} symbols:", symbols.len());
+    for symbol in symbols.iter().take(MAX_LISTED) {
+        let kind = symbol["kind"]
+            .as_u64()
+            .and_then(|k| SYMBOL_KINDS.get((k as usize).wrapping_sub(1)))
+            .copied()
+            .unwrap_or("symbol");
+        let name = symbol["name"].as_str().unwrap_or_default();
+        out.push_str(&format!("\n{kind} {name}"));
+        if let Some(container) = symbol["containerName"].as_str().filter(|c| !c.is_empty()) {
+            out.push_str(&format!(" in {container}"));
+        }
+        let location = &symbol["location"];
+        let uri = location["uri"].as_str().unwrap_or_default();
+        // WorkspaceSymbol may leave the range for a later resolve
+        let place = match location.get("range") {
+            Some(range) => files.location(uri, range),
+            None => uri_path(uri)
+                .map(|p| files.display(&p))
+                .unwrap_or_else(|| uri.to_string()),
+        };
+        out.push_str(&format!(" — {place}"));
+    }
+    if symbols.len() > MAX_LISTED {
+        out.push_str(&format!("\n... and {} more", symbols.len() - MAX_LISTED));
+    }
+    out
+}
+
+/// A WorkspaceEdit as a diff per file; nothing is written.
+fn render_rename(new_name: &str, edit: &Value, files: &mut Files) -> Result<String, String> {
+    if edit.is_null() {
+        return Err("nothing to rename at that position".into());
+    }
+    let mut by_uri: Vec<(String, Vec<Value>)> = Vec::new();
+    let mut operations = Vec::new();
+    if let Some(changes) = edit["changes"].as_object() {
+        for (uri, edits) in changes {
+            by_uri.push((uri.clone(), edits.as_array().cloned().unwrap_or_default()));
+        }
+    }
+    for change in edit["documentChanges"].as_array().into_iter().flatten() {
+        match change["kind"].as_str() {
+            Some("rename") => operations.push(format!(
+                "rename file {} -> {}",
+                uri_display(change["oldUri"].as_str(), files),
+                uri_display(change["newUri"].as_str(), files)
+            )),
+            Some(kind) => operations.push(format!(
+                "{kind} file {}",
+                uri_display(change["uri"].as_str(), files)
+            )),
+            None => by_uri.push((
+                change["textDocument"]["uri"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                change["edits"].as_array().cloned().unwrap_or_default(),
+            )),
+        }
+    }
+
+    let count: usize = by_uri.iter().map(|(_, edits)| edits.len()).sum();
+    let mut out = format!(
+        "rename to {new_name}: {count} edits in {} files (preview only; nothing was written)",
+        by_uri.len()
+    );
+    for (uri, edits) in &by_uri {
+        let path = uri_path(uri).ok_or_else(|| format!("cannot preview edits to {uri}"))?;
+        let shown = files.display(&path);
+        let text = files
+            .text(&path)
+            .ok_or_else(|| format!("read error: {shown}"))?
+            .to_string();
+        let mut ranges = Vec::new();
+        for edit in edits {
+            let start = Files::offset(&text, &edit["range"]["start"]);
+            let end = Files::offset(&text, &edit["range"]["end"]);
+            match (start, end) {
+                (Some(start), Some(end)) if start <= end => {
+                    ranges.push((start, end, edit["newText"].as_str().unwrap_or_default()))
+                }
+                _ => return Err(format!("edit outside {shown}")),
+            }
+        }
+        ranges.sort_by_key(|&(start, end, _)| (start, end));
+        let mut renamed = text.clone();
+        for (start, end, new_text) in ranges.into_iter().rev() {
+            renamed.replace_range(start..end, new_text);
+        }
+        let diff = TextDiff::from_lines(&text, &renamed);
+        out.push_str("\n\n");
+        out.push_str(
+            &diff
+                .unified_diff()
+                .context_radius(1)
+                .header(&format!("a/{shown}"), &format!("b/{shown}"))
+                .to_string(),
+        );
+    }
+    for operation in operations {
+        out.push('\n');
+        out.push_str(&operation);
+    }
+    Ok(out.trim_end().to_string())
+}
+
+fn uri_display(uri: Option<&str>, files: &Files) -> String {
+    let uri = uri.unwrap_or_default();
+    uri_path(uri)
+        .map(|p| files.display(&p))
+        .unwrap_or_else(|| uri.to_string())
+}
+
+fn render_diagnostics(path: &Path, items: &[Value], files: &mut Files) -> String {
+    let shown = files.display(path);
+    if items.is_empty() {
+        return format!("{shown}: no diagnostics");
+    }
+    let mut out = format!("{shown}: {} diagnostics", items.len());
+    for item in items.iter().take(MAX_LISTED) {
+        let (line, column) = files.place(path, &item["range"]["start"]);
+        let severity = match item["severity"].as_u64() {
+            Some(1) => "error",
+            Some(2) => "warning",
+            Some(3) => "info",
+            Some(4) => "hint",
+            _ => "error",
+        };
+        let code = match &item["code"] {
+            Value::String(c) => format!("[{c}]"),
+            Value::Number(n) => format!("[{n}]"),
+            _ => String::new(),
+        };
+        let message = item["message"].as_str().unwrap_or_default();
+        let source = item["source"]
+            .as_str()
+            .map(|s| format!(" ({s})"))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "\n  {line}:{column} {severity}{code}: {}{source}",
+            message.replace('\n', "\n    ")
+        ));
+    }
+    if items.len() > MAX_LISTED {
+        out.push_str(&format!("\n  ... and {} more", items.len() - MAX_LISTED));
+    }
+    out
+}
+
+#[async_trait]
+impl Handler for LspTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+        let payload_xml = match self.run(&xml_str).await {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for LspTool {
+    fn name(&self) -> &str {
+        "lsp"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Semantic code navigation through the workspace's language server (rust-analyzer, pyright, ...), started on first use and kept running. Actions: definition, references, hover — of the symbol at path, line and column (or symbol, a name on that line); workspace-symbol — search symbols by query; rename-preview — the edits renaming the symbol to new_name would make, as a diff (nothing is written); diagnostics — the server's errors and warnings for path. Lines and columns are 1-based.
+interface lsp {
+    record request {
+        /// definition, references, hover, workspace-symbol, rename-preview or diagnostics
+        action: string,
+        /// The file, relative to the workspace
+        path: option<string>,
+        /// 1-based line of the symbol
+        line: option<u32>,
+        /// 1-based column of the symbol
+        column: option<u32>,
+        /// The symbol's name on that line, instead of column
+        symbol: option<string>,
+        /// workspace-symbol: the name to search for
+        query: option<string>,
+        /// rename-preview: the new name
+        new-name: option<string>,
+    }
+    run: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "lsp".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "LspRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    type Script = fn(&str, &Value) -> Value;
+
+    /// A scripted language server: answers requests with `script` and
+    /// records every notification it receives.
+    async fn fake_server(
+        tool: &LspTool,
+        root: &Path,
+        script: Script,
+    ) -> Arc<Mutex<Vec<(String, Value)>>> {
+        let (client_io, server_io) = tokio::io::duplex(1 << 16);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, mut server_write) = tokio::io::split(server_io);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(server_read);
+            while let Ok(Some(message)) = read_message(&mut reader).await {
+                let method = message["method"].as_str().unwrap_or_default().to_string();
+                let params = message["params"].clone();
+                match message.get("id") {
+                    Some(id) => {
+                        let result = script(&method, &params);
+                        let reply = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                        write_message(&mut server_write, &reply).await.unwrap();
+                    }
+                    None => {
+                        if method == "textDocument/didOpen" || method == "textDocument/didChange" {
+                            // publish one warning per "todo" in the text
+                            let text = params["textDocument"]["text"]
+                                .as_str()
+                                .or(params["contentChanges"][0]["text"].as_str())
+                                .unwrap_or_default();
+                            let diagnostics: Vec<Value> = text
+                                .lines()
+                                .enumerate()
+                                .filter(|(_, l)| l.contains("todo"))
+                                .map(|(i, _)| json!({
+                                    "range": { "start": { "line": i, "character": 0 }, "end": { "line": i, "character": 4 } },
+                                    "severity": 2,
+                                    "message": "unfinished",
+                                    "source": "fake",
+                                }))
+                                .collect();
+                            let publish = json!({
+                                "jsonrpc": "2.0",
+                                "method": "textDocument/publishDiagnostics",
+                                "params": { "uri": params["textDocument"]["uri"], "diagnostics": diagnostics },
+                            });
+                            write_message(&mut server_write, &publish).await.unwrap();
+                        }
+                        log.lock().unwrap().push((method, params));
+                    }
+                }
+            }
+        });
+        let client = Client::connect("fake", client_read, client_write, root, None)
+            .await
+            .unwrap();
+        tool.clients
+            .lock()
+            .await
+            .insert("rust-analyzer".into(), client);
+        tool.start_watcher();
+        received
+    }
+
+    fn setup(text: &str) -> (TempDir, LspTool, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let file = dir.path().join("src/lib.rs");
+        std::fs::write(&file, text).unwrap();
+        let mut tool = LspTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+        let file = file.canonicalize().unwrap();
+        (dir, tool, file)
+    }
+
+    #[tokio::test]
+    async fn definition_and_references_use_utf16_positions() {
+        let (_dir, tool, file) = setup("fn greet() {}\nfn main() { let s = \"😀\"; greet(); }\n");
+        fake_server(&tool, &tool.root(), |method, params| match method {
+            "initialize" => json!({ "capabilities": {} }),
+            "textDocument/definition" => {
+                // the emoji is one character but two UTF-16 units
+                assert_eq!(params["position"], json!({ "line": 1, "character": 26 }));
+                json!([{
+                    "targetUri": params["textDocument"]["uri"],
+                    "targetRange": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 13 } },
+                    "targetSelectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 8 } },
+                }])
+            }
+            "textDocument/references" => {
+                let uri = &params["textDocument"]["uri"];
+                json!([
+                    { "uri": uri, "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 8 } } },
+                    { "uri": uri, "range": { "start": { "line": 1, "character": 26 }, "end": { "line": 1, "character": 31 } } },
+                ])
+            }
+            _ => Value::Null,
+        })
+        .await;
+
+        let xml = "<LspRequest><action>definition</action><path>src/lib.rs</path><line>2</line><symbol>greet</symbol></LspRequest>";
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert_eq!(out, "1 definition:\nsrc/lib.rs:1:4: fn greet() {}");
+
+        let xml = "<LspRequest><action>references</action><path>src/lib.rs</path><line>1</line><column>4</column></LspRequest>";
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert!(out.starts_with("2 references:\nsrc/lib.rs:1:4: "), "{out}");
+        assert!(out.contains("\nsrc/lib.rs:2:26: fn main()"), "{out}");
+        assert!(file.exists());
+    }
+
+    #[tokio::test]
+    async fn announced_writes_are_mirrored_as_did_change() {
+        let (_dir, mut tool, file) = setup("fn a() {}\n");
+        let (tx, _rx) = broadcast::channel(16);
+        tool.set_event_sender(tx.clone());
+        let received = fake_server(&tool, &tool.root(), |method, _| match method {
+            "initialize" => json!({ "capabilities": {} }),
+            _ => json!({ "contents": { "kind": "markdown", "value": "```rust\nfn a()\n```" } }),
+        })
+        .await;
+
+        let xml = "<LspRequest><action>hover</action><path>src/lib.rs</path><line>1</line><column>4</column></LspRequest>";
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert_eq!(out, "```rust\nfn a()\n```");
+
+        std::fs::write(&file, "fn b() {}\n").unwrap();
+        super::super::announce_file_changed(Some(&tx), "t1", "file-edit", &file);
+        let change = async {
+            loop {
+                let found = received
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|(m, _)| m == "textDocument/didChange")
+                    .cloned();
+                match found {
+                    Some(change) => return change.1,
+                    None => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        };
+        let params = tokio::time::timeout(Duration::from_secs(5), change)
+            .await
+            .unwrap();
+        assert_eq!(params["textDocument"]["version"], 2);
+        assert_eq!(params["contentChanges"][0]["text"], "fn b() {}\n");
+    }
+
+    #[tokio::test]
+    async fn rename_preview_is_a_diff_and_writes_nothing() {
+        let text = "fn old() {}\nfn main() { old(); }\n";
+        let (_dir, tool, file) = setup(text);
+        fake_server(&tool, &tool.root(), |method, params| match method {
+            "initialize" => json!({ "capabilities": {} }),
+            "textDocument/rename" => {
+                let uri = &params["textDocument"]["uri"];
+                let name = &params["newName"];
+                json!({ "documentChanges": [{
+                    "textDocument": { "uri": uri, "version": 1 },
+                    "edits": [
+                        { "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 6 } }, "newText": name },
+                        { "range": { "start": { "line": 1, "character": 12 }, "end": { "line": 1, "character": 15 } }, "newText": name },
+                    ],
+                }]})
+            }
+            _ => Value::Null,
+        })
+        .await;
+
+        let xml = "<LspRequest><action>rename-preview</action><path>src/lib.rs</path><line>1</line><symbol>old</symbol><new_name>fresh</new_name></LspRequest>";
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert!(
+            out.starts_with("rename to fresh: 2 edits in 1 files"),
+            "{out}"
+        );
+        assert!(
+            out.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains(
+                "-fn old() {}\n-fn main() { old(); }\n+fn fresh() {}\n+fn main() { fresh(); }"
+            ),
+            "{out}"
+        );
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), text);
+    }
+
+    #[tokio::test]
+    async fn diagnostics_wait_for_the_current_text() {
+        let (_dir, tool, file) = setup("fn a() {}\n");
+        fake_server(&tool, &tool.root(), |method, _| match method {
+            "initialize" => json!({ "capabilities": {} }),
+            _ => Value::Null,
+        })
+        .await;
+
+        let xml = "<LspRequest><action>diagnostics</action><path>src/lib.rs</path></LspRequest>";
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert_eq!(out, "src/lib.rs: no diagnostics");
+
+        std::fs::write(&file, "fn a() {}\n// todo\n").unwrap();
+        let (ok, out) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{out}");
+        assert_eq!(
+            out,
+            "src/lib.rs: 1 diagnostics\n  2:1 warning: unfinished (fake)"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_language_and_missing_server() {
+        let (dir, tool, _) = setup("");
+        std::fs::write(dir.path().join("notes.txt"), "hi\n").unwrap();
+        let xml = "<LspRequest><action>hover</action><path>notes.txt</path><line>1</line><column>1</column></LspRequest>";
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert_eq!(err, "no language server configured for .txt files");
+
+        let xml = "<LspRequest><action>workspace-symbol</action><query>main</query></LspRequest>";
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(err.contains("no language server is running"), "{err}");
+    }
+
+    #[test]
+    fn file_uris_round_trip() {
+        let path = Path::new("/work/my crate/src/ü.rs");
+        let uri = file_uri(path);
+        assert_eq!(uri, "file:///work/my%20crate/src/%C3%BC.rs");
+        assert_eq!(uri_path(&uri).unwrap(), path);
+    }
+
+    #[test]
+    fn lsp_metadata() {
+        let tool = LspTool::new();
+        assert_eq!(tool.name(), "lsp");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "LspRequest");
+    }
+}
@@ -4,16 +4,41 @@
 //! but adds self-documenting metadata (name, description, schemas).
 
 pub mod command_exec;
+pub mod command_policy;
+pub mod diagnostics;
+pub mod encoding;
 pub mod file_edit;
 pub mod file_read;
 pub mod file_write;
+pub mod git;
 pub mod glob_tool;
 pub mod grep;
+pub mod html;
+pub mod http_fetch;
+pub mod ignore;
+pub mod list_dir;
+pub mod lsp_client;
+pub mod notebook;
+pub mod output;
+pub mod patch;
+pub mod per_profile;
+pub mod replace;
+pub mod sandbox;
+pub mod shell;
+pub mod test_run;
+pub mod version;
+pub mod workspace;
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use rust_pipeline::prelude::*;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::kernel::Kernel;
+use crate::pipeline::events::PipelineEvent;
 
 /// Marker trait for tool-peers. All tool-peers are Handlers,
 /// but this trait adds tool-specific metadata for self-documentation.
@@ -33,6 +58,75 @@ pub trait ToolPeer: Handler {
     /// - ToolDefinition (JSON Schema) for the LLM
     /// - XML request tag for routing
     fn wit(&self) -> &str;
+
+    /// Confine this tool to a workspace.
+    ///
+    /// Called by the pipeline builder on each profile's instance of a tool
+    /// that touches the filesystem, with that profile's `workspace:` block.
+    /// Those tools override this; the default ignores it.
+    fn set_workspace(&mut self, _workspace: Arc<workspace::Workspace>) {}
+
+    /// Hand the tool the pipeline's event channel.
+    ///
+    /// Called by the pipeline builder at registration. Tools that hold
+    /// per-thread state override this to clean up on `KernelOp` events;
+    /// the default ignores it.
+    fn set_event_sender(&mut self, _tx: broadcast::Sender<PipelineEvent>) {}
+
+    /// Hand the tool the pipeline's store for long outputs.
+    ///
+    /// Called by the pipeline builder at registration. Tools whose results
+    /// can run long override this to shape them through the store, so the
+    /// model sees head and tail and pages through the rest with
+    /// `output-page`; the default ignores it.
+    fn set_output_store(&mut self, _store: Arc<output::OutputStore>) {}
+
+    /// Hand the tool the pipeline's kernel.
+    ///
+    /// Called by the pipeline builder at registration. Tools that keep
+    /// state across threads in the kernel (`http-fetch` caches pages there)
+    /// override this; the default ignores it.
+    fn set_kernel(&mut self, _kernel: Arc<Mutex<Kernel>>) {}
+}
+
+/// Put back the files a failed multi-file write already touched, in place.
+///
+/// Takes (display name, path, original bytes) per file. Returns what to
+/// tell the model: that the files were restored, or which ones could not
+/// be and are left modified.
+pub(crate) fn restore_originals<'a>(
+    originals: impl IntoIterator<Item = (&'a str, &'a Path, &'a [u8])>,
+) -> String {
+    let failed: Vec<String> = originals
+        .into_iter()
+        .filter_map(|(display, path, original)| {
+            std::fs::write(path, original)
+                .err()
+                .map(|e| format!("{display} ({e})"))
+        })
+        .collect();
+    if failed.is_empty() {
+        "the files already written were restored".into()
+    } else {
+        format!("restoring failed, left modified: {}", failed.join(", "))
+    }
+}
+
+/// Announce a file a tool wrote, for observers that mirror the workspace
+/// (e.g. the language servers behind the lsp tool).
+pub(crate) fn announce_file_changed(
+    events: Option<&broadcast::Sender<PipelineEvent>>,
+    thread_id: &str,
+    tool_name: &str,
+    path: &Path,
+) {
+    if let Some(tx) = events {
+        let _ = tx.send(PipelineEvent::FileChanged {
+            thread_id: thread_id.to_string(),
+            tool_name: tool_name.to_string(),
+            path: path.to_path_buf(),
+        });
+    }
 }
 
 /// Schema for the shared ToolResponse envelope.
@@ -76,6 +170,21 @@ impl ToolResponse {
         .into_bytes()
     }
 
+    /// Build a success response carrying an image or PDF.
+    ///
+    /// `result` is the text part; the bytes travel base64-encoded in
+    /// `<media>` and reach the model as an image/document block.
+    pub fn ok_media(result: &str, media_type: &str, data: &[u8]) -> Vec<u8> {
+        format!(
+            "<ToolResponse><success>true</success><result>{}</result>\
+             <media><media_type>{}</media_type><data>{}</data></media></ToolResponse>",
+            xml_escape(result),
+            xml_escape(media_type),
+            crate::llm::media::encode_base64(data)
+        )
+        .into_bytes()
+    }
+
     /// Build an error response as XML bytes.
     pub fn err(error: &str) -> Vec<u8> {
         format!(
@@ -149,6 +258,17 @@ mod tests {
         assert!(err.is_err(), "should reject wrong root tag");
     }
 
+    #[test]
+    fn tool_response_ok_media() {
+        let resp = ToolResponse::ok_media("shot.png", "image/png", b"\x89PNG");
+        let xml = String::from_utf8(resp).unwrap();
+        assert!(xml.contains("<result>shot.png</result>"));
+        assert_eq!(extract_tag(&xml, "media_type").unwrap(), "image/png");
+        assert_eq!(extract_tag(&xml, "data").unwrap(), "iVBORw==");
+        let schema = tool_response_schema();
+        rust_pipeline::validation::validate_payload(xml.as_bytes(), &schema).unwrap();
+    }
+
     #[test]
     fn tool_response_ok() {
         let resp = ToolResponse::ok("file contents here");
@@ -189,4 +309,25 @@ mod tests {
         let xml = "<root><name>hello</name></root>";
         assert_eq!(extract_tag(xml, "missing"), None);
     }
+
+    #[test]
+    fn restore_originals_names_what_it_could_not_restore() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let a = dir.path().join("a.txt");
+        std::fs::write(&a, "changed").unwrap();
+        let gone = dir.path().join("gone/b.txt");
+
+        let note = restore_originals([("a.txt", a.as_path(), b"original".as_slice())]);
+        assert_eq!(note, "the files already written were restored");
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "original");
+
+        let note = restore_originals([
+            ("a.txt", a.as_path(), b"original".as_slice()),
+            ("gone/b.txt", gone.as_path(), b"original".as_slice()),
+        ]);
+        assert!(
+            note.starts_with("restoring failed, left modified: gone/b.txt ("),
+            "{note}"
+        );
+    }
 }
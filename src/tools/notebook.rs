@@ -0,0 +1,750 @@
+//! Jupyter notebooks — cell rendering for `file-read` and the `notebook-edit`
+//! tool peer.
+//!
+//! A `.ipynb` file is JSON, which reads badly and is worse to edit by string
+//! replacement. `file-read` renders it as numbered cells instead (source with
+//! line numbers, outputs truncated), and `notebook-edit` replaces, inserts or
+//! deletes whole cells by index or id, then writes the notebook back the way
+//! Jupyter does: sorted keys, one-space indent (or the file's own), source as
+//! a list of lines.
+//!
+//! Replacing a code cell's source clears its outputs and execution count —
+//! they belonged to the old code.
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::version;
+use super::workspace::{self, Access, Workspace};
+use super::{announce_file_changed, extract_tag, ToolPeer, ToolResponse};
+use crate::pipeline::events::PipelineEvent;
+
+/// Lines of each output shown by `file-read`.
+const MAX_OUTPUT_LINES: usize = 20;
+/// Characters of each output shown by `file-read`.
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+const CELL_TYPES: [&str; 3] = ["code", "markdown", "raw"];
+
+/// Whether `path` names a notebook.
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "ipynb")
+}
+
+/// Parse notebook JSON; an error if it isn't one.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let nb: Value =
+        serde_json::from_str(text).map_err(|e| format!("invalid notebook JSON: {e}"))?;
+    if !nb.get("cells").is_some_and(Value::is_array) {
+        return Err("not a notebook: no cells array".into());
+    }
+    Ok(nb)
+}
+
+/// Render a notebook as numbered cells, for `file-read`.
+pub fn render(nb: &Value) -> String {
+    let cells = cells(nb);
+    let mut out = String::new();
+    for (index, cell) in cells.iter().enumerate() {
+        out.push_str(&cell_header(index, cell));
+        out.push('\n');
+        for (i, line) in source_text(cell).lines().enumerate() {
+            out.push_str(&format!("{}| {line}\n", i + 1));
+        }
+        for output in cell
+            .get("outputs")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            render_output(output, &mut out);
+        }
+        out.push('\n');
+    }
+    if cells.is_empty() {
+        out.push_str("(no cells)\n\n");
+    }
+    out.push_str(&summary(nb));
+    out.push('\n');
+    out
+}
+
+/// `[notebook: 12 cells (8 code, 4 markdown) kernel: python3 nbformat: 4.5]`
+pub fn summary(nb: &Value) -> String {
+    let cells = cells(nb);
+    let counts: Vec<String> = CELL_TYPES
+        .iter()
+        .filter_map(|ty| {
+            let n = cells.iter().filter(|c| cell_type(c) == *ty).count();
+            (n > 0).then(|| format!("{n} {ty}"))
+        })
+        .collect();
+    let mut s = format!("[notebook: {} cells", cells.len());
+    if !counts.is_empty() {
+        s.push_str(&format!(" ({})", counts.join(", ")));
+    }
+    let kernel = nb
+        .pointer("/metadata/kernelspec/name")
+        .or_else(|| nb.pointer("/metadata/language_info/name"))
+        .and_then(Value::as_str);
+    if let Some(kernel) = kernel {
+        s.push_str(&format!(" kernel: {kernel}"));
+    }
+    if let Some(major) = nb.get("nbformat").and_then(Value::as_u64) {
+        let minor = nb
+            .get("nbformat_minor")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        s.push_str(&format!(" nbformat: {major}.{minor}"));
+    }
+    s.push(']');
+    s
+}
+
+fn cells(nb: &Value) -> &[Value] {
+    nb.get("cells")
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn cell_type(cell: &Value) -> &str {
+    cell.get("cell_type").and_then(Value::as_str).unwrap_or("?")
+}
+
+fn cell_id(cell: &Value) -> Option<&str> {
+    cell.get("id").and_then(Value::as_str)
+}
+
+/// `[cell 3] code id=1a2b3c4d exec=7`
+fn cell_header(index: usize, cell: &Value) -> String {
+    let mut s = format!("[cell {index}] {}", cell_type(cell));
+    if let Some(id) = cell_id(cell) {
+        s.push_str(&format!(" id={id}"));
+    }
+    if let Some(n) = cell.get("execution_count").and_then(Value::as_u64) {
+        s.push_str(&format!(" exec={n}"));
+    }
+    s
+}
+
+/// Notebook text fields are a string or a list of lines.
+fn multiline(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn source_text(cell: &Value) -> String {
+    multiline(cell.get("source"))
+}
+
+/// Source as Jupyter stores it: lines, each keeping its `\n`.
+fn source_lines(text: &str) -> Value {
+    Value::Array(
+        text.split_inclusive('\n')
+            .map(|line| Value::String(line.to_string()))
+            .collect(),
+    )
+}
+
+fn render_output(output: &Value, out: &mut String) {
+    let kind = output
+        .get("output_type")
+        .and_then(Value::as_str)
+        .unwrap_or("?");
+    let (label, body) = match kind {
+        "stream" => {
+            let name = output
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("stream");
+            (name.to_string(), multiline(output.get("text")))
+        }
+        "execute_result" | "display_data" => {
+            let data = output.get("data").and_then(Value::as_object);
+            let label = match output.get("execution_count").and_then(Value::as_u64) {
+                Some(n) => format!("result [{n}]"),
+                None => "display".to_string(),
+            };
+            match data.and_then(|d| d.get("text/plain")) {
+                Some(text) => (label, multiline(Some(text))),
+                None => {
+                    let kinds: Vec<&str> = data
+                        .into_iter()
+                        .flat_map(|d| d.keys())
+                        .map(String::as_str)
+                        .collect();
+                    (label, format!("({} not shown)", kinds.join(", ")))
+                }
+            }
+        }
+        "error" => {
+            let name = output
+                .get("ename")
+                .and_then(Value::as_str)
+                .unwrap_or("Error");
+            let value = output.get("evalue").and_then(Value::as_str).unwrap_or("");
+            let traceback: Vec<String> = output
+                .get("traceback")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(strip_ansi)
+                .collect();
+            (format!("error {name}: {value}"), traceback.join("\n"))
+        }
+        other => (other.to_string(), String::new()),
+    };
+
+    out.push_str(&format!("  -> {label}\n"));
+    let lines: Vec<&str> = body.lines().collect();
+    let mut budget = MAX_OUTPUT_CHARS;
+    for (shown, line) in lines.iter().enumerate() {
+        if shown == MAX_OUTPUT_LINES || budget == 0 {
+            out.push_str(&format!(
+                "     ... ({} more output lines)\n",
+                lines.len() - shown
+            ));
+            break;
+        }
+        match line.char_indices().nth(budget) {
+            Some((cut, _)) => {
+                out.push_str(&format!("     {}...\n", &line[..cut]));
+                budget = 0;
+            }
+            None => {
+                out.push_str(&format!("     {line}\n"));
+                budget -= line.chars().count();
+            }
+        }
+    }
+}
+
+/// Drop terminal colour codes (`ESC [ … letter`) from tracebacks.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Serialize the way Jupyter does: sorted keys, `indent` spaces, trailing newline.
+fn to_ipynb(nb: &Value, indent: usize) -> Result<Vec<u8>, String> {
+    let indent = " ".repeat(indent);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut out = Vec::new();
+    let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+    sort_keys(nb)
+        .serialize(&mut ser)
+        .map_err(|e| format!("failed to serialize notebook: {e}"))?;
+    out.push(b'\n');
+    Ok(out)
+}
+
+fn sort_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let sorted: Map<String, Value> = keys
+                .into_iter()
+                .map(|k| (k.clone(), sort_keys(&map[k])))
+                .collect();
+            Value::Object(sorted)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sort_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The indent the file was written with (Jupyter uses one space).
+fn detect_indent(text: &str) -> usize {
+    text.lines()
+        .nth(1)
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .filter(|&n| n > 0)
+        .unwrap_or(1)
+}
+
+/// Whether cells carry ids (nbformat 4.5 and later).
+fn uses_ids(nb: &Value) -> bool {
+    let major = nb.get("nbformat").and_then(Value::as_u64).unwrap_or(4);
+    let minor = nb
+        .get("nbformat_minor")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    (major, minor) >= (4, 5) || cells(nb).iter().any(|c| cell_id(c).is_some())
+}
+
+/// A fresh cell id, as nbformat makes them: 8 hex digits.
+fn new_cell_id(nb: &Value) -> String {
+    loop {
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        if !cells(nb).iter().any(|c| cell_id(c) == Some(id.as_str())) {
+            return id;
+        }
+    }
+}
+
+fn new_cell(cell_type: &str, source: &str) -> Value {
+    let mut cell = json!({
+        "cell_type": cell_type,
+        "metadata": {},
+        "source": source_lines(source),
+    });
+    if cell_type == "code" {
+        cell["execution_count"] = Value::Null;
+        cell["outputs"] = json!([]);
+    }
+    cell
+}
+
+/// Turn a cell into `cell_type`, adding or dropping the code-only fields.
+fn retype(cell: &mut Value, cell_type: &str) {
+    let Some(obj) = cell.as_object_mut() else {
+        return;
+    };
+    obj.insert("cell_type".into(), json!(cell_type));
+    if cell_type == "code" {
+        obj.entry("execution_count").or_insert(Value::Null);
+        obj.entry("outputs").or_insert(json!([]));
+    } else {
+        obj.remove("execution_count");
+        obj.remove("outputs");
+    }
+}
+
+/// One notebook-edit call.
+#[derive(Debug, Default)]
+struct CellEdit {
+    action: String,
+    index: Option<usize>,
+    cell_id: Option<String>,
+    cell_type: Option<String>,
+    source: Option<String>,
+}
+
+impl CellEdit {
+    /// Index of the target cell, by id if given, else by index.
+    fn target(&self, nb: &Value) -> Result<usize, String> {
+        let cells = cells(nb);
+        if let Some(id) = &self.cell_id {
+            return cells
+                .iter()
+                .position(|c| cell_id(c) == Some(id.as_str()))
+                .ok_or_else(|| format!("no cell with id {id}"));
+        }
+        let index = self.index.ok_or("missing <index> or <cell_id>")?;
+        if index >= cells.len() {
+            return Err(format!(
+                "cell index {index} out of range (notebook has {} cells)",
+                cells.len()
+            ));
+        }
+        Ok(index)
+    }
+
+    /// Apply the edit; returns what was done, for the reply.
+    fn apply(&self, nb: &mut Value) -> Result<String, String> {
+        if let Some(ty) = &self.cell_type {
+            if !CELL_TYPES.contains(&ty.as_str()) {
+                return Err(format!(
+                    "invalid <cell_type> {ty:?}: expected code, markdown or raw"
+                ));
+            }
+        }
+        match self.action.as_str() {
+            "replace" => {
+                if self.source.is_none() && self.cell_type.is_none() {
+                    return Err("replace needs <source> and/or <cell_type>".into());
+                }
+                let index = self.target(nb)?;
+                let cell = &mut nb["cells"][index];
+                if let Some(ty) = &self.cell_type {
+                    retype(cell, ty);
+                }
+                if let Some(source) = &self.source {
+                    cell["source"] = source_lines(source);
+                    if cell_type(cell) == "code" {
+                        cell["execution_count"] = Value::Null;
+                        cell["outputs"] = json!([]);
+                    }
+                }
+                Ok(format!("replaced {}", cell_header(index, cell)))
+            }
+            "insert" => {
+                let len = cells(nb).len();
+                let index = match (&self.cell_id, self.index) {
+                    (Some(_), _) => self.target(nb)? + 1,
+                    (None, Some(index)) if index > len => {
+                        return Err(format!(
+                            "cell index {index} out of range (notebook has {len} cells)"
+                        ));
+                    }
+                    (None, Some(index)) => index,
+                    (None, None) => len,
+                };
+                let ty = self.cell_type.as_deref().unwrap_or("code");
+                let mut cell = new_cell(ty, self.source.as_deref().unwrap_or(""));
+                if uses_ids(nb) {
+                    cell["id"] = json!(new_cell_id(nb));
+                }
+                let header = cell_header(index, &cell);
+                nb["cells"]
+                    .as_array_mut()
+                    .expect("checked by parse")
+                    .insert(index, cell);
+                Ok(format!("inserted {header}"))
+            }
+            "delete" => {
+                let index = self.target(nb)?;
+                let cell = nb["cells"]
+                    .as_array_mut()
+                    .expect("checked by parse")
+                    .remove(index);
+                Ok(format!("deleted {}", cell_header(index, &cell)))
+            }
+            "" => Err("missing required <action>".into()),
+            other => Err(format!(
+                "unknown action {other:?}: expected replace, insert or delete"
+            )),
+        }
+    }
+}
+
+/// Replace, insert or delete notebook cells, keeping the JSON valid.
+#[derive(Default)]
+pub struct NotebookEditTool {
+    workspace: Option<Arc<Workspace>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+}
+
+#[async_trait]
+impl Handler for NotebookEditTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+
+        let path = extract_tag(&xml_str, "path").unwrap_or_default();
+        if path.is_empty() {
+            return Ok(HandlerResponse::Reply {
+                payload_xml: ToolResponse::err("missing required <path>"),
+            });
+        }
+
+        let index = match extract_tag(&xml_str, "index").filter(|s| !s.trim().is_empty()) {
+            Some(s) => match s.trim().parse::<usize>() {
+                Ok(n) => Some(n),
+                Err(_) => {
+                    return Ok(HandlerResponse::Reply {
+                        payload_xml: ToolResponse::err(&format!("invalid <index>: {s}")),
+                    });
+                }
+            },
+            None => None,
+        };
+        let edit = CellEdit {
+            action: extract_tag(&xml_str, "action")
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            index,
+            cell_id: extract_tag(&xml_str, "cell_id").filter(|s| !s.trim().is_empty()),
+            cell_type: extract_tag(&xml_str, "cell_type").filter(|s| !s.trim().is_empty()),
+            source: extract_tag(&xml_str, "source"),
+        };
+
+        let file_path = match workspace::resolve_in(
+            self.workspace.as_deref(),
+            "notebook-edit",
+            &path,
+            Access::Write,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        };
+        let file_path = file_path.as_path();
+
+        if let Some(expected) = extract_tag(&xml_str, "expected_hash").filter(|h| !h.is_empty()) {
+            if let Err(e) = version::check_expected(file_path, &path, &expected) {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&e),
+                });
+            }
+        }
+
+        let text = match std::fs::read_to_string(file_path) {
+            Ok(text) => text,
+            Err(e) => {
+                return Ok(HandlerResponse::Reply {
+                    payload_xml: ToolResponse::err(&format!("cannot read {path}: {e}")),
+                });
+            }
+        };
+
+        let result = parse(&text).and_then(|mut nb| {
+            let done = edit.apply(&mut nb)?;
+            let bytes = to_ipynb(&nb, detect_indent(&text))?;
+            std::fs::write(file_path, &bytes).map_err(|e| format!("write error: {e}"))?;
+            Ok(format!(
+                "{done} in {path}\n{}\n{}",
+                summary(&nb),
+                version::footer(file_path, &bytes)
+            ))
+        });
+
+        Ok(HandlerResponse::Reply {
+            payload_xml: match result {
+                Ok(msg) => {
+                    announce_file_changed(
+                        self.events.as_ref(),
+                        &ctx.thread_id,
+                        "notebook-edit",
+                        file_path,
+                    );
+                    ToolResponse::ok(&msg)
+                }
+                Err(e) => ToolResponse::err(&format!("{path}: {e}")),
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for NotebookEditTool {
+    fn name(&self) -> &str {
+        "notebook-edit"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Edit a Jupyter notebook (.ipynb) cell by cell: replace a cell's source or type, insert a new cell, or delete one. Cells are addressed by index (0-based, as file-read shows them) or by id. Replacing a code cell's source clears its outputs. Returns the new [version: <hash> mtime: <secs>].
+interface notebook-edit {
+    record request {
+        /// The notebook path
+        path: string,
+        /// replace, insert or delete
+        action: string,
+        /// Cell index (0-based); for insert, the position of the new cell (default: append)
+        index: option<u32>,
+        /// Cell id, instead of index; for insert, the new cell goes after it
+        cell-id: option<string>,
+        /// code, markdown or raw (insert default: code)
+        cell-type: option<string>,
+        /// The cell's new source
+        source: option<string>,
+        /// Version hash from file-read; the edit is rejected if the file has changed since
+        expected-hash: option<string>,
+    }
+    edit: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "id": "intro",
+   "metadata": {},
+   "source": ["# Sales\n", "Quarterly numbers."]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "id": "load",
+   "metadata": {},
+   "outputs": [
+    {"name": "stdout", "output_type": "stream", "text": ["loaded 120 rows\n"]},
+    {"data": {"image/png": "iVBOR", "text/plain": ["<Figure>"]}, "execution_count": 3, "metadata": {}, "output_type": "execute_result"},
+    {"ename": "KeyError", "evalue": "'q5'", "output_type": "error", "traceback": ["\u001b[0;31mKeyError\u001b[0m: 'q5'"]}
+   ],
+   "source": "df = load()\nplot(df)"
+  }
+ ],
+ "metadata": {"kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "notebook-edit".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "NotebookEditRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[test]
+    fn renders_cells_and_outputs() {
+        let out = render(&parse(NOTEBOOK).unwrap());
+        assert!(
+            out.starts_with("[cell 0] markdown id=intro\n1| # Sales\n2| Quarterly numbers.\n\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains("[cell 1] code id=load exec=3\n1| df = load()\n2| plot(df)\n"),
+            "{out}"
+        );
+        assert!(out.contains("  -> stdout\n     loaded 120 rows\n"), "{out}");
+        assert!(out.contains("  -> result [3]\n     <Figure>\n"), "{out}");
+        assert!(
+            out.contains("  -> error KeyError: 'q5'\n     KeyError: 'q5'\n"),
+            "{out}"
+        );
+        assert!(
+            out.ends_with(
+                "[notebook: 2 cells (1 code, 1 markdown) kernel: python3 nbformat: 4.5]\n"
+            ),
+            "{out}"
+        );
+        assert!(parse("{\"a\": 1}").unwrap_err().contains("no cells"));
+    }
+
+    #[tokio::test]
+    async fn replace_insert_delete_keep_json_valid() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sales.ipynb");
+        std::fs::write(&path, NOTEBOOK).unwrap();
+        let p = path.display();
+        let tool = NotebookEditTool::default();
+
+        let xml = format!("<NotebookEditRequest><path>{p}</path><action>replace</action><cell_id>load</cell_id><source>df = load(\"q4\")\n</source></NotebookEditRequest>");
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert!(
+            content.starts_with("replaced [cell 1] code id=load in "),
+            "{content}"
+        );
+        assert!(version::parse(&content).is_some());
+
+        let xml = format!("<NotebookEditRequest><path>{p}</path><action>insert</action><index>1</index><cell_type>markdown</cell_type><source>## Load</source></NotebookEditRequest>");
+        let (ok, content) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{content}");
+        assert!(
+            content.contains("[notebook: 3 cells (1 code, 2 markdown)"),
+            "{content}"
+        );
+
+        let xml = format!("<NotebookEditRequest><path>{p}</path><action>delete</action><index>0</index></NotebookEditRequest>");
+        let (ok, _) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            written.starts_with(
+                "{\n \"cells\": [\n  {\n   \"cell_type\": \"markdown\",\n   \"id\": \""
+            ),
+            "{written}"
+        );
+        let nb = parse(&written).unwrap();
+        let cells = cells(&nb);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0]["source"], json!(["## Load"]));
+        assert_eq!(cells[0]["id"].as_str().unwrap().len(), 8);
+        assert!(cells[0].get("outputs").is_none());
+        assert_eq!(cells[1]["source"], json!(["df = load(\"q4\")\n"]));
+        assert_eq!(cells[1]["outputs"], json!([]));
+        assert_eq!(cells[1]["execution_count"], Value::Null);
+        assert_eq!(nb["metadata"]["kernelspec"]["name"], "python3");
+    }
+
+    #[tokio::test]
+    async fn bad_edits_leave_file_untouched() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sales.ipynb");
+        std::fs::write(&path, NOTEBOOK).unwrap();
+        let p = path.display();
+        let tool = NotebookEditTool::default();
+
+        for (body, expected) in [
+            (
+                "<action>delete</action><index>5</index>",
+                "cell index 5 out of range (notebook has 2 cells)",
+            ),
+            (
+                "<action>replace</action><cell_id>nope</cell_id><source>x</source>",
+                "no cell with id nope",
+            ),
+            (
+                "<action>insert</action><cell_type>sql</cell_type>",
+                "invalid <cell_type>",
+            ),
+            ("<action>move</action><index>0</index>", "unknown action"),
+        ] {
+            let xml = format!("<NotebookEditRequest><path>{p}</path>{body}</NotebookEditRequest>");
+            let (ok, content) =
+                get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+            assert!(!ok);
+            assert!(content.contains(expected), "{content}");
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), NOTEBOOK);
+    }
+
+    #[test]
+    fn notebook_edit_metadata() {
+        let tool = NotebookEditTool::default();
+        assert_eq!(tool.name(), "notebook-edit");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "NotebookEditRequest");
+    }
+}
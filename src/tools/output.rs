@@ -0,0 +1,374 @@
+//! Output shaping — keep large tool results out of the model's context.
+//!
+//! A result longer than [`INLINE_LIMIT`] is cut to its head and tail (on
+//! line boundaries where possible) with a note saying how much was elided.
+//! With an [`OutputStore`], the full text is also kept as a shelved
+//! `tool-output` segment in the calling thread's context, and the note names
+//! it so the agent can read the middle back with the `output-page` tool.
+//!
+//! Stored outputs live in memory for the life of the pipeline; they are not
+//! journaled, and a pruned thread takes its outputs with it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use tokio::sync::Mutex;
+
+use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::kernel::context_store::{ContextSegment, SegmentStatus};
+use crate::kernel::Kernel;
+
+/// Results up to this many bytes reach the model unchanged.
+pub const INLINE_LIMIT: usize = 32 * 1024;
+
+/// Bytes kept from each end of a longer result.
+const HEAD_BYTES: usize = 12 * 1024;
+const TAIL_BYTES: usize = 12 * 1024;
+
+/// Default `output-page` page size, in bytes.
+pub const PAGE_SIZE: usize = 16 * 1024;
+
+/// Context segment tag of stored outputs.
+pub const SEGMENT_TAG: &str = "tool-output";
+
+/// Full outputs beyond this size are only shown head and tail.
+const MAX_STORED: usize = 16 * 1024 * 1024;
+
+/// Full tool outputs, kept per thread in the kernel's context store.
+pub struct OutputStore {
+    kernel: Arc<Mutex<Kernel>>,
+    next_id: AtomicU64,
+}
+
+impl OutputStore {
+    pub fn new(kernel: Arc<Mutex<Kernel>>) -> Self {
+        Self {
+            kernel,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// `text` as the model should see it: unchanged if short, otherwise
+    /// head and tail around a note naming the stored full output.
+    pub async fn shape(&self, thread_id: &str, text: String) -> String {
+        if text.len() <= INLINE_LIMIT {
+            return text;
+        }
+        let id = format!("output-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        match self.store(thread_id, &id, &text).await {
+            Ok(()) => elide(&text, |head, omitted| {
+                format!(
+                    "[{omitted} of {} bytes omitted; the full output is {id}: \
+                     read on with output-page from offset {head}]",
+                    text.len()
+                )
+            }),
+            Err(e) => {
+                tracing::warn!("could not store {id} for {thread_id}: {e}");
+                shape_inline(text)
+            }
+        }
+    }
+
+    async fn store(&self, thread_id: &str, id: &str, text: &str) -> Result<(), String> {
+        if text.len() > MAX_STORED {
+            return Err(format!(
+                "{} bytes is over the {MAX_STORED} byte limit",
+                text.len()
+            ));
+        }
+        let mut kernel = self.kernel.lock().await;
+        let contexts = kernel.contexts_mut();
+        if !contexts.exists(thread_id) {
+            contexts.create(thread_id).map_err(|e| e.to_string())?;
+        }
+        let segment = ContextSegment {
+            id: id.to_string(),
+            tag: SEGMENT_TAG.into(),
+            content: text.as_bytes().to_vec(),
+            // Read on demand through output-page, not part of the working set
+            status: SegmentStatus::Shelved,
+            relevance: 0.0,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            fold_ref: None,
+        };
+        contexts
+            .add_segment(thread_id, segment)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Up to `length` bytes of stored output `id` from byte `offset`, with a
+    /// header giving the range and where the next page starts.
+    pub async fn page(
+        &self,
+        thread_id: &str,
+        id: &str,
+        offset: usize,
+        length: usize,
+    ) -> Result<String, String> {
+        let kernel = self.kernel.lock().await;
+        let segment = kernel
+            .contexts()
+            .get_segment(thread_id, id)
+            .ok()
+            .filter(|s| s.tag == SEGMENT_TAG)
+            .ok_or_else(|| format!("no stored output {id} in this thread"))?;
+        let text = String::from_utf8_lossy(&segment.content);
+        let total = text.len();
+        if offset >= total {
+            return Err(format!(
+                "offset {offset} is past the end of {id} ({total} bytes)"
+            ));
+        }
+        let start = floor_boundary(&text, offset);
+        let end = floor_boundary(&text, start.saturating_add(length.max(1)).min(total));
+        // A page always makes progress, even inside one wide character
+        let end = if end > start {
+            end
+        } else {
+            ceil_boundary(&text, start + 1)
+        };
+
+        let next = if end < total {
+            format!("[next page: offset {end}]")
+        } else {
+            "[end of output]".to_string()
+        };
+        Ok(format!(
+            "[{id}: bytes {start}-{end} of {total}]\n{}\n{next}",
+            &text[start..end]
+        ))
+    }
+}
+
+/// Shape `text` without a store: head and tail, with the elided size.
+pub fn shape_inline(text: String) -> String {
+    if text.len() <= INLINE_LIMIT {
+        return text;
+    }
+    elide(&text, |_, omitted| {
+        format!("[{omitted} of {} bytes omitted]", text.len())
+    })
+}
+
+/// Shape a tool's result, through `store` if the tool has one.
+pub async fn shape(store: Option<&OutputStore>, thread_id: &str, text: String) -> String {
+    match store {
+        Some(store) => store.shape(thread_id, text).await,
+        None => shape_inline(text),
+    }
+}
+
+/// Head, `note(head_len, omitted)`, tail. Cuts fall on line breaks when one
+/// is near, and always on character boundaries.
+fn elide(text: &str, note: impl FnOnce(usize, usize) -> String) -> String {
+    let mut head = floor_boundary(text, HEAD_BYTES);
+    if let Some(nl) = text[..head].rfind('\n').filter(|&nl| nl >= HEAD_BYTES / 2) {
+        head = nl + 1;
+    }
+    let mut tail = ceil_boundary(text, text.len() - TAIL_BYTES);
+    if let Some(nl) = text[tail..].find('\n').filter(|&nl| nl <= TAIL_BYTES / 2) {
+        tail += nl + 1;
+    }
+    let omitted = tail - head;
+    let sep = if text[..head].ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    format!(
+        "{}{sep}{}\n{}",
+        &text[..head],
+        note(head, omitted),
+        &text[tail..]
+    )
+}
+
+fn floor_boundary(s: &str, mut i: usize) -> usize {
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+fn ceil_boundary(s: &str, mut i: usize) -> usize {
+    while i < s.len() && !s.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+/// Page through a stored tool output.
+#[derive(Default)]
+pub struct OutputPageTool {
+    store: Option<Arc<OutputStore>>,
+}
+
+#[async_trait]
+impl Handler for OutputPageTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+        let id = extract_tag(&xml_str, "id").unwrap_or_default();
+        let number = |tag: &str| extract_tag(&xml_str, tag).and_then(|s| s.trim().parse().ok());
+        let offset = number("offset").unwrap_or(0);
+        let length = number("length").unwrap_or(PAGE_SIZE).min(INLINE_LIMIT);
+
+        let result = match (&self.store, id.trim()) {
+            (_, "") => Err("missing required <id>".to_string()),
+            (None, _) => Err("stored outputs are not available in this pipeline".to_string()),
+            (Some(store), id) => store.page(&ctx.thread_id, id, offset, length).await,
+        };
+        let payload_xml = match result {
+            Ok(page) => ToolResponse::ok(&page),
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for OutputPageTool {
+    fn name(&self) -> &str {
+        "output-page"
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.store = Some(store);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Read a page of a long tool output that was cut short. The note in the cut output names the output (e.g. output-3) and the offset to continue from.
+interface output-page {
+    record request {
+        /// The stored output's id, e.g. output-3
+        id: string,
+        /// Byte offset to start from (default 0)
+        offset: option<u32>,
+        /// Bytes to return (default 16384)
+        length: option<u32>,
+    }
+    run: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "output-page".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "OutputPageRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    fn numbered_lines(n: usize) -> String {
+        (0..n).map(|i| format!("line {i:05} ✓\n")).collect()
+    }
+
+    #[test]
+    fn inline_keeps_head_and_tail() {
+        assert_eq!(shape_inline("short".into()), "short");
+
+        let text = numbered_lines(5000);
+        let shaped = shape_inline(text.clone());
+        assert!(shaped.len() < INLINE_LIMIT);
+        assert!(shaped.starts_with("line 00000 ✓\n"));
+        assert!(shaped.ends_with("line 04999 ✓\n"));
+        assert!(shaped.contains("bytes omitted]"));
+        // Cut on line boundaries
+        for line in shaped.lines().filter(|l| !l.starts_with('[')) {
+            assert!(line.starts_with("line ") && line.ends_with('✓'), "{line}");
+        }
+    }
+
+    #[tokio::test]
+    async fn stored_output_pages_back() {
+        let dir = TempDir::new().unwrap();
+        let kernel = Arc::new(Mutex::new(Kernel::open(dir.path()).unwrap()));
+        let store = Arc::new(OutputStore::new(kernel));
+        let mut tool = OutputPageTool::default();
+        tool.set_output_store(store.clone());
+
+        let text = numbered_lines(5000);
+        let shaped = store.shape("t1", text.clone()).await;
+        assert!(shaped.contains("the full output is output-0"));
+        let offset: usize = shaped
+            .split("from offset ")
+            .nth(1)
+            .and_then(|s| s.split(']').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let xml = format!(
+            "<OutputPageRequest><id>output-0</id><offset>{offset}</offset></OutputPageRequest>"
+        );
+        let (ok, page) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok, "{page}");
+        let end = offset + PAGE_SIZE;
+        assert!(page.starts_with(&format!(
+            "[output-0: bytes {offset}-{end} of {}]\n",
+            text.len()
+        )));
+        assert!(page.contains(&text[offset..end]));
+        assert!(page.ends_with(&format!("[next page: offset {end}]")));
+
+        let xml = format!(
+            "<OutputPageRequest><id>output-0</id><offset>{}</offset></OutputPageRequest>",
+            text.len() - 10
+        );
+        let (ok, page) = get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap());
+        assert!(ok);
+        assert!(page.ends_with("[end of output]"));
+
+        // Other threads can't read it
+        let mut ctx = make_ctx();
+        ctx.thread_id = "t2".into();
+        let xml = "<OutputPageRequest><id>output-0</id></OutputPageRequest>";
+        let (ok, err) = get_result(tool.handle(make_payload(xml), ctx).await.unwrap());
+        assert!(!ok);
+        assert!(err.contains("no stored output"));
+    }
+
+    #[test]
+    fn output_page_metadata() {
+        let tool = OutputPageTool::default();
+        assert_eq!(tool.name(), "output-page");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "OutputPageRequest");
+    }
+}
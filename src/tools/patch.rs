@@ -0,0 +1,419 @@
+//! Unified-diff application with fuzzy hunk matching.
+//!
+//! Hunks are located near the line their header names, tolerating drift
+//! from earlier edits, whitespace differences, and (as a last resort)
+//! up to two stale context lines at either end. Application is
+//! all-or-nothing: if any hunk fails, every failure is reported and the
+//! content is left untouched.
+
+/// Maximum number of context lines dropped from each end of a hunk.
+const MAX_FUZZ: usize = 2;
+
+/// A successfully applied patch.
+#[derive(Debug)]
+pub struct PatchOutcome {
+    /// The patched content.
+    pub content: String,
+    /// One line per hunk that needed an offset, whitespace leniency or fuzz.
+    pub notes: Vec<String>,
+}
+
+/// A parsed `@@ -a,b +c,d @@` hunk.
+#[derive(Debug)]
+struct Hunk {
+    header: String,
+    /// 1-based start line in the original file.
+    old_start: usize,
+    /// Body lines tagged with ' ', '-' or '+'.
+    lines: Vec<(char, String)>,
+}
+
+impl Hunk {
+    fn old_lines(&self, lead: usize, trail: usize) -> Vec<&str> {
+        self.side(lead, trail, '+')
+    }
+
+    /// The new side, keeping the file's own text for context lines so a
+    /// whitespace-lenient match doesn't rewrite them.
+    fn replacement(&self, file: &[String], lead: usize, trail: usize) -> Vec<String> {
+        let mut cursor = 0;
+        let mut out = Vec::new();
+        for (tag, line) in &self.lines[lead..self.lines.len() - trail] {
+            match tag {
+                ' ' => {
+                    out.push(file[cursor].clone());
+                    cursor += 1;
+                }
+                '-' => cursor += 1,
+                _ => out.push(line.clone()),
+            }
+        }
+        out
+    }
+
+    /// Body lines minus `skip`-tagged ones, with `lead`/`trail` lines cut.
+    fn side(&self, lead: usize, trail: usize, skip: char) -> Vec<&str> {
+        self.lines[lead..self.lines.len() - trail]
+            .iter()
+            .filter(|(tag, _)| *tag != skip)
+            .map(|(_, line)| line.as_str())
+            .collect()
+    }
+
+    /// Number of unchanged context lines at the start and end of the hunk.
+    fn context_edges(&self) -> (usize, usize) {
+        let lead = self.lines.iter().take_while(|(tag, _)| *tag == ' ').count();
+        let trail = self.lines[lead..]
+            .iter()
+            .rev()
+            .take_while(|(tag, _)| *tag == ' ')
+            .count();
+        (lead, trail)
+    }
+}
+
+/// How strictly lines are compared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Leniency {
+    Exact,
+    TrailingWhitespace,
+    AllWhitespace,
+}
+
+impl Leniency {
+    fn lines_match(self, a: &str, b: &str) -> bool {
+        match self {
+            Leniency::Exact => a == b,
+            Leniency::TrailingWhitespace => a.trim_end() == b.trim_end(),
+            Leniency::AllWhitespace => a.split_whitespace().eq(b.split_whitespace()),
+        }
+    }
+}
+
+/// Apply a unified diff to `content`.
+///
+/// Returns the patched content, or one message per hunk that could not
+/// be placed (prefixed with the hunk number and header).
+pub fn apply_unified(content: &str, patch: &str) -> Result<PatchOutcome, Vec<String>> {
+    let hunks = parse_hunks(patch).map_err(|e| vec![e])?;
+    if hunks.is_empty() {
+        return Err(vec!["patch contains no @@ hunks".into()]);
+    }
+
+    let eol = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let trailing_eol = content.is_empty() || content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let mut offset: isize = 0;
+    let mut notes = Vec::new();
+    let mut failures = Vec::new();
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        let label = format!("hunk {} ({})", i + 1, hunk.header);
+        match locate(&lines, hunk, offset) {
+            Some(found) => {
+                let old_len = hunk.old_lines(found.lead, found.trail).len();
+                let new = hunk.replacement(&lines[found.pos..], found.lead, found.trail);
+                let new_len = new.len();
+                lines.splice(found.pos..found.pos + old_len, new);
+
+                // Drift is relative to where earlier hunks already moved it
+                let expected = (hunk.old_start.saturating_sub(1) + found.lead) as isize + offset;
+                let drift = found.pos as isize - expected;
+                offset += drift + new_len as isize - old_len as isize;
+
+                let mut how = Vec::new();
+                if drift != 0 {
+                    how.push(format!("offset {drift:+}"));
+                }
+                match found.leniency {
+                    Leniency::Exact => {}
+                    Leniency::TrailingWhitespace => how.push("ignoring trailing whitespace".into()),
+                    Leniency::AllWhitespace => how.push("ignoring whitespace".into()),
+                }
+                if found.fuzz > 0 {
+                    how.push(format!("fuzz {}", found.fuzz));
+                }
+                if !how.is_empty() {
+                    notes.push(format!(
+                        "{label} applied at line {} ({})",
+                        found.pos + 1,
+                        how.join(", ")
+                    ));
+                }
+            }
+            None => failures.push(format!("{label}: {}", explain_miss(&lines, hunk))),
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(failures);
+    }
+
+    let mut patched = lines.join(eol);
+    if trailing_eol && !patched.is_empty() {
+        patched.push_str(eol);
+    }
+    Ok(PatchOutcome {
+        content: patched,
+        notes,
+    })
+}
+
+/// Where a hunk matched.
+struct Found {
+    pos: usize,
+    lead: usize,
+    trail: usize,
+    fuzz: usize,
+    leniency: Leniency,
+}
+
+/// Find a hunk's old side in `lines`, nearest to its expected position.
+///
+/// Tries every leniency at fuzz 0 before dropping any context lines.
+fn locate(lines: &[String], hunk: &Hunk, offset: isize) -> Option<Found> {
+    let (ctx_lead, ctx_trail) = hunk.context_edges();
+    for fuzz in 0..=MAX_FUZZ {
+        let lead = fuzz.min(ctx_lead);
+        let trail = fuzz.min(ctx_trail);
+        if fuzz > 0 && lead + trail == 0 {
+            break;
+        }
+        let old = hunk.old_lines(lead, trail);
+        let expected = (hunk.old_start.saturating_sub(1) + lead) as isize + offset;
+        for leniency in [
+            Leniency::Exact,
+            Leniency::TrailingWhitespace,
+            Leniency::AllWhitespace,
+        ] {
+            if let Some(pos) = search(lines, &old, expected, leniency) {
+                return Some(Found {
+                    pos,
+                    lead,
+                    trail,
+                    fuzz,
+                    leniency,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Search outward from `expected` for a window equal to `old`.
+fn search(lines: &[String], old: &[&str], expected: isize, leniency: Leniency) -> Option<usize> {
+    if old.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - old.len();
+    let expected = expected.clamp(0, last as isize) as usize;
+    if old.is_empty() {
+        return Some(expected);
+    }
+    let matches = |pos: usize| {
+        lines[pos..pos + old.len()]
+            .iter()
+            .zip(old)
+            .all(|(have, want)| leniency.lines_match(have, want))
+    };
+    for distance in 0..=last {
+        if distance <= expected && matches(expected - distance) {
+            return Some(expected - distance);
+        }
+        if distance > 0 && expected + distance <= last && matches(expected + distance) {
+            return Some(expected + distance);
+        }
+        if distance > expected && expected + distance > last {
+            break;
+        }
+    }
+    None
+}
+
+/// Describe why a hunk did not match, pointing at the closest candidate.
+fn explain_miss(lines: &[String], hunk: &Hunk) -> String {
+    let old = hunk.old_lines(0, 0);
+    if old.len() > lines.len() {
+        return format!(
+            "hunk expects {} lines but the file has {}",
+            old.len(),
+            lines.len()
+        );
+    }
+
+    // Closest window = most lines equal ignoring whitespace
+    let mut best: Option<(usize, usize)> = None;
+    for pos in 0..=lines.len() - old.len() {
+        let score = lines[pos..pos + old.len()]
+            .iter()
+            .zip(&old)
+            .filter(|(have, want)| Leniency::AllWhitespace.lines_match(have, want))
+            .count();
+        if score > 0 && best.is_none_or(|(_, s)| score > s) {
+            best = Some((pos, score));
+        }
+    }
+
+    let Some((pos, _)) = best else {
+        return "context not found anywhere in the file".into();
+    };
+    let mismatch = lines[pos..pos + old.len()]
+        .iter()
+        .zip(&old)
+        .position(|(have, want)| !Leniency::AllWhitespace.lines_match(have, want))
+        .unwrap_or(0);
+    format!(
+        "context not found; closest match at line {} differs at line {}: expected `{}`, found `{}`",
+        pos + 1,
+        pos + mismatch + 1,
+        old[mismatch],
+        lines[pos + mismatch]
+    )
+}
+
+/// Parse the hunks of a unified diff, skipping file headers.
+fn parse_hunks(patch: &str) -> Result<Vec<Hunk>, String> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut current: Option<Hunk> = None;
+    let mut iter = patch.lines().peekable();
+
+    while let Some(raw) = iter.next() {
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+
+        if line.starts_with("@@") {
+            hunks.extend(current.take());
+            current = Some(Hunk {
+                header: line.to_string(),
+                old_start: parse_old_start(line)?,
+                lines: Vec::new(),
+            });
+            continue;
+        }
+
+        // File headers end the current hunk (`--- a/x` followed by `+++ b/x`)
+        let is_file_header = line.starts_with("diff ")
+            || line.starts_with("index ")
+            || (line.starts_with("--- ") && iter.peek().is_some_and(|n| n.starts_with("+++ ")));
+        if is_file_header {
+            hunks.extend(current.take());
+            if line.starts_with("--- ") {
+                iter.next();
+            }
+            continue;
+        }
+
+        let Some(hunk) = current.as_mut() else {
+            continue; // preamble before the first hunk
+        };
+        match line.chars().next() {
+            Some(tag @ (' ' | '-' | '+')) => hunk.lines.push((tag, line[1..].to_string())),
+            Some('\\') => {} // "\ No newline at end of file"
+            // Editors and models often strip the space from blank context lines
+            None => hunk.lines.push((' ', String::new())),
+            Some(_) => {
+                return Err(format!("unexpected line in {}: {line}", hunk.header));
+            }
+        }
+    }
+    hunks.extend(current);
+
+    // Trailing blank lines are usually an artifact of how the patch was quoted
+    for hunk in &mut hunks {
+        while hunk
+            .lines
+            .last()
+            .is_some_and(|(tag, l)| *tag == ' ' && l.is_empty())
+        {
+            hunk.lines.pop();
+        }
+    }
+    Ok(hunks)
+}
+
+/// Extract `a` from `@@ -a,b +c,d @@`.
+fn parse_old_start(header: &str) -> Result<usize, String> {
+    header
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('-'))
+        .and_then(|range| range.split(',').next())
+        .and_then(|start| start.parse().ok())
+        .ok_or_else(|| format!("malformed hunk header: {header}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\nfn e() {}\n";
+
+    #[test]
+    fn applies_exact_hunk() {
+        let patch = "--- a/x.rs\n+++ b/x.rs\n@@ -2,3 +2,3 @@\n fn b() {}\n-fn c() {}\n+fn see() {}\n fn d() {}\n";
+        let out = apply_unified(FILE, patch).unwrap();
+        assert_eq!(
+            out.content,
+            "fn a() {}\nfn b() {}\nfn see() {}\nfn d() {}\nfn e() {}\n"
+        );
+        assert!(out.notes.is_empty());
+    }
+
+    #[test]
+    fn tolerates_wrong_line_numbers() {
+        let patch = "@@ -40,2 +40,2 @@\n-fn e() {}\n+fn eee() {}\n";
+        let out = apply_unified(FILE, patch).unwrap();
+        assert!(out.content.ends_with("fn eee() {}\n"));
+        assert!(out.notes[0].contains("offset"));
+    }
+
+    #[test]
+    fn clean_multi_hunk_patch_has_no_notes() {
+        let patch = "@@ -1,1 +1,2 @@\n-fn a() {}\n+fn a() {}\n+fn aa() {}\n@@ -4,1 +5,1 @@\n-fn d() {}\n+fn dee() {}\n";
+        let out = apply_unified(FILE, patch).unwrap();
+        assert_eq!(
+            out.content,
+            "fn a() {}\nfn aa() {}\nfn b() {}\nfn c() {}\nfn dee() {}\nfn e() {}\n"
+        );
+        assert!(out.notes.is_empty(), "{:?}", out.notes);
+    }
+
+    #[test]
+    fn tolerates_stale_context() {
+        let patch = "@@ -1,4 +1,4 @@\n fn a()  {}\n-fn b() {}\n+fn bee() {}\n fn STALE() {}\n";
+        let out = apply_unified(FILE, patch).unwrap();
+        assert!(out.content.contains("fn bee() {}\nfn c() {}"));
+        assert!(out.notes[0].contains("fuzz 1"));
+    }
+
+    #[test]
+    fn tolerates_whitespace_drift() {
+        let patch = "@@ -3,2 +3,2 @@\n   fn c() {}  \n-fn d() {}\n+fn dee() {}\n";
+        let out = apply_unified(FILE, patch).unwrap();
+        assert!(out.content.contains("fn c() {}\nfn dee() {}\n"));
+        assert!(out.notes[0].contains("ignoring whitespace"));
+    }
+
+    #[test]
+    fn failed_hunk_reports_and_changes_nothing() {
+        let patch = "@@ -1,1 +1,1 @@\n-fn a() {}\n+fn ay() {}\n@@ -3,2 +3,2 @@\n-fn cc() {}\n-fn d() {}\n+fn x() {}\n";
+        let failures = apply_unified(FILE, patch).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("hunk 2 (@@ -3,2 +3,2 @@)"));
+        assert!(failures[0].contains("expected `fn cc() {}`, found `fn c() {}`"));
+    }
+
+    #[test]
+    fn preserves_crlf() {
+        let out = apply_unified("one\r\ntwo\r\n", "@@ -2 +2 @@\n-two\n+2\n").unwrap();
+        assert_eq!(out.content, "one\r\n2\r\n");
+    }
+
+    #[test]
+    fn rejects_patch_without_hunks() {
+        assert!(apply_unified(FILE, "just some text").is_err());
+    }
+}
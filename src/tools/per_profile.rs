@@ -0,0 +1,194 @@
+//! Per-profile tool instances — one native tool, configured per profile.
+//!
+//! The pipeline builder makes one instance of a native tool for every
+//! profile that can reach it, each from that profile's own `workspace:`,
+//! `commands:`, `sandbox:`, `git:`, `lsp:` and network blocks.
+//! [`PerProfile`] is the listener they sit behind: it looks up the calling
+//! thread's profile in the kernel's thread table and hands the call to that
+//! profile's instance. A thread whose profile has no instance, or that the
+//! kernel doesn't know, is refused rather than served by another profile's
+//! tool.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rust_pipeline::prelude::*;
+use tokio::sync::{broadcast, Mutex};
+
+use super::output::OutputStore;
+use super::{ToolPeer, ToolResponse};
+use crate::kernel::Kernel;
+use crate::pipeline::events::PipelineEvent;
+
+/// A tool's instances keyed by profile name, dispatched by the caller's.
+pub struct PerProfile<T> {
+    name: String,
+    tools: HashMap<String, T>,
+    kernel: Option<Arc<Mutex<Kernel>>>,
+}
+
+impl<T: ToolPeer> PerProfile<T> {
+    /// Wrap `tools`, one per profile. At least one is required.
+    pub fn new(name: &str, tools: HashMap<String, T>) -> Result<Self, String> {
+        if tools.is_empty() {
+            return Err(format!("no profile can use {name}"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            tools,
+            kernel: None,
+        })
+    }
+
+    /// The instance for `thread_id`'s profile.
+    async fn tool_for(&self, thread_id: &str) -> Result<&T, String> {
+        let Some(kernel) = self.kernel.as_ref() else {
+            return Err(format!(
+                "{}: no kernel to look up the thread's profile",
+                self.name
+            ));
+        };
+        let profile = kernel
+            .lock()
+            .await
+            .threads()
+            .get_profile(thread_id)
+            .map(str::to_string)
+            .ok_or_else(|| format!("{}: thread {thread_id} has no profile", self.name))?;
+        self.tools
+            .get(&profile)
+            .ok_or_else(|| format!("{} is not available to profile '{profile}'", self.name))
+    }
+}
+
+#[async_trait]
+impl<T: ToolPeer> Handler for PerProfile<T> {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        match self.tool_for(&ctx.thread_id).await {
+            Ok(tool) => tool.handle(payload, ctx).await,
+            Err(e) => Ok(HandlerResponse::Reply {
+                payload_xml: ToolResponse::err(&e),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: ToolPeer> ToolPeer for PerProfile<T> {
+    // Every instance is the same tool
+    fn name(&self) -> &str {
+        self.tools
+            .values()
+            .next()
+            .map_or(self.name.as_str(), |t| t.name())
+    }
+
+    fn wit(&self) -> &str {
+        self.tools.values().next().map_or("", |t| t.wit())
+    }
+
+    // No set_workspace: each instance already has its profile's
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        for tool in self.tools.values_mut() {
+            tool.set_event_sender(tx.clone());
+        }
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        for tool in self.tools.values_mut() {
+            tool.set_output_store(store.clone());
+        }
+    }
+
+    fn set_kernel(&mut self, kernel: Arc<Mutex<Kernel>>) {
+        for tool in self.tools.values_mut() {
+            tool.set_kernel(kernel.clone());
+        }
+        self.kernel = Some(kernel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::file_read::FileReadTool;
+    use crate::tools::workspace::Workspace;
+
+    fn payload(path: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: format!("<FileReadRequest><path>{path}</path></FileReadRequest>").into_bytes(),
+            tag: "FileReadRequest".into(),
+        }
+    }
+
+    fn ctx(thread_id: &str) -> HandlerContext {
+        HandlerContext {
+            thread_id: thread_id.into(),
+            from: "agent".into(),
+            own_name: "file-read".into(),
+        }
+    }
+
+    fn result(resp: HandlerResult) -> (bool, String) {
+        match resp.unwrap() {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                (xml.contains("<success>true</success>"), xml)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_go_to_the_callers_profile() {
+        let coding = tempfile::tempdir().unwrap();
+        let docs = tempfile::tempdir().unwrap();
+        std::fs::write(coding.path().join("a.txt"), "coding file").unwrap();
+        std::fs::write(docs.path().join("a.txt"), "docs file").unwrap();
+
+        let mut tools = HashMap::new();
+        for (profile, dir) in [("coding", &coding), ("docs", &docs)] {
+            let mut tool = FileReadTool::default();
+            tool.set_workspace(Arc::new(Workspace::new(profile, dir.path(), &[]).unwrap()));
+            tools.insert(profile.to_string(), tool);
+        }
+        let mut tool = PerProfile::new("file-read", tools).unwrap();
+
+        let kernel = Kernel::open(&coding.path().join("data")).unwrap();
+        let kernel = Arc::new(Mutex::new(kernel));
+        {
+            let mut k = kernel.lock().await;
+            k.threads_mut()
+                .register_thread("t-coding", "console", "a", "coding");
+            k.threads_mut()
+                .register_thread("t-docs", "console", "b", "docs");
+            k.threads_mut()
+                .register_thread("t-admin", "console", "c", "admin");
+        }
+        tool.set_kernel(kernel);
+
+        let (ok, xml) = result(tool.handle(payload("a.txt"), ctx("t-coding")).await);
+        assert!(ok && xml.contains("coding file"), "{xml}");
+        let (ok, xml) = result(tool.handle(payload("a.txt"), ctx("t-docs")).await);
+        assert!(ok && xml.contains("docs file"), "{xml}");
+
+        // No instance for the profile, or no profile at all: refused
+        let (ok, xml) = result(tool.handle(payload("a.txt"), ctx("t-admin")).await);
+        assert!(
+            !ok && xml.contains("not available to profile 'admin'"),
+            "{xml}"
+        );
+        let (ok, xml) = result(tool.handle(payload("a.txt"), ctx("t-unknown")).await);
+        assert!(!ok && xml.contains("has no profile"), "{xml}");
+    }
+
+    #[test]
+    fn needs_at_least_one_profile() {
+        let err = PerProfile::<FileReadTool>::new("file-read", HashMap::new())
+            .err()
+            .unwrap();
+        assert!(err.contains("no profile can use file-read"));
+    }
+}
@@ -0,0 +1,748 @@
+//! ReplaceTool — project-wide search and replace, previewed before it lands.
+//!
+//! A call without `token` is a dry run: the files in scope (walked like
+//! grep, ignores honoured) are rewritten in memory and the result comes
+//! back as per-file diffs with replacement counts, plus a token naming that
+//! exact change. Nothing is written. A call with the token applies it:
+//!
+//! - every file must still be at the version the preview saw, or nothing
+//!   is written and the model is asked to preview again;
+//! - every file's pre-image is checkpointed in the kernel first, so the
+//!   change can be undone or rewound like a file-edit; if any checkpoint
+//!   fails, the ones already recorded are dropped again;
+//! - files are written in place like the other file tools do, so mode,
+//!   owner and hard links survive; if a write fails, every file touched so
+//!   far is put back, and any that can't be are named in the error.
+//!
+//! Files keep their encoding, BOM and line endings (see [`super::encoding`]).
+//! Each thread has at most one pending preview; a new preview replaces it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use async_trait::async_trait;
+use regex::{NoExpand, Regex};
+use rust_pipeline::prelude::*;
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use tokio::sync::broadcast;
+
+use super::encoding::TextFile;
+use super::grep::GrepTool;
+use super::ignore::IgnoreStack;
+use super::output::{self, OutputStore};
+use super::version;
+use super::workspace::{self, Access, Workspace};
+use super::{announce_file_changed, extract_tag, restore_originals, ToolPeer, ToolResponse};
+use crate::kernel::Kernel;
+use crate::pipeline::events::{KernelOpType, PipelineEvent};
+
+/// Hex digits of the plan hash kept in a token.
+const TOKEN_LEN: usize = 12;
+
+/// Unchanged lines shown around each change in the preview.
+const DIFF_CONTEXT: usize = 2;
+
+/// One file a previewed change rewrites.
+#[derive(Debug, Clone)]
+struct FileChange {
+    path: PathBuf,
+    /// The path as shown to the model.
+    display: String,
+    /// The file's version when it was previewed.
+    version: String,
+    file: TextFile,
+    new_text: String,
+    replacements: usize,
+}
+
+/// A planned file, re-read and encoded, ready to be written.
+struct Staged {
+    change: FileChange,
+    /// The file's bytes before the write, to put back if a later one fails.
+    original: Vec<u8>,
+    bytes: Vec<u8>,
+}
+
+/// A previewed change, waiting for its token.
+#[derive(Debug, Clone)]
+struct Plan {
+    token: String,
+    changes: Vec<FileChange>,
+}
+
+impl Plan {
+    fn new(changes: Vec<FileChange>) -> Self {
+        let mut hasher = Sha256::new();
+        for change in &changes {
+            hasher.update(change.path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(change.version.as_bytes());
+            hasher.update([0]);
+            hasher.update(change.new_text.as_bytes());
+            hasher.update([0]);
+        }
+        let mut token = format!("{:x}", hasher.finalize());
+        token.truncate(TOKEN_LEN);
+        Self { token, changes }
+    }
+
+    fn replacements(&self) -> usize {
+        self.changes.iter().map(|c| c.replacements).sum()
+    }
+}
+
+/// What to replace, and where.
+struct Search {
+    re: Regex,
+    replacement: String,
+    literal: bool,
+    /// Matched against the file name, or the path below the scope if it has a `/`.
+    glob_filter: Option<glob::Pattern>,
+}
+
+/// Project-wide search and replace with a dry-run preview.
+#[derive(Default)]
+pub struct ReplaceTool {
+    workspace: Option<Arc<Workspace>>,
+    output_store: Option<Arc<OutputStore>>,
+    kernel: Option<Arc<tokio::sync::Mutex<Kernel>>>,
+    plans: Arc<Mutex<HashMap<String, Plan>>>,
+    events: Option<broadcast::Sender<PipelineEvent>>,
+    /// Started on the first preview, to forget pruned threads' plans.
+    reaper: OnceLock<()>,
+}
+
+impl ReplaceTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rewrite the files under `scope` in memory. Blocking.
+    ///
+    /// Returns the changes and how many matching files were left out
+    /// because the workspace only lets the tool read them.
+    fn plan(
+        scope: &Path,
+        scope_display: &str,
+        search: &Search,
+        jail: Option<&Workspace>,
+    ) -> Result<(Vec<FileChange>, usize), String> {
+        let files = if scope.is_file() {
+            vec![scope.to_path_buf()]
+        } else if scope.is_dir() {
+            let ignores = IgnoreStack::for_dir(scope);
+            GrepTool::collect_files(scope, &ignores, None, jail)
+        } else {
+            return Err(format!("path not found: {scope_display}"));
+        };
+
+        let mut changes = Vec::new();
+        let mut read_only = 0;
+        for path in files {
+            let rel = path.strip_prefix(scope).unwrap_or(&path);
+            if let Some(filter) = &search.glob_filter {
+                let name = path.file_name().map(Path::new).unwrap_or(rel);
+                let subject = if filter.as_str().contains('/') {
+                    rel
+                } else {
+                    name
+                };
+                if !filter.matches_path(subject) {
+                    continue;
+                }
+            }
+            let Ok(raw) = std::fs::read(&path) else {
+                continue;
+            };
+            let Some(file) = TextFile::decode(&raw) else {
+                continue;
+            };
+            let replacements = search.re.find_iter(&file.text).count();
+            if replacements == 0 {
+                continue;
+            }
+            let new_text = if search.literal {
+                search
+                    .re
+                    .replace_all(&file.text, NoExpand(&search.replacement))
+            } else {
+                search
+                    .re
+                    .replace_all(&file.text, search.replacement.as_str())
+            }
+            .into_owned();
+            if new_text == file.text {
+                continue;
+            }
+            if jail.is_some_and(|ws| !ws.contains(&path, Access::Write)) {
+                read_only += 1;
+                continue;
+            }
+            let display = if rel.as_os_str().is_empty() {
+                scope_display.to_string()
+            } else if scope_display == "." {
+                rel.display().to_string()
+            } else {
+                Path::new(scope_display).join(rel).display().to_string()
+            };
+            changes.push(FileChange {
+                path,
+                display,
+                version: version::content_version(&raw),
+                file,
+                new_text,
+                replacements,
+            });
+        }
+        Ok((changes, read_only))
+    }
+
+    /// Per-file diffs and the token to apply them.
+    fn render_preview(plan: &Plan, read_only: usize) -> String {
+        let mut out = String::new();
+        for change in &plan.changes {
+            let noun = if change.replacements == 1 {
+                "replacement"
+            } else {
+                "replacements"
+            };
+            out.push_str(&format!(
+                "{}: {} {noun}\n",
+                change.display, change.replacements
+            ));
+            let diff = TextDiff::from_lines(&change.file.text, &change.new_text);
+            out.push_str(&diff.unified_diff().context_radius(DIFF_CONTEXT).to_string());
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        if read_only > 0 {
+            out.push_str(&format!(
+                "\n{read_only} more matching files are read-only in this workspace and were left out\n"
+            ));
+        }
+        out.push_str(&format!(
+            "\n{} replacements in {} files; nothing written yet. Apply exactly this change with token {}.",
+            plan.replacements(),
+            plan.changes.len(),
+            plan.token
+        ));
+        out
+    }
+
+    /// Dry run: plan the change, remember it for the thread, show it.
+    async fn preview(&self, xml: &str, thread_id: &str) -> Result<String, String> {
+        let pattern = extract_tag(xml, "pattern").unwrap_or_default();
+        if pattern.is_empty() {
+            return Err("missing required <pattern> (or <token> to apply a preview)".into());
+        }
+        let flag = |tag: &str| extract_tag(xml, tag).is_some_and(|v| v == "true");
+        let literal = flag("literal");
+        let source = if literal {
+            regex::escape(&pattern)
+        } else {
+            pattern
+        };
+        let flags = if flag("case_insensitive") { "(?i)" } else { "" };
+        let re =
+            Regex::new(&format!("{flags}{source}")).map_err(|e| format!("invalid regex: {e}"))?;
+        let glob_filter = match extract_tag(xml, "glob_filter").filter(|g| !g.trim().is_empty()) {
+            Some(g) => Some(
+                glob::Pattern::new(g.trim()).map_err(|e| format!("invalid <glob_filter>: {e}"))?,
+            ),
+            None => None,
+        };
+        let search = Search {
+            re,
+            replacement: extract_tag(xml, "replacement").unwrap_or_default(),
+            literal,
+            glob_filter,
+        };
+
+        let scope_display = extract_tag(xml, "path")
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| ".".into());
+        let scope = workspace::resolve_in(
+            self.workspace.as_deref(),
+            "replace",
+            &scope_display,
+            Access::Read,
+        )?;
+
+        // The walk and the file reads are blocking; keep them off the runtime.
+        let jail = self.workspace.clone();
+        let (changes, read_only) = tokio::task::spawn_blocking(move || {
+            Self::plan(&scope, &scope_display, &search, jail.as_deref())
+        })
+        .await
+        .map_err(|e| format!("replace task panicked: {e}"))??;
+
+        if changes.is_empty() {
+            self.plans.lock().unwrap().remove(thread_id);
+            return Ok(match read_only {
+                0 => "no matches; nothing to replace".into(),
+                n => format!("no matches in writable files ({n} read-only files match)"),
+            });
+        }
+        let plan = Plan::new(changes);
+        let text = Self::render_preview(&plan, read_only);
+        self.start_reaper();
+        self.plans
+            .lock()
+            .unwrap()
+            .insert(thread_id.to_string(), plan);
+        Ok(text)
+    }
+
+    /// Apply the thread's previewed change: all files or none.
+    async fn apply(&self, xml: &str, token: &str, thread_id: &str) -> Result<String, String> {
+        let plan = match self.plans.lock().unwrap().get(thread_id) {
+            Some(plan) if plan.token == token => plan.clone(),
+            Some(plan) => {
+                return Err(format!(
+                    "token {token} doesn't match this thread's last preview ({}); preview again",
+                    plan.token
+                ))
+            }
+            None => {
+                return Err("no preview pending for this thread; preview the change first".into())
+            }
+        };
+
+        // Check and encode every file before writing any. The reads and
+        // writes are blocking; keep them off the runtime, as the preview does.
+        let allow_lossy = extract_tag(xml, "allow_lossy").is_some_and(|v| v == "true");
+        let changes = plan.changes.clone();
+        let staged = tokio::task::spawn_blocking(move || Self::stage(changes, allow_lossy))
+            .await
+            .map_err(|e| format!("replace task panicked: {e}"))??;
+
+        let checkpoints = self.record_checkpoints(xml, thread_id, &staged).await?;
+
+        let (staged, written) = tokio::task::spawn_blocking(move || {
+            let written = Self::write_staged(&staged);
+            (staged, written)
+        })
+        .await
+        .map_err(|e| format!("replace task panicked: {e}"))?;
+        if let Err(e) = written {
+            self.discard_checkpoints(&checkpoints).await;
+            return Err(e);
+        }
+        self.plans.lock().unwrap().remove(thread_id);
+
+        let mut out = format!(
+            "replaced {} occurrences in {} files\n",
+            plan.replacements(),
+            plan.changes.len()
+        );
+        let mut kernel = match &self.kernel {
+            Some(kernel) => Some(kernel.lock().await),
+            None => None,
+        };
+        for Staged { change, bytes, .. } in &staged {
+            out.push_str(&format!(
+                "{}: {} {}\n",
+                change.display,
+                change.replacements,
+                version::footer(&change.path, bytes)
+            ));
+            announce_file_changed(self.events.as_ref(), thread_id, "replace", &change.path);
+            // Keep the thread's view of files it had read current, so its
+            // next edit isn't refused as a conflict with this change
+            if let Some(kernel) = kernel.as_mut() {
+                if kernel.file_version(thread_id, &change.path).is_some() {
+                    let _ = kernel.record_file_version(
+                        thread_id,
+                        &change.path,
+                        &version::content_version(bytes),
+                    );
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Re-read every file of a plan, check it is still at the previewed
+    /// version and encode its new text. Blocking.
+    fn stage(changes: Vec<FileChange>, allow_lossy: bool) -> Result<Vec<Staged>, String> {
+        let mut staged = Vec::new();
+        for change in changes {
+            let original = std::fs::read(&change.path)
+                .map_err(|e| format!("read error: {}: {e}; nothing written", change.display))?;
+            if version::content_version(&original) != change.version {
+                return Err(format!(
+                    "{} changed since the preview; nothing written, preview again",
+                    change.display
+                ));
+            }
+            let (bytes, _) = change
+                .file
+                .encode(&change.new_text, allow_lossy)
+                .map_err(|e| format!("{}: {e}; nothing written", change.display))?;
+            staged.push(Staged {
+                change,
+                original,
+                bytes,
+            });
+        }
+        Ok(staged)
+    }
+
+    /// Write staged files in place, in order. If a write fails, the files
+    /// touched so far are put back. Blocking.
+    fn write_staged(staged: &[Staged]) -> Result<(), String> {
+        for (i, Staged { change, bytes, .. }) in staged.iter().enumerate() {
+            if let Err(e) = std::fs::write(&change.path, bytes) {
+                // The failed write may have truncated its file too
+                let restored = restore_originals(staged[..=i].iter().map(|s| {
+                    (
+                        s.change.display.as_str(),
+                        s.change.path.as_path(),
+                        s.original.as_slice(),
+                    )
+                }));
+                return Err(format!("write error: {}: {e}; {restored}", change.display));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checkpoint every staged file under the call's `message_id`, all or
+    /// none. Returns the checkpoints' sequence numbers.
+    async fn record_checkpoints(
+        &self,
+        xml: &str,
+        thread_id: &str,
+        staged: &[Staged],
+    ) -> Result<Vec<u64>, String> {
+        let Some(kernel) = &self.kernel else {
+            return Ok(Vec::new());
+        };
+        let message_id = extract_tag(xml, "message_id").unwrap_or_default();
+        let mut kernel = kernel.lock().await;
+        let mut seqs = Vec::new();
+        for Staged { change, .. } in staged {
+            match kernel.record_checkpoint(thread_id, &message_id, &change.path) {
+                Ok(seq) => seqs.push(seq),
+                Err(e) => {
+                    if let Err(e) = kernel.discard_checkpoints(&seqs) {
+                        tracing::warn!("dropping replace checkpoints failed: {e}");
+                    }
+                    return Err(format!(
+                        "journal error: {}: {e}; nothing written",
+                        change.display
+                    ));
+                }
+            }
+        }
+        Ok(seqs)
+    }
+
+    /// Drop checkpoints of an apply that was rolled back.
+    async fn discard_checkpoints(&self, seqs: &[u64]) {
+        if let Some(kernel) = &self.kernel {
+            if let Err(e) = kernel.lock().await.discard_checkpoints(seqs) {
+                tracing::warn!("dropping replace checkpoints failed: {e}");
+            }
+        }
+    }
+
+    /// Forget a thread's preview when the kernel prunes it.
+    fn start_reaper(&self) {
+        let Some(tx) = &self.events else {
+            return;
+        };
+        self.reaper.get_or_init(|| {
+            let mut rx = tx.subscribe();
+            let plans: Weak<Mutex<HashMap<String, Plan>>> = Arc::downgrade(&self.plans);
+            tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(PipelineEvent::KernelOp {
+                            op: KernelOpType::ThreadPruned,
+                            thread_id,
+                        }) => match plans.upgrade() {
+                            Some(p) => {
+                                p.lock().unwrap().remove(&thread_id);
+                            }
+                            None => break,
+                        },
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        });
+    }
+}
+
+#[async_trait]
+impl Handler for ReplaceTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+
+        let result = match extract_tag(&xml_str, "token").filter(|t| !t.trim().is_empty()) {
+            Some(token) => self.apply(&xml_str, token.trim(), &ctx.thread_id).await,
+            None => self.preview(&xml_str, &ctx.thread_id).await,
+        };
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => ToolResponse::err(&e),
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for ReplaceTool {
+    fn name(&self) -> &str {
+        "replace"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        self.events = Some(tx);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn set_kernel(&mut self, kernel: Arc<tokio::sync::Mutex<Kernel>>) {
+        self.kernel = Some(kernel);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Search and replace across many files, e.g. to rename an identifier. Without token it is a dry run: returns per-file diffs with replacement counts and a token, writing nothing. Call again with just that token to apply exactly the previewed change, all files or none; it is refused if any file changed since the preview. Walks like grep (ignores honoured, hidden and binary files skipped); files keep their encoding and line endings.
+interface replace {
+    record request {
+        /// Regex to search for; it runs over whole files, so use (?m) for ^ and $ at line starts and ends
+        pattern: option<string>,
+        /// Replacement text; $1 or ${name} insert capture groups unless literal
+        replacement: option<string>,
+        /// Treat pattern and replacement as plain text (default: false)
+        literal: option<bool>,
+        /// Case insensitive search (default: false)
+        case-insensitive: option<bool>,
+        /// File or directory to search (default: current directory)
+        path: option<string>,
+        /// Only files matching this glob: a file name pattern (*.rs) or, with a /, a path under path (src/**/*.rs)
+        glob-filter: option<string>,
+        /// Apply the preview that returned this token
+        token: option<string>,
+        /// Write characters a file's encoding can't hold as ? instead of refusing (default: false)
+        allow-lossy: option<bool>,
+        /// Transcript message the change is checkpointed under; filled in by the agent loop
+        message-id: option<string>,
+    }
+    replace: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "replace".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "ReplaceRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    async fn call(tool: &ReplaceTool, body: &str) -> (bool, String) {
+        let xml = format!("<ReplaceRequest>{body}</ReplaceRequest>");
+        get_result(tool.handle(make_payload(&xml), make_ctx()).await.unwrap())
+    }
+
+    fn token_of(preview: &str) -> String {
+        let start = preview.rfind("token ").unwrap() + "token ".len();
+        preview[start..].trim_end_matches('.').to_string()
+    }
+
+    #[tokio::test]
+    async fn preview_writes_nothing_and_token_applies_it() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/a.rs"),
+            "fn old_name() {}\nold_name();\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/b.rs"), "use a::old_name;\r\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "old_name\n").unwrap();
+        let ws = Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap());
+        let kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        let kernel = Arc::new(tokio::sync::Mutex::new(kernel));
+        let mut tool = ReplaceTool::new();
+        tool.set_workspace(ws);
+        tool.set_kernel(kernel.clone());
+
+        let (ok, preview) = call(
+            &tool,
+            r"<pattern>\bold_(name)\b</pattern><replacement>new_$1</replacement><glob_filter>src/**/*.rs</glob_filter>",
+        )
+        .await;
+        assert!(ok, "{preview}");
+        assert!(preview.contains("src/a.rs: 2 replacements"), "{preview}");
+        assert!(
+            preview.contains("-old_name();\n+fn new_name() {}\n+new_name();"),
+            "{preview}"
+        );
+        assert!(preview.contains("src/b.rs: 1 replacement\n"), "{preview}");
+        assert!(!preview.contains("notes.txt"));
+        assert!(preview.contains("3 replacements in 2 files; nothing written yet"));
+        let a = std::fs::read_to_string(dir.path().join("src/a.rs")).unwrap();
+        assert!(a.contains("old_name"));
+
+        let token = token_of(&preview);
+        let (ok, applied) = call(
+            &tool,
+            &format!("<token>{token}</token><message_id>msg-0003</message_id>"),
+        )
+        .await;
+        assert!(ok, "{applied}");
+        assert!(applied.starts_with("replaced 3 occurrences in 2 files"));
+        assert!(applied.contains("src/a.rs: 2 [version: "));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/a.rs")).unwrap(),
+            "fn new_name() {}\nnew_name();\n"
+        );
+        // Line endings survive
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/b.rs")).unwrap(),
+            "use a::new_name;\r\n"
+        );
+
+        // Both pre-images are in the journal under the issuing message
+        let mut k = kernel.lock().await;
+        assert_eq!(k.checkpoints().count(), 2);
+        assert_eq!(
+            k.checkpoints().last_active().unwrap().message_id,
+            "msg-0003"
+        );
+        k.rewind("t1", "msg-0003").unwrap();
+        assert!(std::fs::read_to_string(dir.path().join("src/a.rs"))
+            .unwrap()
+            .contains("old_name"));
+        drop(k);
+
+        // A token is good for one apply
+        let (ok, err) = call(&tool, &format!("<token>{token}</token>")).await;
+        assert!(!ok);
+        assert!(err.contains("no preview pending"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn apply_refused_when_a_file_changed() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "alpha beta\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "alpha\n").unwrap();
+        let tool = ReplaceTool::new();
+        let path = dir.path().to_string_lossy();
+
+        let (ok, preview) = call(
+            &tool,
+            &format!("<pattern>a.p</pattern><replacement>$0!</replacement><literal>true</literal><path>{path}</path>"),
+        )
+        .await;
+        assert!(ok);
+        assert_eq!(preview, "no matches; nothing to replace");
+
+        let (ok, preview) = call(
+            &tool,
+            &format!("<pattern>alpha</pattern><replacement>$0!</replacement><literal>true</literal><path>{path}</path>"),
+        )
+        .await;
+        assert!(ok, "{preview}");
+        let token = token_of(&preview);
+
+        // A human edits one file in between: neither file is written
+        std::fs::write(dir.path().join("b.txt"), "alpha gamma\n").unwrap();
+        let (ok, err) = call(&tool, &format!("<token>{token}</token>")).await;
+        assert!(!ok);
+        assert!(err.contains("b.txt changed since the preview"), "{err}");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "alpha beta\n"
+        );
+
+        let (ok, err) = call(&tool, "<token>000000000000</token>").await;
+        assert!(!ok);
+        assert!(err.contains("doesn't match"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn apply_keeps_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let script = dir.path().join("run.sh");
+        std::fs::write(&script, "#!/bin/sh\necho old\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let tool = ReplaceTool::new();
+        let path = dir.path().to_string_lossy();
+
+        let (ok, preview) = call(
+            &tool,
+            &format!("<pattern>old</pattern><replacement>new</replacement><path>{path}</path>"),
+        )
+        .await;
+        assert!(ok, "{preview}");
+        let (ok, applied) = call(&tool, &format!("<token>{}</token>", token_of(&preview))).await;
+        assert!(ok, "{applied}");
+
+        assert_eq!(
+            std::fs::read_to_string(&script).unwrap(),
+            "#!/bin/sh\necho new\n"
+        );
+        let mode = std::fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn metadata() {
+        let tool = ReplaceTool::new();
+        assert_eq!(tool.name(), "replace");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "ReplaceRequest");
+    }
+}
@@ -0,0 +1,313 @@
+//! Execution sandbox — how command-exec children run under a profile.
+//!
+//! Built from a profile's `sandbox:` block:
+//!
+//! ```yaml
+//! sandbox:
+//!   env: [PATH, HOME, LANG]   # pass-list; everything else is scrubbed
+//!   cpu_secs: 120
+//!   memory_mb: 4096
+//!   file_size_mb: 1024
+//!   max_processes: 512
+//!   deny_network: true        # empty network namespace (Linux)
+//!   run_as_user: true         # become the profile's linux_user
+//! ```
+//!
+//! Limits are rlimits, so they apply per process and are inherited by
+//! everything the command starts. `max_processes` counts every process of
+//! the executing user, which makes it most useful with `run_as_user`.
+
+use std::process::Command;
+
+use crate::organism::profile::SandboxSpec;
+
+/// Variables passed through when a sandbox doesn't list its own.
+pub const DEFAULT_ENV_PASS: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "LANG", "LC_ALL", "TERM", "TMPDIR", "CARGO_HOME",
+    "RUSTUP_HOME", "SYSTEMROOT", "USERPROFILE", "TEMP",
+];
+
+const MIB: u64 = 1024 * 1024;
+
+/// A resolved user to run as.
+#[derive(Debug, Clone, PartialEq)]
+struct User {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: String,
+}
+
+/// Environment, limits and identity applied to every spawned command.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    env_pass: Vec<String>,
+    cpu_secs: Option<u64>,
+    memory_bytes: Option<u64>,
+    file_size_bytes: Option<u64>,
+    max_processes: Option<u64>,
+    deny_network: bool,
+    user: Option<User>,
+}
+
+impl Sandbox {
+    /// Resolve a profile's sandbox. Fails if the platform can't enforce it
+    /// or the process can't switch to `linux_user`.
+    pub fn from_spec(linux_user: &str, spec: &SandboxSpec) -> Result<Self, String> {
+        let env_pass = match &spec.env {
+            Some(names) => names.clone(),
+            None => DEFAULT_ENV_PASS.iter().map(|s| s.to_string()).collect(),
+        };
+        let limited = spec.cpu_secs.is_some()
+            || spec.memory_mb.is_some()
+            || spec.file_size_mb.is_some()
+            || spec.max_processes.is_some();
+        if limited && !cfg!(unix) {
+            return Err("sandbox: resource limits are only supported on Unix".into());
+        }
+        if spec.deny_network && !cfg!(target_os = "linux") {
+            return Err("sandbox: network isolation is only supported on Linux".into());
+        }
+        let user = if spec.run_as_user {
+            switch_target(linux_user)?
+        } else {
+            None
+        };
+        Ok(Self {
+            env_pass,
+            cpu_secs: spec.cpu_secs,
+            memory_bytes: spec.memory_mb.map(|mb| mb * MIB),
+            file_size_bytes: spec.file_size_mb.map(|mb| mb * MIB),
+            max_processes: spec.max_processes,
+            deny_network: spec.deny_network,
+            user,
+        })
+    }
+
+    /// Configure `cmd` to run inside the sandbox. Call before setting the
+    /// command's own environment variables, which the scrub would remove.
+    pub fn apply(&self, cmd: &mut Command) {
+        cmd.env_clear();
+        for name in &self.env_pass {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+        if let Some(user) = &self.user {
+            cmd.env("HOME", &user.home)
+                .env("USER", &user.name)
+                .env("LOGNAME", &user.name);
+        }
+        #[cfg(unix)]
+        self.apply_unix(cmd);
+    }
+
+    #[cfg(unix)]
+    fn apply_unix(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if let Some(user) = &self.user {
+            // std drops supplementary groups when switching from root
+            cmd.uid(user.uid).gid(user.gid);
+        }
+        let limits = [
+            (libc::RLIMIT_CPU, self.cpu_secs),
+            (libc::RLIMIT_AS, self.memory_bytes),
+            (libc::RLIMIT_FSIZE, self.file_size_bytes),
+            (libc::RLIMIT_NPROC, self.max_processes),
+        ];
+        let deny_network = self.deny_network;
+        // SAFETY: only async-signal-safe calls between fork and exec.
+        unsafe {
+            cmd.pre_exec(move || {
+                for (resource, value) in limits {
+                    let Some(value) = value else { continue };
+                    let limit = libc::rlimit {
+                        rlim_cur: value as libc::rlim_t,
+                        rlim_max: value as libc::rlim_t,
+                    };
+                    if libc::setrlimit(resource, &limit) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                if deny_network {
+                    isolate_network()?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Move the calling (forked) process into a fresh network namespace with
+/// only a downed loopback. Without `CAP_SYS_ADMIN`, a new user namespace
+/// grants the right to create one.
+#[cfg(target_os = "linux")]
+unsafe fn isolate_network() -> std::io::Result<()> {
+    if libc::unshare(libc::CLONE_NEWNET) == 0
+        || libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) == 0
+    {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+unsafe fn isolate_network() -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// The user to switch to, or None if the process already runs as them.
+#[cfg(unix)]
+fn switch_target(name: &str) -> Result<Option<User>, String> {
+    let user = lookup_user(name)?;
+    // SAFETY: geteuid has no preconditions.
+    let euid = unsafe { libc::geteuid() };
+    if user.uid == euid {
+        return Ok(None);
+    }
+    if euid != 0 {
+        return Err(format!(
+            "sandbox: running commands as {name} needs root (agentos runs as uid {euid})"
+        ));
+    }
+    Ok(Some(user))
+}
+
+#[cfg(not(unix))]
+fn switch_target(_name: &str) -> Result<Option<User>, String> {
+    Err("sandbox: run_as_user is only supported on Unix".into())
+}
+
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<User, String> {
+    use std::ffi::{CStr, CString};
+
+    let c_name = CString::new(name).map_err(|_| format!("sandbox: invalid user name {name:?}"))?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: passwd is plain data; getpwnam_r only writes into `pwd` and
+    // `buf`, and the strings it points at live in `buf`.
+    unsafe {
+        let mut pwd: libc::passwd = std::mem::zeroed();
+        let mut found: *mut libc::passwd = std::ptr::null_mut();
+        let rc = libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        );
+        if rc != 0 || found.is_null() {
+            return Err(format!("sandbox: unknown user {name}"));
+        }
+        Ok(User {
+            name: name.to_string(),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+            home: CStr::from_ptr(pwd.pw_dir).to_string_lossy().into_owned(),
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn run(sandbox: &Sandbox, script: &str) -> std::process::Output {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        sandbox.apply(&mut cmd);
+        cmd.output().unwrap()
+    }
+
+    #[test]
+    fn environment_is_scrubbed() {
+        std::env::set_var("AGENTOS_SANDBOX_SECRET", "hunter2");
+        let spec = SandboxSpec {
+            env: Some(vec!["PATH".into()]),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::from_spec("nobody", &spec).unwrap();
+        let out = run(&sandbox, "env");
+        let env = String::from_utf8_lossy(&out.stdout);
+        assert!(env.contains("PATH="));
+        assert!(!env.contains("AGENTOS_SANDBOX_SECRET"));
+        assert!(!env.contains("HOME="));
+    }
+
+    #[test]
+    fn file_size_limit_applies() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spec = SandboxSpec {
+            file_size_mb: Some(1),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::from_spec("nobody", &spec).unwrap();
+        let target = dir.path().join("big");
+        let out = run(
+            &sandbox,
+            &format!("ulimit -f; head -c 2000000 /dev/zero > {}", target.display()),
+        );
+        // `ulimit -f` reports 512-byte blocks
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "2048");
+        assert!(!out.status.success());
+        assert!(std::fs::metadata(&target).unwrap().len() <= MIB);
+    }
+
+    /// Whether a child can get a network namespace here, asked of the
+    /// kernel directly rather than through the sandbox under test.
+    #[cfg(target_os = "linux")]
+    fn can_unshare_network() -> bool {
+        use std::os::unix::process::CommandExt;
+
+        let mut cmd = Command::new("true");
+        // SAFETY: only async-signal-safe calls between fork and exec.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::unshare(libc::CLONE_NEWNET) == 0
+                    || libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) == 0
+                {
+                    Ok(())
+                } else {
+                    Err(std::io::Error::last_os_error())
+                }
+            });
+        }
+        cmd.status().is_ok_and(|s| s.success())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn network_is_denied() {
+        if !can_unshare_network() {
+            eprintln!("skipping network_is_denied: unshare(CLONE_NEWNET) is not permitted here");
+            return;
+        }
+        let spec = SandboxSpec {
+            deny_network: true,
+            ..Default::default()
+        };
+        let sandbox = Sandbox::from_spec("nobody", &spec).unwrap();
+        let mut cmd = Command::new("cat");
+        cmd.arg("/proc/net/dev");
+        sandbox.apply(&mut cmd);
+        let out = cmd.output().expect("sandboxed command failed to start");
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let devices = String::from_utf8_lossy(&out.stdout);
+        let names: Vec<&str> = devices
+            .lines()
+            .skip(2)
+            .filter_map(|l| l.split(':').next())
+            .map(str::trim)
+            .collect();
+        assert_eq!(names, vec!["lo"]);
+    }
+
+    #[test]
+    fn run_as_user_resolves_passwd() {
+        let root = lookup_user("root").unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert!(lookup_user("no-such-user-agentos").is_err());
+    }
+}
@@ -0,0 +1,719 @@
+//! Shell sessions — persistent PTY-backed shells for command-exec.
+//!
+//! A session is an `sh` process on its own pseudo-terminal, keyed by thread
+//! and name, so `cd` and exported variables carry over between calls. Each
+//! command is followed by a sentinel line (`printf '\n__agentos_N__:%s:%s\n'
+//! "$?" "$PWD"`): everything the terminal prints before it is the command's
+//! output, and after it come the exit code and the shell's working
+//! directory, which is tracked per session. A command still running when
+//! the call stops waiting — it timed out, or was started in the background —
+//! stays in the session as a job to `poll` or `kill`. Given an
+//! [`OutputStream`], a call also streams the job's output line by line as it
+//! arrives.
+//!
+//! Sessions die with their thread: once given the pipeline's event sender,
+//! [`ShellSessions`] closes a thread's sessions when the kernel reports it
+//! pruned or folded. Unix only; elsewhere opening a session is an error.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc, Mutex as AsyncMutex};
+use tokio::time::Instant;
+
+use super::command_exec::OutputStream;
+use super::sandbox::Sandbox;
+use crate::pipeline::events::{KernelOpType, PipelineEvent};
+
+/// Unread output kept per session; beyond this the oldest is dropped.
+const MAX_PENDING: usize = 256 * 1024;
+
+/// Open sessions allowed per thread.
+const MAX_SESSIONS_PER_THREAD: usize = 8;
+
+/// How long `kill` waits for the shell to come back after an interrupt.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Prefix of sentinel markers; lines starting with it never reach the model.
+const MARKER_PREFIX: &str = "__agentos_";
+
+/// Where a session's command stands after a call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    /// The command finished with this exit code.
+    Exited(i32),
+    /// Still running; poll again for more output, or kill it.
+    Running { command: String, elapsed: Duration },
+    /// Nothing in flight.
+    Idle,
+    /// The shell itself exited (or was killed); the session is gone.
+    Closed,
+}
+
+/// Output gathered by one call, and where the command stands.
+#[derive(Debug, Clone)]
+pub struct JobOutput {
+    pub output: String,
+    pub status: JobStatus,
+    /// The shell's working directory, as of the last finished command.
+    pub cwd: Option<PathBuf>,
+}
+
+/// A command sent into a session whose sentinel hasn't been seen yet.
+struct Job {
+    marker: String,
+    command: String,
+    started: Instant,
+}
+
+struct Session {
+    pty: pty::Pty,
+    chunks: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// Output not yet returned to the model.
+    pending: Vec<u8>,
+    /// Whether output was dropped from `pending` since the last read.
+    dropped: bool,
+    /// Bytes at the front of `pending` already streamed.
+    streamed: usize,
+    job: Option<Job>,
+    /// Working directory reported by the last sentinel (where the shell
+    /// was opened, before the first).
+    cwd: Option<PathBuf>,
+}
+
+impl Session {
+    fn open(dir: Option<&Path>, sandbox: Option<&Sandbox>) -> Result<Self, String> {
+        let (tx, chunks) = mpsc::unbounded_channel();
+        Ok(Self {
+            pty: pty::Pty::spawn(dir, sandbox, tx)?,
+            chunks,
+            pending: Vec::new(),
+            dropped: false,
+            streamed: 0,
+            job: None,
+            cwd: dir.map(Path::to_path_buf),
+        })
+    }
+
+    /// Send `command` followed by its sentinel.
+    fn start(&mut self, marker: String, command: &str) -> Result<(), String> {
+        self.send(&format!("{command}\n{}", sentinel(&marker)))?;
+        self.job = Some(Job {
+            marker,
+            command: command.to_string(),
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn send(&mut self, script: &str) -> Result<(), String> {
+        self.pty
+            .write(script.as_bytes())
+            .map_err(|e| format!("shell write failed: {e}"))
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+            self.streamed = self.streamed.saturating_sub(excess);
+            self.dropped = true;
+        }
+    }
+
+    /// Stream the lines of `pending` not sent yet, stopping at the job's
+    /// sentinel. Each line's newline waits for the next line, since it may
+    /// turn out to be the one the sentinel starts with.
+    fn stream_lines(&mut self, stream: &OutputStream) {
+        let unsent = &self.pending[self.streamed..];
+        let sentinel = self
+            .job
+            .as_ref()
+            .and_then(|job| find(unsent, format!("\n{}:", job.marker).as_bytes()));
+        let Some(end) = sentinel.or_else(|| unsent.iter().rposition(|&b| b == b'\n')) else {
+            return;
+        };
+        let text = String::from_utf8_lossy(&unsent[..end]).replace("\r\n", "\n");
+        self.streamed += end;
+        let text: String = text
+            .split_inclusive('\n')
+            .filter(|line| !line.starts_with(MARKER_PREFIX))
+            .collect();
+        if !text.is_empty() {
+            stream.send(text);
+        }
+    }
+
+    /// Read until the running job's sentinel arrives or `wait` runs out,
+    /// streaming output to `stream` on the way.
+    ///
+    /// Returns everything read since the last call.
+    async fn collect(&mut self, wait: Duration, stream: Option<&OutputStream>) -> JobOutput {
+        let deadline = Instant::now() + wait;
+        loop {
+            while let Ok(chunk) = self.chunks.try_recv() {
+                self.push(&chunk);
+            }
+            if let Some(stream) = stream {
+                self.stream_lines(stream);
+            }
+            if let Some(code) = self.take_finished() {
+                return self.output(JobStatus::Exited(code));
+            }
+            let Some(job) = self.job.as_ref() else {
+                return self.output(JobStatus::Idle);
+            };
+            let running = JobStatus::Running {
+                command: job.command.clone(),
+                elapsed: job.started.elapsed(),
+            };
+            match tokio::time::timeout_at(deadline, self.chunks.recv()).await {
+                Ok(Some(chunk)) => self.push(&chunk),
+                Ok(None) => {
+                    self.job = None;
+                    return self.output(JobStatus::Closed);
+                }
+                Err(_) => return self.output(running),
+            }
+        }
+    }
+
+    /// If the job's sentinel line has arrived, finish the job: cut the line
+    /// out of `pending`, note the working directory and return the exit code.
+    fn take_finished(&mut self) -> Option<i32> {
+        let job = self.job.as_ref()?;
+        let needle = format!("\n{}:", job.marker);
+        let start = find(&self.pending, needle.as_bytes())?;
+        let code_start = start + needle.len();
+        let code_len = self.pending[code_start..].iter().position(|&b| b == b'\n')?;
+        let line = String::from_utf8_lossy(&self.pending[code_start..code_start + code_len])
+            .trim_end_matches('\r')
+            .to_string();
+        let (code, cwd) = line.split_once(':').unwrap_or((line.as_str(), ""));
+        let code = code.trim().parse().unwrap_or(-1);
+        if !cwd.is_empty() {
+            self.cwd = Some(PathBuf::from(cwd));
+        }
+        self.pending.drain(start..=code_start + code_len);
+        self.job = None;
+        Some(code)
+    }
+
+    /// Drain `pending` into a result.
+    fn output(&mut self, status: JobStatus) -> JobOutput {
+        let raw = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).replace("\r\n", "\n");
+        self.streamed = 0;
+        let mut output: String = raw
+            .split_inclusive('\n')
+            .filter(|line| !line.starts_with(MARKER_PREFIX))
+            .collect();
+        if std::mem::take(&mut self.dropped) {
+            output.insert_str(0, "(earlier output dropped)\n");
+        }
+        JobOutput {
+            output,
+            status,
+            cwd: self.cwd.clone(),
+        }
+    }
+}
+
+/// The line that prints `marker`, the last exit code and the shell's
+/// working directory.
+fn sentinel(marker: &str) -> String {
+    format!("printf '\\n{marker}:%s:%s\\n' \"$?\" \"$PWD\"\n")
+}
+
+/// Byte-string search.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+type SessionKey = (String, String);
+
+/// All open shell sessions, keyed by thread and session name.
+#[derive(Default)]
+pub struct ShellSessions {
+    sessions: Mutex<HashMap<SessionKey, Arc<AsyncMutex<Session>>>>,
+    next_marker: AtomicU64,
+    /// Taken when the first session opens, to start the reaper.
+    events: Mutex<Option<broadcast::Sender<PipelineEvent>>>,
+    sandbox: Option<Arc<Sandbox>>,
+}
+
+impl ShellSessions {
+    /// Open every session's shell inside `sandbox`.
+    pub fn with_sandbox(mut self, sandbox: Arc<Sandbox>) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Close a thread's sessions when the kernel prunes or folds it.
+    ///
+    /// The watcher starts with the first session, so this may be called
+    /// outside a runtime.
+    pub fn set_event_sender(&self, tx: broadcast::Sender<PipelineEvent>) {
+        *self.events.lock().unwrap() = Some(tx);
+    }
+
+    fn start_reaper(self: &Arc<Self>) {
+        let Some(tx) = self.events.lock().unwrap().take() else {
+            return;
+        };
+        let mut rx = tx.subscribe();
+        let sessions: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(PipelineEvent::KernelOp {
+                        op: KernelOpType::ThreadPruned | KernelOpType::ContextFolded,
+                        thread_id,
+                    }) => match sessions.upgrade() {
+                        Some(s) => {
+                            s.close_thread(&thread_id);
+                        }
+                        None => break,
+                    },
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Session `name` of `thread_id`, opened in `dir` if it doesn't exist.
+    fn get_or_open(
+        self: &Arc<Self>,
+        thread_id: &str,
+        name: &str,
+        dir: Option<&Path>,
+    ) -> Result<Arc<AsyncMutex<Session>>, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let key = (thread_id.to_string(), name.to_string());
+        if let Some(session) = sessions.get(&key) {
+            return Ok(session.clone());
+        }
+        let open = sessions.keys().filter(|(t, _)| t == thread_id).count();
+        if open >= MAX_SESSIONS_PER_THREAD {
+            return Err(format!(
+                "too many shell sessions for this thread ({open}); close one first"
+            ));
+        }
+        let session = Arc::new(AsyncMutex::new(Session::open(dir, self.sandbox.as_deref())?));
+        sessions.insert(key, session.clone());
+        drop(sessions);
+        self.start_reaper();
+        Ok(session)
+    }
+
+    fn get(&self, thread_id: &str, name: &str) -> Result<Arc<AsyncMutex<Session>>, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&(thread_id.to_string(), name.to_string()))
+            .cloned()
+            .ok_or_else(|| format!("no shell session named {name}"))
+    }
+
+    /// The working directory of session `name`, if it is open and known.
+    pub async fn cwd(&self, thread_id: &str, name: &str) -> Option<PathBuf> {
+        let session = self.get(thread_id, name).ok()?;
+        let cwd = session.lock().await.cwd.clone();
+        cwd
+    }
+
+    /// Run `command` in session `name`, opening it in `dir` if needed, and
+    /// wait up to `wait` for it to finish. A zero `wait` starts it in the
+    /// background.
+    pub async fn run(
+        self: &Arc<Self>,
+        thread_id: &str,
+        name: &str,
+        command: &str,
+        dir: Option<&Path>,
+        wait: Duration,
+        stream: Option<&OutputStream>,
+    ) -> Result<JobOutput, String> {
+        let session = self.get_or_open(thread_id, name, dir)?;
+        let mut session = session.lock().await;
+        if let Some(job) = session.job.as_ref() {
+            return Err(format!(
+                "session {name} is still running `{}`; poll it, kill it, or use another session",
+                job.command
+            ));
+        }
+        let marker = format!(
+            "{MARKER_PREFIX}{}__",
+            self.next_marker.fetch_add(1, Ordering::Relaxed)
+        );
+        // Output that arrived while idle (say, from a `&` job) isn't this
+        // command's
+        session.pending.clear();
+        session.streamed = 0;
+        session.dropped = false;
+        session.start(marker, command)?;
+        let result = session.collect(wait, stream).await;
+        drop(session);
+        self.forget_if_closed(thread_id, name, &result);
+        Ok(result)
+    }
+
+    /// New output from session `name`, waiting up to `wait` for its job.
+    pub async fn poll(
+        &self,
+        thread_id: &str,
+        name: &str,
+        wait: Duration,
+        stream: Option<&OutputStream>,
+    ) -> Result<JobOutput, String> {
+        let session = self.get(thread_id, name)?;
+        let result = session.lock().await.collect(wait, stream).await;
+        self.forget_if_closed(thread_id, name, &result);
+        Ok(result)
+    }
+
+    /// Interrupt the job in session `name` (Ctrl-C).
+    ///
+    /// If the shell doesn't come back, the whole session is closed.
+    pub async fn kill(&self, thread_id: &str, name: &str) -> Result<JobOutput, String> {
+        let session = self.get(thread_id, name)?;
+        let mut session = session.lock().await;
+        let Some(marker) = session.job.as_ref().map(|j| j.marker.clone()) else {
+            return Ok(session.output(JobStatus::Idle));
+        };
+        // The interrupt flushes unread terminal input, the job's sentinel
+        // included, so ask for it again. If both survive, the spare one is
+        // filtered from later output.
+        session
+            .pty
+            .interrupt()
+            .map_err(|e| format!("shell write failed: {e}"))?;
+        session.send(&sentinel(&marker))?;
+        let mut result = session.collect(KILL_GRACE, None).await;
+        drop(session);
+        if matches!(result.status, JobStatus::Running { .. }) {
+            result.status = JobStatus::Closed;
+        }
+        self.forget_if_closed(thread_id, name, &result);
+        Ok(result)
+    }
+
+    /// Close session `name`, killing anything still running in it.
+    pub fn close(&self, thread_id: &str, name: &str) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .remove(&(thread_id.to_string(), name.to_string()))
+            .is_some()
+    }
+
+    /// Close every session of `thread_id`. Returns how many were open.
+    pub fn close_thread(&self, thread_id: &str) -> usize {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|(t, _), _| t != thread_id);
+        before - sessions.len()
+    }
+
+    /// Names of `thread_id`'s sessions with what each is running, sorted.
+    pub fn list(&self, thread_id: &str) -> Vec<(String, Option<String>)> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut list: Vec<_> = sessions
+            .iter()
+            .filter(|((t, _), _)| t == thread_id)
+            .map(|((_, name), session)| {
+                let running = match session.try_lock() {
+                    Ok(s) => s.job.as_ref().map(|j| j.command.clone()),
+                    Err(_) => Some("(busy)".to_string()),
+                };
+                (name.clone(), running)
+            })
+            .collect();
+        list.sort();
+        list
+    }
+
+    fn forget_if_closed(&self, thread_id: &str, name: &str, result: &JobOutput) {
+        if result.status == JobStatus::Closed {
+            self.close(thread_id, name);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+    use std::process::{Child, Command, Stdio};
+
+    use tokio::sync::mpsc;
+
+    use super::Sandbox;
+
+    /// An `sh` process on its own pseudo-terminal.
+    pub struct Pty {
+        child: Child,
+        master: File,
+    }
+
+    impl Pty {
+        /// Start `sh` in `dir`; everything it prints is sent on `chunks`.
+        pub fn spawn(
+            dir: Option<&Path>,
+            sandbox: Option<&Sandbox>,
+            chunks: mpsc::UnboundedSender<Vec<u8>>,
+        ) -> Result<Self, String> {
+            let (master, slave) = open_pty().map_err(|e| format!("pty open failed: {e}"))?;
+            let stdio = |fd: &OwnedFd| {
+                fd.try_clone()
+                    .map(Stdio::from)
+                    .map_err(|e| format!("pty open failed: {e}"))
+            };
+
+            let mut cmd = Command::new("sh");
+            if let Some(sandbox) = sandbox {
+                sandbox.apply(&mut cmd);
+            }
+            // Empty prompts keep them out of the captured output
+            cmd.env("PS1", "")
+                .env("PS2", "")
+                .env("ENV", "")
+                .env("TERM", "dumb")
+                .stdin(stdio(&slave)?)
+                .stdout(stdio(&slave)?)
+                .stderr(stdio(&slave)?);
+            if let Some(dir) = dir {
+                cmd.current_dir(dir);
+            }
+            // SAFETY: only async-signal-safe calls between fork and exec.
+            unsafe {
+                cmd.pre_exec(|| {
+                    // New session with the PTY as its controlling terminal,
+                    // so Ctrl-C reaches the foreground job
+                    if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            let child = cmd.spawn().map_err(|e| format!("shell spawn failed: {e}"))?;
+            // Only the child may hold the slave, or reads never see EOF
+            drop(cmd);
+            drop(slave);
+
+            let master = File::from(master);
+            let mut reader = master.try_clone().map_err(|e| format!("pty open failed: {e}"))?;
+            std::thread::Builder::new()
+                .name("pty-reader".into())
+                .spawn(move || {
+                    let mut buf = [0u8; 8192];
+                    // EIO once the shell and its children are gone
+                    while let Ok(n) = reader.read(&mut buf) {
+                        if n == 0 || chunks.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .map_err(|e| format!("pty reader failed: {e}"))?;
+
+            Ok(Self { child, master })
+        }
+
+        pub fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+            self.master.write_all(bytes)
+        }
+
+        /// Ctrl-C: SIGINT to the foreground job.
+        pub fn interrupt(&mut self) -> std::io::Result<()> {
+            self.write(&[0x03])
+        }
+    }
+
+    impl Drop for Pty {
+        fn drop(&mut self) {
+            // SAFETY: signalling our own child's process group.
+            unsafe {
+                libc::kill(-(self.child.id() as i32), libc::SIGHUP);
+            }
+            let _ = self.child.kill();
+            if matches!(self.child.try_wait(), Ok(Some(_))) {
+                return;
+            }
+            // Drops happen on the runtime (the reaper), so a shell that is
+            // slow to die is reaped on its own thread, not waited for here
+            let pid = self.child.id() as libc::pid_t;
+            let _ = std::thread::Builder::new()
+                .name("pty-reaper".into())
+                .spawn(move || {
+                    // SAFETY: waiting on our own child; no pointer is passed.
+                    unsafe {
+                        libc::waitpid(pid, std::ptr::null_mut(), 0);
+                    }
+                });
+        }
+    }
+
+    /// A master/slave pair with echo and `\n` → `\r\n` translation off.
+    fn open_pty() -> std::io::Result<(OwnedFd, OwnedFd)> {
+        let (mut master, mut slave) = (0, 0);
+        let mut size = libc::winsize {
+            ws_row: 50,
+            ws_col: 200,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: out-pointers are valid; the fds are owned from here on.
+        let (master, slave) = unsafe {
+            if libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::addr_of_mut!(size),
+            ) < 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave))
+        };
+
+        // SAFETY: plain termios/fcntl calls on fds we own.
+        unsafe {
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(slave.as_raw_fd(), &mut term) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            term.c_lflag &= !libc::ECHO;
+            term.c_oflag &= !libc::ONLCR;
+            if libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &term) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for fd in [&master, &slave] {
+                libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        Ok((master, slave))
+    }
+}
+
+#[cfg(not(unix))]
+mod pty {
+    use std::path::Path;
+
+    use tokio::sync::mpsc;
+
+    use super::Sandbox;
+
+    /// Shell sessions need a Unix pseudo-terminal.
+    pub struct Pty;
+
+    impl Pty {
+        pub fn spawn(
+            _dir: Option<&Path>,
+            _sandbox: Option<&Sandbox>,
+            _chunks: mpsc::UnboundedSender<Vec<u8>>,
+        ) -> Result<Self, String> {
+            Err("shell sessions are only supported on Unix".into())
+        }
+
+        pub fn write(&mut self, _bytes: &[u8]) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        pub fn interrupt(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const WAIT: Duration = Duration::from_secs(10);
+
+    #[tokio::test]
+    async fn state_persists_between_commands() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let shells = Arc::new(ShellSessions::default());
+
+        let r = shells.run("t1", "main", "cd sub && export GREETING=hi", Some(dir.path()), WAIT, None).await.unwrap();
+        assert_eq!(r.status, JobStatus::Exited(0));
+        assert!(r.cwd.as_deref().is_some_and(|d| d.ends_with("sub")));
+        assert_eq!(shells.cwd("t1", "main").await, r.cwd);
+
+        let r = shells.run("t1", "main", "echo $GREETING from $(basename $PWD)", None, WAIT, None).await.unwrap();
+        assert_eq!(r.status, JobStatus::Exited(0));
+        assert_eq!(r.output, "hi from sub\n");
+
+        let r = shells.run("t1", "main", "echo oops >&2; false", None, WAIT, None).await.unwrap();
+        assert_eq!(r.status, JobStatus::Exited(1));
+        assert_eq!(r.output, "oops\n");
+
+        // Other threads get their own shell
+        let r = shells.run("t2", "main", "echo ${GREETING:-unset}", Some(dir.path()), WAIT, None).await.unwrap();
+        assert_eq!(r.output, "unset\n");
+    }
+
+    #[tokio::test]
+    async fn background_job_poll_and_kill() {
+        let shells = Arc::new(ShellSessions::default());
+
+        let started = shells.run("t1", "dev", "echo starting; sleep 30", None, Duration::ZERO, None).await.unwrap();
+        assert!(matches!(started.status, JobStatus::Running { .. }));
+
+        let err = shells.run("t1", "dev", "echo hi", None, WAIT, None).await.unwrap_err();
+        assert!(err.contains("still running `echo starting; sleep 30`"));
+
+        let r = shells.poll("t1", "dev", Duration::from_millis(500), None).await.unwrap();
+        assert!(matches!(r.status, JobStatus::Running { .. }));
+        assert_eq!(started.output + &r.output, "starting\n");
+        assert_eq!(shells.list("t1")[0].0, "dev");
+
+        let r = shells.kill("t1", "dev").await.unwrap();
+        assert_eq!(r.status, JobStatus::Exited(130));
+
+        // The session survives the interrupt
+        let r = shells.run("t1", "dev", "echo again", None, WAIT, None).await.unwrap();
+        assert_eq!(r.output, "again\n");
+    }
+
+    #[tokio::test]
+    async fn pruned_thread_sessions_are_closed() {
+        let shells = Arc::new(ShellSessions::default());
+        let (tx, _keep) = broadcast::channel(16);
+        shells.set_event_sender(tx.clone());
+
+        shells.run("t1", "a", "true", None, WAIT, None).await.unwrap();
+        shells.run("t1", "b", "true", None, WAIT, None).await.unwrap();
+        shells.run("t2", "a", "true", None, WAIT, None).await.unwrap();
+
+        tx.send(PipelineEvent::KernelOp {
+            op: KernelOpType::ThreadPruned,
+            thread_id: "t1".into(),
+        })
+        .unwrap();
+        for _ in 0..50 {
+            if shells.list("t1").is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(shells.list("t1").is_empty());
+        assert_eq!(shells.list("t2").len(), 1);
+
+        // Exiting the shell closes the session too
+        let r = shells.run("t2", "a", "exit 3", None, WAIT, None).await.unwrap();
+        assert_eq!(r.status, JobStatus::Closed);
+        assert!(shells.list("t2").is_empty());
+    }
+}
@@ -0,0 +1,992 @@
+//! Test runner — runs a project's tests and reports only what matters.
+//!
+//! Runs tests with machine-readable output and condenses it into counts
+//! plus, for each failure, the assertion message and its file:line:
+//!
+//! - `cargo`: builds with `cargo test --no-run`, then runs each test binary
+//!   with libtest's JSON output. Doc tests are not run.
+//! - `nextest`: `cargo nextest run` with its libtest-compatible JSON.
+//! - `pytest`: `pytest` with a JUnit XML report.
+//!
+//! The failures of each thread's last run are remembered, so the agent can
+//! rerun just those after a fix.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use regex::Regex;
+use rust_pipeline::prelude::*;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+
+use super::output::{self, OutputStore};
+use super::sandbox::Sandbox;
+use super::workspace::{self, Access, Workspace};
+use super::{extract_tag, ToolPeer, ToolResponse};
+use crate::pipeline::events::{KernelOpType, PipelineEvent};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+/// Failures listed in full; the rest are only counted.
+const MAX_LISTED: usize = 30;
+
+/// Lines of each failure's message kept.
+const MAX_MESSAGE_LINES: usize = 12;
+
+/// Compile errors shown when a build fails.
+const MAX_BUILD_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Runner {
+    Cargo,
+    Nextest,
+    Pytest,
+}
+
+impl Runner {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "cargo" => Ok(Self::Cargo),
+            "nextest" => Ok(Self::Nextest),
+            "pytest" => Ok(Self::Pytest),
+            other => Err(format!(
+                "unknown runner: {other} (expected cargo, nextest or pytest)"
+            )),
+        }
+    }
+
+    /// Pick a runner from the project files in `dir`.
+    fn detect(dir: &Path) -> Result<Self, String> {
+        if dir.join("Cargo.toml").is_file() {
+            return Ok(Self::Cargo);
+        }
+        let python = [
+            "pytest.ini",
+            "pyproject.toml",
+            "setup.cfg",
+            "tox.ini",
+            "conftest.py",
+        ];
+        if python.iter().any(|f| dir.join(f).is_file()) {
+            return Ok(Self::Pytest);
+        }
+        Err(format!(
+            "no Cargo.toml or pytest configuration in {}; name a runner",
+            dir.display()
+        ))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo test",
+            Self::Nextest => "cargo nextest",
+            Self::Pytest => "pytest",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// One test's result.
+#[derive(Debug, Clone, PartialEq)]
+struct TestResult {
+    /// Test binary (`name (kind)`) or pytest node id; how a rerun finds it.
+    group: String,
+    name: String,
+    outcome: Outcome,
+    message: Option<String>,
+    location: Option<String>,
+}
+
+/// What a run is asked to do.
+#[derive(Debug, Clone)]
+struct RunSpec {
+    runner: Runner,
+    dir: PathBuf,
+    package: Option<String>,
+    filter: Option<String>,
+    /// pytest: the file or directory to test.
+    target: Option<String>,
+    /// Rerun exactly these (group, name) pairs.
+    only: Option<Vec<(String, String)>>,
+}
+
+/// A thread's last run, for `failed` reruns.
+#[derive(Debug, Clone)]
+struct LastRun {
+    spec: RunSpec,
+    failed: Vec<(String, String)>,
+}
+
+/// A cargo test binary from `cargo test --no-run`.
+#[derive(Debug, Clone, PartialEq)]
+struct TestBinary {
+    group: String,
+    executable: PathBuf,
+    manifest_dir: PathBuf,
+}
+
+/// Run tests and summarize the results.
+#[derive(Default)]
+pub struct TestRunTool {
+    workspace: Option<Arc<Workspace>>,
+    sandbox: Option<Arc<Sandbox>>,
+    output_store: Option<Arc<OutputStore>>,
+    last_runs: Arc<Mutex<HashMap<String, LastRun>>>,
+    /// Taken on the first run, to forget pruned threads' failures.
+    events: Mutex<Option<broadcast::Sender<PipelineEvent>>>,
+}
+
+impl TestRunTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run test processes inside `sandbox`, like command-exec.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(Arc::new(sandbox));
+        self
+    }
+
+    fn root(&self) -> PathBuf {
+        match &self.workspace {
+            Some(ws) => ws.root().to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    }
+
+    /// Work out the run from a request, or from the thread's last run.
+    fn plan(&self, xml: &str, thread_id: &str) -> Result<RunSpec, String> {
+        let rerun = extract_tag(xml, "failed").is_some_and(|s| s.trim() == "true");
+        if rerun {
+            let last = self.last_runs.lock().unwrap().get(thread_id).cloned();
+            return match last {
+                Some(last) if !last.failed.is_empty() => {
+                    for (_, name) in &last.failed {
+                        option_safe(name, "test name")?;
+                    }
+                    Ok(RunSpec {
+                        only: Some(last.failed),
+                        ..last.spec
+                    })
+                }
+                _ => Err("no failed tests recorded for this thread".into()),
+            };
+        }
+
+        let text = |tag: &str| extract_tag(xml, tag).filter(|s| !s.trim().is_empty());
+        let path = match text("path") {
+            Some(p) => Some(workspace::resolve_in(
+                self.workspace.as_deref(),
+                "test-run",
+                p.trim(),
+                Access::Read,
+            )?),
+            None => None,
+        };
+        let root = self.root();
+        let dir = match &path {
+            Some(p) if p.is_dir() => p.clone(),
+            _ => root.clone(),
+        };
+        let runner = match text("runner") {
+            Some(name) => Runner::parse(name.trim())?,
+            None => Runner::detect(&dir)?,
+        };
+        let (dir, target) = match runner {
+            // pytest runs from the root and is pointed at the path
+            Runner::Pytest => (root, path.map(|p| p.to_string_lossy().into_owned())),
+            Runner::Cargo | Runner::Nextest => (dir, None),
+        };
+        Ok(RunSpec {
+            runner,
+            dir,
+            package: text("package")
+                .map(|p| option_safe(&p, "package"))
+                .transpose()?,
+            filter: text("filter")
+                .map(|f| option_safe(&f, "filter"))
+                .transpose()?,
+            target,
+            only: None,
+        })
+    }
+
+    /// A command for `program` in `dir`, inside the sandbox if there is one.
+    fn command(&self, program: &str, dir: &Path) -> Command {
+        let mut cmd = Command::new(program);
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.apply(cmd.as_std_mut());
+        }
+        cmd.current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        cmd
+    }
+
+    async fn output(mut cmd: Command, program: &str) -> Result<std::process::Output, String> {
+        cmd.output()
+            .await
+            .map_err(|e| format!("failed to run {program}: {e}"))
+    }
+
+    async fn run(&self, spec: &RunSpec) -> Result<Vec<TestResult>, String> {
+        match spec.runner {
+            Runner::Cargo => self.run_cargo(spec).await,
+            Runner::Nextest => self.run_nextest(spec).await,
+            Runner::Pytest => self.run_pytest(spec).await,
+        }
+    }
+
+    async fn run_cargo(&self, spec: &RunSpec) -> Result<Vec<TestResult>, String> {
+        let mut cmd = self.command("cargo", &spec.dir);
+        cmd.args(["test", "--no-run", "--message-format=json"]);
+        if let Some(package) = &spec.package {
+            cmd.args(["-p", package]);
+        }
+        let build = Self::output(cmd, "cargo").await?;
+        let binaries = parse_cargo_build(
+            &String::from_utf8_lossy(&build.stdout),
+            &String::from_utf8_lossy(&build.stderr),
+        )?;
+
+        let mut results = Vec::new();
+        for binary in binaries {
+            let mut args = vec![
+                "--format".to_string(),
+                "json".into(),
+                "-Z".into(),
+                "unstable-options".into(),
+            ];
+            match &spec.only {
+                Some(only) => {
+                    let names: Vec<String> = only
+                        .iter()
+                        .filter(|(group, _)| *group == binary.group)
+                        .map(|(_, name)| name.clone())
+                        .collect();
+                    if names.is_empty() {
+                        continue;
+                    }
+                    args.extend(names);
+                    args.push("--exact".into());
+                }
+                None => args.extend(spec.filter.clone()),
+            }
+            let mut cmd = self.command(&binary.executable.to_string_lossy(), &binary.manifest_dir);
+            // libtest's JSON output is unstable; allow it in the test
+            // binary only, so the build isn't fingerprinted differently
+            cmd.args(&args)
+                .env("RUSTC_BOOTSTRAP", "1")
+                .env("CARGO_MANIFEST_DIR", &binary.manifest_dir);
+            let out = Self::output(cmd, &binary.group).await?;
+            let before = results.len();
+            parse_libtest(
+                &String::from_utf8_lossy(&out.stdout),
+                &binary.group,
+                &mut results,
+            );
+            if results.len() == before && !out.status.success() {
+                return Err(format!(
+                    "{} failed without reporting tests:\n{}",
+                    binary.group,
+                    tail(&String::from_utf8_lossy(&out.stderr), 20)
+                ));
+            }
+        }
+        Ok(results)
+    }
+
+    async fn run_nextest(&self, spec: &RunSpec) -> Result<Vec<TestResult>, String> {
+        let mut cmd = self.command("cargo", &spec.dir);
+        cmd.args([
+            "nextest",
+            "run",
+            "--no-fail-fast",
+            "--message-format",
+            "libtest-json",
+        ])
+        .env("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1");
+        if let Some(package) = &spec.package {
+            cmd.args(["-p", package]);
+        }
+        match &spec.only {
+            Some(only) => {
+                let expr: Vec<String> = only
+                    .iter()
+                    .map(|(_, name)| format!("test(={name})"))
+                    .collect();
+                cmd.args(["-E", &expr.join(" | ")]);
+            }
+            None => {
+                cmd.args(spec.filter.iter());
+            }
+        }
+        let out = Self::output(cmd, "cargo nextest").await?;
+        let mut results = Vec::new();
+        parse_libtest(&String::from_utf8_lossy(&out.stdout), "", &mut results);
+        if results.is_empty() && !out.status.success() {
+            return Err(format!(
+                "cargo nextest failed:\n{}",
+                tail(&String::from_utf8_lossy(&out.stderr), 30)
+            ));
+        }
+        Ok(results)
+    }
+
+    async fn run_pytest(&self, spec: &RunSpec) -> Result<Vec<TestResult>, String> {
+        let report = tempfile::NamedTempFile::new().map_err(|e| format!("pytest: {e}"))?;
+        let mut cmd = self.command("pytest", &spec.dir);
+        cmd.arg("-q")
+            .arg(format!("--junitxml={}", report.path().display()))
+            // xunit1 records each test's file and line
+            .args(["-o", "junit_family=xunit1"]);
+        match &spec.only {
+            Some(only) => {
+                cmd.args(only.iter().map(|(_, id)| id));
+            }
+            None => {
+                if let Some(filter) = &spec.filter {
+                    cmd.args(["-k", filter]);
+                }
+                cmd.args(spec.target.iter());
+            }
+        }
+        let out = Self::output(cmd, "pytest").await?;
+        let xml = std::fs::read_to_string(report.path()).unwrap_or_default();
+        if xml.trim().is_empty() {
+            return Err(format!(
+                "pytest produced no report:\n{}",
+                tail(&String::from_utf8_lossy(&out.stdout), 30)
+            ));
+        }
+        Ok(parse_junit(&xml))
+    }
+
+    /// Remember `results`' failures as the thread's last run.
+    fn record(&self, thread_id: &str, spec: RunSpec, results: &[TestResult]) {
+        self.start_reaper();
+        let failed = results
+            .iter()
+            .filter(|r| r.outcome == Outcome::Failed)
+            .map(|r| (r.group.clone(), r.name.clone()))
+            .collect();
+        let spec = RunSpec { only: None, ..spec };
+        self.last_runs
+            .lock()
+            .unwrap()
+            .insert(thread_id.to_string(), LastRun { spec, failed });
+    }
+
+    /// Forget a thread's failures when the kernel prunes it.
+    fn start_reaper(&self) {
+        let Some(tx) = self.events.lock().unwrap().take() else {
+            return;
+        };
+        let mut rx = tx.subscribe();
+        let last_runs: Weak<Mutex<HashMap<String, LastRun>>> = Arc::downgrade(&self.last_runs);
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(PipelineEvent::KernelOp {
+                        op: KernelOpType::ThreadPruned,
+                        thread_id,
+                    }) => match last_runs.upgrade() {
+                        Some(runs) => {
+                            runs.lock().unwrap().remove(&thread_id);
+                        }
+                        None => break,
+                    },
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+/// `value` if the runner can't mistake it for an option.
+fn option_safe(value: &str, what: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.starts_with('-') {
+        return Err(format!("invalid {what}: {value}"));
+    }
+    Ok(value.to_string())
+}
+
+/// Test binaries from `cargo test --no-run --message-format=json`, or the
+/// compile errors if the build failed.
+fn parse_cargo_build(stdout: &str, stderr: &str) -> Result<Vec<TestBinary>, String> {
+    let mut binaries = Vec::new();
+    let mut errors = Vec::new();
+    let mut success = false;
+    for line in stdout.lines() {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        match msg["reason"].as_str() {
+            Some("compiler-artifact") if msg["profile"]["test"] == true => {
+                let Some(executable) = msg["executable"].as_str() else {
+                    continue;
+                };
+                let name = msg["target"]["name"].as_str().unwrap_or_default();
+                let kind = msg["target"]["kind"][0].as_str().unwrap_or_default();
+                let manifest_dir = Path::new(msg["manifest_path"].as_str().unwrap_or_default())
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default();
+                binaries.push(TestBinary {
+                    group: format!("{name} ({kind})"),
+                    executable: executable.into(),
+                    manifest_dir,
+                });
+            }
+            Some("compiler-message") if msg["message"]["level"] == "error" => {
+                if let Some(rendered) = msg["message"]["rendered"].as_str() {
+                    errors.push(rendered.trim_end().to_string());
+                }
+            }
+            Some("build-finished") => success = msg["success"] == true,
+            _ => {}
+        }
+    }
+    if success {
+        return Ok(binaries);
+    }
+    if errors.is_empty() {
+        return Err(format!("cargo test build failed:\n{}", tail(stderr, 30)));
+    }
+    let shown = errors.len().min(MAX_BUILD_ERRORS);
+    let mut message = format!("cargo test build failed with {} errors:\n\n", errors.len());
+    message.push_str(&errors[..shown].join("\n\n"));
+    if errors.len() > shown {
+        message.push_str(&format!("\n\n... and {} more", errors.len() - shown));
+    }
+    Err(message)
+}
+
+/// Collect test events from libtest JSON lines. nextest names tests
+/// `binary$test`; its binary becomes the group.
+fn parse_libtest(out: &str, group: &str, results: &mut Vec<TestResult>) {
+    for line in out.lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if event["type"] != "test" {
+            continue;
+        }
+        let outcome = match event["event"].as_str() {
+            Some("ok") => Outcome::Passed,
+            Some("failed") => Outcome::Failed,
+            Some("ignored") => Outcome::Ignored,
+            _ => continue,
+        };
+        let full = event["name"].as_str().unwrap_or_default();
+        let (group, name) = match full.split_once('$') {
+            Some((binary, name)) => (binary, name),
+            None => (group, full),
+        };
+        let (message, location) = match outcome {
+            Outcome::Failed => parse_panic(event["stdout"].as_str().unwrap_or_default()),
+            _ => (None, None),
+        };
+        results.push(TestResult {
+            group: group.to_string(),
+            name: name.to_string(),
+            outcome,
+            message,
+            location,
+        });
+    }
+}
+
+/// The panic message and location in a failed Rust test's output.
+fn parse_panic(output: &str) -> (Option<String>, Option<String>) {
+    let lines: Vec<&str> = output.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(at) = line.find("panicked at ") else {
+            continue;
+        };
+        let rest = &line[at + "panicked at ".len()..];
+        // Rust 1.73+: `panicked at src/lib.rs:3:5:` with the message below
+        if let Some(location) = rest.strip_suffix(':') {
+            let message: Vec<&str> = lines[i + 1..]
+                .iter()
+                .take_while(|l| !l.starts_with("note: ") && !l.starts_with("stack backtrace:"))
+                .copied()
+                .collect();
+            return (Some(message.join("\n")), Some(location.to_string()));
+        }
+        // Older: `panicked at 'message', src/lib.rs:3:5`
+        if let Some((message, location)) = rest.rsplit_once("', ") {
+            return (
+                Some(message.trim_start_matches('\'').to_string()),
+                Some(location.to_string()),
+            );
+        }
+    }
+    let output = output.trim();
+    (
+        (!output.is_empty()).then(|| tail(output, MAX_MESSAGE_LINES)),
+        None,
+    )
+}
+
+/// Test cases from a pytest JUnit report (xunit1). Each test's group is
+/// its pytest node id, so a rerun can name it.
+fn parse_junit(xml: &str) -> Vec<TestResult> {
+    let attr = Regex::new(r#"([\w-]+)="([^"]*)""#).unwrap();
+    let location = Regex::new(r"(?m)^(\S+\.py):(\d+): ").unwrap();
+    let mut results = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<testcase") {
+        rest = &rest[start..];
+        let Some(open_end) = rest.find('>') else {
+            break;
+        };
+        let open = &rest[..open_end];
+        let attrs: HashMap<&str, String> = attr
+            .captures_iter(open)
+            .map(|c| (c.get(1).unwrap().as_str(), junit_unescape(&c[2])))
+            .collect();
+        let body = if open.ends_with('/') {
+            ""
+        } else {
+            let close = rest.find("</testcase>").unwrap_or(rest.len());
+            &rest[open_end + 1..close]
+        };
+        rest = &rest[open_end + 1..];
+
+        let get = |key: &str| attrs.get(key).cloned().unwrap_or_default();
+        let (file, class, name) = (get("file"), get("classname"), get("name"));
+        // tests.test_x.TestY → TestY, given tests/test_x.py
+        let module = file.trim_end_matches(".py").replace(['/', '\\'], ".");
+        let class = class
+            .strip_prefix(&module)
+            .map(|c| c.trim_start_matches('.').to_string())
+            .unwrap_or_default();
+        let node = [file.as_str(), class.as_str(), name.as_str()]
+            .iter()
+            .filter(|p| !p.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("::");
+
+        let failure = ["<failure", "<error"]
+            .iter()
+            .find_map(|tag| body.find(tag).map(|at| &body[at..]));
+        let outcome = if failure.is_some() {
+            Outcome::Failed
+        } else if body.contains("<skipped") {
+            Outcome::Ignored
+        } else {
+            Outcome::Passed
+        };
+        let (message, location) = match failure {
+            Some(element) => {
+                let open = &element[..element.find('>').unwrap_or(element.len())];
+                let message = attr
+                    .captures_iter(open)
+                    .find(|c| &c[1] == "message")
+                    .map(|c| junit_unescape(&c[2]));
+                let text = junit_unescape(element);
+                let location = location
+                    .captures_iter(&text)
+                    .last()
+                    .map(|c| format!("{}:{}", &c[1], &c[2]))
+                    .or_else(|| attrs.get("line").map(|l| format!("{file}:{l}")));
+                (message, location)
+            }
+            None => (None, None),
+        };
+        results.push(TestResult {
+            group: node.clone(),
+            name: node,
+            outcome,
+            message,
+            location,
+        });
+    }
+    results
+}
+
+/// Unescape JUnit attribute and text entities, including numeric ones.
+fn junit_unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|&i| i <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The last `n` lines of `s`.
+fn tail(s: &str, n: usize) -> String {
+    let lines: Vec<&str> = s.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
+
+/// Counts, then each failure with its message and location.
+fn summarize(runner: Runner, results: &[TestResult], elapsed: Duration) -> String {
+    let count = |outcome| results.iter().filter(|r| r.outcome == outcome).count();
+    let failed = count(Outcome::Failed);
+    let mut out = format!(
+        "{}: {} passed, {failed} failed, {} ignored ({:.1}s)",
+        runner.name(),
+        count(Outcome::Passed),
+        count(Outcome::Ignored),
+        elapsed.as_secs_f64()
+    );
+    if results.is_empty() {
+        out.push_str("\nno tests matched");
+    }
+    let failures = results.iter().filter(|r| r.outcome == Outcome::Failed);
+    for result in failures.take(MAX_LISTED) {
+        out.push_str("\n\nFAILED ");
+        out.push_str(&result.name);
+        if !result.group.is_empty() && result.group != result.name {
+            out.push_str(&format!(" [{}]", result.group));
+        }
+        if let Some(location) = &result.location {
+            out.push_str(&format!(" at {location}"));
+        }
+        if let Some(message) = &result.message {
+            for line in message.lines().take(MAX_MESSAGE_LINES) {
+                out.push_str("\n  ");
+                out.push_str(line);
+            }
+        }
+    }
+    if failed > MAX_LISTED {
+        out.push_str(&format!(
+            "\n\n... and {} more failures",
+            failed - MAX_LISTED
+        ));
+    }
+    if failed > 0 {
+        out.push_str("\n\nrerun only these with failed: true");
+    }
+    out
+}
+
+#[async_trait]
+impl Handler for TestRunTool {
+    async fn handle(&self, payload: ValidatedPayload, ctx: HandlerContext) -> HandlerResult {
+        let xml_str = String::from_utf8_lossy(&payload.xml);
+        let timeout_secs = extract_tag(&xml_str, "timeout")
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        let result = match self.plan(&xml_str, &ctx.thread_id) {
+            Ok(spec) => {
+                let started = Instant::now();
+                match tokio::time::timeout(Duration::from_secs(timeout_secs), self.run(&spec)).await
+                {
+                    Ok(Ok(results)) => {
+                        let summary = summarize(spec.runner, &results, started.elapsed());
+                        self.record(&ctx.thread_id, spec, &results);
+                        Ok(summary)
+                    }
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err(format!(
+                        "{} timed out after {timeout_secs}s",
+                        spec.runner.name()
+                    )),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        let payload_xml = match result {
+            Ok(text) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, text).await;
+                ToolResponse::ok(&text)
+            }
+            Err(e) => {
+                let text = output::shape(self.output_store.as_deref(), &ctx.thread_id, e).await;
+                ToolResponse::err(&text)
+            }
+        };
+        Ok(HandlerResponse::Reply { payload_xml })
+    }
+}
+
+#[async_trait]
+impl ToolPeer for TestRunTool {
+    fn name(&self) -> &str {
+        "test-run"
+    }
+
+    fn set_workspace(&mut self, workspace: Arc<Workspace>) {
+        self.workspace = Some(workspace);
+    }
+
+    fn set_event_sender(&mut self, tx: broadcast::Sender<PipelineEvent>) {
+        *self.events.lock().unwrap() = Some(tx);
+    }
+
+    fn set_output_store(&mut self, store: Arc<OutputStore>) {
+        self.output_store = Some(store);
+    }
+
+    fn wit(&self) -> &str {
+        r#"
+/// Run tests and get a compact summary: counts, then each failure with its assertion message and file:line. Runs cargo test (doc tests excluded), cargo nextest or pytest, picked from the project unless runner is given. Set failed to rerun only the tests that failed in this thread's last run.
+interface test-run {
+    record request {
+        /// cargo, nextest or pytest (default: detected from the project)
+        runner: option<string>,
+        /// Only tests whose names contain this (pytest: a -k expression)
+        filter: option<string>,
+        /// Package directory (cargo, nextest) or test file or directory (pytest)
+        path: option<string>,
+        /// cargo, nextest: the package to test
+        package: option<string>,
+        /// Rerun only the tests that failed in this thread's last run
+        failed: option<bool>,
+        /// Timeout in seconds (default: 600)
+        timeout: option<u32>,
+    }
+    run: func(req: request) -> result<string, string>;
+}
+"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_ctx() -> HandlerContext {
+        HandlerContext {
+            thread_id: "t1".into(),
+            from: "agent".into(),
+            own_name: "test-run".into(),
+        }
+    }
+
+    fn make_payload(xml: &str) -> ValidatedPayload {
+        ValidatedPayload {
+            xml: xml.as_bytes().to_vec(),
+            tag: "TestRunRequest".into(),
+        }
+    }
+
+    fn get_result(resp: HandlerResponse) -> (bool, String) {
+        match resp {
+            HandlerResponse::Reply { payload_xml } => {
+                let xml = String::from_utf8(payload_xml).unwrap();
+                let success = xml.contains("<success>true</success>");
+                let content = if success {
+                    extract_tag(&xml, "result").unwrap_or_default()
+                } else {
+                    extract_tag(&xml, "error").unwrap_or_default()
+                };
+                (success, content)
+            }
+            _ => panic!("expected Reply"),
+        }
+    }
+
+    #[test]
+    fn libtest_json_failures_have_message_and_location() {
+        let out = r#"{ "type": "suite", "event": "started", "test_count": 3 }
+{ "type": "test", "event": "started", "name": "tests::good" }
+{ "type": "test", "name": "tests::good", "event": "ok" }
+{ "type": "test", "name": "tests::slow", "event": "ignored" }
+{ "type": "test", "name": "tests::bad", "event": "failed", "stdout": "\nthread 'tests::bad' panicked at src/lib.rs:12:9:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 1 }"#;
+        let mut results = Vec::new();
+        parse_libtest(out, "demo (lib)", &mut results);
+        assert_eq!(results.len(), 3);
+        let bad = &results[2];
+        assert_eq!(bad.outcome, Outcome::Failed);
+        assert_eq!(bad.group, "demo (lib)");
+        assert_eq!(bad.location.as_deref(), Some("src/lib.rs:12:9"));
+        assert_eq!(
+            bad.message.as_deref(),
+            Some("assertion `left == right` failed\n  left: 1\n right: 2")
+        );
+
+        // nextest prefixes the binary
+        let mut results = Vec::new();
+        parse_libtest(
+            r#"{"type":"test","event":"ok","name":"demo::integration$it_works"}"#,
+            "",
+            &mut results,
+        );
+        assert_eq!(
+            (results[0].group.as_str(), results[0].name.as_str()),
+            ("demo::integration", "it_works")
+        );
+
+        let summary = summarize(
+            Runner::Cargo,
+            std::slice::from_ref(bad),
+            Duration::from_millis(1500),
+        );
+        assert!(summary.starts_with("cargo test: 0 passed, 1 failed, 0 ignored (1.5s)"));
+        assert!(summary.contains("FAILED tests::bad [demo (lib)] at src/lib.rs:12:9\n  assertion"));
+    }
+
+    #[test]
+    fn junit_report_gives_node_ids() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?><testsuites><testsuite name="pytest" errors="0" failures="1" skipped="1" tests="3">
+<testcase classname="tests.test_math" file="tests/test_math.py" line="3" name="test_add" time="0.001" />
+<testcase classname="tests.test_math.TestDiv" file="tests/test_math.py" line="10" name="test_zero" time="0.002"><failure message="assert 1 == 2&#10; +  where 1 = div(2, 2)">def test_zero(self):
+&gt;       assert div(2, 2) == 2
+E       assert 1 == 2
+
+tests/test_math.py:12: AssertionError</failure></testcase>
+<testcase classname="tests.test_math" file="tests/test_math.py" line="20" name="test_skip" time="0.000"><skipped type="pytest.skip" message="later">tests/test_math.py:21: later</skipped></testcase>
+</testsuite></testsuites>"#;
+        let results = parse_junit(xml);
+        let outcomes: Vec<Outcome> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::Passed, Outcome::Failed, Outcome::Ignored]
+        );
+        assert_eq!(results[0].name, "tests/test_math.py::test_add");
+        let failed = &results[1];
+        assert_eq!(failed.name, "tests/test_math.py::TestDiv::test_zero");
+        assert_eq!(failed.location.as_deref(), Some("tests/test_math.py:12"));
+        assert_eq!(
+            failed.message.as_deref(),
+            Some("assert 1 == 2\n +  where 1 = div(2, 2)")
+        );
+    }
+
+    #[tokio::test]
+    async fn cargo_run_and_rerun_failed() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let lib = |expected: u32| {
+            format!(
+                "#[cfg(test)]\nmod tests {{\n    #[test]\n    fn passes() {{}}\n\n    #[test]\n    fn compares() {{\n        assert_eq!(1 + 1, {expected});\n    }}\n}}\n"
+            )
+        };
+        std::fs::write(dir.path().join("src/lib.rs"), lib(3)).unwrap();
+        let mut tool = TestRunTool::new();
+        tool.set_workspace(Arc::new(Workspace::new("coding", dir.path(), &[]).unwrap()));
+
+        let xml = "<TestRunRequest></TestRunRequest>";
+        let (ok, summary) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{summary}");
+        assert!(
+            summary.starts_with("cargo test: 1 passed, 1 failed, 0 ignored"),
+            "{summary}"
+        );
+        assert!(
+            summary.contains("FAILED tests::compares [demo (lib)] at src/lib.rs:8:9"),
+            "{summary}"
+        );
+        assert!(summary.contains("left: 2"), "{summary}");
+
+        std::fs::write(dir.path().join("src/lib.rs"), lib(2)).unwrap();
+        let xml = "<TestRunRequest><failed>true</failed></TestRunRequest>";
+        let (ok, summary) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(ok, "{summary}");
+        assert!(
+            summary.starts_with("cargo test: 1 passed, 0 failed"),
+            "{summary}"
+        );
+
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(err.contains("no failed tests"));
+
+        std::fs::write(dir.path().join("src/lib.rs"), "fn broken( {}\n").unwrap();
+        let xml = "<TestRunRequest></TestRunRequest>";
+        let (ok, err) = get_result(tool.handle(make_payload(xml), make_ctx()).await.unwrap());
+        assert!(!ok);
+        assert!(err.contains("build failed with"), "{err}");
+    }
+
+    #[test]
+    fn option_like_values_are_refused() {
+        let tool = TestRunTool::new();
+        let plan = |xml: &str| tool.plan(xml, "t1").err().unwrap_or_default();
+        assert_eq!(
+            plan("<runner>cargo</runner><filter>--include-ignored</filter>"),
+            "invalid filter: --include-ignored"
+        );
+        assert_eq!(
+            plan("<runner>pytest</runner><filter>-p evil</filter>"),
+            "invalid filter: -p evil"
+        );
+        assert_eq!(
+            plan("<runner>cargo</runner><package>--config=x</package>"),
+            "invalid package: --config=x"
+        );
+
+        let spec = RunSpec {
+            runner: Runner::Pytest,
+            dir: PathBuf::from("."),
+            package: None,
+            filter: None,
+            target: None,
+            only: None,
+        };
+        let failed = vec![("".to_string(), "--pdb".to_string())];
+        tool.last_runs
+            .lock()
+            .unwrap()
+            .insert("t1".into(), LastRun { spec, failed });
+        assert_eq!(plan("<failed>true</failed>"), "invalid test name: --pdb");
+    }
+
+    #[test]
+    fn test_run_metadata() {
+        let tool = TestRunTool::new();
+        assert_eq!(tool.name(), "test-run");
+        let iface = crate::wit::parser::parse_wit(tool.wit()).unwrap();
+        assert_eq!(iface.request_tag(), "TestRunRequest");
+    }
+}
@@ -0,0 +1,94 @@
+//! File version tokens — optimistic concurrency for the file tools.
+//!
+//! `file-read`, `file-write` and `file-edit` end their output with
+//! `[version: <hash> mtime: <secs>]`. The hash is the first 16 hex digits of
+//! the content's SHA-256; passing it back as `expected_hash` makes a write
+//! fail with a conflict if the file changed in the meantime (say, a human
+//! saved it from their editor). The mtime is informational.
+
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Hex digits of SHA-256 kept in a version token.
+const VERSION_LEN: usize = 16;
+
+/// Version token for file content.
+pub fn content_version(data: &[u8]) -> String {
+    let mut hash = format!("{:x}", Sha256::digest(data));
+    hash.truncate(VERSION_LEN);
+    hash
+}
+
+/// The `[version: … mtime: …]` footer for a file's current content.
+pub fn footer(path: &Path, data: &[u8]) -> String {
+    let mtime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    format!("[version: {} mtime: {mtime}]", content_version(data))
+}
+
+/// Extract the version token from tool output, if it carries one.
+pub fn parse(output: &str) -> Option<&str> {
+    let start = output.rfind("[version: ")? + "[version: ".len();
+    let token = output[start..].split([' ', ']']).next()?;
+    (!token.is_empty()).then_some(token)
+}
+
+/// Reject a write whose `expected` version no longer matches the file.
+///
+/// `display` is the path as the caller gave it, for the error message.
+pub fn check_expected(path: &Path, display: &str, expected: &str) -> Result<(), String> {
+    let current = match std::fs::read(path) {
+        Ok(data) => content_version(&data),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "conflict: {display} was deleted since version {expected} was read"
+            ));
+        }
+        Err(e) => return Err(format!("read error: {e}")),
+    };
+    if current != expected.trim() {
+        return Err(format!(
+            "conflict: {display} changed since it was read (expected version {expected}, \
+             found {current}); re-read the file and retry"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn footer_round_trips_through_parse() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "hello").unwrap();
+
+        let output = format!("1| hello\n{}", footer(&path, b"hello"));
+        assert_eq!(parse(&output), Some(content_version(b"hello").as_str()));
+        assert_eq!(content_version(b"hello").len(), 16);
+        assert_eq!(parse("no token here"), None);
+    }
+
+    #[test]
+    fn check_expected_detects_change() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "v1").unwrap();
+        let seen = content_version(b"v1");
+        assert!(check_expected(&path, "a.txt", &seen).is_ok());
+
+        std::fs::write(&path, "v2 from the IDE").unwrap();
+        let err = check_expected(&path, "a.txt", &seen).unwrap_err();
+        assert!(err.starts_with("conflict: a.txt changed"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(check_expected(&path, "a.txt", &seen).unwrap_err().contains("deleted"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Workspace jail — confines the native tools to a profile's workspace.
+//!
+//! Every path a tool touches is resolved against the workspace root,
+//! canonicalized (symlinks followed) and checked for containment before the
+//! tool acts on it. Writes must land under the root; reads may also use the
+//! profile's extra read-only roots. Nothing inside a `.git` directory is
+//! writable, so a tool can't plant hooks or config that git would run.
+//! Anything else is refused and reported as a `SecurityBlocked` event.
+
+use std::path::{Path, PathBuf};
+
+use tokio::sync::broadcast;
+
+use crate::organism::profile::WorkspaceSpec;
+use crate::pipeline::events::PipelineEvent;
+
+/// What a tool intends to do with a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Read only — the workspace root or any read-only root.
+    Read,
+    /// Create or modify — the workspace root only, outside `.git`.
+    Write,
+}
+
+/// A profile's workspace: the root tools may write under, plus read-only roots.
+#[derive(Debug)]
+pub struct Workspace {
+    profile: String,
+    root: PathBuf,
+    read_only: Vec<PathBuf>,
+    event_tx: Option<broadcast::Sender<PipelineEvent>>,
+}
+
+impl Workspace {
+    /// Create a workspace. All roots must exist; they are canonicalized here.
+    pub fn new(profile: &str, root: &Path, read_only: &[PathBuf]) -> Result<Self, String> {
+        let canonical_dir = |p: &Path| -> Result<PathBuf, String> {
+            let c = p
+                .canonicalize()
+                .map_err(|e| format!("workspace root {}: {e}", p.display()))?;
+            if !c.is_dir() {
+                return Err(format!("workspace root {} is not a directory", p.display()));
+            }
+            Ok(c)
+        };
+        Ok(Self {
+            profile: profile.to_string(),
+            root: canonical_dir(root)?,
+            read_only: read_only
+                .iter()
+                .map(|p| canonical_dir(p))
+                .collect::<Result<_, _>>()?,
+            event_tx: None,
+        })
+    }
+
+    /// Create a workspace from a profile's `workspace:` block.
+    pub fn from_spec(profile: &str, spec: &WorkspaceSpec) -> Result<Self, String> {
+        Self::new(profile, &spec.root, &spec.read_only)
+    }
+
+    /// Report violations on the pipeline event channel (builder-style).
+    pub fn with_event_sender(mut self, tx: broadcast::Sender<PipelineEvent>) -> Self {
+        self.event_tx = Some(tx);
+        self
+    }
+
+    /// The canonical workspace root.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a tool-supplied path for the given access.
+    ///
+    /// Relative paths are taken from the workspace root. Paths that don't
+    /// exist yet (new files) are resolved through their nearest existing
+    /// ancestor, so a symlinked parent can't smuggle a write outside.
+    pub fn resolve(&self, tool: &str, path: &str, access: Access) -> Result<PathBuf, String> {
+        self.locate(path, access).ok_or_else(|| {
+            self.report(tool, path);
+            format!("path outside workspace: {path}")
+        })
+    }
+
+    /// Resolve a path like [`resolve`](Self::resolve), without reporting.
+    ///
+    /// For components that only need to know where a tool call will land
+    /// (e.g. checkpointing before a write); the tool itself reports.
+    pub fn locate(&self, path: &str, access: Access) -> Option<PathBuf> {
+        canonicalize_lenient(&self.root.join(path)).filter(|p| self.allows(p, access))
+    }
+
+    /// Whether an already-resolved path (e.g. a directory-walk entry) is inside.
+    ///
+    /// Used to skip symlinks that point out of the workspace; does not report.
+    pub fn contains(&self, path: &Path, access: Access) -> bool {
+        path.canonicalize()
+            .map(|p| self.allows(&p, access))
+            .unwrap_or(false)
+    }
+
+    fn allows(&self, canonical: &Path, access: Access) -> bool {
+        match access {
+            Access::Read => {
+                canonical.starts_with(&self.root)
+                    || self.read_only.iter().any(|r| canonical.starts_with(r))
+            }
+            Access::Write => canonical
+                .strip_prefix(&self.root)
+                .is_ok_and(|rel| !rel.components().any(|c| c.as_os_str() == ".git")),
+        }
+    }
+
+    fn report(&self, tool: &str, path: &str) {
+        tracing::warn!("workspace jail ({}): {tool} refused {path}", self.profile);
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(PipelineEvent::SecurityBlocked {
+                profile: self.profile.clone(),
+                target: format!("{tool}:{path}"),
+            });
+        }
+    }
+}
+
+/// Resolve a path through an optional workspace.
+///
+/// Unjailed tools (no workspace configured) get the path back unchanged.
+pub fn resolve_in(
+    workspace: Option<&Workspace>,
+    tool: &str,
+    path: &str,
+    access: Access,
+) -> Result<PathBuf, String> {
+    match workspace {
+        Some(ws) => ws.resolve(tool, path, access),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// Canonicalize a path that may not exist yet.
+///
+/// The longest existing prefix is canonicalized and the remaining
+/// components appended. Returns None when a missing component is followed
+/// by `..` (`file_name()` has nothing to peel), rather than guessing.
+fn canonicalize_lenient(path: &Path) -> Option<PathBuf> {
+    if let Ok(p) = path.canonicalize() {
+        return Some(p);
+    }
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    // symlink_metadata: a dangling symlink counts as existing, so it is
+    // canonicalized (and fails) instead of being appended as a plain name
+    while existing.symlink_metadata().is_err() {
+        rest.push(existing.file_name()?.to_os_string());
+        existing = existing.parent()?.to_path_buf();
+    }
+    let mut resolved = existing.canonicalize().ok()?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    Some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, tempfile::TempDir, Workspace) {
+        let root = tempfile::tempdir().unwrap();
+        let docs = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("src")).unwrap();
+        std::fs::write(root.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(docs.path().join("spec.md"), "# spec").unwrap();
+        let ws = Workspace::new("coding", root.path(), &[docs.path().to_path_buf()]).unwrap();
+        (root, docs, ws)
+    }
+
+    #[test]
+    fn relative_paths_resolve_under_root() {
+        let (root, _docs, ws) = setup();
+        let p = ws.resolve("file-read", "src/main.rs", Access::Read).unwrap();
+        assert_eq!(p, root.path().canonicalize().unwrap().join("src/main.rs"));
+    }
+
+    #[test]
+    fn traversal_is_rejected() {
+        let (_root, _docs, ws) = setup();
+        let err = ws.resolve("file-read", "../../etc/passwd", Access::Read).unwrap_err();
+        assert!(err.contains("outside workspace"));
+        assert!(ws.resolve("file-read", "/etc/passwd", Access::Read).is_err());
+    }
+
+    #[test]
+    fn read_only_roots_are_not_writable() {
+        let (_root, docs, ws) = setup();
+        let spec = docs.path().join("spec.md");
+        let spec = spec.to_str().unwrap();
+        assert!(ws.resolve("file-read", spec, Access::Read).is_ok());
+        assert!(ws.resolve("file-write", spec, Access::Write).is_err());
+    }
+
+    #[test]
+    fn new_files_resolve_through_existing_parent() {
+        let (root, _docs, ws) = setup();
+        let p = ws.resolve("file-write", "src/new/mod.rs", Access::Write).unwrap();
+        assert!(p.starts_with(root.path().canonicalize().unwrap()));
+        assert!(ws.resolve("file-write", "src/new/../../../x.rs", Access::Write).is_err());
+    }
+
+    #[test]
+    fn git_directories_are_not_writable() {
+        let (root, _docs, ws) = setup();
+        std::fs::create_dir_all(root.path().join(".git/hooks")).unwrap();
+        assert!(ws.resolve("file-read", ".git/config", Access::Read).is_ok());
+        assert!(ws.resolve("file-write", ".git/hooks/pre-commit", Access::Write).is_err());
+        assert!(ws.resolve("file-write", "vendor/dep/.git/config", Access::Write).is_err());
+        assert!(ws.resolve("file-write", ".gitignore", Access::Write).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escape_is_rejected() {
+        let (root, _docs, ws) = setup();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "key").unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+
+        assert!(ws.resolve("file-read", "link/secret", Access::Read).is_err());
+        assert!(ws.resolve("file-write", "link/new.txt", Access::Write).is_err());
+        assert!(!ws.contains(&root.path().join("link"), Access::Read));
+    }
+
+    #[test]
+    fn violations_emit_security_blocked() {
+        let (_root, _docs, ws) = setup();
+        let (tx, mut rx) = broadcast::channel(4);
+        let ws = ws.with_event_sender(tx);
+        let _ = ws.resolve("file-read", "/etc/shadow", Access::Read);
+        match rx.try_recv().unwrap() {
+            PipelineEvent::SecurityBlocked { profile, target } => {
+                assert_eq!(profile, "coding");
+                assert_eq!(target, "file-read:/etc/shadow");
+            }
+            other => panic!("expected SecurityBlocked, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        assert!(Workspace::new("p", Path::new("/definitely/not/here"), &[]).is_err());
+    }
+}
@@ -9,9 +9,11 @@ use tokio::sync::Mutex;
 use tui_menu::{MenuItem, MenuState};
 
 use crate::config::{AgentsConfig, ModelsConfig};
+use crate::kernel::checkpoints::ChangedFile;
 use crate::kernel::context_store::{ContextInventory, SegmentMeta, SegmentStatus};
 use crate::kernel::journal::JournalEntry;
 use crate::kernel::thread_table::ThreadRecord;
+use crate::kernel::Kernel;
 use crate::llm::LlmPool;
 use crate::lsp::command_line::CommandLineService;
 use crate::lsp::organism::OrganismYamlService;
@@ -191,12 +193,22 @@ pub struct ActivityEntry {
 /// A chat message in the conversation log.
 #[derive(Debug, Clone)]
 pub struct ChatEntry {
-    /// "user" or "agent"
+    /// "user", "agent", "system", or "thinking"
     pub role: String,
     /// The message text.
     pub text: String,
 }
 
+/// Live output of the tool call in progress (Messages tab).
+#[derive(Debug, Clone)]
+pub struct ToolOutputView {
+    pub thread_id: String,
+    pub tool_name: String,
+    pub call_id: u64,
+    /// The most recent output, at most `TOOL_OUTPUT_CAPACITY` bytes.
+    pub text: String,
+}
+
 /// The main TUI application state (TEA model).
 pub struct TuiApp {
     /// Which tab is currently visible.
@@ -221,6 +233,8 @@ pub struct TuiApp {
     pub messages: Vec<MessageView>,
     /// Context for the selected thread.
     pub context: Option<ContextView>,
+    /// Files touched by file-write/file-edit, from the checkpoint journal.
+    pub changed_files: Vec<ChangedFile>,
     /// Total input tokens across all API calls.
     pub total_input_tokens: u64,
     /// Total output tokens across all API calls.
@@ -233,10 +247,16 @@ pub struct TuiApp {
     pub agent_status: AgentStatus,
     /// Task pending injection into the pipeline (set by input, consumed by runner).
     pub pending_task: Option<String>,
+    /// Images/PDFs attached to the next task via `/attach` (consumed on submit).
+    pub attachments: Vec<String>,
     /// Last agent response text (for display).
     pub last_response: Option<String>,
     /// Conversation log (user tasks + agent responses).
     pub chat_log: Vec<ChatEntry>,
+    /// Streaming output of the running tool call, shown under it.
+    pub tool_output: Option<ToolOutputView>,
+    /// Whether "thinking" entries (model reasoning) are shown in the Messages tab.
+    pub show_thinking: bool,
     /// Viewport height of the messages pane (set by renderer, used by PageUp/PageDown).
     pub viewport_height: u16,
     /// Live activity trace (ring buffer, Threads tab).
@@ -257,6 +277,8 @@ pub struct TuiApp {
     pub conversation_viewport_height: u16,
     /// LLM pool handle (for `/model` command).
     pub llm_pool: Option<Arc<Mutex<LlmPool>>>,
+    /// Kernel handle (for `/undo` and `/rewind`).
+    pub kernel: Option<Arc<Mutex<Kernel>>>,
     /// Command pending async execution (set by input handler on Enter with `/`).
     pub pending_command: Option<String>,
     /// Which sub-pane has focus within the Threads tab.
@@ -321,6 +343,9 @@ const EVENT_LOG_CAPACITY: usize = 256;
 /// Maximum number of activity entries in the ring buffer.
 const ACTIVITY_LOG_CAPACITY: usize = 512;
 
+/// Bytes of live tool output kept for display.
+const TOOL_OUTPUT_CAPACITY: usize = 64 * 1024;
+
 /// Build the menu item tree for the menu bar.
 pub fn build_menu_items(
     debug_mode: bool,
@@ -401,14 +426,18 @@ impl TuiApp {
             threads: Vec::new(),
             messages: Vec::new(),
             context: None,
+            changed_files: Vec::new(),
             total_input_tokens: 0,
             total_output_tokens: 0,
             input_editor,
             input_area: Rect::new(0, 0, 80, 3), // sensible default, updated by renderer
             agent_status: AgentStatus::Idle,
             pending_task: None,
+            attachments: Vec::new(),
             last_response: None,
             chat_log: Vec::new(),
+            tool_output: None,
+            show_thinking: false,
             viewport_height: 20, // sensible default, updated by renderer
             activity_log: Vec::new(),
             activity_scroll: 0,
@@ -419,6 +448,7 @@ impl TuiApp {
             conversation_auto_scroll: true,
             conversation_viewport_height: 20,
             llm_pool: None,
+            kernel: None,
             pending_command: None,
             threads_focus: ThreadsFocus::ThreadList,
             context_tree_state: tui_tree_widget::TreeState::default(),
@@ -691,6 +721,15 @@ impl TuiApp {
                 // Complete any pending "thinking" activity
                 self.complete_thinking();
             }
+            PipelineEvent::AgentReasoning { text, .. } => {
+                self.chat_log.push(ChatEntry {
+                    role: "thinking".into(),
+                    text: text.clone(),
+                });
+                if self.show_thinking {
+                    self.message_auto_scroll = true;
+                }
+            }
             PipelineEvent::AgentThinking { .. } => {
                 self.agent_status = AgentStatus::Thinking;
                 self.push_activity(ActivityEntry {
@@ -701,8 +740,13 @@ impl TuiApp {
                 });
             }
             PipelineEvent::ToolDispatched {
-                tool_name, detail, ..
+                thread_id,
+                tool_name,
+                detail,
             } => {
+                // A thread runs its calls one at a time: a new one ends
+                // whatever it was streaming
+                self.end_tool_output(thread_id, None);
                 self.agent_status = AgentStatus::ToolCall(tool_name.clone());
                 self.push_activity(ActivityEntry {
                     timestamp: now_secs(),
@@ -712,13 +756,24 @@ impl TuiApp {
                 });
             }
             PipelineEvent::ToolCompleted {
+                thread_id,
                 tool_name,
                 success,
                 detail,
-                ..
             } => {
+                self.end_tool_output(thread_id, Some(tool_name));
                 self.complete_activity(tool_name, *success, detail);
             }
+            PipelineEvent::ToolOutput {
+                thread_id,
+                tool_name,
+                call_id,
+                chunk,
+            } => {
+                self.push_tool_output(thread_id, tool_name, *call_id, chunk);
+                // Chunks arrive too often to keep in the event log
+                return;
+            }
             PipelineEvent::ConversationSync {
                 thread_id, entries, ..
             } => {
@@ -785,6 +840,50 @@ impl TuiApp {
         self.activity_auto_scroll = true;
     }
 
+    /// Drop the live tool output if it is `thread_id`'s (and, if given,
+    /// `tool_name`'s); other threads' calls keep streaming.
+    fn end_tool_output(&mut self, thread_id: &str, tool_name: Option<&str>) {
+        let ended = self.tool_output.as_ref().is_some_and(|o| {
+            o.thread_id == thread_id && tool_name.is_none_or(|name| o.tool_name == name)
+        });
+        if ended {
+            self.tool_output = None;
+        }
+    }
+
+    /// Append a chunk of live tool output, starting over for a new call.
+    pub fn push_tool_output(
+        &mut self,
+        thread_id: &str,
+        tool_name: &str,
+        call_id: u64,
+        chunk: &str,
+    ) {
+        let same_call = self
+            .tool_output
+            .as_ref()
+            .is_some_and(|o| o.thread_id == thread_id && o.call_id == call_id);
+        if !same_call {
+            self.tool_output = Some(ToolOutputView {
+                thread_id: thread_id.to_string(),
+                tool_name: tool_name.to_string(),
+                call_id,
+                text: String::new(),
+            });
+        }
+        let Some(output) = self.tool_output.as_mut() else {
+            return;
+        };
+        output.text.push_str(chunk);
+        if output.text.len() > TOOL_OUTPUT_CAPACITY {
+            let mut cut = output.text.len() - TOOL_OUTPUT_CAPACITY;
+            while !output.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            output.text.drain(..cut);
+        }
+    }
+
     /// Find the last matching InProgress activity and mark it Done/Error.
     pub fn complete_activity(&mut self, tool_name: &str, success: bool, detail: &str) {
         for entry in self.activity_log.iter_mut().rev() {
@@ -1134,6 +1233,52 @@ mod tests {
         assert_eq!(app.activity_log[0].status, ActivityStatus::Done);
     }
 
+    #[test]
+    fn tool_output_streams_until_completed() {
+        let mut app = TuiApp::new();
+        let chunk = |call_id: u64, text: &str| {
+            TuiMessage::Pipeline(PipelineEvent::ToolOutput {
+                thread_id: "t1".into(),
+                tool_name: "command-exec".into(),
+                call_id,
+                chunk: text.into(),
+            })
+        };
+        app.update(chunk(0, "Compiling agentos\n"));
+        app.update(chunk(0, "running 12 tests\n"));
+        assert_eq!(
+            app.tool_output.as_ref().unwrap().text,
+            "Compiling agentos\nrunning 12 tests\n"
+        );
+        assert!(app.event_log.is_empty());
+
+        // A new call starts over
+        app.update(chunk(1, "ok\n"));
+        assert_eq!(app.tool_output.as_ref().unwrap().text, "ok\n");
+
+        // Other threads' calls leave it alone
+        app.update(TuiMessage::Pipeline(PipelineEvent::ToolDispatched {
+            thread_id: "t2".into(),
+            tool_name: "file-read".into(),
+            detail: "src/main.rs".into(),
+        }));
+        app.update(TuiMessage::Pipeline(PipelineEvent::ToolCompleted {
+            thread_id: "t2".into(),
+            tool_name: "command-exec".into(),
+            success: true,
+            detail: String::new(),
+        }));
+        assert_eq!(app.tool_output.as_ref().unwrap().text, "ok\n");
+
+        app.update(TuiMessage::Pipeline(PipelineEvent::ToolCompleted {
+            thread_id: "t1".into(),
+            tool_name: "command-exec".into(),
+            success: true,
+            detail: String::new(),
+        }));
+        assert!(app.tool_output.is_none());
+    }
+
     #[test]
     fn tool_completed_error_status() {
         let mut app = TuiApp::new();
@@ -1169,6 +1314,19 @@ mod tests {
         assert_eq!(app.activity_log[0].label, "tool-88");
     }
 
+    #[test]
+    fn agent_reasoning_logged_as_thinking_entry() {
+        let mut app = TuiApp::new();
+        assert!(!app.show_thinking);
+        app.update(TuiMessage::Pipeline(PipelineEvent::AgentReasoning {
+            thread_id: "t1".into(),
+            text: "Check the lexer first.".into(),
+        }));
+        assert_eq!(app.chat_log.len(), 1);
+        assert_eq!(app.chat_log[0].role, "thinking");
+        assert_eq!(app.chat_log[0].text, "Check the lexer first.");
+    }
+
     #[test]
     fn thinking_completed_on_agent_response() {
         let mut app = TuiApp::new();
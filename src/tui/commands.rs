@@ -152,6 +152,49 @@ pub static COMMANDS: &[SlashCommand] = &[
             },
         ],
     },
+    SlashCommand {
+        name: "/attach",
+        aliases: &[],
+        description: "Attach an image or PDF to the next task (no arg lists, `clear` drops)",
+        has_arg: true,
+        args: &[ArgSpec {
+            name: "path",
+            kind: ArgKind::Free("image or PDF path"),
+        }],
+        subcommands: &[],
+    },
+    SlashCommand {
+        name: "/undo",
+        aliases: &[],
+        description: "Restore the file changed by the last file-write/file-edit",
+        has_arg: true,
+        args: &[],
+        subcommands: &[SubcommandSpec {
+            name: "thread",
+            description: "Restore everything a thread changed (default: latest thread)",
+            args: &[ArgSpec {
+                name: "thread id",
+                kind: ArgKind::Free("thread id"),
+            }],
+        }],
+    },
+    SlashCommand {
+        name: "/rewind",
+        aliases: &[],
+        description: "Restore files to their state before a message",
+        has_arg: true,
+        args: &[
+            ArgSpec {
+                name: "message",
+                kind: ArgKind::Free("message id, e.g. msg-0004"),
+            },
+            ArgSpec {
+                name: "thread",
+                kind: ArgKind::Free("thread id (default: latest thread)"),
+            },
+        ],
+        subcommands: &[],
+    },
 ];
 
 /// Return all commands whose name or alias prefix-matches the input.
@@ -349,6 +392,13 @@ pub async fn execute(
         "/provider" => {
             execute_provider(app, arg, arg2).await
         }
+        "/attach" => {
+            // Paths may contain spaces — take everything after the command
+            let path = input["/attach".len()..].trim();
+            execute_attach(app, path)
+        }
+        "/undo" => execute_undo(app, arg, arg2).await,
+        "/rewind" => execute_rewind(app, arg, arg2).await,
         "/help" => {
             let mut lines = Vec::new();
             for cmd in COMMANDS {
@@ -390,6 +440,110 @@ pub fn push_feedback(app: &mut TuiApp, text: &str) {
     app.message_auto_scroll = true;
 }
 
+/// Handle `/attach`: list, clear, or add a file for the next task.
+fn execute_attach(app: &mut TuiApp, path: &str) -> CommandResult {
+    let feedback = match path {
+        "" if app.attachments.is_empty() => "No attachments. Use /attach <path>.".to_string(),
+        "" => format!("Attached to next task:\n  {}", app.attachments.join("\n  ")),
+        "clear" => {
+            app.attachments.clear();
+            "Attachments cleared.".to_string()
+        }
+        _ => {
+            let checked = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| {
+                    crate::llm::media::check_media(&data).map(|media_type| (media_type, data.len()))
+                });
+            match checked {
+                Ok((media_type, size)) => {
+                    app.attachments.push(path.to_string());
+                    format!("Attached {path} ({media_type}, {size} bytes)")
+                }
+                Err(e) => format!("Cannot attach {path}: {e}"),
+            }
+        }
+    };
+    CommandResult {
+        feedback: Some(feedback),
+        handled: true,
+    }
+}
+
+/// Handle `/undo` and `/undo thread [id]`.
+async fn execute_undo(app: &mut TuiApp, subcommand: &str, thread_arg: &str) -> CommandResult {
+    let Some(kernel) = app.kernel.clone() else {
+        return feedback("No kernel attached — nothing to undo.");
+    };
+    let mut k = kernel.lock().await;
+    let (what, result) = match subcommand {
+        "" => ("last change".to_string(), k.undo_last()),
+        "thread" => {
+            let thread_id = if thread_arg.is_empty() {
+                match k.checkpoints().last_active() {
+                    Some(cp) => cp.thread_id.clone(),
+                    None => return feedback("Nothing to undo."),
+                }
+            } else {
+                thread_arg.to_string()
+            };
+            let result = k.undo_thread(&thread_id);
+            (format!("thread {thread_id}"), result)
+        }
+        other => return feedback(&format!("Unknown /undo subcommand: {other}")),
+    };
+    app.changed_files = k.checkpoints().changed_files();
+    drop(k);
+    restore_feedback(&what, result)
+}
+
+/// Handle `/rewind <message-id> [thread-id]`.
+///
+/// Message ids are numbered per thread; without a thread id the latest
+/// thread with changes is meant.
+async fn execute_rewind(app: &mut TuiApp, message_id: &str, thread_arg: &str) -> CommandResult {
+    if message_id.is_empty() {
+        return feedback("Usage: /rewind <message id> [thread id] (e.g. msg-0004)");
+    }
+    let Some(kernel) = app.kernel.clone() else {
+        return feedback("No kernel attached — nothing to rewind.");
+    };
+    let mut k = kernel.lock().await;
+    let thread_id = if thread_arg.is_empty() {
+        match k.checkpoints().last_active() {
+            Some(cp) => cp.thread_id.clone(),
+            None => return feedback("Nothing to undo."),
+        }
+    } else {
+        thread_arg.to_string()
+    };
+    let result = k.rewind(&thread_id, message_id);
+    app.changed_files = k.checkpoints().changed_files();
+    drop(k);
+    restore_feedback(
+        &format!("changes since {message_id} in thread {thread_id}"),
+        result,
+    )
+}
+
+fn restore_feedback(
+    what: &str,
+    result: crate::kernel::error::KernelResult<Vec<String>>,
+) -> CommandResult {
+    match result {
+        Ok(paths) if paths.is_empty() => feedback("Nothing to undo."),
+        Ok(paths) => feedback(&format!("Undid {what}:\n  {}", paths.join("\n  "))),
+        Err(e) => feedback(&format!("Undo failed: {e}")),
+    }
+}
+
+fn feedback(text: &str) -> CommandResult {
+    CommandResult {
+        feedback: Some(text.to_string()),
+        handled: true,
+    }
+}
+
 /// Handle `/agents` subcommands.
 fn execute_agents(app: &mut TuiApp, subcommand: &str) -> CommandResult {
     match subcommand {
@@ -759,6 +913,92 @@ mod tests {
         assert!(app.chat_log.is_empty());
     }
 
+    #[tokio::test]
+    async fn execute_attach_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mockup.png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let mut app = TuiApp::new();
+        let result = execute(&mut app, &format!("/attach {path}"), None).await;
+        assert!(result.feedback.unwrap().contains("image/png"));
+        assert_eq!(app.attachments, vec![path.clone()]);
+
+        let listed = execute(&mut app, "/attach", None).await;
+        assert!(listed.feedback.unwrap().contains(&path));
+
+        execute(&mut app, "/attach clear", None).await;
+        assert!(app.attachments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn execute_attach_rejects_text_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "just text").unwrap();
+
+        let mut app = TuiApp::new();
+        let result = execute(&mut app, &format!("/attach {}", path.display()), None).await;
+        assert!(result.feedback.unwrap().contains("Cannot attach"));
+        assert!(app.attachments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn execute_undo_and_rewind() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        let mut kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        std::fs::write(&file, "v1").unwrap();
+        kernel.record_checkpoint("t1", "msg-0002", &file).unwrap();
+        std::fs::write(&file, "v2").unwrap();
+        kernel.record_checkpoint("t1", "msg-0004", &file).unwrap();
+        std::fs::write(&file, "v3").unwrap();
+
+        let mut app = TuiApp::new();
+        app.kernel = Some(Arc::new(Mutex::new(kernel)));
+
+        let result = execute(&mut app, "/undo", None).await;
+        assert!(result.feedback.unwrap().contains("main.rs"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v2");
+        assert_eq!(app.changed_files.len(), 1);
+
+        execute(&mut app, "/rewind msg-0002", None).await;
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1");
+        assert!(app.changed_files.is_empty());
+
+        let result = execute(&mut app, "/undo thread", None).await;
+        assert_eq!(result.feedback.as_deref(), Some("Nothing to undo."));
+    }
+
+    #[tokio::test]
+    async fn execute_rewind_picks_the_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let ours = dir.path().join("ours.rs");
+        let theirs = dir.path().join("theirs.rs");
+        let mut kernel = crate::kernel::Kernel::open(&dir.path().join("data")).unwrap();
+        // Both threads edit at their own msg-0002
+        std::fs::write(&ours, "ours v1").unwrap();
+        kernel.record_checkpoint("t1", "msg-0002", &ours).unwrap();
+        std::fs::write(&ours, "ours v2").unwrap();
+        std::fs::write(&theirs, "theirs v1").unwrap();
+        kernel.record_checkpoint("t2", "msg-0002", &theirs).unwrap();
+        std::fs::write(&theirs, "theirs v2").unwrap();
+
+        let mut app = TuiApp::new();
+        app.kernel = Some(Arc::new(Mutex::new(kernel)));
+
+        let result = execute(&mut app, "/rewind msg-0002 t1", None).await;
+        assert!(result.feedback.unwrap().contains("ours.rs"));
+        assert_eq!(std::fs::read_to_string(&ours).unwrap(), "ours v1");
+        assert_eq!(std::fs::read_to_string(&theirs).unwrap(), "theirs v2");
+
+        // Without a thread id, the latest thread is meant
+        execute(&mut app, "/rewind msg-0002", None).await;
+        assert_eq!(std::fs::read_to_string(&theirs).unwrap(), "theirs v1");
+        assert!(app.changed_files.is_empty());
+    }
+
     #[tokio::test]
     async fn execute_model_switch() {
         let pool = LlmPool::new("test-key".into(), "opus");
@@ -49,6 +49,7 @@ fn dispatch_menu_action(app: &mut TuiApp, action: MenuAction) {
                     "  F10           Open/close menu bar\n",
                     "  Alt+F/V/A/M/H Open File/View/Agents/Model/Help menu\n",
                     "  Ctrl+1..5     Switch tabs\n",
+                    "  Ctrl+T        Show/hide model reasoning (Messages)\n",
                     "  Tab           Cycle focus (Threads) / autocomplete (/commands)\n",
                     "  Enter         Submit task or confirm\n",
                     "  Esc           Clear input\n",
@@ -238,6 +239,10 @@ pub fn handle_key(app: &mut TuiApp, key: KeyEvent) {
                 app.active_tab = ActiveTab::Debug;
                 return;
             }
+            KeyCode::Char('t') if app.active_tab == ActiveTab::Messages => {
+                app.show_thinking = !app.show_thinking;
+                return;
+            }
             _ => {}
         }
     }
@@ -394,9 +399,13 @@ pub fn handle_key(app: &mut TuiApp, key: KeyEvent) {
                     // Slash command — defer to async executor in runner
                     app.pending_command = Some(text);
                 } else {
+                    let mut shown = text.clone();
+                    for path in &app.attachments {
+                        shown.push_str(&format!("\n[attached: {path}]"));
+                    }
                     app.chat_log.push(ChatEntry {
                         role: "user".into(),
-                        text: text.clone(),
+                        text: shown,
                     });
                     app.agent_status = AgentStatus::Thinking;
                     app.message_auto_scroll = true;
@@ -652,6 +661,23 @@ mod tests {
         assert_eq!(app.chat_log[0].role, "user");
     }
 
+    #[test]
+    fn enter_echoes_attachments() {
+        let mut app = TuiApp::new();
+        app.attachments.push("mockup.png".into());
+        type_text(&mut app, "match this");
+
+        handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+        );
+
+        assert_eq!(app.pending_task, Some("match this".into()));
+        assert_eq!(app.chat_log[0].text, "match this\n[attached: mockup.png]");
+        // Consumed by the runner at injection time, not here
+        assert_eq!(app.attachments.len(), 1);
+    }
+
     // ── Threads tab focus cycling ──
 
     #[test]
@@ -748,6 +774,22 @@ mod tests {
         assert_eq!(app.active_tab, ActiveTab::Debug);
     }
 
+    #[test]
+    fn ctrl_t_toggles_thinking_on_messages_tab() {
+        let mut app = TuiApp::new();
+        assert!(!app.show_thinking);
+        handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL),
+        );
+        assert!(app.show_thinking);
+        handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL),
+        );
+        assert!(!app.show_thinking);
+    }
+
     #[test]
     fn ctrl_5_ignored_when_not_debug() {
         let mut app = TuiApp::new();
@@ -355,7 +355,21 @@ fn draw_threads(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     // ── Pane 2: Conversation ──
     draw_conversation(f, app, chunks[1]);
 
-    // ── Pane 3: Context tree (tui-tree-widget) ──
+    // ── Pane 3: Context tree (tui-tree-widget), changed files beside it ──
+    let bottom = if app.changed_files.is_empty() {
+        vec![chunks[2]]
+    } else {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[2])
+            .to_vec()
+    };
+    if let Some(&files_area) = bottom.get(1) {
+        draw_changed_files(f, app, files_area);
+    }
+    let ctx_area = bottom[0];
+
     let ctx_border_color = if app.threads_focus == ThreadsFocus::ContextTree {
         Color::Cyan
     } else {
@@ -385,10 +399,10 @@ fn draw_threads(f: &mut Frame, app: &mut TuiApp, area: Rect) {
                         .add_modifier(Modifier::BOLD),
                 )
                 .highlight_symbol(">> ");
-            f.render_stateful_widget(tree, chunks[2], &mut app.context_tree_state);
+            f.render_stateful_widget(tree, ctx_area, &mut app.context_tree_state);
         } else {
             let para = Paragraph::new("Error building context tree").block(ctx_block);
-            f.render_widget(para, chunks[2]);
+            f.render_widget(para, ctx_area);
         }
     } else {
         let para = Paragraph::new(Span::styled(
@@ -396,10 +410,38 @@ fn draw_threads(f: &mut Frame, app: &mut TuiApp, area: Rect) {
             Style::default().fg(Color::DarkGray),
         ))
         .block(ctx_block);
-        f.render_widget(para, chunks[2]);
+        f.render_widget(para, ctx_area);
     }
 }
 
+/// Render the changed-files panel (files with unrestored checkpoints).
+fn draw_changed_files(f: &mut Frame, app: &TuiApp, area: Rect) {
+    let block = Block::default()
+        .title(format!(" Changed files ({}) ", app.changed_files.len()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    let items: Vec<ListItem> = app
+        .changed_files
+        .iter()
+        .map(|file| {
+            let (marker, color) = if file.created {
+                ("A ", Color::Green)
+            } else {
+                ("M ", Color::Yellow)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(marker, Style::default().fg(color)),
+                Span::raw(file.path.clone()),
+                Span::styled(
+                    format!("  x{} @{}", file.changes, file.message_id),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    f.render_widget(List::new(items).block(block), area);
+}
+
 /// Render the conversation pane for the selected thread.
 fn draw_conversation(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     let border_color = if app.threads_focus == ThreadsFocus::Conversation {
@@ -644,6 +686,22 @@ fn format_timestamp(secs: u64) -> String {
     format!("{h:02}:{m:02}:{s:02}")
 }
 
+/// Lines of live tool output shown under the running call.
+const TOOL_OUTPUT_LINES: usize = 12;
+
+/// The last few lines of live output, as a terminal would show them:
+/// a `\r` (progress bars) restarts its line.
+fn tool_output_tail(text: &str) -> Vec<&str> {
+    let lines: Vec<&str> = text
+        .trim_end_matches('\n')
+        .lines()
+        .map(|l| l.trim_end_matches('\r'))
+        .map(|l| l.rsplit('\r').next().unwrap_or(l))
+        .collect();
+    let start = lines.len().saturating_sub(TOOL_OUTPUT_LINES);
+    lines[start..].to_vec()
+}
+
 fn draw_messages(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     let block = Block::default()
         .title(" Messages ")
@@ -714,6 +772,28 @@ fn draw_messages(f: &mut Frame, app: &mut TuiApp, area: Rect) {
                     }
                 }
             }
+            "thinking" if app.show_thinking => {
+                lines.push(Line::from(""));
+                nowrap.push(false);
+                lines.push(Line::from(vec![Span::styled(
+                    "[Thinking]",
+                    Style::default()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::BOLD),
+                )]));
+                nowrap.push(false);
+                for text_line in entry.text.lines() {
+                    let thought = Line::from(vec![Span::styled(
+                        text_line.to_string(),
+                        Style::default()
+                            .fg(Color::DarkGray)
+                            .add_modifier(Modifier::ITALIC),
+                    )]);
+                    let wrapped = wrap_line(thought, wrap_width);
+                    nowrap.extend(std::iter::repeat(false).take(wrapped.len()));
+                    lines.extend(wrapped);
+                }
+            }
             "system" => {
                 lines.push(Line::from(""));
                 nowrap.push(false);
@@ -748,6 +828,17 @@ fn draw_messages(f: &mut Frame, app: &mut TuiApp, area: Rect) {
             Style::default().fg(Color::Cyan),
         )]));
         nowrap.push(false);
+        if let Some(ref output) = app.tool_output {
+            for text_line in tool_output_tail(&output.text) {
+                let out_line = Line::from(vec![
+                    Span::styled("  \u{2502} ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(text_line.to_string(), Style::default().fg(Color::Gray)),
+                ]);
+                let wrapped = wrap_line(out_line, wrap_width);
+                nowrap.extend(std::iter::repeat(false).take(wrapped.len()));
+                lines.extend(wrapped);
+            }
+        }
     }
 
     if lines.is_empty() {
@@ -46,16 +46,21 @@ pub async fn refresh_from_kernel(app: &mut TuiApp, kernel: &Arc<Mutex<Kernel>>)
     } else {
         app.context = None;
     }
+
+    // Refresh the changed-files panel from the checkpoint journal
+    app.changed_files = k.checkpoints().changed_files();
     // Lock released here — microseconds
 }
 
 /// Inject a task from the input bar into the pipeline.
 ///
 /// Routes to the selected agent if set, otherwise to the first agent listener.
+/// Attached file paths ride along as `<attach>` elements.
 async fn inject_task(
     pipeline: &AgentPipeline,
     kernel: &Arc<Mutex<Kernel>>,
     task: &str,
+    attachments: &[String],
     selected_agent: Option<&str>,
 ) {
     let root_uuid = {
@@ -79,7 +84,11 @@ async fn inject_task(
 
     if let Some(uuid) = root_uuid {
         let escaped = xml_escape(task);
-        let xml = format!("<{payload_tag}><task>{escaped}</task></{payload_tag}>");
+        let attached: String = attachments
+            .iter()
+            .map(|path| format!("<attach>{}</attach>", xml_escape(path)))
+            .collect();
+        let xml = format!("<{payload_tag}><task>{escaped}</task>{attached}</{payload_tag}>");
         if let Ok(envelope) =
             build_envelope("user", &agent_name, &uuid, xml.as_bytes())
         {
@@ -108,6 +117,7 @@ pub async fn run_tui(
     let mut app = TuiApp::new();
     app.debug_mode = debug;
     app.llm_pool = pipeline.llm_pool();
+    app.kernel = Some(pipeline.kernel());
     app.models_config = std::sync::Arc::new(tokio::sync::Mutex::new(models_config));
     app.agents_config = agents_config;
     app.load_yaml_editor(organism_yaml);
@@ -267,7 +277,15 @@ pub async fn run_tui(
 
         // Check for pending task submission (set by input handler on Enter)
         if let Some(task) = app.pending_task.take() {
-            inject_task(pipeline, &kernel, &task, app.selected_agent.as_deref()).await;
+            let attachments = std::mem::take(&mut app.attachments);
+            inject_task(
+                pipeline,
+                &kernel,
+                &task,
+                &attachments,
+                app.selected_agent.as_deref(),
+            )
+            .await;
         }
     }
 
@@ -411,6 +429,24 @@ mod tests {
         assert_eq!(ctx.segments[0].id, "s1");
     }
 
+    #[tokio::test]
+    async fn refresh_populates_changed_files() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn old() {}").unwrap();
+        let mut kernel = Kernel::open(&dir.path().join("data")).unwrap();
+        kernel.record_checkpoint("t1", "msg-0002", &file).unwrap();
+        kernel.record_checkpoint("t1", "msg-0004", &file).unwrap();
+
+        let kernel_arc = Arc::new(Mutex::new(kernel));
+        let mut app = TuiApp::new();
+        refresh_from_kernel(&mut app, &kernel_arc).await;
+
+        assert_eq!(app.changed_files.len(), 1);
+        assert_eq!(app.changed_files[0].changes, 2);
+        assert_eq!(app.changed_files[0].message_id, "msg-0004");
+    }
+
     #[tokio::test]
     async fn event_log_ring_buffer() {
         let mut app = TuiApp::new();
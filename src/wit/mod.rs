@@ -24,13 +24,13 @@ pub struct ToolInterface {
 }
 
 /// Parsed record (collection of typed fields).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ToolRecord {
     pub fields: Vec<ToolField>,
 }
 
 /// A single field in a record.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ToolField {
     /// Field name (e.g. "path", "offset").
     pub name: String,
@@ -53,6 +53,8 @@ pub enum ToolFieldType {
     F64,
     Option(Box<ToolFieldType>),
     List(Box<ToolFieldType>),
+    /// A named record declared earlier in the same interface.
+    Record(ToolRecord),
 }
 
 impl ToolInterface {
@@ -114,6 +116,7 @@ impl ToolInterface {
 
             let mut prop = serde_json::Map::new();
             prop.insert("type".into(), serde_json::Value::String(json_type));
+            add_json_structure(&field.field_type, &mut prop);
             if let Some(ref desc) = field.description {
                 prop.insert("description".into(), serde_json::Value::String(desc.clone()));
             }
@@ -191,6 +194,7 @@ fn wit_to_field_schema(ty: &ToolFieldType) -> (bool, FieldType) {
             (false, field_type) // option = not required
         }
         ToolFieldType::List(_) => (true, FieldType::String), // lists serialize as string content
+        ToolFieldType::Record(_) => (true, FieldType::String), // records serialize as JSON text
     }
 }
 
@@ -208,6 +212,44 @@ fn wit_to_json_schema(ty: &ToolFieldType) -> (bool, String) {
             (false, json_type) // option = not required
         }
         ToolFieldType::List(_) => (true, "array".into()),
+        ToolFieldType::Record(_) => (true, "object".into()),
+    }
+}
+
+/// Add `items` / `properties` to a JSON Schema property for lists and records.
+fn add_json_structure(ty: &ToolFieldType, prop: &mut serde_json::Map<String, serde_json::Value>) {
+    match ty {
+        ToolFieldType::Option(inner) => add_json_structure(inner, prop),
+        ToolFieldType::List(inner) => {
+            let (_, json_type) = wit_to_json_schema(inner);
+            let mut items = serde_json::Map::new();
+            items.insert("type".into(), serde_json::Value::String(json_type));
+            add_json_structure(inner, &mut items);
+            prop.insert("items".into(), serde_json::Value::Object(items));
+        }
+        ToolFieldType::Record(record) => {
+            let mut properties = serde_json::Map::new();
+            let mut required = Vec::new();
+            for field in &record.fields {
+                let (is_required, json_type) = wit_to_json_schema(&field.field_type);
+                let field_name = wit_name_to_underscore(&field.name);
+                let mut inner = serde_json::Map::new();
+                inner.insert("type".into(), serde_json::Value::String(json_type));
+                add_json_structure(&field.field_type, &mut inner);
+                if let Some(ref desc) = field.description {
+                    inner.insert("description".into(), serde_json::Value::String(desc.clone()));
+                }
+                properties.insert(field_name.clone(), serde_json::Value::Object(inner));
+                if is_required {
+                    required.push(serde_json::Value::String(field_name));
+                }
+            }
+            prop.insert("properties".into(), serde_json::Value::Object(properties));
+            if !required.is_empty() {
+                prop.insert("required".into(), serde_json::Value::Array(required));
+            }
+        }
+        _ => {}
     }
 }
 
@@ -230,7 +272,7 @@ fn wit_to_codellm_type(
             let (_, codellm_type) = wit_to_codellm_type(inner)?;
             Some((false, codellm_type)) // option = not required
         }
-        ToolFieldType::List(_) => None, // no codeLlm representation
+        ToolFieldType::List(_) | ToolFieldType::Record(_) => None, // no codeLlm representation
     }
 }
 
@@ -641,6 +683,27 @@ interface file-write {
         assert!(iface.to_codellm_schema("EmptyRequest").is_none());
     }
 
+    #[test]
+    fn to_tool_definition_list_of_records() {
+        let wit = r#"
+interface batch {
+    record edit {
+        old-string: string,
+        replace-all: option<bool>,
+    }
+    record request {
+        edits: list<edit>,
+    }
+}
+"#;
+        let def = parser::parse_wit(wit).unwrap().to_tool_definition();
+        let edits = &def.input_schema["properties"]["edits"];
+        assert_eq!(edits["type"], "array");
+        assert_eq!(edits["items"]["type"], "object");
+        assert_eq!(edits["items"]["properties"]["old_string"]["type"], "string");
+        assert_eq!(edits["items"]["required"], serde_json::json!(["old_string"]));
+    }
+
     #[test]
     fn to_codellm_schema_roundtrip_from_wit() {
         let wit = r#"
@@ -5,6 +5,7 @@
 //! - `record request { ... }` with typed fields
 //! - Primitive types: string, bool, u32, u64, s32, s64, f32, f64
 //! - Wrappers: `option<T>`, `list<T>`
+//! - Helper records (`record edit { ... }`) referenced by name from later records
 //! - `func` declaration (parsed but not used beyond validation)
 
 use super::{ToolField, ToolFieldType, ToolInterface, ToolRecord};
@@ -59,8 +60,9 @@ pub fn parse_wit(input: &str) -> Result<ToolInterface, String> {
         interface_doc.join(" ")
     };
 
-    // Parse body: expect `record request { ... }` and optionally a func line
-    let mut record: Option<ToolRecord> = None;
+    // Parse body: expect `record request { ... }` and optionally a func line.
+    // Records declared before it can be used as field types by name.
+    let mut records: Vec<(String, ToolRecord)> = Vec::new();
 
     while let Some(line) = lines.next() {
         let trimmed = line.trim();
@@ -73,14 +75,20 @@ pub fn parse_wit(input: &str) -> Result<ToolInterface, String> {
         }
 
         if trimmed.starts_with("record ") {
-            record = Some(parse_record(trimmed, &mut lines)?);
+            let parsed = parse_record(trimmed, &mut lines, &records)?;
+            records.push(parsed);
         }
         // Skip func declarations and other lines inside the interface
     }
 
-    let request = record.unwrap_or_else(|| ToolRecord {
-        fields: Vec::new(),
-    });
+    // The request is the record named `request`, else the last one declared
+    let request = match records.iter().position(|(name, _)| name == "request") {
+        Some(i) => records.swap_remove(i).1,
+        None => records
+            .pop()
+            .map(|(_, record)| record)
+            .unwrap_or_else(|| ToolRecord { fields: Vec::new() }),
+    };
 
     Ok(ToolInterface {
         name,
@@ -89,19 +97,22 @@ pub fn parse_wit(input: &str) -> Result<ToolInterface, String> {
     })
 }
 
-/// Parse a `record <name> { ... }` block.
+/// Parse a `record <name> { ... }` block, returning its name and fields.
 fn parse_record(
     first_line: &str,
     lines: &mut std::iter::Peekable<std::str::Lines<'_>>,
-) -> Result<ToolRecord, String> {
+    known: &[(String, ToolRecord)],
+) -> Result<(String, ToolRecord), String> {
     // first_line is like `record request {`
     let rest = first_line
         .strip_prefix("record ")
         .unwrap()
         .trim();
-    if !rest.ends_with('{') {
-        return Err(format!("expected '{{' after record name, found: {rest}"));
-    }
+    let record_name = rest
+        .strip_suffix('{')
+        .ok_or_else(|| format!("expected '{{' after record name, found: {rest}"))?
+        .trim()
+        .to_string();
 
     let mut fields = Vec::new();
     let mut field_doc = Vec::new();
@@ -131,7 +142,7 @@ fn parse_record(
 
         let field_name = field_name.trim().to_string();
         let type_str = type_str.trim();
-        let field_type = parse_type(type_str)?;
+        let field_type = parse_type(type_str, known)?;
 
         let description = if field_doc.is_empty() {
             None
@@ -148,11 +159,13 @@ fn parse_record(
         field_doc.clear();
     }
 
-    Ok(ToolRecord { fields })
+    Ok((record_name, ToolRecord { fields }))
 }
 
 /// Parse a WIT type string into a `ToolFieldType`.
-fn parse_type(s: &str) -> Result<ToolFieldType, String> {
+///
+/// Names of previously declared records resolve to `ToolFieldType::Record`.
+fn parse_type(s: &str, known: &[(String, ToolRecord)]) -> Result<ToolFieldType, String> {
     let s = s.trim();
     match s {
         "string" => Ok(ToolFieldType::String),
@@ -165,15 +178,19 @@ fn parse_type(s: &str) -> Result<ToolFieldType, String> {
         "f64" => Ok(ToolFieldType::F64),
         _ if s.starts_with("option<") && s.ends_with('>') => {
             let inner = &s[7..s.len() - 1];
-            let inner_type = parse_type(inner)?;
+            let inner_type = parse_type(inner, known)?;
             Ok(ToolFieldType::Option(Box::new(inner_type)))
         }
         _ if s.starts_with("list<") && s.ends_with('>') => {
             let inner = &s[5..s.len() - 1];
-            let inner_type = parse_type(inner)?;
+            let inner_type = parse_type(inner, known)?;
             Ok(ToolFieldType::List(Box::new(inner_type)))
         }
-        _ => Err(format!("unknown WIT type: {s}")),
+        _ => known
+            .iter()
+            .find(|(name, _)| name == s)
+            .map(|(_, record)| ToolFieldType::Record(record.clone()))
+            .ok_or_else(|| format!("unknown WIT type: {s}")),
     }
 }
 
@@ -326,6 +343,51 @@ interface nested {
         );
     }
 
+    #[test]
+    fn parse_list_of_named_record() {
+        let wit = r#"
+interface batch {
+    record edit {
+        /// Text to find
+        old-string: string,
+        new-string: string,
+    }
+    record request {
+        path: string,
+        edits: option<list<edit>>,
+    }
+}
+"#;
+        let iface = parse_wit(wit).unwrap();
+        assert_eq!(iface.request.fields.len(), 2);
+        let ToolFieldType::Option(list) = &iface.request.fields[1].field_type else {
+            panic!("expected option");
+        };
+        let ToolFieldType::List(inner) = list.as_ref() else {
+            panic!("expected list");
+        };
+        let ToolFieldType::Record(edit) = inner.as_ref() else {
+            panic!("expected record");
+        };
+        assert_eq!(edit.fields[0].name, "old-string");
+        assert_eq!(edit.fields[0].description.as_deref(), Some("Text to find"));
+    }
+
+    #[test]
+    fn error_record_used_before_declared() {
+        let wit = r#"
+interface late {
+    record request {
+        edits: list<edit>,
+    }
+    record edit {
+        x: string,
+    }
+}
+"#;
+        assert!(parse_wit(wit).is_err());
+    }
+
     #[test]
     fn parse_no_doc_comments() {
         let wit = r#"
@@ -85,6 +85,11 @@ async fn haiku_tool_use_round_trip() {
                 println!("  [{i}] ToolUse: id={id}, name={name}, input={input}")
             }
             ContentBlock::ToolResult { .. } => println!("  [{i}] ToolResult (unexpected)"),
+            ContentBlock::Thinking { thinking, .. } => println!("  [{i}] Thinking: {thinking}"),
+            ContentBlock::RedactedThinking { .. } => println!("  [{i}] RedactedThinking"),
+            ContentBlock::Image { .. } | ContentBlock::Document { .. } => {
+                println!("  [{i}] Media (unexpected)")
+            }
         }
     }
 